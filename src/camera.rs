@@ -0,0 +1,1158 @@
+//! Camera controls: free flight, following, framing, projections and recorded paths
+
+use bevy::prelude::*;
+use bevy::input::mouse::MouseMotion;
+use bevy_egui::{egui, EguiContexts};
+use bevy::render::camera::{CameraProjection, ScalingMode};
+use bevy::render::primitives::Frustum;
+use crate::physics::{CelestialBody, Mass, Name, PhysicsConfig, Radius, UpAxis, Velocity};
+use crate::orbital::orbit_normal;
+use crate::ui::Selected;
+
+/// Keeps the camera trailing a body, aiming a little ahead of it
+#[derive(Component)]
+pub struct FollowTarget {
+    pub target: Entity,
+    /// Where the camera is looking, sprung towards the target's lead point
+    pub aim: Vec3,
+    pub aim_velocity: Vec3,
+}
+
+/// Smooth camera move towards a target transform, removed when it arrives
+#[derive(Component)]
+pub struct CameraFlight {
+    pub from: Transform,
+    pub to: Transform,
+    pub elapsed: f32,
+}
+
+#[derive(Component)]
+pub struct CameraController {
+    pub sensitivity: f32,
+    pub speed: f32,
+    /// Scale `speed` with the distance to the nearest body's surface, clamped to the bounds below.
+    /// Toggled with V.
+    pub adaptive_speed: bool,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// Spring constant pulling the follow camera's aim point towards where it should be
+    pub follow_stiffness: f32,
+    /// Damping on the aim point's motion; `2 * sqrt(follow_stiffness)` settles without overshoot
+    pub follow_damping: f32,
+    /// Seconds of the target's velocity the follow camera aims ahead by
+    pub follow_lead: f32,
+}
+
+/// Clipping planes of the camera. The far plane is pushed out as needed to keep every body in
+/// range, so large systems don't vanish at a distance; `far` is only the least it will be.
+#[derive(Resource, Clone, Copy)]
+pub struct CameraClip {
+    pub near: f32,
+    pub far: f32,
+    /// Expand the far plane past the outermost body
+    pub auto_far: bool,
+}
+
+impl Default for CameraClip {
+    fn default() -> Self {
+        let perspective = PerspectiveProjection::default();
+        Self {
+            near: perspective.near,
+            far: perspective.far,
+            auto_far: true,
+        }
+    }
+}
+
+impl CameraClip {
+    pub fn perspective(&self) -> PerspectiveProjection {
+        PerspectiveProjection { near: self.near, far: self.far, ..default() }
+    }
+}
+
+// The auto-expanded far plane sits this much past the far side of the outermost body
+pub const FAR_CLIP_MARGIN: f32 = 1.1;
+
+/// Far clip distance that reaches past every (position, radius) body seen from `camera`, and is
+/// never less than `far`
+pub fn far_clip_distance(camera: Vec3, bodies: &[(Vec3, f32)], far: f32) -> f32 {
+    bodies
+        .iter()
+        .map(|(position, radius)| (camera.distance(*position) + radius) * FAR_CLIP_MARGIN)
+        .fold(far, f32::max)
+}
+
+/// Keep the camera's far plane past the outermost body, in either projection
+pub fn far_clip_system(
+    clip: Res<CameraClip>,
+    mut cameras: Query<(&Transform, &mut Projection), With<CameraController>>,
+    bodies: Query<(&Transform, &Radius), With<CelestialBody>>,
+) {
+    if !clip.auto_far {
+        return;
+    }
+    let bodies: Vec<(Vec3, f32)> = bodies.iter().map(|(transform, radius)| (transform.translation, radius.0)).collect();
+    for (transform, mut projection) in cameras.iter_mut() {
+        let far = far_clip_distance(transform.translation, &bodies, clip.far);
+        let current = match projection.as_ref() {
+            Projection::Perspective(perspective) => perspective.far,
+            Projection::Orthographic(orthographic) => orthographic.far,
+        };
+        if current == far {
+            continue;
+        }
+        match projection.as_mut() {
+            Projection::Perspective(perspective) => perspective.far = far,
+            Projection::Orthographic(orthographic) => orthographic.far = far,
+        }
+    }
+}
+
+/// Keeps the free camera from flying into bodies. Toggled with N for flying inside them.
+#[derive(Resource)]
+pub struct CameraCollision {
+    pub enabled: bool,
+    /// Closest the camera may get to a body's surface
+    pub margin: f32,
+}
+
+impl Default for CameraCollision {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            margin: 0.5,
+        }
+    }
+}
+
+/// Recorded camera keyframes for flythrough videos
+#[derive(Resource)]
+pub struct CameraPath {
+    pub keyframes: Vec<Transform>,
+    /// Seconds a full playback takes
+    pub duration: f32,
+    /// Seconds into the current playback, or `None` when not playing
+    pub playback: Option<f32>,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            duration: 10.0,
+            playback: None,
+        }
+    }
+}
+
+impl CameraPath {
+    /// Camera transform at `t` in [0, 1] along the path: Catmull-Rom through the
+    /// keyframe positions and slerp between their rotations. Needs at least two keyframes.
+    pub fn sample(&self, t: f32) -> Option<Transform> {
+        let count = self.keyframes.len();
+        if count < 2 {
+            return None;
+        }
+        let scaled = t.clamp(0.0, 1.0) * (count - 1) as f32;
+        let segment = (scaled.floor() as usize).min(count - 2);
+        let u = scaled - segment as f32;
+
+        let key = |index: isize| self.keyframes[index.clamp(0, count as isize - 1) as usize];
+        let i = segment as isize;
+        let (p0, p1, p2, p3) = (
+            key(i - 1).translation,
+            key(i).translation,
+            key(i + 1).translation,
+            key(i + 2).translation,
+        );
+        let translation = 0.5
+            * (2.0 * p1
+                + (p2 - p0) * u
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u * u
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u * u * u);
+        let rotation = key(i).rotation.slerp(key(i + 1).rotation, u);
+        Some(Transform::from_translation(translation).with_rotation(rotation))
+    }
+
+    /// One keyframe per line: translation x y z then rotation x y z w
+    pub fn to_text(&self) -> String {
+        self.keyframes
+            .iter()
+            .map(|key| {
+                let [tx, ty, tz] = key.translation.to_array();
+                let [rx, ry, rz, rw] = key.rotation.to_array();
+                format!("{tx} {ty} {tz} {rx} {ry} {rz} {rw}\n")
+            })
+            .collect()
+    }
+
+    pub fn from_text(text: &str) -> Result<Vec<Transform>, String> {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(number, line)| {
+                let values: Vec<f32> = line
+                    .split_whitespace()
+                    .map(|value| value.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|err| format!("line {}: {err}", number + 1))?;
+                let [tx, ty, tz, rx, ry, rz, rw] = values[..] else {
+                    return Err(format!("line {}: expected 7 numbers", number + 1));
+                };
+                Ok(Transform::from_xyz(tx, ty, tz)
+                    .with_rotation(Quat::from_xyzw(rx, ry, rz, rw).normalize()))
+            })
+            .collect()
+    }
+}
+
+pub const CAMERA_PATH_FILE: &str = "camera_path.txt";
+
+// With adaptive camera speed, the camera moves at its base speed this far from the nearest surface
+pub const ADAPTIVE_SPEED_REFERENCE_DISTANCE: f32 = 25.0;
+
+/// Camera speed scaled by the distance to the nearest surface, so it crawls near bodies and
+/// races across open space
+pub fn adaptive_camera_speed(controller: &CameraController, nearest_surface_distance: f32) -> f32 {
+    (controller.speed * nearest_surface_distance / ADAPTIVE_SPEED_REFERENCE_DISTANCE)
+        .clamp(controller.min_speed, controller.max_speed)
+}
+
+/// Camera controller system
+#[allow(clippy::type_complexity)]
+pub fn camera_controller(
+    time: Res<Time>,
+    mut mouse_events: EventReader<MouseMotion>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    (key_input, config): (Res<ButtonInput<KeyCode>>, Res<PhysicsConfig>),
+    camera_path: Res<CameraPath>,
+    mut query: Query<(&mut Transform, &mut CameraController, &mut Projection, Option<&SavedPerspective>), With<Camera3d>>,
+    bodies: Query<(&Transform, &Radius), (With<CelestialBody>, Without<Camera3d>)>,
+) {
+    // The recorded path drives the camera during playback
+    if camera_path.playback.is_some() {
+        return;
+    }
+
+    for (mut transform, mut controller, mut projection, saved) in query.iter_mut() {
+        if key_input.just_pressed(KeyCode::KeyV) {
+            controller.adaptive_speed = !controller.adaptive_speed;
+            info!("Adaptive camera speed {}", if controller.adaptive_speed { "on" } else { "off" });
+        }
+        let speed = if controller.adaptive_speed {
+            bodies
+                .iter()
+                .map(|(body, radius)| (transform.translation.distance(body.translation) - radius.0).max(0.0))
+                .reduce(f32::min)
+                .map_or(controller.speed, |distance| adaptive_camera_speed(&controller, distance))
+        } else {
+            controller.speed
+        };
+
+        // Worked out as if Y were up, then turned to the configured up axis
+        let basis = config.up_axis.rotation_from_y_up();
+        let mut velocity = Vec3::ZERO;
+        let local_z = basis.inverse() * *transform.local_z();
+        let forward = basis * -Vec3::new(local_z.x, 0., local_z.z);
+        let right = basis * Vec3::new(local_z.z, 0., -local_z.x);
+
+        if key_input.pressed(KeyCode::KeyW) {
+            velocity += forward;
+        }
+        if key_input.pressed(KeyCode::KeyS) {
+            velocity -= forward;
+        }
+        if key_input.pressed(KeyCode::KeyA) {
+            velocity -= right;
+        }
+        if key_input.pressed(KeyCode::KeyD) {
+            velocity += right;
+        }
+        if key_input.pressed(KeyCode::Space) {
+            velocity += config.up_axis.up();
+        }
+        if key_input.pressed(KeyCode::ShiftLeft) {
+            velocity -= config.up_axis.up();
+        }
+
+        velocity = velocity.normalize_or_zero();
+        let step = velocity * time.delta_seconds() * speed;
+        transform.translation += step;
+        // Moving along the view doesn't change an orthographic picture, so zoom as much as the
+        // same move would in perspective
+        if let Projection::Orthographic(orthographic) = projection.as_mut() {
+            let fov = saved.map_or(PerspectiveProjection::default().fov, |saved| saved.0.fov);
+            let approach = step.dot(*transform.forward());
+            orthographic.scale = (orthographic.scale - approach * (fov / 2.0).tan()).max(MIN_ORTHOGRAPHIC_SCALE);
+        }
+
+        if mouse_button.pressed(MouseButton::Right) {
+            for mouse_event in mouse_events.read() {
+                let (mut yaw, mut pitch, _) = (basis.inverse() * transform.rotation).to_euler(EulerRot::YXZ);
+                yaw -= mouse_event.delta.x * controller.sensitivity * time.delta_seconds();
+                pitch -= mouse_event.delta.y * controller.sensitivity * time.delta_seconds();
+                pitch = pitch.clamp(-1.54, 1.54);
+
+                transform.rotation = basis * Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
+            }
+        }
+    }
+}
+
+// Smallest orthographic half-height, so zooming in can't flip the view
+pub const MIN_ORTHOGRAPHIC_SCALE: f32 = 0.1;
+
+/// The perspective projection to go back to after an orthographic stretch
+#[derive(Component)]
+pub struct SavedPerspective(pub PerspectiveProjection);
+
+/// Orthographic projection showing about what `perspective` shows at `distance` along the view,
+/// clipped at the same near plane
+pub fn matching_orthographic(perspective: &PerspectiveProjection, clip: &CameraClip, distance: f32) -> OrthographicProjection {
+    OrthographicProjection {
+        // Half the view height, since the vertical extent is fixed at 2
+        scale: (distance * (perspective.fov / 2.0).tan()).max(MIN_ORTHOGRAPHIC_SCALE),
+        scaling_mode: ScalingMode::FixedVertical(2.0),
+        near: clip.near,
+        far: perspective.far,
+        ..default()
+    }
+}
+
+/// Q switches the camera between perspective and orthographic projection, keeping where it is
+/// and which way it looks. The orthographic view is sized to match the perspective view at the
+/// system's barycenter.
+pub fn projection_toggle(
+    mut commands: Commands,
+    key_input: Res<ButtonInput<KeyCode>>,
+    clip: Res<CameraClip>,
+    mut cameras: Query<(Entity, &Transform, &mut Projection, Option<&SavedPerspective>), With<CameraController>>,
+    bodies: Query<(&Transform, &Mass), With<CelestialBody>>,
+) {
+    if !key_input.just_pressed(KeyCode::KeyQ) {
+        return;
+    }
+    let (weighted, total_mass) = bodies
+        .iter()
+        .fold((Vec3::ZERO, 0.0), |(sum, total), (transform, mass)| (sum + transform.translation * mass.0, total + mass.0));
+    for (entity, transform, mut projection, saved) in cameras.iter_mut() {
+        let next = match projection.as_ref() {
+            Projection::Perspective(perspective) => {
+                let distance = if total_mass > 0.0 {
+                    (weighted / total_mass - transform.translation).dot(*transform.forward())
+                } else {
+                    0.0
+                };
+                commands.entity(entity).insert(SavedPerspective(perspective.clone()));
+                info!("Orthographic camera");
+                Projection::Orthographic(matching_orthographic(perspective, &clip, distance))
+            }
+            Projection::Orthographic(_) => {
+                info!("Perspective camera");
+                Projection::Perspective(saved.map_or_else(|| clip.perspective(), |saved| saved.0.clone()))
+            }
+        };
+        *projection = next;
+    }
+}
+
+/// Push a camera at `position` out of any of the (center, radius) spheres it has entered, to
+/// `margin` above the surface. Only the inward part of the motion is undone, so a camera flying
+/// into a body slides along it instead of stopping dead.
+pub fn resolve_camera_collision(mut position: Vec3, bodies: &[(Vec3, f32)], margin: f32) -> Vec3 {
+    for &(center, radius) in bodies {
+        let offset = position - center;
+        let limit = radius + margin;
+        if offset.length_squared() < limit * limit {
+            // Dead center has no way out, so pick one
+            position = center + offset.try_normalize().unwrap_or(Vec3::Y) * limit;
+        }
+    }
+    position
+}
+
+/// N toggles camera collision
+pub fn camera_collision_toggle(key_input: Res<ButtonInput<KeyCode>>, mut collision: ResMut<CameraCollision>) {
+    if key_input.just_pressed(KeyCode::KeyN) {
+        collision.enabled = !collision.enabled;
+        info!("Camera collision: {}", if collision.enabled { "on" } else { "off" });
+    }
+}
+
+/// Keep the free camera outside every body
+#[allow(clippy::type_complexity)]
+pub fn camera_collision_system(
+    collision: Res<CameraCollision>,
+    camera_path: Res<CameraPath>,
+    mut cameras: Query<&mut Transform, (With<CameraController>, Without<CameraFlight>)>,
+    bodies: Query<(&Transform, &Radius), (With<CelestialBody>, Without<CameraController>)>,
+) {
+    // Scripted camera paths go where they were recorded
+    if !collision.enabled || camera_path.playback.is_some() {
+        return;
+    }
+    let spheres: Vec<(Vec3, f32)> = bodies
+        .iter()
+        .map(|(transform, radius)| (transform.translation, radius.0))
+        .collect();
+    for mut transform in cameras.iter_mut() {
+        let resolved = resolve_camera_collision(transform.translation, &spheres, collision.margin);
+        if resolved != transform.translation {
+            transform.translation = resolved;
+        }
+    }
+}
+
+/// Distance along the ray to the first hit on a sphere, if any
+pub fn ray_sphere_hit(origin: Vec3, direction: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+    let to_center = center - origin;
+    let along = to_center.dot(direction);
+    let closest_sq = to_center.length_squared() - along * along;
+    let radius_sq = radius * radius;
+    if closest_sq > radius_sq {
+        return None;
+    }
+    let half_chord = (radius_sq - closest_sq).sqrt();
+    let distance = if along - half_chord >= 0.0 { along - half_chord } else { along + half_chord };
+    (distance >= 0.0).then_some(distance)
+}
+
+/// Point where a ray crosses a plane, if it hits the plane in front of the ray origin
+pub fn ray_plane_intersection(origin: Vec3, direction: Vec3, plane_point: Vec3, plane_normal: Vec3) -> Option<Vec3> {
+    let denominator = direction.dot(plane_normal);
+    if denominator.abs() < 1e-6 {
+        return None;
+    }
+    let distance = (plane_point - origin).dot(plane_normal) / denominator;
+    (distance >= 0.0).then(|| origin + direction * distance)
+}
+
+/// Smallest sphere (approximately) containing every given sphere, or `None` if there are none
+pub fn bounding_sphere(spheres: &[(Vec3, f32)]) -> Option<(Vec3, f32)> {
+    if spheres.is_empty() {
+        return None;
+    }
+    let center = spheres.iter().map(|(position, _)| *position).sum::<Vec3>() / spheres.len() as f32;
+    let radius = spheres
+        .iter()
+        .map(|(position, radius)| position.distance(center) + radius)
+        .fold(0.0, f32::max);
+    Some((center, radius))
+}
+
+/// Where the camera starts, and where F flies back to when there is nothing to frame
+pub fn default_camera_transform(up_axis: UpAxis) -> Transform {
+    Transform::from_translation(up_axis.rotation_from_y_up() * Vec3::new(-50.0, 30.0, 50.0)).looking_at(Vec3::ZERO, up_axis.up())
+}
+
+/// How far back a camera with this field of view has to be to fit the sphere in the view
+pub fn framing_distance(radius: f32, fov: f32) -> f32 {
+    // Leave a little margin around the sphere
+    radius * 1.2 / (fov / 2.0).sin()
+}
+
+/// Camera transform that keeps looking along `view_direction` and fits the sphere in the view
+pub fn framing_transform(center: Vec3, radius: f32, fov: f32, view_direction: Vec3, up_axis: UpAxis) -> Transform {
+    let distance = framing_distance(radius, fov);
+    let direction = view_direction.try_normalize().unwrap_or(Vec3::NEG_Z);
+    Transform::from_translation(center - direction * distance).looking_at(center, up_axis.up())
+}
+
+// Seconds a framing flight takes
+pub const CAMERA_FLIGHT_SECONDS: f32 = 1.0;
+
+/// F frames the selected bodies, or the whole system when nothing is selected. An empty system
+/// gets the starting view back.
+#[allow(clippy::type_complexity)]
+pub fn frame_selection_system(
+    mut commands: Commands,
+    key_input: Res<ButtonInput<KeyCode>>,
+    config: Res<PhysicsConfig>,
+    cameras: Query<(Entity, &Transform, &Projection), With<CameraController>>,
+    selected: Query<(&Transform, &Radius), (With<CelestialBody>, With<Selected>)>,
+    bodies: Query<(&Transform, &Radius), With<CelestialBody>>,
+) {
+    if !key_input.just_pressed(KeyCode::KeyF) {
+        return;
+    }
+    let Ok((camera, camera_transform, projection)) = cameras.get_single() else {
+        return;
+    };
+
+    let mut spheres: Vec<(Vec3, f32)> = selected
+        .iter()
+        .map(|(transform, radius)| (transform.translation, radius.0))
+        .collect();
+    if spheres.is_empty() {
+        spheres = bodies
+            .iter()
+            .map(|(transform, radius)| (transform.translation, radius.0))
+            .collect();
+    }
+    let fov = match projection {
+        Projection::Perspective(perspective) => perspective.fov,
+        Projection::Orthographic(_) => std::f32::consts::FRAC_PI_4,
+    };
+    let to = match bounding_sphere(&spheres) {
+        Some((center, radius)) => framing_transform(center, radius, fov, *camera_transform.forward(), config.up_axis),
+        None => default_camera_transform(config.up_axis),
+    };
+    commands.entity(camera).insert(CameraFlight {
+        from: *camera_transform,
+        to,
+        elapsed: 0.0,
+    });
+}
+
+/// Which way the \ key shows the selected body's orbit
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OrbitView {
+    /// Looking down the orbit normal, so the orbit is seen as a whole
+    #[default]
+    FaceOn,
+    /// Looking along the orbital plane with the orbit normal as up, so the orbit is a flat line
+    EdgeOn,
+}
+
+impl OrbitView {
+    pub fn next(self) -> Self {
+        match self {
+            OrbitView::FaceOn => OrbitView::EdgeOn,
+            OrbitView::EdgeOn => OrbitView::FaceOn,
+        }
+    }
+}
+
+/// The view the next press of \ flies to
+#[derive(Resource, Default)]
+pub struct NorthUpView {
+    pub next: OrbitView,
+}
+
+/// Camera transform `distance` from `center` showing the orbit with the given normal. Face-on
+/// keeps `toward_body` (in the plane) at the top of the screen; edge-on looks along it,
+/// with the normal as up so the plane lies horizontal.
+pub fn orbit_view_transform(view: OrbitView, center: Vec3, normal: Vec3, toward_body: Vec3, distance: f32) -> Transform {
+    match view {
+        OrbitView::FaceOn => Transform::from_translation(center + normal * distance).looking_at(center, toward_body),
+        OrbitView::EdgeOn => Transform::from_translation(center - toward_body * distance).looking_at(center, normal),
+    }
+}
+
+/// \ flies the camera to north-up views of the selected body's orbit about the body it
+/// orbits, alternating between face-on and edge-on
+#[allow(clippy::type_complexity)]
+pub fn north_up_view_system(
+    mut commands: Commands,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut view: ResMut<NorthUpView>,
+    cameras: Query<(Entity, &Transform, &Projection), With<CameraController>>,
+    selected: Query<(Entity, &Transform, &Velocity, &Radius), (With<Selected>, With<CelestialBody>)>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+) {
+    if !key_input.just_pressed(KeyCode::Backslash) {
+        return;
+    }
+    let (Ok((camera, camera_transform, projection)), Ok((entity, transform, velocity, radius))) =
+        (cameras.get_single(), selected.get_single())
+    else {
+        return;
+    };
+    // The body orbits whichever other body is most massive, as for the orbital plane
+    let Some((_, central_transform, central_velocity, _)) = bodies
+        .iter()
+        .filter(|(other, ..)| *other != entity)
+        .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))
+    else {
+        return;
+    };
+    let relative_position = transform.translation - central_transform.translation;
+    let Some(normal) = orbit_normal(relative_position, velocity.0 - central_velocity.0) else {
+        return;
+    };
+
+    let fov = match projection {
+        Projection::Perspective(perspective) => perspective.fov,
+        Projection::Orthographic(_) => std::f32::consts::FRAC_PI_4,
+    };
+    let distance = framing_distance(relative_position.length() + radius.0, fov);
+    commands.entity(camera).insert(CameraFlight {
+        from: *camera_transform,
+        to: orbit_view_transform(view.next, central_transform.translation, normal, relative_position.normalize(), distance),
+        elapsed: 0.0,
+    });
+    view.next = view.next.next();
+}
+
+/// Ease the camera along its current flight
+pub fn camera_flight_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut cameras: Query<(Entity, &mut Transform, &mut CameraFlight)>,
+) {
+    for (entity, mut transform, mut flight) in cameras.iter_mut() {
+        flight.elapsed += time.delta_seconds();
+        let t = (flight.elapsed / CAMERA_FLIGHT_SECONDS).min(1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+
+        transform.translation = flight.from.translation.lerp(flight.to.translation, eased);
+        transform.rotation = flight.from.rotation.slerp(flight.to.rotation, eased);
+
+        if t >= 1.0 {
+            commands.entity(entity).remove::<CameraFlight>();
+        }
+    }
+}
+
+/// Point `lead` seconds ahead of a body along its velocity
+pub fn lead_point(position: Vec3, velocity: Vec3, lead: f32) -> Vec3 {
+    position + velocity * lead
+}
+
+/// One damped spring step moving `point` towards `goal`, returning its new position and velocity
+pub fn spring_step(point: Vec3, velocity: Vec3, goal: Vec3, stiffness: f32, damping: f32, dt: f32) -> (Vec3, Vec3) {
+    let acceleration = (goal - point) * stiffness - velocity * damping;
+    let velocity = velocity + acceleration * dt;
+    (point + velocity * dt, velocity)
+}
+
+/// R starts following the selected body, or stops following
+#[allow(clippy::type_complexity)]
+pub fn follow_toggle(
+    mut commands: Commands,
+    key_input: Res<ButtonInput<KeyCode>>,
+    cameras: Query<(Entity, Has<FollowTarget>), With<CameraController>>,
+    selected: Query<(Entity, &Transform, &Name), (With<Selected>, With<CelestialBody>)>,
+) {
+    // Alt+R is the scenario reroll
+    if !key_input.just_pressed(KeyCode::KeyR) || key_input.pressed(KeyCode::AltLeft) {
+        return;
+    }
+    for (camera, following) in cameras.iter() {
+        if following {
+            commands.entity(camera).remove::<FollowTarget>();
+            info!("Stopped following");
+        } else if let Some((target, transform, name)) = selected.iter().next() {
+            commands.entity(camera).insert(FollowTarget {
+                target,
+                aim: transform.translation,
+                aim_velocity: Vec3::ZERO,
+            });
+            info!("Following {}", name.0);
+        }
+    }
+}
+
+/// Whether the distance ruler is drawn while following a body, toggled with 4
+#[derive(Resource)]
+pub struct DistanceRuler(pub bool);
+
+impl Default for DistanceRuler {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+// Roughly how many ticks the ruler is split into
+pub const RULER_TICKS: f32 = 10.0;
+
+/// Distance from the camera to the followed body's center and the distances of the ruler's
+/// ticks from the camera, at a round 1, 2 or 5 times a power of ten apart
+pub struct Ruler {
+    pub distance: f32,
+    pub ticks: Vec<f32>,
+}
+
+pub fn ruler(camera: Vec3, target: Vec3) -> Ruler {
+    let distance = camera.distance(target);
+    let rough = distance / RULER_TICKS;
+    let magnitude = 10f32.powf(rough.max(f32::MIN_POSITIVE).log10().floor());
+    let spacing = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(rough);
+    let ticks = (1..)
+        .map(|tick| tick as f32 * spacing)
+        .take_while(|tick| *tick < distance)
+        .collect();
+    Ruler { distance, ticks }
+}
+
+/// 4 shows and hides the distance ruler
+pub fn distance_ruler_toggle(key_input: Res<ButtonInput<KeyCode>>, mut shown: ResMut<DistanceRuler>) {
+    if key_input.just_pressed(KeyCode::Digit4) {
+        shown.0 = !shown.0;
+    }
+}
+
+/// While following a body, draw a ruler from just below the camera to its center with ticks and
+/// the distance written beside it
+pub fn distance_ruler_system(
+    mut gizmos: Gizmos,
+    mut contexts: EguiContexts,
+    shown: Res<DistanceRuler>,
+    cameras: Query<(&Camera, &GlobalTransform, &FollowTarget)>,
+    bodies: Query<(&Transform, &Name), With<CelestialBody>>,
+) {
+    if !shown.0 {
+        return;
+    }
+    let Ok((camera, camera_transform, follow)) = cameras.get_single() else {
+        return;
+    };
+    let Ok((target_transform, name)) = bodies.get(follow.target) else {
+        return;
+    };
+    let eye = camera_transform.translation();
+    let target = target_transform.translation;
+    let ruler = ruler(eye, target);
+    let Some(direction) = (target - eye).try_normalize() else {
+        return;
+    };
+
+    // Seen from the camera a line straight at the target is a dot, so it starts a little below
+    let start = eye + camera_transform.forward() - camera_transform.up() * 0.3;
+    let color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+    gizmos.line(start, target, color);
+    let side = direction.cross(camera_transform.up()).try_normalize().unwrap_or(camera_transform.right());
+    for &tick in &ruler.ticks {
+        let fraction = tick / ruler.distance;
+        let point = start.lerp(target, fraction);
+        // Ticks grow with distance so they look about the same size
+        let half = side * tick * 0.01;
+        gizmos.line(point - half, point + half, color);
+    }
+
+    let label_point = start.lerp(target, 0.5);
+    if let Some(position) = camera.world_to_viewport(camera_transform, label_point) {
+        egui::Area::new(egui::Id::new("distance_ruler"))
+            .fixed_pos(egui::pos2(position.x, position.y))
+            .interactable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.colored_label(egui::Color32::WHITE, format!("{} {:.1}", name.0, ruler.distance));
+            });
+    }
+}
+
+/// Carry the camera along with its target, aiming at a sprung point ahead of it so fast bodies
+/// stay in view. WASD still moves the camera relative to the aim point.
+#[allow(clippy::type_complexity)]
+pub fn follow_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<PhysicsConfig>,
+    camera_path: Res<CameraPath>,
+    mut cameras: Query<(Entity, &mut Transform, &CameraController, &mut FollowTarget), Without<CameraFlight>>,
+    bodies: Query<(&Transform, &Velocity), (With<CelestialBody>, Without<CameraController>)>,
+) {
+    if camera_path.playback.is_some() {
+        return;
+    }
+    let dt = time.delta_seconds();
+    for (camera, mut transform, controller, mut follow) in cameras.iter_mut() {
+        let Ok((target, velocity)) = bodies.get(follow.target) else {
+            commands.entity(camera).remove::<FollowTarget>();
+            continue;
+        };
+        let offset = transform.translation - follow.aim;
+        let goal = lead_point(target.translation, velocity.0, controller.follow_lead);
+        let (aim, aim_velocity) = spring_step(
+            follow.aim,
+            follow.aim_velocity,
+            goal,
+            controller.follow_stiffness,
+            controller.follow_damping,
+            dt,
+        );
+        follow.aim = aim;
+        follow.aim_velocity = aim_velocity;
+        transform.translation = aim + offset;
+        transform.look_at(aim, config.up_axis.up());
+    }
+}
+
+/// Whether a sphere overlaps the view of a perspective camera, the same test Bevy's frustum
+/// culling makes against each mesh's bounds
+pub fn sphere_in_view(camera_transform: &GlobalTransform, projection: &PerspectiveProjection, center: Vec3, radius: f32) -> bool {
+    let view_projection = projection.get_projection_matrix() * camera_transform.compute_matrix().inverse();
+    Frustum::from_view_projection(&view_projection)
+        .intersects_sphere(&bevy::render::primitives::Sphere { center: center.into(), radius }, true)
+}
+
+/// F2 logs, for every body, why it is or isn't being drawn
+pub fn visibility_report_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    cameras: Query<(&GlobalTransform, &Projection), With<CameraController>>,
+    bodies: Query<(&Name, &Transform, &Radius, &Visibility, &ViewVisibility), With<CelestialBody>>,
+) {
+    if !key_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+    let Ok((camera_transform, Projection::Perspective(projection))) = cameras.get_single() else {
+        return;
+    };
+    info!("Visibility of {} bodies:", bodies.iter().len());
+    for (name, transform, radius, visibility, view_visibility) in bodies.iter() {
+        let distance = transform.translation.distance(camera_transform.translation());
+        let in_view = sphere_in_view(camera_transform, projection, transform.translation, radius.0);
+        let reason = if *visibility == Visibility::Hidden {
+            "hidden"
+        } else if !in_view && distance > projection.far {
+            "beyond the far plane"
+        } else if !in_view {
+            "outside the view"
+        } else if !view_visibility.get() {
+            "culled"
+        } else {
+            "drawn"
+        };
+        info!(
+            "  {}: distance {distance:.1}, {} the frustum, {reason}",
+            name.0,
+            if in_view { "inside" } else { "outside" }
+        );
+    }
+}
+
+/// K records a keyframe, J plays or stops the path, F9 saves it and F10 loads it
+pub fn camera_path_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut camera_path: ResMut<CameraPath>,
+    cameras: Query<&Transform, With<CameraController>>,
+) {
+    if key_input.just_pressed(KeyCode::KeyK) {
+        if let Ok(transform) = cameras.get_single() {
+            camera_path.keyframes.push(*transform);
+            info!("Recorded camera keyframe {}", camera_path.keyframes.len());
+        }
+    }
+    if key_input.just_pressed(KeyCode::KeyJ) {
+        if camera_path.playback.is_some() {
+            camera_path.playback = None;
+        } else if camera_path.keyframes.len() >= 2 {
+            camera_path.playback = Some(0.0);
+        } else {
+            warn!("Record at least two camera keyframes (K) before playing the path");
+        }
+    }
+    if key_input.just_pressed(KeyCode::F9) {
+        match std::fs::write(CAMERA_PATH_FILE, camera_path.to_text()) {
+            Ok(()) => info!("Saved {} keyframes to {CAMERA_PATH_FILE}", camera_path.keyframes.len()),
+            Err(err) => warn!("Could not save {CAMERA_PATH_FILE}: {err}"),
+        }
+    }
+    if key_input.just_pressed(KeyCode::F10) {
+        match std::fs::read_to_string(CAMERA_PATH_FILE)
+            .map_err(|err| err.to_string())
+            .and_then(|text| CameraPath::from_text(&text))
+        {
+            Ok(keyframes) => {
+                info!("Loaded {} keyframes from {CAMERA_PATH_FILE}", keyframes.len());
+                camera_path.keyframes = keyframes;
+                camera_path.playback = None;
+            }
+            Err(err) => warn!("Could not load {CAMERA_PATH_FILE}: {err}"),
+        }
+    }
+}
+
+/// Move the camera along the recorded path while playing
+pub fn camera_path_playback(
+    time: Res<Time>,
+    mut camera_path: ResMut<CameraPath>,
+    mut cameras: Query<&mut Transform, With<CameraController>>,
+) {
+    let Some(elapsed) = camera_path.playback else {
+        return;
+    };
+    let elapsed = elapsed + time.delta_seconds();
+    let t = elapsed / camera_path.duration;
+
+    if let (Some(sample), Ok(mut transform)) = (camera_path.sample(t), cameras.get_single_mut()) {
+        *transform = sample;
+    }
+    camera_path.playback = (t < 1.0).then_some(elapsed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{headless_app, movement_system};
+    use crate::scene::BodySpec;
+
+    fn spec(name: &str, position: Vec3) -> BodySpec {
+        BodySpec {
+            name: name.to_string(),
+            mass: 1.0,
+            radius: 1.0,
+            position,
+            velocity: Vec3::ZERO,
+            color: Color::WHITE,
+            atmosphere: None,
+        }
+    }
+
+    fn controller() -> CameraController {
+        CameraController {
+            sensitivity: 0.0,
+            speed: 0.0,
+            adaptive_speed: false,
+            min_speed: 0.0,
+            max_speed: 0.0,
+            follow_stiffness: 0.0,
+            follow_damping: 0.0,
+            follow_lead: 0.0,
+        }
+    }
+
+    fn press(app: &mut App, key: KeyCode) {
+        let mut input = ButtonInput::<KeyCode>::default();
+        input.press(key);
+        app.insert_resource(input);
+    }
+
+    #[test]
+    fn framing_a_selected_pair_fits_just_the_pair() {
+        let mut app = headless_app(&[
+            spec("Inner", Vec3::ZERO),
+            spec("Middle", Vec3::X * 10.0),
+            spec("Outer", Vec3::X * 500.0),
+        ]);
+        app.add_systems(Update, frame_selection_system);
+        let mut names = app.world.query::<(Entity, &Name)>();
+        let pair: Vec<Entity> = names
+            .iter(&app.world)
+            .filter(|(_, name)| name.0 != "Outer")
+            .map(|(entity, _)| entity)
+            .collect();
+        for &body in &pair {
+            app.world.entity_mut(body).insert(Selected);
+        }
+        let camera = app
+            .world
+            .spawn((Transform::from_xyz(0.0, 0.0, 100.0), Projection::default(), controller()))
+            .id();
+        press(&mut app, KeyCode::KeyF);
+        app.update();
+
+        let to = GlobalTransform::from(app.world.get::<CameraFlight>(camera).expect("F starts a flight").to);
+        let projection = PerspectiveProjection::default();
+        for position in [Vec3::ZERO, Vec3::X * 10.0] {
+            // The whole body, not just some of it, is inside the view cone
+            let offset = position - to.translation();
+            let edge = offset.angle_between(to.forward()) + (1.0 / offset.length()).asin();
+            assert!(edge < projection.fov / 2.0);
+        }
+        assert!(!sphere_in_view(&to, &projection, Vec3::X * 500.0, 1.0));
+    }
+
+    #[test]
+    fn camera_path_midpoint_lies_between_its_keyframes() {
+        let (from, to) = (Vec3::new(0.0, 5.0, 40.0), Vec3::new(30.0, 10.0, -20.0));
+        let path = CameraPath {
+            keyframes: vec![
+                Transform::from_translation(from).looking_at(Vec3::ZERO, Vec3::Y),
+                Transform::from_translation(to).looking_at(Vec3::ZERO, Vec3::Y),
+            ],
+            ..default()
+        };
+        let midpoint = path.sample(0.5).expect("two keyframes make a path");
+
+        // With only two keyframes the spline runs straight between them
+        assert!(midpoint.translation.distance((from + to) / 2.0) < 1e-4);
+        let rotations = (path.keyframes[0].rotation, path.keyframes[1].rotation);
+        let half_turn = rotations.0.angle_between(rotations.1) / 2.0;
+        assert!((midpoint.rotation.angle_between(rotations.0) - half_turn).abs() < 1e-3);
+        assert!((midpoint.rotation.angle_between(rotations.1) - half_turn).abs() < 1e-3);
+    }
+
+    #[test]
+    fn camera_path_text_round_trips() {
+        let path = CameraPath {
+            keyframes: vec![
+                Transform::from_xyz(1.5, -2.25, 3.0).looking_at(Vec3::ZERO, Vec3::Y),
+                Transform::from_xyz(-40.0, 12.0, 0.125).looking_at(Vec3::X, Vec3::Y),
+                Transform::from_xyz(0.0, 100.0, 0.1).looking_at(Vec3::ZERO, Vec3::Z),
+            ],
+            ..default()
+        };
+        let loaded = CameraPath::from_text(&path.to_text()).unwrap();
+
+        assert_eq!(loaded.len(), path.keyframes.len());
+        for (loaded, saved) in loaded.iter().zip(&path.keyframes) {
+            assert_eq!(loaded.translation, saved.translation);
+            assert!(loaded.rotation.angle_between(saved.rotation) < 1e-3);
+        }
+        assert!(CameraPath::from_text("1 2 3").is_err());
+    }
+
+    #[test]
+    fn flying_into_a_body_stops_at_its_surface_margin() {
+        let mut app = headless_app(&[spec("Sun", Vec3::ZERO)]);
+        app.init_resource::<CameraCollision>()
+            .init_resource::<CameraPath>()
+            .add_systems(Update, camera_collision_system);
+        let camera = app.world.spawn((Transform::from_xyz(0.0, 0.0, 10.0), controller())).id();
+        let margin = app.world.resource::<CameraCollision>().margin;
+
+        // Fly straight at the center, well past where the surface is
+        for _ in 0..20 {
+            app.world.get_mut::<Transform>(camera).unwrap().translation.z -= 0.75;
+            app.update();
+        }
+        let position = app.world.get::<Transform>(camera).unwrap().translation;
+        assert!((position.length() - (1.0 + margin)).abs() < 1e-5);
+        assert!(position.z > 0.0);
+
+        // With collision off the camera can fly inside
+        app.world.resource_mut::<CameraCollision>().enabled = false;
+        app.world.get_mut::<Transform>(camera).unwrap().translation = Vec3::Z * 0.25;
+        app.update();
+        assert_eq!(app.world.get::<Transform>(camera).unwrap().translation, Vec3::Z * 0.25);
+    }
+
+    #[test]
+    fn adaptive_speed_is_faster_far_from_every_body() {
+        let step_from = |distance: f32| {
+            let mut app = headless_app(&[spec("Sun", Vec3::ZERO)]);
+            app.add_event::<MouseMotion>()
+                .init_resource::<ButtonInput<MouseButton>>()
+                .init_resource::<CameraPath>()
+                .add_systems(Update, camera_controller);
+            press(&mut app, KeyCode::KeyW);
+            let camera = app
+                .world
+                .spawn((
+                    Camera3d::default(),
+                    Transform::from_xyz(0.0, 0.0, distance),
+                    Projection::default(),
+                    CameraController {
+                        speed: 25.0,
+                        adaptive_speed: true,
+                        min_speed: 1.0,
+                        max_speed: 1000.0,
+                        ..controller()
+                    },
+                ))
+                .id();
+            // The first update only starts the clock
+            app.update();
+            let before = app.world.get::<Transform>(camera).unwrap().translation;
+            app.update();
+            app.world.get::<Transform>(camera).unwrap().translation.distance(before)
+        };
+
+        let (near, far) = (step_from(5.0), step_from(500.0));
+        assert!(near > 0.0);
+        assert!(far > near * 10.0);
+    }
+
+    #[test]
+    fn follow_camera_aims_ahead_of_a_moving_target() {
+        let velocity = Vec3::new(3.0, 0.0, -4.0);
+        let mut app = headless_app(&[BodySpec {
+            velocity,
+            ..spec("Comet", Vec3::ZERO)
+        }]);
+        app.init_resource::<CameraPath>()
+            .add_systems(Update, follow_system.after(movement_system));
+        let target = app.world.query_filtered::<Entity, With<CelestialBody>>().single(&app.world);
+        let (stiffness, damping, lead) = (100.0, 20.0, 0.5);
+        let camera = app
+            .world
+            .spawn((
+                Transform::from_xyz(0.0, 10.0, 30.0),
+                CameraController {
+                    follow_stiffness: stiffness,
+                    follow_damping: damping,
+                    follow_lead: lead,
+                    ..controller()
+                },
+                FollowTarget {
+                    target,
+                    aim: Vec3::ZERO,
+                    aim_velocity: Vec3::ZERO,
+                },
+            ))
+            .id();
+        for _ in 0..600 {
+            app.update();
+        }
+
+        // Once settled the spring trails its goal by damping / stiffness seconds of motion, less
+        // the 60 Hz step the aim has just taken towards it
+        let ahead = app.world.get::<FollowTarget>(camera).unwrap().aim
+            - app.world.get::<Transform>(target).unwrap().translation;
+        let expected = velocity * (lead - damping / stiffness + 1.0 / 60.0);
+        assert!(ahead.dot(velocity) > 0.0);
+        assert!(ahead.distance(expected) < 1e-2 * expected.length());
+    }
+
+    #[test]
+    fn body_ahead_is_in_view_and_behind_is_not() {
+        let camera = GlobalTransform::from(Transform::from_xyz(0.0, 5.0, 20.0).looking_at(Vec3::new(0.0, 5.0, 0.0), Vec3::Y));
+        let projection = PerspectiveProjection::default();
+
+        assert!(sphere_in_view(&camera, &projection, Vec3::new(0.0, 5.0, 0.0), 1.0));
+        assert!(!sphere_in_view(&camera, &projection, Vec3::new(0.0, 5.0, 40.0), 1.0));
+    }
+
+    #[test]
+    fn projection_toggle_swaps_the_projection_and_keeps_the_camera() {
+        let clip = CameraClip { near: 0.5, ..default() };
+        let mut app = App::new();
+        app.insert_resource(clip).add_systems(Update, projection_toggle);
+        let camera_transform = Transform::from_xyz(-50.0, 30.0, 50.0).looking_at(Vec3::ZERO, Vec3::Y);
+        let camera = app.world.spawn((camera_transform, Projection::Perspective(clip.perspective()), controller())).id();
+        let toggle = |app: &mut App| {
+            press(app, KeyCode::KeyQ);
+            app.update();
+            assert_eq!(*app.world.get::<Transform>(camera).unwrap(), camera_transform);
+            app.world.get::<Projection>(camera).unwrap().clone()
+        };
+
+        // Both projections clip at the configured near plane
+        assert!(matches!(toggle(&mut app), Projection::Orthographic(orthographic) if orthographic.near == clip.near));
+        assert!(matches!(toggle(&mut app), Projection::Perspective(perspective) if perspective.near == clip.near));
+    }
+
+    #[test]
+    fn north_up_views_follow_the_orbit_normal() {
+        let normal = Vec3::new(0.3, 1.0, -0.5).normalize();
+        let toward_body = normal.any_orthonormal_vector();
+        let center = Vec3::new(5.0, -2.0, 7.0);
+        let face_on = orbit_view_transform(OrbitView::FaceOn, center, normal, toward_body, 50.0);
+        let edge_on = orbit_view_transform(OrbitView::EdgeOn, center, normal, toward_body, 50.0);
+
+        // Face-on looks straight down the normal; edge-on keeps it as up
+        assert!((face_on.forward().dot(-normal) - 1.0).abs() < 1e-4);
+        assert!((edge_on.up().dot(normal) - 1.0).abs() < 1e-4);
+        assert!(edge_on.forward().dot(normal).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ruler_measures_the_camera_distance_with_even_ticks() {
+        let (eye, target) = (Vec3::new(3.0, 40.0, -12.0), Vec3::new(-20.0, 1.0, 35.0));
+        let measured = ruler(eye, target);
+
+        assert!((measured.distance - eye.distance(target)).abs() / eye.distance(target) < 1e-4);
+        assert!(*measured.ticks.last().unwrap() < measured.distance);
+        for pair in measured.ticks.windows(2) {
+            assert!((pair[1] - pair[0] - measured.ticks[0]).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn fitted_far_clip_reaches_past_the_farthest_body() {
+        // A system far bigger than the default far plane
+        let spheres = [(Vec3::new(4000.0, 0.0, 0.0), 30.0), (Vec3::new(-2500.0, 0.0, 3200.0), 5.0), (Vec3::ZERO, 50.0)];
+        let (center, radius) = bounding_sphere(&spheres).unwrap();
+        let camera = framing_transform(center, radius, PerspectiveProjection::default().fov, Vec3::new(1.0, -1.0, -1.0), UpAxis::Y).translation;
+
+        let far = far_clip_distance(camera, &spheres, CameraClip::default().far);
+        for (position, radius) in spheres {
+            assert!(far > camera.distance(position) + radius);
+        }
+    }
+}
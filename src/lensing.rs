@@ -0,0 +1,196 @@
+//! Fullscreen post-process that bends the frame around a massive body, evoking gravitational lensing
+
+use bevy::prelude::*;
+use bevy::core_pipeline::core_3d::graph::{Core3d, Node3d};
+use bevy::core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state;
+use bevy::ecs::query::QueryItem;
+use bevy::render::extract_component::{
+    ComponentUniforms, ExtractComponentPlugin, UniformComponentPlugin,
+};
+use bevy::render::render_graph::{
+    NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+};
+use bevy::render::render_resource::binding_types::{sampler, texture_2d, uniform_buffer};
+use bevy::render::render_resource::{
+    BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId,
+    ColorTargetState, ColorWrites, FragmentState, MultisampleState, Operations, PipelineCache,
+    PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
+    Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages, TextureFormat,
+    TextureSampleType,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::ViewTarget;
+use bevy::render::RenderApp;
+
+pub use uniforms::LensingSettings;
+
+mod uniforms {
+    // ShaderType's generated layout checks trip dead_code on newer compilers, and an allow on
+    // the struct itself doesn't reach derived code
+    #![allow(dead_code)]
+
+    use bevy::prelude::*;
+    use bevy::render::extract_component::ExtractComponent;
+    use bevy::render::render_resource::ShaderType;
+
+    /// Uniforms for the lensing pass, kept on the camera while lensing is on
+    #[derive(Component, Default, Clone, Copy, PartialEq, Debug, ExtractComponent, ShaderType)]
+    pub struct LensingSettings {
+        /// Lens position in uv coordinates, (0, 0) top left
+        pub center: Vec2,
+        /// Squared Einstein radius, in units of screen height
+        pub strength: f32,
+        /// Viewport width over height
+        pub aspect: f32,
+    }
+}
+
+/// Fullscreen pass that distorts the frame around the most massive body
+pub struct LensingPlugin;
+
+impl Plugin for LensingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            ExtractComponentPlugin::<LensingSettings>::default(),
+            UniformComponentPlugin::<LensingSettings>::default(),
+        ));
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_graph_node::<ViewNodeRunner<LensingNode>>(Core3d, LensingLabel)
+            .add_render_graph_edges(
+                Core3d,
+                (Node3d::Tonemapping, LensingLabel, Node3d::EndMainPassPostProcessing),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<LensingPipeline>();
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct LensingLabel;
+
+#[derive(Default)]
+struct LensingNode;
+
+impl ViewNode for LensingNode {
+    type ViewQuery = (&'static ViewTarget, &'static LensingSettings);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, _settings): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let lensing_pipeline = world.resource::<LensingPipeline>();
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(lensing_pipeline.pipeline_id(view_target))
+        else {
+            // Still compiling
+            return Ok(());
+        };
+        let Some(settings_binding) = world.resource::<ComponentUniforms<LensingSettings>>().uniforms().binding() else {
+            return Ok(());
+        };
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "lensing_bind_group",
+            &lensing_pipeline.layout,
+            &BindGroupEntries::sequential((post_process.source, &lensing_pipeline.sampler, settings_binding)),
+        );
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("lensing_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+struct LensingPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline_id: CachedRenderPipelineId,
+    /// Same pipeline for HDR cameras, whose main texture has a different format
+    hdr_pipeline_id: CachedRenderPipelineId,
+}
+
+impl LensingPipeline {
+    fn pipeline_id(&self, view_target: &ViewTarget) -> CachedRenderPipelineId {
+        if view_target.is_hdr() {
+            self.hdr_pipeline_id
+        } else {
+            self.pipeline_id
+        }
+    }
+}
+
+impl FromWorld for LensingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(
+            "lensing_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer::<LensingSettings>(false),
+                ),
+            ),
+        );
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        // Embedded so the binary doesn't depend on an assets folder
+        let shader = world
+            .resource_mut::<Assets<Shader>>()
+            .add(Shader::from_wgsl(include_str!("lensing.wgsl"), "lensing.wgsl"));
+        let descriptor = |format| RenderPipelineDescriptor {
+            label: Some("lensing_pipeline".into()),
+            layout: vec![layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: vec![],
+        };
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline_id = pipeline_cache.queue_render_pipeline(descriptor(TextureFormat::bevy_default()));
+        let hdr_pipeline_id = pipeline_cache.queue_render_pipeline(descriptor(ViewTarget::TEXTURE_FORMAT_HDR));
+        Self {
+            layout,
+            sampler,
+            pipeline_id,
+            hdr_pipeline_id,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// Bends the rendered frame around the most massive body, evoking gravitational lensing.
+// Not physically accurate: it is a point-lens style radial remap in screen space.
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+struct LensingSettings {
+    // Lens position in uv coordinates
+    center: vec2<f32>,
+    // Squared Einstein radius, in units of screen height
+    strength: f32,
+    // Viewport width over height
+    aspect: f32,
+}
+@group(0) @binding(2) var<uniform> settings: LensingSettings;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    // Work in units of screen height so the distortion stays round
+    var offset = in.uv - settings.center;
+    offset.x *= settings.aspect;
+
+    // Sample closer to the lens than the pixel is, fading to no change far away
+    let r2 = dot(offset, offset);
+    offset *= r2 / (r2 + settings.strength);
+
+    offset.x /= settings.aspect;
+    return textureSample(screen_texture, texture_sampler, settings.center + offset);
+}
@@ -0,0 +1,466 @@
+//! A small N-body solar system sandbox built on Bevy
+//!
+//! [`SolarSystemPlugin`] wires up every resource and system; the binary only parses the command line,
+//! overrides the matching resources and adds the plugin next to `DefaultPlugins`.
+//! The two-body orbital math in [`orbital`] needs none of it and can be used on its own.
+
+pub mod camera;
+pub mod lensing;
+pub mod orbital;
+pub mod physics;
+pub mod render;
+pub mod scene;
+pub mod selftest;
+pub mod ui;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use std::time::Duration;
+use bevy_egui::{EguiPlugin, EguiSet};
+use crate::camera::{CameraClip, CameraCollision, CameraPath, DistanceRuler, NorthUpView, camera_collision_system, camera_collision_toggle, camera_controller, camera_flight_system, camera_path_input, camera_path_playback, distance_ruler_system, distance_ruler_toggle, far_clip_system, follow_system, follow_toggle, frame_selection_system, north_up_view_system, projection_toggle, visibility_report_input};
+use crate::physics::{AnalyticBinaries, AngularMomentumMonitor, Calendar, CollisionHistory, EjectionWarning, MassTransfer, OriginRebasing, Paused, PhysicsConfig, PhysicsProfile, PlanarMode, RadiationPressure, ReverseMode, analytic_binary_system, analytic_binary_toggle, angular_momentum_system, boundary_system, calendar_system, collision_history_system, collision_mode_toggle, collision_system, ejection_monitor_system, force_exponent_input, force_method_toggle, gravitational_constant_input, gravity_schedule_log, gravity_system, kepler_particle_system, maneuver_system, mass_transfer_system, mass_transfer_toggle, movement_system, patched_conics_active, pause_input, planar_mode_toggle, radiation_pressure_system, radiation_pressure_toggle, rebase_system, report_ejections, restabilize_input, reverse_mode_input, rotation_system, running_backward, running_forward, simulation_unpaused, softening_input, soi_system, time_scale_input, warn_physics_approximations};
+use crate::render::{AdaptiveQuality, AmbientOcclusion, ApsidalMarkers, BoundPairLines, BoundaryShown, FieldLines, GravitationalLensing, HabitableZone, LightingConfig, MeshQuality, OrbitCompleted, OrbitDirectionMarkers, OrbitStrobe, Palettes, ReferenceGrid, RenderQuality, SetupPreview, SofteningCores, TrailConfig, TrailFrame, TrailFrameHistory, TrailLegend, TrajectoryFan, adaptive_quality_system, adaptive_quality_toggle, ambient_occlusion_system, ambient_occlusion_toggle, angular_momentum_arrow_system, apply_palette, apply_render_quality, apsidal_markers_system, apsidal_markers_toggle, assign_palette_slots, bound_pair_system, bound_pair_toggle, boundary_draw_system, boundary_toggle, dominant_attractor_system, fading_trail_system, field_lines_system, field_lines_toggle, habitable_zone_system, habitable_zone_toggle, lensing_system, lensing_toggle, maneuver_preview_system, mass_transfer_stream_system, mesh_quality_input, orbit_counter_labels, orbit_counter_system, orbit_direction_system, orbit_direction_toggle, orbit_strobe_system, orbit_strobe_toggle, orbital_plane_system, palette_toggle, physical_light_system, physical_light_toggle, plan_setup_preview, reference_grid_system, regenerate_body_meshes, render_quality_input, setup_preview_system, shadow_casting_system, softening_core_system, softening_core_toggle, soi_draw_system, spawn_atmosphere_shells, trail_draw_system, trail_frame_system, trail_frame_toggle, trail_legend_overlay, trail_legend_toggle, trail_ribbon_system, trail_update_system, trajectory_fan_panel, trajectory_fan_system, trajectory_fan_toggle, unbound_highlight_system};
+use crate::scene::{BodyConfig, ClearScene, LaunchOptions, LoadedScene, RerollScenario, SaveMenu, Scenario, SimRng, apply_scenario_view, clear_scene_input, clear_scene_system, export_diagram_input, export_scene_input, load_slot_system, orient_scenario, reroll_input, reroll_scenario, save_slot_system, setup_scene, spawn_headless_scenario, spawn_headless_scene, spawn_kepler_particles, spawn_loaded_scene, spawn_scenario};
+use crate::ui::{ApsidalDrift, BodySearch, FlingDrag, InspectorOpen, MassHistogram, OrbitSpawner, KeyboardOverUi, PlacementDrag, PointerOverUi, SpawnPanel, VelocityScaling, anchor_toggle, apsidal_drift_plot, apsidal_drift_system, apsidal_drift_toggle, body_search_panel, body_search_toggle, calendar_overlay, fling_apply_system, fling_system, flyby_panel, hover_readout_overlay, inspector_panel, inspector_toggle, keyboard_not_over_ui, maneuver_panel, mass_histogram_panel, mass_histogram_toggle, orbit_progress_overlay, orbit_spawner_drag, orbit_spawner_inactive, orbit_spawner_panel, orbit_spawner_spawn, orbit_spawner_toggle, placement_apply_system, placement_drag_system, pointer_not_over_ui, profiler_overlay, profiler_toggle, save_menu_panel, save_menu_toggle, selection_system, spawn_panel, spawn_panel_toggle, track_keyboard_over_ui, track_pointer_over_ui, velocity_scaling_panel, velocity_scaling_toggle, window_title_system};
+use crate::lensing::LensingPlugin;
+
+/// Everything the simulator needs, configured builder-style
+///
+/// Resources already inserted before the plugin is added are kept unless the builder sets them,
+/// so callers can pick a scenario, physics settings or flags either way.
+#[derive(Clone)]
+pub struct SolarSystemPlugin {
+    scenario: Option<Scenario>,
+    physics: Option<PhysicsConfig>,
+    bodies: Option<Vec<BodyConfig>>,
+    camera: bool,
+    ui: bool,
+    deterministic: bool,
+}
+
+impl Default for SolarSystemPlugin {
+    fn default() -> Self {
+        Self {
+            scenario: None,
+            physics: None,
+            bodies: None,
+            camera: true,
+            ui: true,
+            deterministic: false,
+        }
+    }
+}
+
+impl SolarSystemPlugin {
+    /// Start with one of the built-in systems
+    pub fn scenario(mut self, scenario: Scenario) -> Self {
+        self.scenario = Some(scenario);
+        self
+    }
+
+    pub fn physics(mut self, physics: PhysicsConfig) -> Self {
+        self.physics = Some(physics);
+        self
+    }
+
+    /// Start with these bodies instead of a built-in system, as if loaded from a scene config
+    pub fn bodies(mut self, bodies: Vec<BodyConfig>) -> Self {
+        self.bodies = Some(bodies);
+        self
+    }
+
+    /// Spawn the camera and lights and draw the bodies, trails and overlays (needs the render and
+    /// input plugins). Without it the bodies are spawned bare and only simulated, which runs
+    /// under `MinimalPlugins`.
+    pub fn camera(mut self, camera: bool) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    /// Add the egui panels, mouse picking and keyboard shortcuts; only used with the camera
+    pub fn ui(mut self, ui: bool) -> Self {
+        self.ui = ui;
+        self
+    }
+
+    /// Make runs repeat exactly: step a fixed 1/60 s per frame instead of following the wall
+    /// clock, and keep adaptive render quality (which watches the frame rate) off. Forces are
+    /// already summed serially and randomness only comes from the seeded SimRng.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+}
+
+impl Plugin for SolarSystemPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(bodies) = &self.bodies {
+            app.insert_resource(LoadedScene(bodies.clone()))
+                .insert_resource(Scenario::Config);
+        } else if let Some(scenario) = self.scenario {
+            app.insert_resource(scenario);
+        }
+        if let Some(physics) = &self.physics {
+            app.insert_resource(physics.clone());
+        }
+        if let Some(scenario) = self.scenario.filter(|_| self.bodies.is_none()) {
+            scenario.adjust_physics(&mut app.world.get_resource_or_insert_with(PhysicsConfig::default));
+        }
+        if self.deterministic {
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+            app.world.get_resource_or_insert_with(AdaptiveQuality::default).enabled = false;
+        }
+        app
+            .init_resource::<PhysicsConfig>()
+            .init_resource::<TrailConfig>()
+            .init_resource::<MeshQuality>()
+            .init_resource::<RenderQuality>()
+            .init_resource::<AdaptiveQuality>()
+            .init_resource::<ReferenceGrid>()
+            .init_resource::<Calendar>()
+            .init_resource::<Palettes>()
+            .init_resource::<Paused>()
+            .init_resource::<SimRng>()
+            .init_resource::<Scenario>()
+            .init_resource::<LaunchOptions>()
+            .init_resource::<PlanarMode>()
+            .init_resource::<ReverseMode>()
+            .init_resource::<CollisionHistory>()
+            .init_resource::<InspectorOpen>()
+            .init_resource::<PointerOverUi>()
+            .init_resource::<KeyboardOverUi>()
+            .init_resource::<AngularMomentumMonitor>()
+            .init_resource::<PhysicsProfile>()
+            .init_resource::<CameraPath>()
+            .init_resource::<LightingConfig>()
+            .init_resource::<FlingDrag>()
+            .init_resource::<PlacementDrag>()
+            .init_resource::<SetupPreview>()
+            .init_resource::<CameraCollision>()
+            .init_resource::<CameraClip>()
+            .init_resource::<OrbitDirectionMarkers>()
+            .init_resource::<SaveMenu>()
+            .init_resource::<HabitableZone>()
+            .init_resource::<GravitationalLensing>()
+            .init_resource::<AmbientOcclusion>()
+            .init_resource::<OriginRebasing>()
+            .init_resource::<RadiationPressure>()
+            .init_resource::<MassTransfer>()
+            .init_resource::<ApsidalDrift>()
+            .init_resource::<MassHistogram>()
+            .init_resource::<BoundPairLines>()
+            .init_resource::<SofteningCores>()
+            .init_resource::<NorthUpView>()
+            .init_resource::<AnalyticBinaries>()
+            .init_resource::<TrailFrame>()
+            .init_resource::<OrbitSpawner>()
+            .init_resource::<SpawnPanel>()
+            .init_resource::<BodySearch>()
+            .init_resource::<DistanceRuler>()
+            .init_resource::<ApsidalMarkers>()
+            .init_resource::<OrbitStrobe>()
+            .init_resource::<FieldLines>()
+            .init_resource::<BoundaryShown>()
+            .init_resource::<VelocityScaling>()
+            .init_resource::<TrailLegend>()
+            .init_resource::<TrajectoryFan>()
+            .init_resource::<TrailFrameHistory>()
+            .add_event::<EjectionWarning>()
+            .add_event::<OrbitCompleted>()
+            .add_event::<RerollScenario>()
+            .add_event::<ClearScene>();
+
+        add_simulation_systems(app, self.camera);
+        if self.camera {
+            add_camera_systems(app);
+            if self.ui {
+                add_ui_systems(app);
+            }
+        }
+    }
+}
+
+/// Physics and scene spawning, with no rendering, windows or input
+fn add_simulation_systems(app: &mut App, camera: bool) {
+    if !camera {
+        app.add_systems(Startup, (spawn_headless_scenario, spawn_headless_scene.run_if(resource_exists::<LoadedScene>)))
+            .add_systems(Update, spawn_headless_scene.run_if(resource_exists::<LoadedScene>))
+            .add_systems(Update, spawn_headless_scenario.after(reroll_scenario).run_if(on_event::<RerollScenario>()));
+    }
+    app.add_systems(Startup, warn_physics_approximations)
+        .add_systems(Update, reroll_scenario.before(soi_system))
+        .add_systems(Update, clear_scene_system.after(reroll_scenario).before(soi_system))
+        .add_systems(Update, apply_scenario_view.run_if(resource_changed::<Scenario>).before(soi_system))
+        .add_systems(PostStartup, spawn_kepler_particles)
+        .add_systems(Update, kepler_particle_system.run_if(simulation_unpaused).after(rebase_system))
+        .add_systems(Update, rotation_system.run_if(simulation_unpaused).after(movement_system))
+        .add_systems(
+            Update,
+            (
+                soi_system.run_if(patched_conics_active),
+                collision_history_system.run_if(simulation_unpaused),
+                gravity_system.run_if(running_forward.and_then(simulation_unpaused)),
+                radiation_pressure_system.run_if(running_forward.and_then(simulation_unpaused)),
+                movement_system.run_if(simulation_unpaused),
+                gravity_system.run_if(running_backward.and_then(simulation_unpaused)),
+                radiation_pressure_system.run_if(running_backward.and_then(simulation_unpaused)),
+                collision_system.run_if(running_forward.and_then(simulation_unpaused)),
+            )
+                .chain(),
+        )
+        .add_systems(Update, rebase_system.after(collision_system).before(trail_update_system))
+        .add_systems(Update, boundary_system.after(collision_system).before(rebase_system))
+        .add_systems(Update, gravity_schedule_log)
+        .add_systems(
+            Update,
+            analytic_binary_system
+                .run_if(simulation_unpaused)
+                .after(collision_system)
+                .before(mass_transfer_system),
+        )
+        .add_systems(
+            Update,
+            (calendar_system, maneuver_system.run_if(running_forward.and_then(simulation_unpaused)))
+                .chain()
+                .after(reverse_mode_input)
+                .before(soi_system),
+        )
+        .add_systems(
+            Update,
+            mass_transfer_system
+                .run_if(running_forward.and_then(simulation_unpaused))
+                .after(collision_system)
+                .before(rebase_system),
+        )
+        .add_systems(Update, angular_momentum_system.after(movement_system))
+        .add_systems(Update, (ejection_monitor_system, report_ejections).chain().after(collision_system));
+}
+
+/// The camera, lights, meshes, trails and gizmo overlays
+fn add_camera_systems(app: &mut App) {
+    app.add_plugins(LensingPlugin)
+        .add_systems(Startup, (setup_scene, spawn_scenario, spawn_loaded_scene.run_if(resource_exists::<LoadedScene>)))
+        .add_systems(Startup, orient_scenario.after(spawn_scenario))
+        .add_systems(Update, (spawn_scenario, orient_scenario).chain().after(reroll_scenario).run_if(on_event::<RerollScenario>()))
+        .add_systems(PostStartup, plan_setup_preview)
+        .add_systems(Update, spawn_loaded_scene.run_if(resource_exists::<LoadedScene>).after(load_slot_system))
+        .add_systems(Update, shadow_casting_system.after(spawn_loaded_scene))
+        .add_systems(Update, visibility_report_input.run_if(keyboard_not_over_ui))
+        .add_systems(Update, camera_controller.run_if(keyboard_not_over_ui).after(collision_system))
+        .add_systems(Update, setup_preview_system.after(pause_input))
+        .add_systems(Update, orbital_plane_system.after(movement_system).after(selection_system))
+        .add_systems(Update, (boundary_draw_system, reference_grid_system, spawn_atmosphere_shells))
+        .add_systems(Update, (trail_update_system, trail_frame_system, trail_draw_system).chain().after(movement_system))
+        .add_systems(Update, fading_trail_system.before(trail_draw_system))
+        .add_systems(Update, trail_ribbon_system.after(fading_trail_system).after(trail_frame_system).after(camera_controller))
+        .add_systems(Update, (adaptive_quality_system, apply_render_quality, ambient_occlusion_system, regenerate_body_meshes).chain())
+        .add_systems(Update, (assign_palette_slots, apply_palette).chain().before(physical_light_system))
+        .add_systems(Update, physical_light_system.after(movement_system))
+        .add_systems(Update, lensing_system.after(camera_controller))
+        .add_systems(Update, projection_toggle.run_if(keyboard_not_over_ui).before(camera_controller))
+        .add_systems(Update, softening_core_system)
+        .add_systems(Update, bound_pair_system.after(movement_system))
+        .add_systems(Update, (maneuver_preview_system, trajectory_fan_system).after(selection_system))
+        .add_systems(Update, mass_transfer_stream_system.after(mass_transfer_system).before(rebase_system))
+        .add_systems(Update, (follow_toggle.run_if(keyboard_not_over_ui), follow_system).chain().after(camera_controller).before(camera_collision_system))
+        .add_systems(Update, (camera_collision_toggle.run_if(keyboard_not_over_ui), camera_collision_system).chain().after(camera_controller))
+        .add_systems(Update, (frame_selection_system.run_if(keyboard_not_over_ui), north_up_view_system.run_if(keyboard_not_over_ui), camera_flight_system).chain().after(camera_collision_system))
+        .add_systems(Update, (camera_path_input.run_if(keyboard_not_over_ui), camera_path_playback).chain().after(camera_flight_system))
+        .add_systems(Update, far_clip_system.after(camera_path_playback))
+        .add_systems(Update, (apsidal_markers_system, habitable_zone_system, orbit_direction_system).after(movement_system))
+        .add_systems(Update, field_lines_system.after(movement_system))
+        .add_systems(Update, (orbit_counter_system.run_if(simulation_unpaused), orbit_strobe_system).chain().after(movement_system))
+        .add_systems(Update, soi_draw_system.run_if(patched_conics_active).after(movement_system))
+        .add_systems(Update, angular_momentum_arrow_system.after(angular_momentum_system))
+        .add_systems(Update, unbound_highlight_system.after(report_ejections));
+}
+
+/// Egui panels and overlays, mouse picking and the keyboard shortcuts
+fn add_ui_systems(app: &mut App) {
+    if !app.is_plugin_added::<EguiPlugin>() {
+        app.add_plugins(EguiPlugin);
+    }
+    app.add_systems(PreUpdate, track_keyboard_over_ui.after(EguiSet::BeginFrame))
+        .add_systems(Update, ((export_scene_input, export_diagram_input).run_if(keyboard_not_over_ui), window_title_system))
+        .add_systems(Update, (save_menu_toggle.run_if(keyboard_not_over_ui), save_menu_panel, save_slot_system, load_slot_system).chain().before(pause_input))
+        .add_systems(
+            Update,
+            (pause_input, planar_mode_toggle, collision_mode_toggle, force_method_toggle, force_exponent_input, gravitational_constant_input, restabilize_input, reverse_mode_input)
+                .chain()
+                .run_if(keyboard_not_over_ui)
+                .before(soi_system),
+        )
+        .add_systems(
+            Update,
+            (track_pointer_over_ui, selection_system.run_if(pointer_not_over_ui).run_if(keyboard_not_over_ui))
+                .chain()
+                .after(movement_system)
+                .before(orbital_plane_system),
+        )
+        .add_systems(Update, hover_readout_overlay.after(track_pointer_over_ui))
+        .add_systems(
+            Update,
+            (fling_system, fling_apply_system)
+                .chain()
+                .run_if(orbit_spawner_inactive)
+                .run_if(simulation_unpaused)
+                .after(track_pointer_over_ui),
+        )
+        .add_systems(
+            Update,
+            (placement_drag_system.run_if(keyboard_not_over_ui), placement_apply_system)
+                .chain()
+                .run_if(orbit_spawner_inactive)
+                .run_if(not(simulation_unpaused))
+                .after(track_pointer_over_ui),
+        )
+        .add_systems(
+            Update,
+            (
+                orbit_spawner_toggle.run_if(keyboard_not_over_ui),
+                orbit_spawner_panel,
+                orbit_spawner_drag,
+                orbit_spawner_spawn,
+                spawn_panel_toggle.run_if(keyboard_not_over_ui),
+                spawn_panel,
+                body_search_toggle.run_if(keyboard_not_over_ui),
+                body_search_panel,
+                velocity_scaling_toggle.run_if(keyboard_not_over_ui),
+                velocity_scaling_panel,
+            )
+                .chain()
+                .after(track_pointer_over_ui),
+        )
+        .add_systems(Update, boundary_toggle.run_if(keyboard_not_over_ui).before(boundary_draw_system))
+        .add_systems(Update, trail_frame_toggle.run_if(keyboard_not_over_ui).after(movement_system).before(trail_update_system))
+        .add_systems(Update, (trail_legend_toggle.run_if(keyboard_not_over_ui), trail_legend_overlay).chain())
+        .add_systems(Update, (render_quality_input, adaptive_quality_toggle).chain().run_if(keyboard_not_over_ui).before(adaptive_quality_system))
+        .add_systems(Update, mesh_quality_input.run_if(keyboard_not_over_ui).after(apply_render_quality).before(regenerate_body_meshes))
+        .add_systems(Update, palette_toggle.run_if(keyboard_not_over_ui).before(assign_palette_slots))
+        .add_systems(Update, physical_light_toggle.run_if(keyboard_not_over_ui).after(movement_system).before(physical_light_system))
+        .add_systems(Update, lensing_toggle.run_if(keyboard_not_over_ui).after(camera_controller).before(lensing_system))
+        .add_systems(Update, ambient_occlusion_toggle.run_if(keyboard_not_over_ui).before(ambient_occlusion_system))
+        .add_systems(Update, field_lines_toggle.run_if(keyboard_not_over_ui).before(field_lines_system))
+        .add_systems(Update, analytic_binary_toggle.run_if(keyboard_not_over_ui).after(collision_system).before(analytic_binary_system))
+        .add_systems(Update, (softening_input, softening_core_toggle).chain().run_if(keyboard_not_over_ui).before(softening_core_system))
+        .add_systems(Update, bound_pair_toggle.run_if(keyboard_not_over_ui).after(movement_system).before(bound_pair_system))
+        .add_systems(Update, (radiation_pressure_toggle, mass_transfer_toggle).run_if(keyboard_not_over_ui).before(pause_input))
+        .add_systems(Update, time_scale_input.run_if(keyboard_not_over_ui).after(reverse_mode_input).before(calendar_system))
+        .add_systems(Update, maneuver_panel.after(selection_system).before(maneuver_preview_system))
+        .add_systems(Update, (trajectory_fan_toggle.run_if(keyboard_not_over_ui), trajectory_fan_panel).chain().after(selection_system).before(trajectory_fan_system))
+        .add_systems(Update, calendar_overlay)
+        .add_systems(Update, reroll_input.run_if(keyboard_not_over_ui).before(reroll_scenario))
+        .add_systems(Update, clear_scene_input.run_if(keyboard_not_over_ui).before(clear_scene_system))
+        .add_systems(Update, (distance_ruler_toggle.run_if(keyboard_not_over_ui), distance_ruler_system).chain().after(camera_flight_system))
+        .add_systems(Update, (inspector_toggle.run_if(keyboard_not_over_ui), inspector_panel).chain())
+        .add_systems(Update, (mass_histogram_toggle.run_if(keyboard_not_over_ui), mass_histogram_panel).chain().after(collision_system))
+        .add_systems(Update, (orbit_progress_overlay, flyby_panel, dominant_attractor_system, orbit_counter_labels.after(orbit_counter_system)).after(orbital_plane_system))
+        .add_systems(
+            Update,
+            (apsidal_drift_toggle.run_if(keyboard_not_over_ui), apsidal_drift_system.run_if(simulation_unpaused), apsidal_drift_plot)
+                .chain()
+                .after(selection_system),
+        )
+        .add_systems(
+            Update,
+            (apsidal_markers_toggle, habitable_zone_toggle, orbit_direction_toggle, orbit_strobe_toggle)
+                .run_if(keyboard_not_over_ui)
+                .after(movement_system)
+                .before(orbit_counter_system)
+                .before(apsidal_markers_system)
+                .before(habitable_zone_system)
+                .before(orbit_direction_system),
+        )
+        .add_systems(Update, anchor_toggle.run_if(keyboard_not_over_ui).after(selection_system))
+        .add_systems(Update, (profiler_toggle.run_if(keyboard_not_over_ui), profiler_overlay).chain().after(collision_system));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashMap;
+    use crate::physics::{CelestialBody, Mass, Name, Star, body_states};
+
+    #[test]
+    fn plugin_spawns_and_steps_the_default_system_without_a_window() {
+        let mut app = App::new();
+        app.add_plugins((
+            DefaultPlugins
+                .build()
+                .disable::<bevy::winit::WinitPlugin>()
+                .disable::<bevy::log::LogPlugin>()
+                .set(bevy::render::RenderPlugin {
+                    render_creation: bevy::render::settings::WgpuSettings { backends: None, ..default() }.into(),
+                    ..default()
+                }),
+            SolarSystemPlugin::default(),
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+        app.finish();
+        app.cleanup();
+        app.update();
+
+        let mut planets = app.world.query_filtered::<(Entity, &Transform), (With<CelestialBody>, Without<Star>)>();
+        let before: HashMap<Entity, Vec3> = planets.iter(&app.world).map(|(entity, transform)| (entity, transform.translation)).collect();
+        assert!(!before.is_empty());
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(app.world.query_filtered::<(), With<CelestialBody>>().iter(&app.world).count(), 4);
+        for (entity, transform) in planets.iter(&app.world) {
+            assert_ne!(before[&entity], transform.translation);
+        }
+    }
+
+    #[test]
+    fn plugin_spawns_exactly_the_given_bodies() {
+        let body = |name: &str, mass, x: f32| BodyConfig::new(name, mass, 1.0, [x, 0.0, 0.0], [0.0; 3]);
+        let custom = vec![body("Alpha", 50.0, -10.0), body("Beta", 2.0, 15.0), body("Gamma", 3.0, 40.0)];
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, SolarSystemPlugin::default().camera(false).bodies(custom.clone())));
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let mut spawned: Vec<(String, f32)> = app
+            .world
+            .query_filtered::<(&Name, &Mass), With<CelestialBody>>()
+            .iter(&app.world)
+            .map(|(name, mass)| (name.0.clone(), mass.0))
+            .collect();
+        spawned.sort_by(|a, b| a.0.cmp(&b.0));
+        let expected: Vec<(String, f32)> = custom.iter().map(|body| (body.name.clone(), body.mass)).collect();
+        assert_eq!(spawned, expected);
+    }
+
+    #[test]
+    fn deterministic_launches_match_bit_for_bit() {
+        // However long the frames really take
+        let run = || {
+            let options = LaunchOptions::from_args(["--deterministic", "--stress", "12"].map(String::from).into_iter()).unwrap();
+            let mut app = App::new();
+            app.add_plugins((
+                MinimalPlugins,
+                SolarSystemPlugin::default()
+                    .camera(false)
+                    .deterministic(options.deterministic)
+                    .scenario(Scenario::from_options(&options)),
+            ));
+            for _ in 0..120 {
+                app.update();
+            }
+            body_states(&mut app)
+                .into_iter()
+                .map(|(position, velocity, mass)| {
+                    let [px, py, pz] = position.to_array();
+                    let [vx, vy, vz] = velocity.to_array();
+                    [px, py, pz, vx, vy, vz, mass].map(f32::to_bits)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = run();
+        assert_eq!(first.len(), 12);
+        assert_eq!(first, run());
+    }
+}
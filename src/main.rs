@@ -1,228 +1,144 @@
 use bevy::prelude::*;
-use bevy::input::mouse::MouseMotion;
-
-// Components for our celestial bodies
-#[derive(Component)]
-struct CelestialBody;
-
-#[derive(Component)]
-struct Name(String);
-
-#[derive(Component)]
-struct Mass(f32);
-
-#[derive(Component)]
-struct Velocity(Vec3);
-
-#[derive(Component)]
-struct CameraController {
-    pub sensitivity: f32,
-    pub speed: f32,
-}
-
-// Much smaller gravitational constant for stability
-const GRAVITATIONAL_CONSTANT: f32 = 10.0;
+use std::path::Path;
+use solar_system_simulator::SolarSystemPlugin;
+use solar_system_simulator::camera::CameraClip;
+use solar_system_simulator::physics::{Calendar, Paused, PhysicsConfig};
+use solar_system_simulator::render::{AdaptiveQuality, AmbientOcclusion, FIELD_LINE_LENGTH, FIELD_LINE_SEEDS, FieldLines, MeshQuality, Palettes, ReferenceGrid, TRAIL_DECIMATION_PIXELS, TrailConfig};
+use solar_system_simulator::scene::{BodyConfig, DEFAULT_SEED, LaunchOptions, LoadedScene, Scenario, SceneConfig, SimRng, orient_specs, scenario_specs, trajectory_csv};
+use solar_system_simulator::ui::WINDOW_TITLE;
+use solar_system_simulator::selftest::{SelfTestResult, check_determinism, run_selftest};
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_systems(Startup, setup_scene)
-        .add_systems(Update, (gravity_system, movement_system, camera_controller).chain())
-        .run();
-}
+    let options = LaunchOptions::from_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        std::process::exit(2);
+    });
 
-/// Gravity system with much more conservative physics
-fn gravity_system(
-    mut query: Query<(&mut Velocity, &Transform, &Mass), With<CelestialBody>>,
-    time: Res<Time>,
-) {
-    let mut combinations = query.iter_combinations_mut();
-    let dt = time.delta_seconds().min(1.0/60.0); // Cap delta time for stability
-    
-    while let Some([(mut vel1, transform1, mass1), (mut vel2, transform2, mass2)]) = 
-        combinations.fetch_next() {
-        
-        let direction = transform2.translation - transform1.translation;
-        let distance = direction.length();
-        
-        // Skip if too close to avoid singularities
-        if distance < 2.0 {
-            continue;
+    if options.selftest {
+        let results = run_selftest();
+        for result in &results {
+            println!("{result}");
+        }
+        if results.iter().all(SelfTestResult::passed) {
+            println!("all {} checks passed", results.len());
+            return;
         }
-        
-        // Calculate gravitational force
-        let force_magnitude = GRAVITATIONAL_CONSTANT * mass1.0 * mass2.0 / (distance * distance);
-        let force_direction = direction / distance; // Normalize
-        
-        // Apply much smaller force changes
-        let force_multiplier = 0.01; // Make forces much weaker
-        vel1.0 += force_direction * force_magnitude / mass1.0 * dt * force_multiplier;
-        vel2.0 -= force_direction * force_magnitude / mass2.0 * dt * force_multiplier;
+        std::process::exit(1);
     }
-}
 
-/// Movement system
-fn movement_system(
-    mut query: Query<(&mut Transform, &Velocity), With<CelestialBody>>,
-    time: Res<Time>,
-) {
-    let dt = time.delta_seconds().min(1.0/60.0); // Cap delta time
-    for (mut transform, velocity) in query.iter_mut() {
-        transform.translation += velocity.0 * dt;
+    if let Some(steps) = options.check_determinism {
+        let seed = options.seed.unwrap_or(DEFAULT_SEED);
+        if check_determinism(seed, steps) {
+            println!("deterministic: two runs of {steps} steps with seed {seed} matched");
+            return;
+        }
+        eprintln!("NOT deterministic: two runs of {steps} steps with seed {seed} differed");
+        std::process::exit(1);
     }
-}
-
-/// Camera controller system
-fn camera_controller(
-    time: Res<Time>,
-    mut mouse_events: EventReader<MouseMotion>,
-    mouse_button: Res<ButtonInput<MouseButton>>,
-    key_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&mut Transform, &CameraController), With<Camera3d>>,
-) {
-    for (mut transform, controller) in query.iter_mut() {
-        let mut velocity = Vec3::ZERO;
-        let local_z = transform.local_z();
-        let forward = -Vec3::new(local_z.x, 0., local_z.z);
-        let right = Vec3::new(local_z.z, 0., -local_z.x);
 
-        if key_input.pressed(KeyCode::KeyW) {
-            velocity += forward;
-        }
-        if key_input.pressed(KeyCode::KeyS) {
-            velocity -= forward;
-        }
-        if key_input.pressed(KeyCode::KeyA) {
-            velocity -= right;
-        }
-        if key_input.pressed(KeyCode::KeyD) {
-            velocity += right;
-        }
-        if key_input.pressed(KeyCode::Space) {
-            velocity += Vec3::Y;
-        }
-        if key_input.pressed(KeyCode::ShiftLeft) {
-            velocity -= Vec3::Y;
+    let mut palettes = Palettes::default();
+    let (mut physics, loaded_scene, config_paused) = match &options.config {
+        Some(path) => {
+            let scene = SceneConfig::load(Path::new(path)).unwrap_or_else(|err| {
+                eprintln!("error: {err}");
+                std::process::exit(2);
+            });
+            palettes.palettes.extend(scene.palettes);
+            if let Some(name) = &scene.palette {
+                palettes.select(name).unwrap_or_else(|err| {
+                    eprintln!("error: {path}: {err}");
+                    std::process::exit(2);
+                });
+            }
+            (scene.physics, Some(LoadedScene(scene.bodies)), scene.start_paused)
         }
+        None => (PhysicsConfig::default(), None, false),
+    };
+    if let Some(radius) = options.boundary {
+        physics.boundary.radius = Some(radius);
+    }
+    if let Some(up_axis) = options.up_axis {
+        physics.up_axis = up_axis;
+    }
+    Scenario::from_options(&options).adjust_physics(&mut physics);
+    if let Some(name) = &options.palette {
+        palettes.select(name).unwrap_or_else(|err| {
+            eprintln!("error: {err}");
+            std::process::exit(2);
+        });
+    }
 
-        velocity = velocity.normalize_or_zero();
-        transform.translation += velocity * time.delta_seconds() * controller.speed;
-
-        if mouse_button.pressed(MouseButton::Right) {
-            for mouse_event in mouse_events.read() {
-                let (mut yaw, mut pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
-                yaw -= mouse_event.delta.x * controller.sensitivity * time.delta_seconds();
-                pitch -= mouse_event.delta.y * controller.sensitivity * time.delta_seconds();
-                pitch = pitch.clamp(-1.54, 1.54);
-
-                transform.rotation = Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
+    if let Some(path) = &options.export_trajectory {
+        let Some(steps) = options.steps else {
+            eprintln!("error: `--export-trajectory` needs `--steps N`");
+            std::process::exit(2);
+        };
+        let (specs, material) = match &loaded_scene {
+            Some(scene) => (scene.0.iter().map(BodyConfig::spec).collect(), None),
+            None => {
+                let scenario = Scenario::from_options(&options);
+                let mut specs = scenario_specs(&scenario, options.seed.unwrap_or(DEFAULT_SEED), &physics);
+                orient_specs(&mut specs, physics.up_axis);
+                (specs, scenario.surface_material())
             }
+        };
+        let csv = trajectory_csv(&specs, physics, material, steps);
+        if let Err(err) = std::fs::write(path, csv) {
+            eprintln!("error: could not write {path}: {err}");
+            std::process::exit(2);
         }
+        println!("wrote {steps} steps of {} bodies to {path}", specs.len());
+        return;
     }
-}
-
-/// Setup scene with very conservative orbital velocities
-fn setup_scene(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    // Camera positioned to see the whole system
-    commands.spawn((
-        Camera3dBundle {
-            transform: Transform::from_xyz(-50.0, 30.0, 50.0).looking_at(Vec3::ZERO, Vec3::Y),
-            ..default()
-        },
-        CameraController {
-            sensitivity: 2.0,
-            speed: 25.0,
-        },
-    ));
-
-    // Sun - stationary and massive
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Sphere::new(3.0)),
-            material: materials.add(StandardMaterial {
-                base_color: Color::YELLOW,
-                emissive: Color::rgb(1.0, 1.0, 0.0) * 2.0,
-                ..default()
-            }),
-            transform: Transform::from_xyz(0.0, 0.0, 0.0),
-            ..default()
-        },
-        CelestialBody,
-        Name("Sun".to_string()),
-        Mass(1000.0),
-        Velocity(Vec3::ZERO),
-    ));
 
-    // Inner planet - very slow orbit
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Sphere::new(0.8)),
-            material: materials.add(StandardMaterial {
-                base_color: Color::rgb(0.8, 0.4, 0.2),
-                ..default()
-            }),
-            transform: Transform::from_xyz(12.0, 0.0, 0.0),
-            ..default()
-        },
-        CelestialBody,
-        Name("Inner Planet".to_string()),
-        Mass(5.0),
-        Velocity(Vec3::new(0.0, 0.0, 0.8)), // Very slow
-    ));
-
-    // Middle planet
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Sphere::new(1.0)),
-            material: materials.add(StandardMaterial {
-                base_color: Color::rgb(0.2, 0.4, 0.8),
+    let deterministic = options.deterministic;
+    let default_clip = CameraClip::default();
+    let mut app = App::new();
+    if let Some(loaded_scene) = loaded_scene {
+        app.insert_resource(loaded_scene);
+    }
+    app
+        .add_plugins(
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: WINDOW_TITLE.to_string(),
+                    ..default()
+                }),
                 ..default()
             }),
-            transform: Transform::from_xyz(20.0, 0.0, 0.0),
+        )
+        .insert_resource(physics)
+        .insert_resource(TrailConfig {
+            trail_sample_interval: options.trail_interval.unwrap_or_default(),
+            ribbon_width: options.trail_ribbons,
+            decimation_pixels: options.trail_decimation.unwrap_or(TRAIL_DECIMATION_PIXELS),
             ..default()
-        },
-        CelestialBody,
-        Name("Middle Planet".to_string()),
-        Mass(8.0),
-        Velocity(Vec3::new(0.0, 0.0, 0.6)), // Even slower
-    ));
-
-    // Outer planet
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Sphere::new(0.9)),
-            material: materials.add(StandardMaterial {
-                base_color: Color::rgb(0.8, 0.3, 0.1),
-                ..default()
-            }),
-            transform: Transform::from_xyz(30.0, 0.0, 0.0),
+        })
+        .insert_resource(MeshQuality { subdivisions: options.quality.settings().sphere_subdivisions })
+        .insert_resource(options.quality)
+        .insert_resource(AdaptiveQuality { ceiling: options.quality, ..default() })
+        .insert_resource(ReferenceGrid(options.grid))
+        .insert_resource(AmbientOcclusion(options.ssao))
+        .insert_resource(CameraClip {
+            near: options.near_clip.unwrap_or(default_clip.near),
+            far: options.far_clip.unwrap_or(default_clip.far),
+            ..default_clip
+        })
+        .insert_resource(FieldLines {
+            seeds: options.field_seeds.unwrap_or(FIELD_LINE_SEEDS),
+            length: options.field_length.unwrap_or(FIELD_LINE_LENGTH),
             ..default()
-        },
-        CelestialBody,
-        Name("Outer Planet".to_string()),
-        Mass(6.0),
-        Velocity(Vec3::new(0.0, 0.0, 0.4)), // Slowest
-    ));
-
-    // Strong point light at the sun
-    commands.spawn(PointLightBundle {
-        point_light: PointLight {
-            intensity: 10000.0,
-            shadows_enabled: true,
-            range: 200.0,
+        })
+        .insert_resource(Calendar {
+            locked: options.days_per_second.is_some(),
+            days_per_second: options.days_per_second.unwrap_or(1.0),
             ..default()
-        },
-        transform: Transform::from_xyz(0.0, 5.0, 0.0),
-        ..default()
-    });
-
-    // Ambient light for visibility
-    commands.insert_resource(AmbientLight {
-        color: Color::WHITE,
-        brightness: 0.4,
-    });
-}
\ No newline at end of file
+        })
+        .insert_resource(palettes)
+        .insert_resource(Paused(options.start_paused || config_paused))
+        .insert_resource(SimRng::new(options.seed.unwrap_or(DEFAULT_SEED)))
+        .insert_resource(Scenario::from_options(&options))
+        .insert_resource(options)
+        .add_plugins(SolarSystemPlugin::default().deterministic(deterministic))
+        .run();
+}
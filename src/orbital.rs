@@ -0,0 +1,465 @@
+//! Two-body orbital mechanics: circular speeds, periods, state vectors to and from Keplerian
+//! elements, Kepler's equation and flyby and apsis predictions.
+//!
+//! Everything here works on plain `Vec3`/`f32` values with no ECS types, so it can be used
+//! without the app. Positions and velocities are relative to the central body, and `mu` is the
+//! pair's gravitational parameter (PhysicsConfig::gravitational_parameter gives the one the
+//! simulation uses). Angles in KeplerElements are degrees; everything else is radians.
+
+use bevy::math::{Quat, Vec3};
+
+/// Keplerian elements of a body relative to the mass it orbits
+pub struct OrbitalElements {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    /// Points from the focus towards periapsis, with the eccentricity as its length
+    pub eccentricity_vector: Vec3,
+}
+
+/// Speed of a circular orbit of the given radius when gravity falls off as `1 / r^force_exponent`
+pub fn circular_velocity(mu: f32, radius: f32, force_exponent: f32) -> f32 {
+    (mu / radius.powf(force_exponent - 1.0)).sqrt()
+}
+
+/// Period of an orbit with the given semi-major axis (Kepler's third law)
+pub fn orbital_period(mu: f32, semi_major_axis: f32) -> f32 {
+    std::f32::consts::TAU * (semi_major_axis.powi(3) / mu).sqrt()
+}
+
+/// Compute orbital elements from a position and velocity relative to the central body
+pub fn orbital_elements(relative_position: Vec3, relative_velocity: Vec3, mu: f32) -> OrbitalElements {
+    let r = relative_position.length();
+    let v2 = relative_velocity.length_squared();
+    let angular_momentum = relative_position.cross(relative_velocity);
+
+    let eccentricity_vector =
+        relative_velocity.cross(angular_momentum) / mu - relative_position / r;
+    let eccentricity = eccentricity_vector.length();
+
+    // Vis-viva; negative for unbound orbits
+    let specific_energy = v2 / 2.0 - mu / r;
+    let semi_major_axis = -mu / (2.0 * specific_energy);
+
+    OrbitalElements {
+        semi_major_axis,
+        eccentricity,
+        eccentricity_vector,
+    }
+}
+
+/// Relative position and velocity `dt` seconds on along the exact Kepler orbit through the given
+/// state, or `None` if it is not a bound ellipse. Kepler's equation is solved from the state
+/// itself rather than by integrating, so the error doesn't grow however far ahead it looks;
+/// the working is in f64 to keep it that way out to many periods.
+pub fn kepler_propagate(relative_position: Vec3, relative_velocity: Vec3, mu: f32, dt: f64) -> Option<(Vec3, Vec3)> {
+    let (position, velocity, mu) = (relative_position.as_dvec3(), relative_velocity.as_dvec3(), f64::from(mu));
+    let r0 = position.length();
+    let semi_major_axis = 1.0 / (2.0 / r0 - velocity.length_squared() / mu);
+    if !(semi_major_axis > 0.0 && semi_major_axis.is_finite()) {
+        return None;
+    }
+    let sqrt_a = semi_major_axis.sqrt();
+    let sqrt_mu = mu.sqrt();
+    let sigma = position.dot(velocity) / sqrt_mu;
+    let mean_motion = sqrt_mu / (sqrt_a * semi_major_axis);
+    // Mean anomaly advanced, wrapped to one orbit so Newton's method starts close
+    let mean_anomaly = (mean_motion * dt).rem_euclid(std::f64::consts::TAU);
+
+    // Kepler's equation in the change of eccentric anomaly
+    let kepler = |delta: f64| delta - (1.0 - r0 / semi_major_axis) * delta.sin() + sigma / sqrt_a * (1.0 - delta.cos()) - mean_anomaly;
+    let slope = |delta: f64| 1.0 - (1.0 - r0 / semi_major_axis) * delta.cos() + sigma / sqrt_a * delta.sin();
+    let mut delta = mean_anomaly;
+    for _ in 0..KEPLER_ITERATIONS {
+        let step = kepler(delta) / slope(delta);
+        delta -= step;
+        if step.abs() < 1e-14 {
+            break;
+        }
+    }
+
+    // Lagrange f and g coefficients
+    let (sin, cos) = delta.sin_cos();
+    let r = semi_major_axis + (r0 - semi_major_axis) * cos + sigma * sqrt_a * sin;
+    let f = 1.0 - semi_major_axis / r0 * (1.0 - cos);
+    let g = semi_major_axis * sigma / sqrt_mu * (1.0 - cos) + r0 * (semi_major_axis / mu).sqrt() * sin;
+    let f_dot = -(mu * semi_major_axis).sqrt() / (r * r0) * sin;
+    let g_dot = 1.0 - semi_major_axis / r * (1.0 - cos);
+    Some(((position * f + velocity * g).as_vec3(), (position * f_dot + velocity * g_dot).as_vec3()))
+}
+
+// Newton iterations allowed when solving Kepler's equation
+pub const KEPLER_ITERATIONS: usize = 50;
+
+/// A Keplerian orbit as entered in the add planet panel, angles in degrees. Inclination is
+/// measured from the XZ plane, with 0 going round the same way as the default planets.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KeplerElements {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    pub ascending_node: f32,
+    pub argument_of_periapsis: f32,
+    pub true_anomaly: f32,
+}
+
+impl Default for KeplerElements {
+    fn default() -> Self {
+        Self {
+            semi_major_axis: 25.0,
+            eccentricity: 0.1,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            argument_of_periapsis: 0.0,
+            true_anomaly: 0.0,
+        }
+    }
+}
+
+/// Position and velocity relative to the central body for a bound orbit with these elements
+pub fn state_from_elements(elements: &KeplerElements, mu: f32) -> Result<(Vec3, Vec3), String> {
+    let KeplerElements { semi_major_axis, eccentricity, .. } = *elements;
+    if !(semi_major_axis > 0.0 && semi_major_axis.is_finite()) {
+        return Err("semi-major axis must be positive".to_string());
+    }
+    if !(0.0..1.0).contains(&eccentricity) {
+        return Err("eccentricity must be at least 0 and below 1 for a bound orbit".to_string());
+    }
+    if mu.is_nan() || mu <= 0.0 {
+        return Err("the central body needs mass".to_string());
+    }
+    // In the orbit's own plane, with periapsis along x
+    let anomaly = elements.true_anomaly.to_radians();
+    let semi_latus_rectum = semi_major_axis * (1.0 - eccentricity * eccentricity);
+    let r = semi_latus_rectum / (1.0 + eccentricity * anomaly.cos());
+    let position = Vec3::new(anomaly.cos(), anomaly.sin(), 0.0) * r;
+    let velocity = Vec3::new(-anomaly.sin(), eccentricity + anomaly.cos(), 0.0) * (mu / semi_latus_rectum).sqrt();
+
+    let rotation = Quat::from_rotation_z(elements.ascending_node.to_radians())
+        * Quat::from_rotation_x(elements.inclination.to_radians())
+        * Quat::from_rotation_z(elements.argument_of_periapsis.to_radians());
+    // The textbook frame has z as the pole; here the reference plane is XZ and planets go round -Y
+    let to_world = |v: Vec3| Vec3::new(v.x, -v.z, v.y);
+    Ok((to_world(rotation * position), to_world(rotation * velocity)))
+}
+
+/// Orbits closer to circular than this have no meaningful periapsis
+pub const MIN_PROGRESS_ECCENTRICITY: f32 = 1e-3;
+
+/// Where a body is along a bound elliptical orbit
+pub struct OrbitProgress {
+    /// Fraction of the orbit completed since periapsis, from 0 to 1
+    pub completion: f32,
+    pub period: f32,
+    pub time_to_periapsis: f32,
+    pub time_to_apoapsis: f32,
+}
+
+/// Progress along the current orbit, from the mean anomaly. `None` for unbound orbits and
+/// for near-circular ones, whose periapsis is ill-defined.
+pub fn orbit_progress(relative_position: Vec3, relative_velocity: Vec3, mu: f32) -> Option<OrbitProgress> {
+    let elements = orbital_elements(relative_position, relative_velocity, mu);
+    let e = elements.eccentricity;
+    if !(MIN_PROGRESS_ECCENTRICITY..1.0).contains(&e) || elements.semi_major_axis <= 0.0 {
+        return None;
+    }
+
+    // True anomaly from the eccentricity vector, past pi once the body is heading back in
+    let angular_momentum = relative_position.cross(relative_velocity);
+    let eccentricity_vector =
+        relative_velocity.cross(angular_momentum) / mu - relative_position.normalize();
+    let cos_true = (eccentricity_vector.dot(relative_position) / (e * relative_position.length())).clamp(-1.0, 1.0);
+    let mut true_anomaly = cos_true.acos();
+    if relative_position.dot(relative_velocity) < 0.0 {
+        true_anomaly = std::f32::consts::TAU - true_anomaly;
+    }
+
+    // Eccentric anomaly, then Kepler's equation for the mean anomaly
+    let eccentric_anomaly = ((1.0 - e * e).sqrt() * true_anomaly.sin())
+        .atan2(e + true_anomaly.cos())
+        .rem_euclid(std::f32::consts::TAU);
+    let mean_anomaly = eccentric_anomaly - e * eccentric_anomaly.sin();
+
+    let period = orbital_period(mu, elements.semi_major_axis);
+    let completion = mean_anomaly / std::f32::consts::TAU;
+    Some(OrbitProgress {
+        completion,
+        period,
+        time_to_periapsis: (1.0 - completion) * period,
+        time_to_apoapsis: (0.5 - completion).rem_euclid(1.0) * period,
+    })
+}
+
+/// Prediction for a probe swinging past a planet, in the planet's frame unless noted
+pub struct Flyby {
+    /// Hyperbolic excess speed, the same on the way in and out
+    pub excess_speed: f32,
+    /// Relative velocity far out on the way in and on the way out
+    pub incoming: Vec3,
+    pub outgoing: Vec3,
+    /// Angle between the incoming and outgoing asymptotes
+    pub turning_angle: f32,
+    pub periapsis: f32,
+}
+
+impl Flyby {
+    /// Velocity change the flyby gives the probe, the same in every frame
+    pub fn delta_v(&self) -> Vec3 {
+        self.outgoing - self.incoming
+    }
+}
+
+/// Why an encounter won't turn into a flyby
+#[derive(Debug, PartialEq)]
+pub enum NoFlyby {
+    /// Bound to the planet, so it never leaves again
+    Captured,
+    /// Already past closest approach
+    Receding,
+    /// Heads straight at the planet, with no sideways motion to swing it round
+    Radial,
+    /// Closest approach is inside the planet
+    Impact,
+}
+
+/// Two-body flyby of a probe past a planet of the given radius from their relative position and
+/// velocity. The planet's own motion is left out, as in patched conics, which holds while the
+/// flyby is short next to its orbit.
+pub fn predict_flyby(relative_position: Vec3, relative_velocity: Vec3, mu: f32, planet_radius: f32) -> Result<Flyby, NoFlyby> {
+    let specific_energy = relative_velocity.length_squared() / 2.0 - mu / relative_position.length();
+    if specific_energy <= 0.0 {
+        return Err(NoFlyby::Captured);
+    }
+    if relative_position.dot(relative_velocity) >= 0.0 {
+        return Err(NoFlyby::Receding);
+    }
+    let angular_momentum = relative_position.cross(relative_velocity);
+    let Some(normal) = angular_momentum.try_normalize() else {
+        return Err(NoFlyby::Radial);
+    };
+
+    let eccentricity_vector =
+        relative_velocity.cross(angular_momentum) / mu - relative_position.normalize();
+    let e = eccentricity_vector.length();
+    let semi_major_axis = -mu / (2.0 * specific_energy);
+    let periapsis = semi_major_axis * (1.0 - e);
+    if periapsis <= planet_radius {
+        return Err(NoFlyby::Impact);
+    }
+
+    // The asymptotes sit at true anomaly +-acos(-1/e); the velocity there points along
+    // -+sin(nu) p + (e + cos nu) q, which simplifies to the directions below
+    let towards_periapsis = eccentricity_vector / e;
+    let ahead = normal.cross(towards_periapsis);
+    let sideways = (e * e - 1.0).sqrt();
+    let excess_speed = (2.0 * specific_energy).sqrt();
+    Ok(Flyby {
+        excess_speed,
+        incoming: (towards_periapsis + ahead * sideways) / e * excess_speed,
+        outgoing: (-towards_periapsis + ahead * sideways) / e * excess_speed,
+        turning_angle: 2.0 * (1.0 / e).asin(),
+        periapsis,
+    })
+}
+
+/// Unit normal of the orbital plane, or `None` for purely radial motion
+pub fn orbit_normal(relative_position: Vec3, relative_velocity: Vec3) -> Option<Vec3> {
+    relative_position.cross(relative_velocity).try_normalize()
+}
+
+// Below this eccentricity an orbit is drawn as circular, with no apsides to mark
+pub const APSIDES_MIN_ECCENTRICITY: f32 = 1e-3;
+
+/// Periapsis and apoapsis of the orbit through the given relative state, as offsets from the
+/// focus. Unbound orbits never come back out, so they only have a periapsis; near-circular ones
+/// have neither.
+pub fn apsides(relative_position: Vec3, relative_velocity: Vec3, mu: f32) -> Option<(Vec3, Option<Vec3>)> {
+    let elements = orbital_elements(relative_position, relative_velocity, mu);
+    if !elements.eccentricity.is_finite() || elements.eccentricity < APSIDES_MIN_ECCENTRICITY {
+        return None;
+    }
+    let toward_periapsis = elements.eccentricity_vector / elements.eccentricity;
+    // The semi-latus rectum works for every conic, including the parabola where a is infinite
+    let semi_latus_rectum = relative_position.cross(relative_velocity).length_squared() / mu;
+    let periapsis = toward_periapsis * semi_latus_rectum / (1.0 + elements.eccentricity);
+    let apoapsis = (elements.eccentricity < 1.0)
+        .then(|| -toward_periapsis * elements.semi_major_axis * (1.0 + elements.eccentricity));
+    Some((periapsis, apoapsis))
+}
+
+/// Eccentric anomaly E solving Kepler's equation `M = E - e sin E` for a mean anomaly and an
+/// eccentricity below 1, by Newton's method. Highly eccentric orbits start from pi, where the
+/// iteration can't overshoot.
+pub fn eccentric_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let mean_anomaly = mean_anomaly.rem_euclid(std::f64::consts::TAU);
+    let mut anomaly = if eccentricity > 0.8 { std::f64::consts::PI } else { mean_anomaly };
+    for _ in 0..KEPLER_ITERATIONS {
+        let step = (anomaly - eccentricity * anomaly.sin() - mean_anomaly) / (1.0 - eccentricity * anomaly.cos());
+        anomaly -= step;
+        if step.abs() < 1e-14 {
+            break;
+        }
+    }
+    anomaly
+}
+
+/// Keplerian elements of the bound orbit through a relative state, the inverse of
+/// state_from_elements. Angles that an orbit doesn't define are 0 and folded into the next
+/// one: the ascending node of an orbit in the reference plane (measured from +X instead) and
+/// the argument of periapsis of a circular one (the true anomaly is then measured from the node).
+/// `None` for unbound orbits and purely radial motion.
+pub fn state_to_elements(relative_position: Vec3, relative_velocity: Vec3, mu: f32) -> Option<KeplerElements> {
+    // Back into the textbook frame state_from_elements works in, with z as the pole
+    let from_world = |v: Vec3| Vec3::new(v.x, v.z, -v.y);
+    let (position, velocity) = (from_world(relative_position), from_world(relative_velocity));
+    let elements = orbital_elements(position, velocity, mu);
+    if !(elements.semi_major_axis > 0.0 && elements.eccentricity < 1.0) {
+        return None;
+    }
+    let normal = orbit_normal(position, velocity)?;
+    let node = Vec3::Z.cross(normal);
+    let node = if node.length() > 1e-5 { node.normalize() } else { Vec3::X };
+    // Angle from `from` to `to` going round the orbit's own direction of motion
+    let angle = |from: Vec3, to: Vec3| normal.dot(from.cross(to)).atan2(from.dot(to)).to_degrees().rem_euclid(360.0);
+    let (argument_of_periapsis, true_anomaly) = if elements.eccentricity > APSIDES_MIN_ECCENTRICITY {
+        (angle(node, elements.eccentricity_vector), angle(elements.eccentricity_vector, position))
+    } else {
+        (0.0, angle(node, position))
+    };
+    Some(KeplerElements {
+        semi_major_axis: elements.semi_major_axis,
+        eccentricity: elements.eccentricity,
+        inclination: normal.z.clamp(-1.0, 1.0).acos().to_degrees(),
+        ascending_node: node.y.atan2(node.x).to_degrees().rem_euclid(360.0),
+        argument_of_periapsis,
+        true_anomaly,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orbit_progress_at_periapsis_is_zero_with_a_period_to_go() {
+        let mu = 1000.0;
+        let periapsis = 10.0;
+        let speed = circular_velocity(mu, periapsis, 2.0) * 1.2;
+        let progress = orbit_progress(Vec3::X * periapsis, Vec3::Z * speed, mu).expect("the orbit is bound and eccentric");
+
+        assert!(progress.completion.abs() < 1e-3);
+        assert!((progress.time_to_periapsis - progress.period).abs() / progress.period < 1e-3);
+        assert!((progress.time_to_apoapsis - progress.period / 2.0).abs() / progress.period < 1e-3);
+        // A circle has no periapsis to count from
+        let circular = circular_velocity(mu, periapsis, 2.0);
+        assert!(orbit_progress(Vec3::X * periapsis, Vec3::Z * circular, mu).is_none());
+    }
+
+    #[test]
+    fn kepler_propagate_closes_the_orbit_after_whole_periods() {
+        let (position, velocity) = (Vec3::new(25.0, 0.0, 0.0), Vec3::new(0.0, 0.3, 1.6));
+        let mu = 100.0;
+        // Worked out in f64 too, or the period's own rounding shows up after many orbits
+        let semi_major_axis = 1.0 / (2.0 / position.as_dvec3().length() - velocity.as_dvec3().length_squared() / mu);
+        let period = std::f64::consts::TAU * (semi_major_axis.powi(3) / mu).sqrt();
+
+        for orbits in [1.0, 10.0, 1000.0, 100_000.0] {
+            let (end, _) = kepler_propagate(position, velocity, mu as f32, period * orbits).expect("the orbit is bound");
+            assert!(end.distance(position) / position.length() < 1e-4, "{orbits} orbits");
+        }
+    }
+
+    #[test]
+    fn state_from_elements_gives_those_elements_back() {
+        let elements = KeplerElements {
+            semi_major_axis: 30.0,
+            eccentricity: 0.3,
+            inclination: 20.0,
+            ascending_node: 40.0,
+            argument_of_periapsis: 60.0,
+            true_anomaly: 100.0,
+        };
+        let mu = 100.0;
+        let (position, velocity) = state_from_elements(&elements, mu).unwrap();
+        let measured = orbital_elements(position, velocity, mu);
+        let inclination = orbit_normal(position, velocity).unwrap().dot(Vec3::NEG_Y).acos().to_degrees();
+
+        assert!((measured.semi_major_axis - elements.semi_major_axis).abs() / elements.semi_major_axis < 1e-4);
+        assert!((measured.eccentricity - elements.eccentricity).abs() < 1e-4);
+        assert!((inclination - elements.inclination).abs() < 1e-2);
+        // Parabolic and hyperbolic orbits can't be placed from a semi-major axis
+        assert!(state_from_elements(&KeplerElements { eccentricity: 1.0, ..elements }, mu).is_err());
+    }
+
+    #[test]
+    fn apsides_sit_a_one_minus_e_and_a_one_plus_e_from_the_focus() {
+        let (semi_major_axis, eccentricity) = (30.0, 0.4);
+        let mu = 100.0;
+        let elements = KeplerElements {
+            semi_major_axis,
+            eccentricity,
+            inclination: 20.0,
+            argument_of_periapsis: 75.0,
+            true_anomaly: 140.0,
+            ..Default::default()
+        };
+        let (position, velocity) = state_from_elements(&elements, mu).unwrap();
+        let (periapsis, apoapsis) = apsides(position, velocity, mu).unwrap();
+        let toward_periapsis = orbital_elements(position, velocity, mu).eccentricity_vector.normalize();
+
+        assert!(periapsis.distance(toward_periapsis * semi_major_axis * (1.0 - eccentricity)) / semi_major_axis < 1e-4);
+        let apoapsis = apoapsis.expect("a bound orbit has an apoapsis");
+        assert!(apoapsis.distance(-toward_periapsis * semi_major_axis * (1.0 + eccentricity)) / semi_major_axis < 1e-4);
+    }
+
+    #[test]
+    fn elements_round_trip_through_a_state_vector() {
+        let mu = 100.0;
+        let angle_error = |a: f32, b: f32| ((a - b + 180.0).rem_euclid(360.0) - 180.0).abs();
+        // Inclined, retrograde and equatorial
+        for elements in [
+            KeplerElements { semi_major_axis: 20.0, eccentricity: 0.3, inclination: 25.0, ascending_node: 40.0, argument_of_periapsis: 70.0, true_anomaly: 130.0 },
+            KeplerElements { semi_major_axis: 8.0, eccentricity: 0.6, inclination: 150.0, ascending_node: 300.0, argument_of_periapsis: 10.0, true_anomaly: 250.0 },
+            KeplerElements { semi_major_axis: 15.0, eccentricity: 0.1, inclination: 0.0, ascending_node: 0.0, argument_of_periapsis: 200.0, true_anomaly: 45.0 },
+        ] {
+            let (position, velocity) = state_from_elements(&elements, mu).unwrap();
+            let back = state_to_elements(position, velocity, mu).unwrap();
+
+            let error = (back.semi_major_axis / elements.semi_major_axis - 1.0).abs()
+                + (back.eccentricity - elements.eccentricity).abs()
+                + (angle_error(back.inclination, elements.inclination)
+                    + angle_error(back.ascending_node, elements.ascending_node)
+                    + angle_error(back.argument_of_periapsis, elements.argument_of_periapsis)
+                    + angle_error(back.true_anomaly, elements.true_anomaly))
+                    .to_radians();
+            assert!(error < 1e-3, "{elements:?} came back as {back:?}");
+        }
+    }
+
+    #[test]
+    fn keplers_equation_converges_even_for_nearly_parabolic_orbits() {
+        for eccentricity in [0.0, 0.3, 0.9, 0.99] {
+            for mean in (0..24).map(|i| i as f64 * 0.3 - 1.0) {
+                let anomaly = eccentric_anomaly(mean, eccentricity);
+                let residual = anomaly - eccentricity * anomaly.sin() - mean.rem_euclid(std::f64::consts::TAU);
+                assert!(residual.abs() < 1e-9, "e = {eccentricity}, M = {mean}");
+            }
+        }
+    }
+
+    #[test]
+    fn half_a_period_from_periapsis_is_apoapsis() {
+        let mu = 100.0;
+        let (a, e) = (18.0, 0.4);
+        let periapsis = Vec3::X * a * (1.0 - e);
+        let periapsis_velocity = Vec3::Z * ((mu / a) * (1.0 + e) / (1.0 - e)).sqrt();
+        let period = f64::from(orbital_period(mu, a));
+        let propagated = |time| kepler_propagate(periapsis, periapsis_velocity, mu, time).unwrap().0;
+
+        assert!(propagated(period / 2.0).distance(-Vec3::X * a * (1.0 + e)) / a < 1e-4);
+        assert!(propagated(period).distance(periapsis) / a < 1e-4);
+        // Circular speed gives a circle of that radius
+        let circle = orbital_elements(Vec3::X * 12.0, Vec3::Z * circular_velocity(mu, 12.0, 2.0), mu);
+        assert!(circle.eccentricity < 1e-4);
+        assert!((circle.semi_major_axis / 12.0 - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,3178 @@
+//! Bodies, gravity, integration, collisions and the orbital mechanics built on them
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::HashMap;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::orbital::{circular_velocity, kepler_propagate, orbital_elements, orbital_period};
+use crate::camera::{CameraFlight, CameraPath, FollowTarget};
+use crate::render::{PREVIEW_SAMPLE_EVERY, SetupPreview, SurfaceMaterial, Trail, TrailColor, TrailFrameHistory, irradiance, leave_fading_trail};
+use crate::scene::{BodySpec, SaveMenu};
+use crate::ui::Selected;
+
+// Components for our celestial bodies
+#[derive(Component)]
+pub struct CelestialBody;
+
+#[derive(Component)]
+pub struct Name(pub String);
+
+#[derive(Component)]
+pub struct Mass(pub f32);
+
+#[derive(Component)]
+pub struct Velocity(pub Vec3);
+
+#[derive(Component)]
+pub struct Radius(pub f32);
+
+/// The body a minor body currently orbits under patched conics
+#[derive(Component)]
+pub struct Primary(pub Entity);
+
+/// Radius around a body inside which it, rather than its primary, dominates a test body's motion
+#[derive(Component)]
+pub struct SphereOfInfluence(pub f32);
+
+/// A body that shines on its own, so lighting corrections leave it alone
+#[derive(Component)]
+pub struct Star;
+
+/// Pins a body in place: it keeps attracting others but forces never move it. Toggled with F8.
+#[derive(Component)]
+pub struct Anchored;
+
+/// Spin as an angular velocity vector, in radians per second about its direction
+#[derive(Component)]
+pub struct Rotation(pub Vec3);
+
+/// Pushed away from the nearest star by its light. The value is the cross-section-to-mass
+/// ratio, scaled so that 1 exactly cancels the star's gravity; dust sits well below that and
+/// anything planet-sized is effectively 0.
+#[derive(Component)]
+pub struct RadiationSusceptible(pub f32);
+
+/// Steps this body with its own integrator instead of the config's, such as RK4 for a fast
+/// probe among slow planets. See advance_group for how bodies on different integrators mix.
+#[derive(Component)]
+pub struct IntegratorOverride(pub Integrator);
+
+/// A body whose orbital energy has turned positive, so it is on its way out of the system
+#[derive(Component)]
+pub struct Unbound;
+
+/// Sent once when a body goes from bound to unbound
+#[derive(Event)]
+pub struct EjectionWarning {
+    pub entity: Entity,
+    /// Specific orbital energy at the moment it crossed zero
+    pub energy: f32,
+}
+
+// Much smaller gravitational constant for stability
+pub const GRAVITATIONAL_CONSTANT: f32 = 10.0;
+
+// Forces are scaled down by this before being applied to velocities
+pub const FORCE_MULTIPLIER: f32 = 0.01;
+
+/// How overlapping bodies interact
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum CollisionMode {
+    /// Bodies pass straight through each other
+    #[default]
+    None,
+    /// Bodies bounce off each other according to their SurfaceMaterial
+    Bounce,
+}
+
+/// How gravitational forces are computed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ForceMethod {
+    /// Every pair of bodies attracts each other
+    #[default]
+    Direct,
+    /// Each body only feels its primary, switching primaries at sphere-of-influence boundaries.
+    /// Fast and stable, but ignores perturbations from everything else.
+    PatchedConics,
+    /// Only the most massive body attracts, and nothing attracts it back. Planets ignore each
+    /// other entirely, giving clean closed ellipses for teaching Kepler's laws.
+    CentralOnly,
+}
+
+/// What happens to a body that crosses the simulation boundary
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum BoundaryAction {
+    /// The body is removed from the simulation
+    #[default]
+    Despawn,
+    /// The body comes back in on the opposite side of the boundary, still moving the same way
+    Wrap,
+}
+
+/// A sphere around the origin (which origin rebasing keeps near the barycenter) that escaping
+/// bodies are despawned or wrapped at
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoundaryConfig {
+    /// `None` lets bodies go anywhere
+    pub radius: Option<f32>,
+    pub action: BoundaryAction,
+}
+
+/// Which world axis points up. The default orbital plane lies across it, and the camera,
+/// grid, planar mode and orbit tools all follow it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum UpAxis {
+    /// Orbits in the XZ plane
+    #[default]
+    Y,
+    /// Orbits in the XY plane, as many datasets are given
+    Z,
+}
+
+impl UpAxis {
+    pub fn up(self) -> Vec3 {
+        match self {
+            UpAxis::Y => Vec3::Y,
+            UpAxis::Z => Vec3::Z,
+        }
+    }
+
+    pub fn direction(self) -> Direction3d {
+        match self {
+            UpAxis::Y => Direction3d::Y,
+            UpAxis::Z => Direction3d::Z,
+        }
+    }
+
+    /// Rotation from the Y-up layout the built-in scenarios and views are written in to this axis
+    pub fn rotation_from_y_up(self) -> Quat {
+        match self {
+            UpAxis::Y => Quat::IDENTITY,
+            UpAxis::Z => Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+        }
+    }
+
+    /// `vector` with its component along the up axis removed, for planar mode
+    pub fn flatten(self, vector: Vec3) -> Vec3 {
+        vector * (Vec3::ONE - self.up())
+    }
+}
+
+impl std::str::FromStr for UpAxis {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "y" => Ok(UpAxis::Y),
+            "z" => Ok(UpAxis::Z),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How a body's position and velocity are advanced each step
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Integrator {
+    /// Kick the velocity with the current forces, then drift with the new velocity. One force
+    /// evaluation per step and its energy error stays bounded, but it is only first order.
+    #[default]
+    SemiImplicitEuler,
+    /// Velocity Verlet: second order and time-reversible, two force evaluations per step
+    Verlet,
+    /// Classic fourth-order Runge-Kutta: far more accurate over a short span, four force
+    /// evaluations per step, though its energy does slowly drift
+    Rk4,
+}
+
+/// Scripted change of the gravitational constant over simulated time
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum GravitySchedule {
+    /// G stays at its configured value
+    #[default]
+    Constant,
+    /// G changes by `rate` times its configured value every simulated second (negative
+    /// weakens it), never falling below zero
+    Ramp { rate: f32 },
+    /// G swings `amplitude` times its configured value either side of it, once every `period`
+    /// simulated seconds
+    Sine { amplitude: f32, period: f32 },
+}
+
+impl GravitySchedule {
+    /// Multiple of the configured gravitational constant in effect `elapsed` simulated seconds in
+    pub fn factor(&self, elapsed: f32) -> f32 {
+        match *self {
+            GravitySchedule::Constant => 1.0,
+            GravitySchedule::Ramp { rate } => (1.0 + rate * elapsed).max(0.0),
+            GravitySchedule::Sine { amplitude, period } if period > 0.0 => {
+                1.0 + amplitude * (std::f32::consts::TAU * elapsed / period).sin()
+            }
+            GravitySchedule::Sine { .. } => 1.0,
+        }
+    }
+}
+
+/// Tunable physics settings
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    pub force_method: ForceMethod,
+    pub collision_mode: CollisionMode,
+    /// Skip pairs farther apart than `max_interaction_distance`.
+    /// Dropping those forces is an approximation, so energy and momentum
+    /// are no longer exactly conserved while this is on.
+    pub interaction_cutoff: bool,
+    pub max_interaction_distance: f32,
+    /// Gravity falls off as `1 / distance^force_exponent`. Only 2 gives closed Kepler
+    /// ellipses, so the analytic orbit overlays are wrong for any other value.
+    pub force_exponent: f32,
+    /// Longest step the physics takes, however long the frame was. Larger caps keep the
+    /// simulation in step with wall-clock time on slow frames, but each big step is less
+    /// accurate and close encounters can fling bodies off; smaller caps stay stable by letting
+    /// the simulation fall behind real time instead.
+    pub max_dt: f32,
+    /// Strength of gravity, editable while running with 9 and 0. Changing it leaves every body
+    /// at its old speed, so orbits tighten or fly apart until re-stabilized with /.
+    pub gravitational_constant: f32,
+    /// How gravity_system varies `gravitational_constant` over simulated time, to show orbits
+    /// spreading out as gravity weakens and pulling in as it strengthens
+    pub gravity_schedule: GravitySchedule,
+    /// Plummer softening length: each pair feels gravity as if `sqrt(d^2 + softening^2)` apart,
+    /// which keeps close encounters finite instead of skipping pairs closer than 2 units.
+    /// 0 turns it off; anything else weakens gravity inside about this distance.
+    pub softening: f32,
+    /// Allow bodies with negative mass, a non-physical toy: a negative mass is pushed away by
+    /// ordinary ones while chasing them, so a mixed pair runs away together. Collisions and
+    /// mass transfer leave such bodies alone.
+    pub exotic_matter: bool,
+    /// Simulated seconds per real second. The capped frame time is scaled, so fast-forwarding
+    /// takes bigger steps and gets less accurate just like raising `max_dt` does.
+    pub time_scale: f32,
+    /// Integrator for every body without an IntegratorOverride
+    pub integrator: Integrator,
+    pub boundary: BoundaryConfig,
+    pub up_axis: UpAxis,
+}
+
+impl PhysicsConfig {
+    /// The step the physics takes for a frame of `frame_dt` seconds
+    pub fn step_dt(&self, frame_dt: f32) -> f32 {
+        frame_dt.min(self.max_dt) * self.time_scale
+    }
+
+    /// The gravitational constant its schedule gives `elapsed` simulated seconds in
+    pub fn gravitational_constant_at(&self, elapsed: f32) -> f32 {
+        self.gravitational_constant * self.gravity_schedule.factor(elapsed)
+    }
+
+    /// This config with the gravitational constant its schedule gives `elapsed` simulated
+    /// seconds in, and no schedule left to apply
+    pub fn at_time(&self, elapsed: f32) -> Self {
+        Self {
+            gravitational_constant: self.gravitational_constant_at(elapsed),
+            gravity_schedule: GravitySchedule::Constant,
+            ..self.clone()
+        }
+    }
+
+    /// Gravitational parameter of a pair under this config's gravitational constant
+    pub fn gravitational_parameter(&self, central_mass: f32, body_mass: f32) -> f32 {
+        self.gravitational_constant * FORCE_MULTIPLIER * (central_mass + body_mass)
+    }
+
+    /// The integrator a body with this override is stepped with. Patched conics and
+    /// central-only gravity only ever kick with semi-implicit Euler.
+    pub fn integrator_for(&self, body_override: Option<&IntegratorOverride>) -> Integrator {
+        if self.force_method != ForceMethod::Direct {
+            return Integrator::SemiImplicitEuler;
+        }
+        body_override.map_or(self.integrator, |body_override| body_override.0)
+    }
+}
+
+/// While set (Backspace held), the simulation runs backwards.
+///
+/// Gravity is time-reversible: the forward step kicks velocities then drifts positions, so
+/// undoing it means drifting back first and then kicking back, both with a negated dt.
+/// Collisions and mass transfer are not reversible by stepping, so they are skipped while reversing
+/// and instead undone from the CollisionHistory, which puts the bodies back to their logged states.
+#[derive(Resource, Default)]
+pub struct ReverseMode(pub bool);
+
+impl ReverseMode {
+    /// Sign applied to dt
+    pub fn direction(&self) -> f32 {
+        if self.0 { -1.0 } else { 1.0 }
+    }
+}
+
+/// While set, the physics is frozen. Toggled with P.
+#[derive(Resource, Default)]
+pub struct Paused(pub bool);
+
+/// Recenters the world on the barycenter when it drifts far from the origin, since f32 positions
+/// lose precision (and bodies start to jitter) the further out they are
+#[derive(Resource)]
+pub struct OriginRebasing {
+    pub enabled: bool,
+    /// Distance of the barycenter from the origin that triggers a rebase
+    pub threshold: f32,
+}
+
+impl Default for OriginRebasing {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 1000.0,
+        }
+    }
+}
+
+/// Constrains all motion to the XZ plane for clean top-down 2D demos
+#[derive(Resource, Default)]
+pub struct PlanarMode(pub bool);
+
+/// Tracks the system's total angular momentum so drift can be reported
+#[derive(Resource, Default)]
+pub struct AngularMomentumMonitor {
+    /// Angular momentum when monitoring started, the reference for drift
+    pub initial: Option<Vec3>,
+    /// Center of mass and angular momentum at the last measurement, for the arrow
+    pub latest: Option<(Vec3, Vec3)>,
+    /// Measurements taken so far
+    pub samples: usize,
+    /// Unpaused real seconds since the last log line
+    pub since_log: f32,
+}
+
+/// Smoothed time spent in each physics system, in milliseconds
+#[derive(Resource, Default)]
+pub struct PhysicsProfile {
+    pub gravity_ms: f32,
+    pub movement_ms: f32,
+    pub collision_ms: f32,
+    pub frame_ms: f32,
+    pub overlay_open: bool,
+}
+
+// Weight of the newest sample in the profile's moving averages
+pub const PROFILE_SMOOTHING: f32 = 0.05;
+
+impl PhysicsProfile {
+    pub fn smooth(average: &mut f32, sample: Duration) {
+        let sample_ms = sample.as_secs_f32() * 1000.0;
+        if *average == 0.0 {
+            *average = sample_ms;
+        } else {
+            *average += (sample_ms - *average) * PROFILE_SMOOTHING;
+        }
+    }
+}
+
+// Stellar luminosity per unit of star mass, chosen so the default Sun gives irradiance 1 near
+// the middle planet's orbit
+pub const LUMINOSITY_PER_MASS: f32 = 5.0;
+
+/// Luminosity of a star of the given mass
+pub fn star_luminosity(mass: f32) -> f32 {
+    // A negative-mass star gives no light rather than negative light
+    mass.max(0.0) * LUMINOSITY_PER_MASS
+}
+
+/// Converts irradiance times a cross-section-to-mass ratio into an acceleration, chosen so a
+/// ratio of 1 balances the star's gravity at every distance (both fall off as 1/d^2) under the
+/// config's gravitational constant
+pub fn radiation_pressure_coefficient(config: &PhysicsConfig) -> f32 {
+    config.gravitational_constant * FORCE_MULTIPLIER * 4.0 * std::f32::consts::PI / LUMINOSITY_PER_MASS
+}
+
+/// Outward acceleration from the light of the nearest of the (position, luminosity) stars on a
+/// body with the given cross-section-to-mass ratio
+pub fn radiation_acceleration(position: Vec3, stars: &[(Vec3, f32)], ratio: f32, config: &PhysicsConfig) -> Vec3 {
+    let Some(&(star_position, luminosity)) = stars
+        .iter()
+        .min_by(|a, b| a.0.distance_squared(position).total_cmp(&b.0.distance_squared(position)))
+    else {
+        return Vec3::ZERO;
+    };
+    let offset = position - star_position;
+    offset.normalize_or_zero() * irradiance(luminosity, offset.length()) * ratio * radiation_pressure_coefficient(config)
+}
+
+/// Whether starlight pushes on RadiationSusceptible bodies. Toggled with X.
+#[derive(Resource)]
+pub struct RadiationPressure(pub bool);
+
+impl Default for RadiationPressure {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// X turns radiation pressure on and off
+pub fn radiation_pressure_toggle(key_input: Res<ButtonInput<KeyCode>>, mut radiation: ResMut<RadiationPressure>) {
+    if key_input.just_pressed(KeyCode::KeyX) {
+        radiation.0 = !radiation.0;
+        info!("Radiation pressure {}", if radiation.0 { "on" } else { "off" });
+    }
+}
+
+/// Kick susceptible bodies away from the nearest star, alongside the gravity kick
+pub fn radiation_pressure_system(
+    radiation: Res<RadiationPressure>,
+    stars: Query<(&Transform, &Mass), With<Star>>,
+    mut bodies: Query<(&Transform, &mut Velocity, &RadiationSusceptible), Without<Anchored>>,
+    (config, calendar): (Res<PhysicsConfig>, Option<Res<Calendar>>),
+    planar: Res<PlanarMode>,
+    reverse: Res<ReverseMode>,
+    time: Res<Time>,
+) {
+    if !radiation.0 {
+        return;
+    }
+    // Balanced against gravity as it is right now
+    let config = config.at_time(calendar.map_or(0.0, |calendar| calendar.elapsed));
+    let stars: Vec<(Vec3, f32)> = stars
+        .iter()
+        .map(|(transform, mass)| (transform.translation, star_luminosity(mass.0)))
+        .collect();
+    let dt = config.step_dt(time.delta_seconds()) * reverse.direction();
+    for (transform, mut velocity, susceptible) in bodies.iter_mut() {
+        let mut acceleration = radiation_acceleration(transform.translation, &stars, susceptible.0, &config);
+        if planar.0 {
+            acceleration = config.up_axis.flatten(acceleration);
+        }
+        velocity.0 += acceleration * dt;
+    }
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            force_method: ForceMethod::Direct,
+            collision_mode: CollisionMode::None,
+            interaction_cutoff: false,
+            max_interaction_distance: 100.0,
+            force_exponent: 2.0,
+            max_dt: 1.0 / 60.0,
+            time_scale: 1.0,
+            softening: 0.0,
+            gravitational_constant: GRAVITATIONAL_CONSTANT,
+            gravity_schedule: GravitySchedule::Constant,
+            exotic_matter: false,
+            integrator: Integrator::SemiImplicitEuler,
+            boundary: BoundaryConfig::default(),
+            up_axis: UpAxis::Y,
+        }
+    }
+}
+
+/// Gravitational force on the first body from the second, or `None` if the pair is skipped
+pub fn pairwise_force(
+    position1: Vec3,
+    mass1: f32,
+    position2: Vec3,
+    mass2: f32,
+    config: &PhysicsConfig,
+) -> Option<Vec3> {
+    let direction = position2 - position1;
+    let distance = direction.length();
+
+    // Skip if too close to avoid singularities, unless softening already keeps the force finite
+    if distance < 2.0 && config.softening <= 0.0 || distance == 0.0 {
+        return None;
+    }
+
+    // Distant pairs contribute almost nothing, so skip them when the cutoff is on
+    if config.interaction_cutoff && distance > config.max_interaction_distance {
+        return None;
+    }
+
+    // Calculate gravitational force
+    let force_magnitude = if config.softening > 0.0 {
+        let softened = (distance * distance + config.softening * config.softening).sqrt();
+        config.gravitational_constant * mass1 * mass2 * distance / softened.powf(config.force_exponent + 1.0)
+    } else {
+        config.gravitational_constant * mass1 * mass2 / distance.powf(config.force_exponent)
+    };
+    let force_direction = direction / distance; // Normalize
+    Some(force_direction * force_magnitude)
+}
+
+/// Acceleration a massless test particle at `point` would feel from bodies given as (position, mass),
+/// under the same force law, softening and cutoff the bodies feel
+pub fn field_acceleration(point: Vec3, bodies: &[(Vec3, f32)], config: &PhysicsConfig) -> Vec3 {
+    bodies
+        .iter()
+        .filter_map(|&(position, mass)| pairwise_force(point, 1.0, position, mass, config))
+        .sum::<Vec3>()
+        * FORCE_MULTIPLIER
+}
+
+/// Gravity system with much more conservative physics
+#[allow(clippy::type_complexity)]
+pub fn gravity_system(
+    mut query: Query<
+        (Entity, &mut Velocity, &mut Transform, &Mass, Option<&Primary>, Has<Anchored>, Option<&IntegratorOverride>),
+        With<CelestialBody>,
+    >,
+    config: Res<PhysicsConfig>,
+    planar: Res<PlanarMode>,
+    reverse: Res<ReverseMode>,
+    mut profile: ResMut<PhysicsProfile>,
+    time: Res<Time>,
+    calendar: Option<Res<Calendar>>,
+) {
+    let start = Instant::now();
+    let config = config.at_time(calendar.map_or(0.0, |calendar| calendar.elapsed));
+    let dt = config.step_dt(time.delta_seconds()) * reverse.direction();
+    let flatten = |force: Vec3| if planar.0 { config.up_axis.flatten(force) } else { force };
+
+    if config.force_method == ForceMethod::CentralOnly {
+        let Some((star, star_position, star_mass)) = query
+            .iter()
+            .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))
+            .map(|(entity, _, transform, mass, ..)| (entity, transform.translation, mass.0))
+        else {
+            return;
+        };
+
+        for (entity, mut velocity, transform, mass, _, anchored, _) in query.iter_mut() {
+            if entity == star || anchored {
+                continue;
+            }
+            if let Some(force) =
+                pairwise_force(transform.translation, mass.0, star_position, star_mass, &config)
+            {
+                velocity.0 += flatten(force) / mass.0 * dt * FORCE_MULTIPLIER;
+            }
+        }
+        PhysicsProfile::smooth(&mut profile.gravity_ms, start.elapsed());
+        return;
+    }
+
+    if config.force_method == ForceMethod::PatchedConics {
+        // Snapshot first so every body is pulled towards where its primary was at the start of the step
+        let attractors: HashMap<Entity, (Vec3, f32)> = query
+            .iter()
+            .map(|(entity, _, transform, mass, ..)| (entity, (transform.translation, mass.0)))
+            .collect();
+
+        for (_, mut velocity, transform, mass, primary, anchored, _) in query.iter_mut() {
+            if anchored {
+                continue;
+            }
+            let Some(&(primary_position, primary_mass)) =
+                primary.and_then(|primary| attractors.get(&primary.0))
+            else {
+                continue;
+            };
+            if let Some(force) = pairwise_force(
+                transform.translation,
+                mass.0,
+                primary_position,
+                primary_mass,
+                &config,
+            ) {
+                velocity.0 += flatten(force) / mass.0 * dt * FORCE_MULTIPLIER;
+            }
+        }
+        PhysicsProfile::smooth(&mut profile.gravity_ms, start.elapsed());
+        return;
+    }
+
+    // Bodies on a higher-order integrator are stepped whole further down, from where everything
+    // was at the start of the step; only the rest are kicked here
+    let integrators: Vec<Integrator> = query
+        .iter()
+        .map(|(.., anchored, body_override)| if anchored { Integrator::SemiImplicitEuler } else { config.integrator_for(body_override) })
+        .collect();
+    let higher_order = integrators.iter().any(|integrator| *integrator != Integrator::SemiImplicitEuler);
+    let states: Vec<(Vec3, Vec3, f32)> = if higher_order {
+        query.iter().map(|(_, velocity, transform, mass, ..)| (transform.translation, velocity.0, mass.0)).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut combinations = query.iter_combinations_mut();
+    while let Some([(_, mut vel1, transform1, mass1, _, anchored1, override1), (_, mut vel2, transform2, mass2, _, anchored2, override2)]) = 
+        combinations.fetch_next() {
+        
+        let Some(force) = pairwise_force(
+            transform1.translation,
+            mass1.0,
+            transform2.translation,
+            mass2.0,
+            &config,
+        ) else {
+            continue;
+        };
+        let force = flatten(force);
+        
+        // Apply much smaller force changes; anchored bodies pull but are never pulled
+        if !anchored1 && config.integrator_for(override1) == Integrator::SemiImplicitEuler {
+            vel1.0 += force / mass1.0 * dt * FORCE_MULTIPLIER;
+        }
+        if !anchored2 && config.integrator_for(override2) == Integrator::SemiImplicitEuler {
+            vel2.0 -= force / mass2.0 * dt * FORCE_MULTIPLIER;
+        }
+    }
+
+    if higher_order {
+        let mut stepped: Vec<Option<(Vec3, Vec3)>> = vec![None; states.len()];
+        for integrator in [Integrator::Verlet, Integrator::Rk4] {
+            let group: Vec<usize> = (0..states.len()).filter(|&index| integrators[index] == integrator).collect();
+            if group.is_empty() {
+                continue;
+            }
+            for (&index, state) in group.iter().zip(advance_group(&states, &group, integrator, &config, dt, flatten)) {
+                stepped[index] = Some(state);
+            }
+        }
+        for ((_, mut velocity, mut transform, ..), state) in query.iter_mut().zip(stepped) {
+            let Some((mut position, mut new_velocity)) = state else {
+                continue;
+            };
+            if planar.0 {
+                position = config.up_axis.flatten(position);
+                new_velocity = config.up_axis.flatten(new_velocity);
+            }
+            transform.translation = position;
+            velocity.0 = new_velocity;
+        }
+    }
+    PhysicsProfile::smooth(&mut profile.gravity_ms, start.elapsed());
+}
+
+/// Step the bodies at the `group` indices of `states` (position, velocity, mass) by `dt` with
+/// `integrator`, returning their new positions and velocities in group order.
+///
+/// Only the group moves through the intermediate stages; every other body is held where it was
+/// at the start of the step. Bodies sharing an integrator are stepped together, so Verlet's
+/// coupled position and force updates (and RK4's stages) stay consistent between them, but the
+/// pull between bodies on different integrators is only as accurate as semi-implicit Euler, and
+/// momentum between them is no longer exactly conserved. That suits a light probe among
+/// planets, which barely moves them; two heavy bodies on different integrators lose the benefit.
+pub fn advance_group(
+    states: &[(Vec3, Vec3, f32)],
+    group: &[usize],
+    integrator: Integrator,
+    config: &PhysicsConfig,
+    dt: f32,
+    flatten: impl Fn(Vec3) -> Vec3,
+) -> Vec<(Vec3, Vec3)> {
+    let mut slots = vec![None; states.len()];
+    for (slot, &index) in group.iter().enumerate() {
+        slots[index] = Some(slot);
+    }
+    // Acceleration on each group member with the group at `positions` and the rest where they started
+    let accelerations = |positions: &[Vec3]| -> Vec<Vec3> {
+        group
+            .iter()
+            .zip(positions)
+            .map(|(&index, &position)| {
+                let mass = states[index].2;
+                let pull: Vec3 = states
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .filter_map(|(other, &(other_position, _, other_mass))| {
+                        let other_position = slots[other].map_or(other_position, |slot| positions[slot]);
+                        pairwise_force(position, mass, other_position, other_mass, config)
+                    })
+                    .sum();
+                flatten(pull / mass * FORCE_MULTIPLIER)
+            })
+            .collect()
+    };
+    let moved = |base: &[Vec3], rate: &[Vec3], by: f32| -> Vec<Vec3> {
+        base.iter().zip(rate).map(|(base, rate)| *base + *rate * by).collect()
+    };
+    let positions: Vec<Vec3> = group.iter().map(|&index| states[index].0).collect();
+    let velocities: Vec<Vec3> = group.iter().map(|&index| states[index].1).collect();
+
+    match integrator {
+        Integrator::SemiImplicitEuler => {
+            let velocities = moved(&velocities, &accelerations(&positions), dt);
+            moved(&positions, &velocities, dt).into_iter().zip(velocities).collect()
+        }
+        Integrator::Verlet => {
+            // The starting forces are recomputed rather than carried over from the last step,
+            // since collisions, flings and edits change the state in between
+            let start = accelerations(&positions);
+            let positions: Vec<Vec3> = positions
+                .iter()
+                .zip(&velocities)
+                .zip(&start)
+                .map(|((position, velocity), acceleration)| *position + *velocity * dt + *acceleration * (dt * dt / 2.0))
+                .collect();
+            let end = accelerations(&positions);
+            let velocities = velocities
+                .iter()
+                .zip(start.iter().zip(&end))
+                .map(|(velocity, (start, end))| *velocity + (*start + *end) * (dt / 2.0));
+            positions.into_iter().zip(velocities).collect()
+        }
+        Integrator::Rk4 => {
+            let k1 = (velocities.clone(), accelerations(&positions));
+            let k2 = (moved(&velocities, &k1.1, dt / 2.0), accelerations(&moved(&positions, &k1.0, dt / 2.0)));
+            let k3 = (moved(&velocities, &k2.1, dt / 2.0), accelerations(&moved(&positions, &k2.0, dt / 2.0)));
+            let k4 = (moved(&velocities, &k3.1, dt), accelerations(&moved(&positions, &k3.0, dt)));
+            let combine = |start: &[Vec3], rates: [&[Vec3]; 4]| -> Vec<Vec3> {
+                (0..start.len())
+                    .map(|slot| start[slot] + (rates[0][slot] + rates[1][slot] * 2.0 + rates[2][slot] * 2.0 + rates[3][slot]) * (dt / 6.0))
+                    .collect()
+            };
+            combine(&positions, [&k1.0, &k2.0, &k3.0, &k4.0])
+                .into_iter()
+                .zip(combine(&velocities, [&k1.1, &k2.1, &k3.1, &k4.1]))
+                .collect()
+        }
+    }
+}
+
+/// Laplace sphere-of-influence radius of a body at `distance` from its primary
+pub fn soi_radius(distance: f32, mass: f32, primary_mass: f32) -> f32 {
+    // Sized by magnitude, so negative masses get a sphere rather than NaN
+    distance * (mass / primary_mass).abs().powf(0.4)
+}
+
+/// Index of the body whose sphere of influence most tightly contains body `index`.
+/// `bodies` holds (position, mass, soi radius); only more massive bodies can be primaries.
+pub fn primary_index(index: usize, bodies: &[(Vec3, f32, f32)]) -> Option<usize> {
+    let (position, mass, _) = bodies[index];
+    bodies
+        .iter()
+        .enumerate()
+        .filter(|(other, (other_position, other_mass, soi))| {
+            *other != index && *other_mass > mass && position.distance(*other_position) < *soi
+        })
+        .min_by(|a, b| a.1 .2.total_cmp(&b.1 .2))
+        .map(|(other, _)| other)
+}
+
+/// Recompute spheres of influence and hand bodies over to a new primary when they cross into one
+#[allow(clippy::type_complexity)]
+pub fn soi_system(
+    mut commands: Commands,
+    query: Query<(Entity, &Transform, &Mass, &Name, Option<&Primary>), With<CelestialBody>>,
+    names: Query<&Name>,
+) {
+    let bodies: Vec<_> = query.iter().collect();
+    let Some(root) = bodies
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1 .2 .0.total_cmp(&b.1 .2 .0))
+        .map(|(index, _)| index)
+    else {
+        return;
+    };
+
+    // The most massive body dominates everywhere; everyone else's SOI is measured against their primary
+    let spheres: Vec<(Vec3, f32, f32)> = bodies
+        .iter()
+        .enumerate()
+        .map(|(index, (_, transform, mass, _, primary))| {
+            if index == root {
+                return (transform.translation, mass.0, f32::INFINITY);
+            }
+            let (primary_position, primary_mass) = primary
+                .and_then(|primary| query.get(primary.0).ok())
+                .filter(|(_, _, primary_mass, ..)| primary_mass.0 > mass.0)
+                .map(|(_, primary_transform, primary_mass, ..)| (primary_transform.translation, primary_mass.0))
+                .unwrap_or((bodies[root].1.translation, bodies[root].2 .0));
+            let distance = transform.translation.distance(primary_position);
+            (transform.translation, mass.0, soi_radius(distance, mass.0, primary_mass))
+        })
+        .collect();
+
+    for (index, (entity, _, _, name, primary)) in bodies.iter().enumerate() {
+        commands.entity(*entity).insert(SphereOfInfluence(spheres[index].2));
+        if index == root {
+            commands.entity(*entity).remove::<Primary>();
+            continue;
+        }
+        let Some(new_primary) = primary_index(index, &spheres).map(|other| bodies[other].0) else {
+            continue;
+        };
+        if primary.map(|primary| primary.0) != Some(new_primary) {
+            if let Ok(primary_name) = names.get(new_primary) {
+                info!("{} entered the sphere of influence of {}", name.0, primary_name.0);
+            }
+            commands.entity(*entity).insert(Primary(new_primary));
+        }
+    }
+}
+
+/// M cycles the force method. Alt+M switches straight between full N-body and central-only, to
+/// compare the perturbed orbits with clean ellipses from the same state; bodies carry on from
+/// wherever they are either way.
+pub fn force_method_toggle(key_input: Res<ButtonInput<KeyCode>>, mut config: ResMut<PhysicsConfig>) {
+    if !key_input.just_pressed(KeyCode::KeyM) {
+        return;
+    }
+    config.force_method = match (config.force_method, key_input.pressed(KeyCode::AltLeft)) {
+        (ForceMethod::CentralOnly, true) => ForceMethod::Direct,
+        (_, true) => ForceMethod::CentralOnly,
+        (ForceMethod::Direct, false) => ForceMethod::PatchedConics,
+        (ForceMethod::PatchedConics, false) => ForceMethod::CentralOnly,
+        (ForceMethod::CentralOnly, false) => ForceMethod::Direct,
+    };
+    info!("Force method: {:?}", config.force_method);
+}
+
+/// Run condition for systems that only matter under patched conics
+pub fn patched_conics_active(config: Res<PhysicsConfig>) -> bool {
+    config.force_method == ForceMethod::PatchedConics
+}
+
+/// Let the user know when an approximation that breaks conservation is active
+pub fn warn_physics_approximations(config: Res<PhysicsConfig>) {
+    if config.interaction_cutoff {
+        warn!(
+            "Interaction cutoff at {} units is on; energy and momentum will not be exactly conserved",
+            config.max_interaction_distance
+        );
+    }
+    warn_force_exponent(&config);
+    warn_softening(&config);
+    if config.exotic_matter {
+        warn!("Exotic matter is on: negative masses are allowed, which nothing real does");
+    }
+}
+
+pub fn warn_softening(config: &PhysicsConfig) {
+    if config.softening > 0.0 {
+        warn!(
+            "Gravity is softened within about {} units; close encounters are weaker than real gravity",
+            config.softening
+        );
+    }
+}
+
+// Softening lengths the ' key steps through
+pub const SOFTENING_STEPS: [f32; 5] = [0.0, 0.5, 1.0, 2.0, 4.0];
+
+/// ' steps the softening length through a few presets, back round to off
+pub fn softening_input(key_input: Res<ButtonInput<KeyCode>>, mut config: ResMut<PhysicsConfig>) {
+    if !key_input.just_pressed(KeyCode::Quote) {
+        return;
+    }
+    let next = SOFTENING_STEPS
+        .iter()
+        .copied()
+        .find(|&step| step > config.softening)
+        .unwrap_or(SOFTENING_STEPS[0]);
+    config.softening = next;
+    info!("Softening length: {next}");
+    warn_softening(&config);
+}
+
+pub fn warn_force_exponent(config: &PhysicsConfig) {
+    if config.force_exponent != 2.0 {
+        warn!(
+            "Gravity now falls off as 1/r^{}; orbits won't close and the Kepler overlays are no longer accurate",
+            config.force_exponent
+        );
+    }
+}
+
+// Step used when adjusting the force exponent from the keyboard
+pub const FORCE_EXPONENT_STEP: f32 = 0.25;
+
+/// , and . lower and raise the gravity law exponent
+pub fn force_exponent_input(key_input: Res<ButtonInput<KeyCode>>, mut config: ResMut<PhysicsConfig>) {
+    let step = if key_input.just_pressed(KeyCode::Comma) {
+        -FORCE_EXPONENT_STEP
+    } else if key_input.just_pressed(KeyCode::Period) {
+        FORCE_EXPONENT_STEP
+    } else {
+        return;
+    };
+    config.force_exponent = (config.force_exponent + step).clamp(0.5, 4.0);
+    info!("Force exponent: {}", config.force_exponent);
+    warn_force_exponent(&config);
+}
+
+// Relative change in the scheduled gravitational constant worth a log line
+pub const GRAVITY_SCHEDULE_LOG_CHANGE: f32 = 0.1;
+
+/// Log the scheduled gravitational constant whenever it has moved far from the last value logged
+pub fn gravity_schedule_log(config: Res<PhysicsConfig>, calendar: Res<Calendar>, mut logged: Local<Option<f32>>) {
+    if config.gravity_schedule == GravitySchedule::Constant {
+        *logged = None;
+        return;
+    }
+    let g = config.gravitational_constant_at(calendar.elapsed);
+    let changed = logged.is_none_or(|last| (g - last).abs() > GRAVITY_SCHEDULE_LOG_CHANGE * last.abs().max(f32::EPSILON));
+    if changed {
+        info!("Gravitational constant is now {g:.3} ({:.0}% of {})", g / config.gravitational_constant * 100.0, config.gravitational_constant);
+        *logged = Some(g);
+    }
+}
+
+// Factor each press of 9 or 0 scales the gravitational constant by
+pub const GRAVITATIONAL_CONSTANT_STEP: f32 = 1.25;
+
+/// 9 and 0 weaken and strengthen gravity
+pub fn gravitational_constant_input(key_input: Res<ButtonInput<KeyCode>>, mut config: ResMut<PhysicsConfig>) {
+    let factor = if key_input.just_pressed(KeyCode::Digit9) {
+        1.0 / GRAVITATIONAL_CONSTANT_STEP
+    } else if key_input.just_pressed(KeyCode::Digit0) {
+        GRAVITATIONAL_CONSTANT_STEP
+    } else {
+        return;
+    };
+    config.gravitational_constant *= factor;
+    info!("Gravitational constant: {} (press / to re-stabilize orbits)", config.gravitational_constant);
+}
+
+/// Index of the heavier body pulling hardest on body `index`, if any is heavier
+pub fn dominant_attractor(states: &[(Vec3, Vec3, f32)], index: usize, force_exponent: f32) -> Option<usize> {
+    let (position, _, mass) = states[index];
+    (0..states.len())
+        .filter(|&other| states[other].2 > mass && states[other].0 != position)
+        .max_by(|&a, &b| {
+            let pull = |other: usize| states[other].2 / position.distance(states[other].0).powf(force_exponent);
+            pull(a).total_cmp(&pull(b))
+        })
+}
+
+/// Velocities that put every body on a circular orbit about its dominant attractor at its current
+/// radius, keeping its direction of travel around it. Heavier bodies are settled first so
+/// moons follow their planet's new velocity; bodies with nothing heavier keep theirs.
+pub fn restabilized_velocities(states: &[(Vec3, Vec3, f32)], config: &PhysicsConfig) -> Vec<Vec3> {
+    let mut velocities: Vec<Vec3> = states.iter().map(|state| state.1).collect();
+    let mut order: Vec<usize> = (0..states.len()).collect();
+    order.sort_by(|&a, &b| states[b].2.total_cmp(&states[a].2));
+    for index in order {
+        let Some(central) = dominant_attractor(states, index, config.force_exponent) else {
+            continue;
+        };
+        let (position, velocity, mass) = states[index];
+        let relative_position = position - states[central].0;
+        let normal = relative_position.cross(velocity - states[central].1).try_normalize().unwrap_or(config.up_axis.up());
+        let Some(tangent) = normal.cross(relative_position).try_normalize() else {
+            continue;
+        };
+        let mu = config.gravitational_parameter(states[central].2, mass);
+        // No circular orbit exists when negative mass outweighs the pair's positive mass
+        if mu <= 0.0 {
+            continue;
+        }
+        velocities[index] =
+            velocities[central] + tangent * circular_velocity(mu, relative_position.length(), config.force_exponent);
+    }
+    velocities
+}
+
+/// / puts every orbiting body back on a circular orbit under the current gravity
+pub fn restabilize_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    config: Res<PhysicsConfig>,
+    mut query: Query<(&Transform, &mut Velocity, &Mass, Has<Anchored>), With<CelestialBody>>,
+) {
+    if !key_input.just_pressed(KeyCode::Slash) {
+        return;
+    }
+    let states: Vec<_> = query
+        .iter()
+        .map(|(transform, velocity, mass, _)| (transform.translation, velocity.0, mass.0))
+        .collect();
+    let velocities = restabilized_velocities(&states, &config);
+    for ((_, mut velocity, _, anchored), new_velocity) in query.iter_mut().zip(velocities) {
+        if !anchored {
+            velocity.0 = new_velocity;
+        }
+    }
+    info!("Orbits re-stabilized for G = {}", config.gravitational_constant);
+}
+
+/// Every velocity of (position, velocity, mass) states scaled by `factor` about the center of
+/// mass velocity, which is kept unless `include_center` scales it as well
+pub fn scaled_velocities(states: &[(Vec3, Vec3, f32)], factor: f32, include_center: bool) -> Vec<Vec3> {
+    let center = if include_center { Vec3::ZERO } else { center_of_mass(states).map_or(Vec3::ZERO, |(_, velocity)| velocity) };
+    states.iter().map(|(_, velocity, _)| center + (*velocity - center) * factor).collect()
+}
+
+/// Movement system
+#[allow(clippy::type_complexity)]
+pub fn movement_system(
+    mut query: Query<(&mut Transform, &mut Velocity, Has<Anchored>, Option<&IntegratorOverride>), With<CelestialBody>>,
+    config: Res<PhysicsConfig>,
+    planar: Res<PlanarMode>,
+    reverse: Res<ReverseMode>,
+    mut profile: ResMut<PhysicsProfile>,
+    time: Res<Time>,
+) {
+    let start = Instant::now();
+    let dt = config.step_dt(time.delta_seconds()) * reverse.direction();
+    for (mut transform, mut velocity, anchored, body_override) in query.iter_mut() {
+        // Higher-order integrators already moved their bodies in gravity_system
+        if anchored || config.integrator_for(body_override) != Integrator::SemiImplicitEuler {
+            continue;
+        }
+        if planar.0 {
+            // Flatten onto the orbital plane so small inclinations can't build up
+            velocity.0 = config.up_axis.flatten(velocity.0);
+            transform.translation = config.up_axis.flatten(transform.translation);
+        }
+        transform.translation += velocity.0 * dt;
+    }
+    PhysicsProfile::smooth(&mut profile.movement_ms, start.elapsed());
+}
+
+/// Velocity changes for two touching bodies, or `None` if they are already separating.
+/// `normal` points from the first body to the second.
+pub fn bounce_impulse(
+    normal: Vec3,
+    velocity1: Vec3,
+    mass1: f32,
+    material1: SurfaceMaterial,
+    velocity2: Vec3,
+    mass2: f32,
+    material2: SurfaceMaterial,
+) -> Option<(Vec3, Vec3)> {
+    let relative_velocity = velocity2 - velocity1;
+    let normal_speed = relative_velocity.dot(normal);
+    if normal_speed >= 0.0 {
+        return None;
+    }
+
+    // Either body being dead or grippy is enough to make the contact so
+    let restitution = material1.restitution * material2.restitution;
+    let friction = (material1.friction * material2.friction).sqrt();
+    let inverse_mass_sum = 1.0 / mass1 + 1.0 / mass2;
+
+    let normal_impulse = -(1.0 + restitution) * normal_speed / inverse_mass_sum;
+    let mut impulse = normal * normal_impulse;
+
+    // Friction opposes sliding, limited by Coulomb's law and by what would stop the sliding entirely
+    let tangential_velocity = relative_velocity - normal * normal_speed;
+    if let Some(tangent) = tangential_velocity.try_normalize() {
+        let stopping_impulse = tangential_velocity.length() / inverse_mass_sum;
+        impulse -= tangent * (friction * normal_impulse).min(stopping_impulse);
+    }
+
+    // Equal and opposite, so momentum is conserved
+    Some((-impulse / mass1, impulse / mass2))
+}
+
+/// Resolve overlapping bodies according to the collision mode
+#[allow(clippy::type_complexity)]
+pub fn collision_system(
+    mut query: Query<
+        (Entity, &mut Transform, &mut Velocity, &Mass, &Radius, Option<&SurfaceMaterial>),
+        With<CelestialBody>,
+    >,
+    config: Res<PhysicsConfig>,
+    mut profile: ResMut<PhysicsProfile>,
+    mut history: ResMut<CollisionHistory>,
+    calendar: Option<Res<Calendar>>,
+) {
+    if config.collision_mode != CollisionMode::Bounce {
+        return;
+    }
+    let start = Instant::now();
+    let elapsed = calendar.map_or(0.0, |calendar| calendar.elapsed);
+
+    let mut combinations = query.iter_combinations_mut();
+    while let Some(
+        [
+            (entity1, mut transform1, mut vel1, mass1, radius1, material1),
+            (entity2, mut transform2, mut vel2, mass2, radius2, material2),
+        ],
+    ) = combinations.fetch_next()
+    {
+        // A bounce with negative mass has no sensible outcome (the push-apart split can divide
+        // by zero), so exotic matter passes through
+        if mass1.0 <= 0.0 || mass2.0 <= 0.0 {
+            continue;
+        }
+        let offset = transform2.translation - transform1.translation;
+        let distance = offset.length();
+        let overlap = radius1.0 + radius2.0 - distance;
+        if overlap <= 0.0 {
+            continue;
+        }
+        let normal = offset.try_normalize().unwrap_or(Vec3::X);
+        history.record(
+            elapsed,
+            [
+                (entity1, transform1.translation, vel1.0, mass1.0, radius1.0),
+                (entity2, transform2.translation, vel2.0, mass2.0, radius2.0),
+            ],
+        );
+
+        if let Some((dv1, dv2)) = bounce_impulse(
+            normal,
+            vel1.0,
+            mass1.0,
+            material1.copied().unwrap_or_default(),
+            vel2.0,
+            mass2.0,
+            material2.copied().unwrap_or_default(),
+        ) {
+            vel1.0 += dv1;
+            vel2.0 += dv2;
+        }
+
+        // Push the pair apart, the lighter body moving further, so they don't sink into each other
+        let share1 = mass2.0 / (mass1.0 + mass2.0);
+        transform1.translation -= normal * overlap * share1;
+        transform2.translation += normal * overlap * (1.0 - share1);
+    }
+    PhysicsProfile::smooth(&mut profile.collision_ms, start.elapsed());
+}
+
+/// How many collisions the history keeps before forgetting the oldest
+pub const COLLISION_HISTORY_LIMIT: usize = 100_000;
+
+/// A body as it was just before a collision or mass transfer changed it
+#[derive(Clone, Copy, Debug)]
+pub struct CollisionBodyState {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+    pub radius: f32,
+}
+
+/// One resolved collision or mass exchange, stamped with the physics step and simulated time it happened at
+#[derive(Clone, Debug)]
+pub struct CollisionRecord {
+    pub step: u64,
+    /// Simulated seconds, as on the calendar
+    pub elapsed: f32,
+    pub before: [CollisionBodyState; 2],
+}
+
+/// Every collision resolved so far, and every step of mass transfer, with the full states going
+/// into it, so reverse mode can reconstruct the bodies as they were instead of retracing a bounce
+/// that never happened or leaving the transferred mass where it ended up.
+/// Records are dropped as they are undone, and only the last COLLISION_HISTORY_LIMIT are kept.
+/// Bodies never merge (CollisionMode has no merging mode), so every record is between two bodies
+/// that both still exist and there is nothing to split back apart.
+#[derive(Resource, Default)]
+pub struct CollisionHistory {
+    /// Physics steps taken, less those stepped back over
+    pub step: u64,
+    pub records: Vec<CollisionRecord>,
+}
+
+impl CollisionHistory {
+    /// Log a collision in the current step from (entity, position, velocity, mass, radius) of both
+    /// bodies
+    pub fn record(&mut self, elapsed: f32, bodies: [(Entity, Vec3, Vec3, f32, f32); 2]) {
+        let before = bodies
+            .map(|(entity, position, velocity, mass, radius)| CollisionBodyState { entity, position, velocity, mass, radius });
+        self.records.push(CollisionRecord { step: self.step, elapsed, before });
+        if self.records.len() > COLLISION_HISTORY_LIMIT {
+            let excess = self.records.len() - COLLISION_HISTORY_LIMIT;
+            self.records.drain(..excess);
+        }
+    }
+}
+
+/// Counts physics steps for the collision history, and while reversing undoes the collisions of
+/// the step being stepped back over (latest first) before its drift and kick are undone
+pub fn collision_history_system(
+    mut history: ResMut<CollisionHistory>,
+    reverse: Res<ReverseMode>,
+    mut bodies: Query<(&mut Transform, &mut Velocity, &mut Mass, &mut Radius), With<CelestialBody>>,
+) {
+    if !reverse.0 {
+        history.step += 1;
+        return;
+    }
+    let step = history.step;
+    while let Some(record) = history.records.pop_if(|record| record.step >= step) {
+        for state in record.before {
+            if let Ok((mut transform, mut velocity, mut mass, mut radius)) = bodies.get_mut(state.entity) {
+                transform.translation = state.position;
+                velocity.0 = state.velocity;
+                mass.0 = state.mass;
+                // Mass transfer scaled the mesh along with the radius
+                transform.scale *= state.radius / radius.0;
+                radius.0 = state.radius;
+            }
+        }
+    }
+    history.step = step.saturating_sub(1);
+}
+
+/// Mass flowing between close pairs, such as a star feeding its binary companion. Off by default,
+/// toggled with B.
+#[derive(Resource)]
+pub struct MassTransfer {
+    pub enabled: bool,
+    /// Pairs closer than this many times their combined radii exchange mass
+    pub reach: f32,
+    /// Fraction of the donor's mass that flows over per second
+    pub rate: f32,
+    /// (donor, accretor) pairs that exchanged mass in the last step, for drawing the streams
+    pub streams: Vec<(Entity, Entity)>,
+}
+
+impl Default for MassTransfer {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reach: 3.0,
+            rate: 0.05,
+            streams: Vec::new(),
+        }
+    }
+}
+
+/// Mass density, taking bodies as uniform spheres
+pub fn density(mass: f32, radius: f32) -> f32 {
+    mass / (4.0 / 3.0 * std::f32::consts::PI * radius.powi(3))
+}
+
+/// Radius after a body gains or loses mass at constant density
+pub fn radius_for_mass(radius: f32, old_mass: f32, new_mass: f32) -> f32 {
+    radius * (new_mass / old_mass).cbrt()
+}
+
+/// B turns mass transfer on and off
+pub fn mass_transfer_toggle(key_input: Res<ButtonInput<KeyCode>>, mut transfer: ResMut<MassTransfer>) {
+    if key_input.just_pressed(KeyCode::KeyB) {
+        transfer.enabled = !transfer.enabled;
+        info!("Mass transfer {}", if transfer.enabled { "on" } else { "off" });
+    }
+}
+
+/// Move mass from the less dense body of each close pair to the denser one. Total mass and
+/// momentum are conserved: the transferred mass arrives carrying the donor's velocity. Each
+/// exchange is logged in the CollisionHistory so reverse mode can hand the mass back.
+#[allow(clippy::type_complexity)]
+pub fn mass_transfer_system(
+    mut transfer: ResMut<MassTransfer>,
+    mut query: Query<
+        (Entity, &mut Transform, &mut Velocity, &mut Mass, &mut Radius, Has<Anchored>),
+        With<CelestialBody>,
+    >,
+    config: Res<PhysicsConfig>,
+    mut history: ResMut<CollisionHistory>,
+    calendar: Option<Res<Calendar>>,
+    time: Res<Time>,
+) {
+    transfer.streams.clear();
+    if !transfer.enabled {
+        return;
+    }
+    let dt = config.step_dt(time.delta_seconds());
+    let elapsed = calendar.map_or(0.0, |calendar| calendar.elapsed);
+
+    let mut combinations = query.iter_combinations_mut();
+    while let Some([mut body1, mut body2]) = combinations.fetch_next() {
+        let distance = body1.1.translation.distance(body2.1.translation);
+        // Negative masses neither give nor take
+        if distance > transfer.reach * (body1.4 .0 + body2.4 .0) || body1.3 .0 <= 0.0 || body2.3 .0 <= 0.0 {
+            continue;
+        }
+        if density(body1.3 .0, body1.4 .0) > density(body2.3 .0, body2.4 .0) {
+            std::mem::swap(&mut body1, &mut body2);
+        }
+        let (
+            (donor, mut donor_transform, donor_velocity, mut donor_mass, mut donor_radius, _),
+            (accretor, mut accretor_transform, mut accretor_velocity, mut accretor_mass, mut accretor_radius, anchored),
+        ) = (body1, body2);
+        let amount = donor_mass.0 * transfer.rate * dt;
+        if amount <= 0.0 {
+            continue;
+        }
+        history.record(
+            elapsed,
+            [
+                (donor, donor_transform.translation, donor_velocity.0, donor_mass.0, donor_radius.0),
+                (accretor, accretor_transform.translation, accretor_velocity.0, accretor_mass.0, accretor_radius.0),
+            ],
+        );
+
+        if !anchored {
+            accretor_velocity.0 =
+                (accretor_velocity.0 * accretor_mass.0 + donor_velocity.0 * amount) / (accretor_mass.0 + amount);
+        }
+        // Meshes are built at the original radius, so the scale follows the radius
+        let new_radius = radius_for_mass(donor_radius.0, donor_mass.0, donor_mass.0 - amount);
+        donor_transform.scale *= new_radius / donor_radius.0;
+        donor_radius.0 = new_radius;
+        let new_radius = radius_for_mass(accretor_radius.0, accretor_mass.0, accretor_mass.0 + amount);
+        accretor_transform.scale *= new_radius / accretor_radius.0;
+        accretor_radius.0 = new_radius;
+        donor_mass.0 -= amount;
+        accretor_mass.0 += amount;
+        transfer.streams.push((donor, accretor));
+    }
+}
+
+/// C cycles the collision mode
+pub fn collision_mode_toggle(key_input: Res<ButtonInput<KeyCode>>, mut config: ResMut<PhysicsConfig>) {
+    if key_input.just_pressed(KeyCode::KeyC) {
+        config.collision_mode = match config.collision_mode {
+            CollisionMode::None => CollisionMode::Bounce,
+            CollisionMode::Bounce => CollisionMode::None,
+        };
+        info!("Collision mode: {:?}", config.collision_mode);
+    }
+}
+
+/// Holding Backspace runs time backwards
+pub fn reverse_mode_input(key_input: Res<ButtonInput<KeyCode>>, mut reverse: ResMut<ReverseMode>) {
+    let held = key_input.pressed(KeyCode::Backspace);
+    if reverse.0 != held {
+        reverse.0 = held;
+    }
+}
+
+pub fn running_forward(reverse: Res<ReverseMode>) -> bool {
+    !reverse.0
+}
+
+pub fn running_backward(reverse: Res<ReverseMode>) -> bool {
+    reverse.0
+}
+
+/// P pauses and resumes the physics. Space also starts a simulation waiting in the setup preview.
+pub fn pause_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut paused: ResMut<Paused>,
+    mut preview: ResMut<SetupPreview>,
+    save_menu: Res<SaveMenu>,
+) {
+    // The save menu keeps the simulation paused until it closes
+    if save_menu.open {
+        return;
+    }
+    let start_from_preview = paused.0 && !preview.started && key_input.just_pressed(KeyCode::Space);
+    if key_input.just_pressed(KeyCode::KeyP) || start_from_preview {
+        paused.0 = !paused.0;
+        info!("{}", if paused.0 { "Paused" } else { "Running" });
+    }
+    if !paused.0 {
+        preview.started = true;
+    }
+}
+
+pub fn simulation_unpaused(paused: Res<Paused>) -> bool {
+    !paused.0
+}
+
+// The calendar treats one orbit of its reference body as a year of this many days
+pub const CALENDAR_YEAR_DAYS: f32 = 365.25;
+
+/// Simulated time as calendar days, measured in orbits of a reference body
+#[derive(Resource)]
+pub struct Calendar {
+    /// Simulated seconds since the start
+    pub elapsed: f32,
+    /// Body whose orbit sets the length of a year. Falls back to the heaviest body orbiting the
+    /// heaviest one, the Middle Planet in the default system.
+    pub reference: Option<Entity>,
+    /// (body, orbital period) the current day length came from
+    pub period: Option<(Entity, f32)>,
+    /// Keep the time scale at `days_per_second` calendar days per real second
+    pub locked: bool,
+    pub days_per_second: f32,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Self {
+            elapsed: 0.0,
+            reference: None,
+            period: None,
+            locked: false,
+            days_per_second: 1.0,
+        }
+    }
+}
+
+impl Calendar {
+    /// Simulated seconds in one calendar day
+    pub fn day_length(&self) -> Option<f32> {
+        self.period.map(|(_, period)| period / CALENDAR_YEAR_DAYS)
+    }
+
+    pub fn days(&self) -> Option<f32> {
+        self.day_length().map(|day| self.elapsed / day)
+    }
+}
+
+/// Period of `body` around the heaviest other body, if it is on a bound orbit
+pub fn reference_period(
+    body: Entity,
+    bodies: &Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+    config: &PhysicsConfig,
+) -> Option<f32> {
+    let (_, transform, velocity, mass) = bodies.get(body).ok()?;
+    let (_, central_transform, central_velocity, central_mass) = bodies
+        .iter()
+        .filter(|(other, ..)| *other != body)
+        .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))?;
+    let mu = config.gravitational_parameter(central_mass.0, mass.0);
+    if mu.is_nan() || mu <= 0.0 {
+        return None;
+    }
+    let elements = orbital_elements(
+        transform.translation - central_transform.translation,
+        velocity.0 - central_velocity.0,
+        mu,
+    );
+    (elements.eccentricity < 1.0 && elements.semi_major_axis > 0.0)
+        .then(|| orbital_period(mu, elements.semi_major_axis))
+}
+
+/// Keep the day length in step with the reference body, hold the time scale at the locked
+/// calendar rate and advance the calendar by the physics step
+pub fn calendar_system(
+    mut calendar: ResMut<Calendar>,
+    mut config: ResMut<PhysicsConfig>,
+    paused: Res<Paused>,
+    reverse: Res<ReverseMode>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+    time: Res<Time>,
+) {
+    let reference = calendar.reference.filter(|&entity| bodies.contains(entity)).or_else(|| {
+        let heaviest = bodies.iter().max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))?.0;
+        bodies
+            .iter()
+            .filter(|(entity, ..)| *entity != heaviest)
+            .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))
+            .map(|(entity, ..)| entity)
+    });
+    // Measured once per reference body, so the day length doesn't wobble with perturbations
+    if reference != calendar.period.map(|(entity, _)| entity) {
+        calendar.period = reference.and_then(|entity| Some((entity, reference_period(entity, &bodies, &config.at_time(calendar.elapsed))?)));
+        if let Some(day) = calendar.day_length() {
+            info!("Calendar day is {day:.4} simulated seconds");
+        }
+    }
+
+    if calendar.locked {
+        if let Some(day) = calendar.day_length() {
+            let time_scale = calendar.days_per_second * day;
+            if config.time_scale != time_scale {
+                config.time_scale = time_scale;
+            }
+        }
+    }
+    if !paused.0 {
+        calendar.elapsed += config.step_dt(time.delta_seconds()) * reverse.direction();
+    }
+}
+
+/// Minus and equals halve and double the time scale, or the calendar rate while it is locked. U locks the
+/// time scale to the calendar rate and unlocks it again, and Y makes the selected body the
+/// calendar's reference.
+pub fn time_scale_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut config: ResMut<PhysicsConfig>,
+    mut calendar: ResMut<Calendar>,
+    selected: Query<Entity, (With<Selected>, With<CelestialBody>)>,
+) {
+    let factor = if key_input.just_pressed(KeyCode::Minus) {
+        0.5
+    } else if key_input.just_pressed(KeyCode::Equal) {
+        2.0
+    } else {
+        1.0
+    };
+    if factor != 1.0 {
+        if calendar.locked {
+            calendar.days_per_second *= factor;
+            info!("Calendar rate: {} days per second", calendar.days_per_second);
+        } else {
+            config.time_scale *= factor;
+            info!("Time scale: {}x", config.time_scale);
+        }
+    }
+    if key_input.just_pressed(KeyCode::KeyU) {
+        calendar.locked = !calendar.locked;
+        if calendar.locked {
+            info!("Time scale locked to {} days per second", calendar.days_per_second);
+        } else {
+            info!("Time scale unlocked");
+        }
+    }
+    if key_input.just_pressed(KeyCode::KeyY) {
+        if let Some(entity) = selected.iter().next() {
+            calendar.reference = Some(entity);
+        }
+    }
+}
+
+/// Predict every body's path for `steps` steps of `dt` with the direct pairwise method,
+/// reporting each body's position every `sample_every` steps
+pub fn predict_paths(
+    bodies: &[(Vec3, Vec3, f32)],
+    config: &PhysicsConfig,
+    dt: f32,
+    steps: usize,
+    sample_every: usize,
+) -> Vec<Vec<Vec3>> {
+    let mut states = bodies.to_vec();
+    let mut paths: Vec<Vec<Vec3>> = states.iter().map(|(position, ..)| vec![*position]).collect();
+    for step in 1..=steps {
+        predict_step(&mut states, config, dt);
+        if step % sample_every == 0 {
+            for (state, path) in states.iter().zip(&mut paths) {
+                path.push(state.0);
+            }
+        }
+    }
+    paths
+}
+
+/// Advance (position, velocity, mass) states by one step of the direct pairwise method
+pub fn predict_step(states: &mut [(Vec3, Vec3, f32)], config: &PhysicsConfig, dt: f32) {
+    // Same kick-then-drift order as gravity_system followed by movement_system
+    for i in 0..states.len() {
+        for j in i + 1..states.len() {
+            let (position1, _, mass1) = states[i];
+            let (position2, _, mass2) = states[j];
+            if let Some(force) = pairwise_force(position1, mass1, position2, mass2, config) {
+                states[i].1 += force / mass1 * dt * FORCE_MULTIPLIER;
+                states[j].1 -= force / mass2 * dt * FORCE_MULTIPLIER;
+            }
+        }
+    }
+    for state in states.iter_mut() {
+        state.0 += state.1 * dt;
+    }
+}
+
+/// A planned burn: at simulated time `time` (on the calendar clock) the body's velocity changes by
+/// `delta_v`, given as (prograde, radial, normal) in its orbital frame at that moment
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ManeuverNode {
+    pub time: f32,
+    pub delta_v: Vec3,
+}
+
+/// Planned burns of a body, soonest first
+#[derive(Component, Default)]
+pub struct ManeuverNodes(pub Vec<ManeuverNode>);
+
+/// Columns point prograde, radially outwards and along the orbit normal, for motion relative to
+/// the central body. `None` when the body isn't moving relative to it or moves purely radially.
+pub fn maneuver_frame(relative_position: Vec3, relative_velocity: Vec3) -> Option<Mat3> {
+    let prograde = relative_velocity.try_normalize()?;
+    let normal = relative_position.cross(relative_velocity).try_normalize()?;
+    Some(Mat3::from_cols(prograde, prograde.cross(normal), normal))
+}
+
+/// A burn's delta-v in world coordinates, for a body and the body it orbits
+pub fn maneuver_world_delta_v(delta_v: Vec3, body: (Vec3, Vec3), central: (Vec3, Vec3)) -> Vec3 {
+    maneuver_frame(body.0 - central.0, body.1 - central.1).map_or(Vec3::ZERO, |frame| frame * delta_v)
+}
+
+/// Index of the heaviest state other than `index`
+pub fn heaviest_other(states: &[(Vec3, Vec3, f32)], index: usize) -> Option<usize> {
+    (0..states.len())
+        .filter(|&other| other != index)
+        .max_by(|&a, &b| states[a].2.total_cmp(&states[b].2))
+}
+
+/// Predicted path of body `index` through its burns (with times relative to now), split into
+/// one segment before the first burn and one after each, each segment starting where the
+/// previous one ended
+pub fn predict_maneuvers(
+    bodies: &[(Vec3, Vec3, f32)],
+    index: usize,
+    nodes: &[ManeuverNode],
+    config: &PhysicsConfig,
+    dt: f32,
+    duration: f32,
+    sample_every: usize,
+) -> Vec<Vec<Vec3>> {
+    let mut states = bodies.to_vec();
+    let central = heaviest_other(&states, index);
+    let mut segments = vec![vec![states[index].0]];
+    let mut pending = nodes.iter().peekable();
+    let steps = (duration / dt).ceil() as usize;
+    for step in 1..=steps {
+        // Burns fire at the start of the first step at or past their time, like maneuver_system
+        let time = (step - 1) as f32 * dt;
+        while let Some(node) = pending.next_if(|node| node.time <= time) {
+            if let Some(central) = central {
+                let (position, velocity, _) = states[index];
+                let (central_position, central_velocity, _) = states[central];
+                states[index].1 +=
+                    maneuver_world_delta_v(node.delta_v, (position, velocity), (central_position, central_velocity));
+            }
+            segments.push(vec![states[index].0]);
+        }
+        predict_step(&mut states, config, dt);
+        if step % sample_every == 0 {
+            if let Some(segment) = segments.last_mut() {
+                segment.push(states[index].0);
+            }
+        }
+    }
+    segments
+}
+
+/// Fire the burns whose time has come, relative to the heaviest other body
+#[allow(clippy::type_complexity)]
+pub fn maneuver_system(
+    calendar: Res<Calendar>,
+    mut bodies: Query<(Entity, &Transform, &mut Velocity, &Mass, Option<&mut ManeuverNodes>), With<CelestialBody>>,
+) {
+    let states: Vec<(Entity, Vec3, Vec3, f32)> = bodies
+        .iter()
+        .map(|(entity, transform, velocity, mass, _)| (entity, transform.translation, velocity.0, mass.0))
+        .collect();
+    for (entity, transform, mut velocity, _, nodes) in bodies.iter_mut() {
+        let Some(mut nodes) = nodes else {
+            continue;
+        };
+        let due = nodes.0.iter().take_while(|node| node.time <= calendar.elapsed).count();
+        if due == 0 {
+            continue;
+        }
+        let Some(&(_, central_position, central_velocity, _)) = states
+            .iter()
+            .filter(|(other, ..)| *other != entity)
+            .max_by(|a, b| a.3.total_cmp(&b.3))
+        else {
+            continue;
+        };
+        for node in nodes.0.drain(..due) {
+            let delta_v = maneuver_world_delta_v(
+                node.delta_v,
+                (transform.translation, velocity.0),
+                (central_position, central_velocity),
+            );
+            velocity.0 += delta_v;
+            info!("Burn of {:.3} executed", delta_v.length());
+        }
+    }
+}
+
+/// The `sample`th of `samples` directions spread evenly over the unit sphere
+pub fn fan_direction(sample: usize, samples: usize) -> Vec3 {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+    let y = 1.0 - 2.0 * (sample as f32 + 0.5) / samples as f32;
+    let ring = (1.0 - y * y).max(0.0).sqrt();
+    let angle = golden_angle * sample as f32;
+    Vec3::new(ring * angle.cos(), y, ring * angle.sin())
+}
+
+/// Predicted paths of body `index` when its velocity is nudged by `spread` in each of `samples`
+/// directions, with the other bodies left as they are
+pub fn trajectory_fan(
+    states: &[(Vec3, Vec3, f32)],
+    index: usize,
+    config: &PhysicsConfig,
+    dt: f32,
+    steps: usize,
+    samples: usize,
+    spread: f32,
+) -> Vec<Vec<Vec3>> {
+    (0..samples)
+        .map(|sample| {
+            let mut nudged = states.to_vec();
+            nudged[index].1 += fan_direction(sample, samples) * spread;
+            predict_paths(&nudged, config, dt, steps, PREVIEW_SAMPLE_EVERY).swap_remove(index)
+        })
+        .collect()
+}
+
+/// L toggles planar mode
+pub fn planar_mode_toggle(key_input: Res<ButtonInput<KeyCode>>, mut planar: ResMut<PlanarMode>) {
+    if key_input.just_pressed(KeyCode::KeyL) {
+        planar.0 = !planar.0;
+        info!("Planar mode {}", if planar.0 { "on" } else { "off" });
+    }
+}
+
+/// Mass-weighted position and velocity of (position, velocity, mass) states, or `None` without mass.
+/// A lone body is its own center of mass, exactly.
+pub fn center_of_mass(bodies: &[(Vec3, Vec3, f32)]) -> Option<(Vec3, Vec3)> {
+    if let [(position, velocity, _)] = bodies {
+        return Some((*position, *velocity));
+    }
+    let total_mass: f32 = bodies.iter().map(|(_, _, mass)| mass).sum();
+    if total_mass == 0.0 {
+        return None;
+    }
+    let position = bodies.iter().map(|(position, _, mass)| *position * *mass).sum::<Vec3>() / total_mass;
+    let velocity = bodies.iter().map(|(_, velocity, mass)| *velocity * *mass).sum::<Vec3>() / total_mass;
+    Some((position, velocity))
+}
+
+/// Total angular momentum `sum(m * r x v)` with positions and velocities taken relative to the center of mass
+pub fn total_angular_momentum(bodies: &[(Vec3, Vec3, f32)], com_position: Vec3, com_velocity: Vec3) -> Vec3 {
+    bodies
+        .iter()
+        .map(|(position, velocity, mass)| {
+            (*position - com_position).cross(*velocity - com_velocity) * *mass
+        })
+        .sum()
+}
+
+// Seconds between angular momentum drift log lines
+pub const ANGULAR_MOMENTUM_LOG_SECONDS: f32 = 5.0;
+
+/// Measure the total angular momentum and log how far it drifts. In a closed system it should
+/// stay fixed; a good integrator keeps it that way. Nothing is measured or logged while paused.
+pub fn angular_momentum_system(
+    mut monitor: ResMut<AngularMomentumMonitor>,
+    query: Query<(&Transform, &Velocity, &Mass), With<CelestialBody>>,
+    paused: Res<Paused>,
+    time: Res<Time>,
+) {
+    if paused.0 {
+        return;
+    }
+    let bodies: Vec<(Vec3, Vec3, f32)> = query
+        .iter()
+        .map(|(transform, velocity, mass)| (transform.translation, velocity.0, mass.0))
+        .collect();
+    let Some((com_position, com_velocity)) = center_of_mass(&bodies) else {
+        return;
+    };
+    let momentum = total_angular_momentum(&bodies, com_position, com_velocity);
+    let initial = *monitor.initial.get_or_insert(momentum);
+    monitor.latest = Some((com_position, momentum));
+    monitor.samples += 1;
+
+    let Some(initial_direction) = initial.try_normalize() else {
+        return;
+    };
+    let relative_magnitude = momentum.length() / initial.length();
+    let direction = momentum.try_normalize().unwrap_or(initial_direction);
+    monitor.since_log += time.delta_seconds();
+    if monitor.since_log >= ANGULAR_MOMENTUM_LOG_SECONDS {
+        monitor.since_log = 0.0;
+        info!(
+            "Angular momentum |L| = {:.3}, drift {:+.4}%, direction off by {:.4} deg",
+            momentum.length(),
+            (relative_magnitude - 1.0) * 100.0,
+            direction.angle_between(initial_direction).to_degrees()
+        );
+    }
+}
+
+/// Which way a body goes around the barycenter compared to the system as a whole
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OrbitDirection {
+    Prograde,
+    Retrograde,
+}
+
+/// Compare a body's angular momentum about the center of mass with the system's total.
+/// `None` when either is zero, like a body sitting on the barycenter or heading straight at it.
+pub fn orbit_direction(
+    position: Vec3,
+    velocity: Vec3,
+    com_position: Vec3,
+    com_velocity: Vec3,
+    system_momentum: Vec3,
+) -> Option<OrbitDirection> {
+    let alignment = (position - com_position).cross(velocity - com_velocity).dot(system_momentum);
+    if alignment > 0.0 {
+        Some(OrbitDirection::Prograde)
+    } else if alignment < 0.0 {
+        Some(OrbitDirection::Retrograde)
+    } else {
+        None
+    }
+}
+
+/// Pairs of (position, velocity, mass) states whose two-body energy is negative, so left to
+/// themselves they would orbit each other, with how tightly: binding energy over the potential
+/// at their separation, 0 when barely bound and 0.5 for a circular orbit
+pub fn bound_pairs(states: &[(Vec3, Vec3, f32)], config: &PhysicsConfig) -> Vec<(usize, usize, f32)> {
+    let mut pairs = Vec::new();
+    for i in 0..states.len() {
+        for j in i + 1..states.len() {
+            let (position1, velocity1, mass1) = states[i];
+            let (position2, velocity2, mass2) = states[j];
+            let distance = position1.distance(position2);
+            if distance <= 0.0 {
+                continue;
+            }
+            let potential = config.gravitational_parameter(mass1, mass2) / distance;
+            let specific_energy = velocity1.distance_squared(velocity2) / 2.0 - potential;
+            if specific_energy < 0.0 {
+                pairs.push((i, j, -specific_energy / potential));
+            }
+        }
+    }
+    pairs
+}
+
+/// Specific orbital energy of body `index`: kinetic energy relative to the barycenter plus the
+/// potential from every other body, per unit mass. Negative means bound.
+pub fn specific_orbital_energy(index: usize, bodies: &[(Vec3, Vec3, f32)], com_velocity: Vec3, config: &PhysicsConfig) -> f32 {
+    let (position, velocity, _) = bodies[index];
+    let potential: f32 = bodies
+        .iter()
+        .enumerate()
+        .filter(|(other, _)| *other != index)
+        .map(|(_, (other_position, _, other_mass))| {
+            -config.gravitational_parameter(*other_mass, 0.0) / position.distance(*other_position)
+        })
+        .sum();
+    0.5 * (velocity - com_velocity).length_squared() + potential
+}
+
+/// Watch for bodies whose orbital energy turns positive, warning once per escape and marking them
+/// Unbound until they fall back into a bound orbit
+#[allow(clippy::type_complexity)]
+pub fn ejection_monitor_system(
+    mut commands: Commands,
+    mut warnings: EventWriter<EjectionWarning>,
+    query: Query<(Entity, &Transform, &Velocity, &Mass, Has<Unbound>), With<CelestialBody>>,
+    config: Res<PhysicsConfig>,
+    calendar: Option<Res<Calendar>>,
+) {
+    let config = config.at_time(calendar.map_or(0.0, |calendar| calendar.elapsed));
+    let bodies: Vec<(Vec3, Vec3, f32)> = query
+        .iter()
+        .map(|(_, transform, velocity, mass, ..)| (transform.translation, velocity.0, mass.0))
+        .collect();
+    let Some((_, com_velocity)) = center_of_mass(&bodies) else {
+        return;
+    };
+    for (index, (entity, _, _, _, unbound)) in query.iter().enumerate() {
+        let energy = specific_orbital_energy(index, &bodies, com_velocity, &config);
+        if energy > 0.0 && !unbound {
+            warnings.send(EjectionWarning { entity, energy });
+            commands.entity(entity).insert(Unbound);
+        } else if energy <= 0.0 && unbound {
+            commands.entity(entity).remove::<Unbound>();
+        }
+    }
+}
+
+pub fn report_ejections(mut warnings: EventReader<EjectionWarning>, names: Query<&Name>) {
+    for warning in warnings.read() {
+        if let Ok(name) = names.get(warning.entity) {
+            warn!("{} is no longer bound to the system (orbital energy {:.4})", name.0, warning.energy);
+        }
+    }
+}
+
+/// Where a body at `position` ends up under the boundary, `None` if it is despawned, or its
+/// position unchanged while it is inside
+pub fn boundary_position(boundary: &BoundaryConfig, position: Vec3) -> Option<Vec3> {
+    let Some(radius) = boundary.radius else {
+        return Some(position);
+    };
+    let distance = position.length();
+    if distance <= radius {
+        return Some(position);
+    }
+    match boundary.action {
+        BoundaryAction::Despawn => None,
+        // Straight through the center to the far side, so an outbound body heads back in
+        BoundaryAction::Wrap => Some(-position * (radius / distance)),
+    }
+}
+
+/// Despawn or wrap every body that has crossed the boundary
+#[allow(clippy::type_complexity)]
+pub fn boundary_system(
+    mut commands: Commands,
+    config: Res<PhysicsConfig>,
+    mut bodies: Query<(Entity, &mut Transform, &Name, Option<&mut Trail>, Option<&TrailColor>), With<CelestialBody>>,
+) {
+    if config.boundary.radius.is_none() {
+        return;
+    }
+    for (entity, mut transform, name, trail, trail_color) in bodies.iter_mut() {
+        match boundary_position(&config.boundary, transform.translation) {
+            Some(position) if position == transform.translation => {}
+            Some(position) => {
+                transform.translation = position;
+                // The jump across would otherwise be drawn as a line through the system
+                if let Some(mut trail) = trail {
+                    trail.points.clear();
+                }
+                info!("{} wrapped around the boundary", name.0);
+            }
+            None => {
+                info!("{} left the boundary and was removed", name.0);
+                if let Some(trail) = trail {
+                    leave_fading_trail(&mut commands, name, &trail, trail_color);
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Shift to subtract from every position so the barycenter moves back to the origin, once it
+/// is further out than `threshold`
+pub fn rebase_offset(barycenter: Vec3, threshold: f32) -> Option<Vec3> {
+    (barycenter.length() > threshold).then_some(barycenter)
+}
+
+/// Shift the whole world, camera included, so the barycenter is back at the origin. Everything
+/// stored in world space moves by the same amount, so nothing visibly changes.
+pub fn rebase_system(
+    rebasing: Res<OriginRebasing>,
+    mut roots: Query<(&mut Transform, Option<&Mass>), Without<Parent>>,
+    mut trails: Query<&mut Trail>,
+    mut flights: Query<&mut CameraFlight>,
+    mut follows: Query<&mut FollowTarget>,
+    (mut camera_path, mut preview, mut frames): (ResMut<CameraPath>, ResMut<SetupPreview>, ResMut<TrailFrameHistory>),
+) {
+    if !rebasing.enabled {
+        return;
+    }
+    // Only bodies have mass, so this is the barycenter of the system
+    let (weighted, total_mass) = roots
+        .iter()
+        .filter_map(|(transform, mass)| mass.map(|mass| (transform.translation * mass.0, mass.0)))
+        .fold((Vec3::ZERO, 0.0), |(sum, total), (weighted, mass)| (sum + weighted, total + mass));
+    if total_mass <= 0.0 {
+        return;
+    }
+    let Some(offset) = rebase_offset(weighted / total_mass, rebasing.threshold) else {
+        return;
+    };
+
+    // Children move with their parents
+    for (mut transform, _) in roots.iter_mut() {
+        transform.translation -= offset;
+    }
+    for mut trail in trails.iter_mut() {
+        for (_, point) in trail.points.iter_mut() {
+            *point -= offset;
+        }
+    }
+    for mut flight in flights.iter_mut() {
+        flight.from.translation -= offset;
+        flight.to.translation -= offset;
+    }
+    for mut follow in follows.iter_mut() {
+        follow.aim -= offset;
+    }
+    for keyframe in camera_path.keyframes.iter_mut() {
+        keyframe.translation -= offset;
+    }
+    for (_, path) in preview.paths.iter_mut() {
+        for point in path.iter_mut() {
+            *point -= offset;
+        }
+    }
+    for (_, center, _) in frames.0.iter_mut() {
+        *center -= offset;
+    }
+    info!("Rebased the world origin by {offset}");
+}
+
+/// A massless test particle moved along its Kepler orbit about `primary` each frame instead of
+/// being integrated. It isn't a CelestialBody, so nothing feels it and it feels nothing but its
+/// primary, which keeps visual swarms of thousands of particles cheap.
+#[derive(Component)]
+pub struct KeplerParticle {
+    pub primary: Entity,
+    /// Position and velocity relative to the primary when `elapsed` was 0
+    pub epoch: (Vec3, Vec3),
+    /// Simulated seconds since the epoch
+    pub elapsed: f64,
+}
+
+impl KeplerParticle {
+    /// Position relative to the primary, solved from the epoch so it never drifts; `None` once
+    /// the orbit isn't bound about a primary with this gravitational parameter
+    pub fn relative_position(&self, mu: f32) -> Option<Vec3> {
+        kepler_propagate(self.epoch.0, self.epoch.1, mu, self.elapsed).map(|(position, _)| position)
+    }
+}
+
+/// Carry every KeplerParticle along its orbit, despawning those whose primary is gone or no
+/// longer holds them
+pub fn kepler_particle_system(
+    mut commands: Commands,
+    config: Res<PhysicsConfig>,
+    reverse: Res<ReverseMode>,
+    time: Res<Time>,
+    primaries: Query<(&Transform, &Mass), Without<KeplerParticle>>,
+    mut particles: Query<(Entity, &mut KeplerParticle, &mut Transform)>,
+) {
+    let dt = f64::from(config.step_dt(time.delta_seconds()) * reverse.direction());
+    for (entity, mut particle, mut transform) in particles.iter_mut() {
+        particle.elapsed += dt;
+        let position = primaries.get(particle.primary).ok().and_then(|(primary, mass)| {
+            Some(primary.translation + particle.relative_position(config.gravitational_parameter(mass.0, 0.0))?)
+        });
+        match position {
+            Some(position) => transform.translation = position,
+            None => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+// A pair counts as isolated while every other body's tidal pull on it is below this fraction of
+// their mutual pull
+pub const BINARY_ISOLATION: f32 = 1e-5;
+
+// A pair is handed back to the integrator if its velocities stray further than this (relative)
+// from the analytic orbit, as after a fling or an inspector edit
+pub const BINARY_EDIT_TOLERANCE: f32 = 1e-2;
+
+// Above this many bodies isolated pairs aren't searched for
+pub const BINARY_MAX_BODIES: usize = 200;
+
+/// An isolated bound pair moved along its exact Kepler orbit instead of being integrated
+pub struct AnalyticPair {
+    pub bodies: [Entity; 2],
+    pub mu: f32,
+    /// Relative position and velocity (second body minus first) when the pair was picked up
+    pub epoch: (Vec3, Vec3),
+    /// Simulated seconds since the epoch
+    pub elapsed: f64,
+}
+
+/// Solve isolated binaries exactly with the Kepler two-body solution, toggled with `
+///
+/// Only exact for plain inverse-square gravity, so it stands down while the force exponent,
+/// softening or gravity schedule is changed. The pair still pulls on everything else as usual;
+/// it only ignores their pull back, which isolation makes negligible.
+#[derive(Resource, Default)]
+pub struct AnalyticBinaries {
+    pub enabled: bool,
+    pub pairs: Vec<AnalyticPair>,
+}
+
+/// ` turns the analytic binary solver on and off
+pub fn analytic_binary_toggle(key_input: Res<ButtonInput<KeyCode>>, mut binaries: ResMut<AnalyticBinaries>) {
+    if key_input.just_pressed(KeyCode::Backquote) {
+        binaries.enabled = !binaries.enabled;
+        info!("Analytic binaries {}", if binaries.enabled { "on" } else { "off" });
+    }
+}
+
+/// Largest tidal pull of the other bodies on a pair, relative to the pair's mutual pull
+pub fn tidal_ratio(states: &[(Vec3, Vec3, f32)], pair: (usize, usize)) -> f32 {
+    let ((position1, _, mass1), (position2, _, mass2)) = (states[pair.0], states[pair.1]);
+    let separation = position1.distance(position2);
+    let center = (position1 * mass1 + position2 * mass2) / (mass1 + mass2);
+    states
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != pair.0 && *index != pair.1)
+        .map(|(_, (position, _, mass))| 2.0 * mass * separation.powi(3) / (position.distance(center).powi(3) * (mass1 + mass2)))
+        .sum()
+}
+
+/// Pairs of bodies that are each other's strongest pull, bound, clear of each other at
+/// periapsis and isolated enough from everything else to be solved as a two-body problem
+pub fn isolated_binaries(states: &[(Vec3, Vec3, f32)], radii: &[f32], config: &PhysicsConfig) -> Vec<(usize, usize)> {
+    let strongest = |index: usize| {
+        (0..states.len()).filter(|&other| other != index).max_by(|&a, &b| {
+            let pull = |other: usize| states[other].2 / states[index].0.distance_squared(states[other].0);
+            pull(a).total_cmp(&pull(b))
+        })
+    };
+    (0..states.len())
+        .filter_map(|index| strongest(index).filter(|&other| index < other && strongest(other) == Some(index)).map(|other| (index, other)))
+        .filter(|&(first, second)| {
+            let mu = config.gravitational_parameter(states[first].2, states[second].2);
+            let elements = orbital_elements(states[second].0 - states[first].0, states[second].1 - states[first].1, mu);
+            let periapsis = elements.semi_major_axis * (1.0 - elements.eccentricity);
+            // Periapsis also has to stay clear of the integrator's close-range cutoff
+            states[first].2 > 0.0
+                && states[second].2 > 0.0
+                && elements.semi_major_axis > 0.0
+                && elements.eccentricity < 1.0
+                && periapsis > (radii[first] + radii[second]).max(2.0)
+                && tidal_ratio(states, (first, second)) < BINARY_ISOLATION
+        })
+        .collect()
+}
+
+/// Move each isolated binary along its exact orbit, overwriting what the integrator did to
+/// it this step. Pairs that stop being isolated or get anchored go back to the integrator, and
+/// pairs that are edited or whose G or masses change restart their orbit from where they are.
+#[allow(clippy::type_complexity)]
+pub fn analytic_binary_system(
+    mut binaries: ResMut<AnalyticBinaries>,
+    config: Res<PhysicsConfig>,
+    reverse: Res<ReverseMode>,
+    time: Res<Time>,
+    mut bodies: Query<(Entity, &mut Transform, &mut Velocity, &Mass, &Radius, Has<Anchored>), With<CelestialBody>>,
+    names: Query<&Name>,
+) {
+    let exact = config.force_exponent == 2.0 && config.softening <= 0.0 && config.gravity_schedule == GravitySchedule::Constant;
+    if !binaries.enabled || !exact {
+        binaries.pairs.clear();
+        return;
+    }
+    let dt = f64::from(config.step_dt(time.delta_seconds()) * reverse.direction());
+    let entities: Vec<Entity> = bodies.iter().map(|(entity, ..)| entity).collect();
+    let states: Vec<(Vec3, Vec3, f32)> = bodies
+        .iter()
+        .map(|(_, transform, velocity, mass, ..)| (transform.translation, velocity.0, mass.0))
+        .collect();
+    let anchored: Vec<bool> = bodies.iter().map(|(.., anchored)| anchored).collect();
+    let index_of = |entity: Entity| entities.iter().position(|&other| other == entity);
+    let name = |entity: Entity| names.get(entity).map_or("?".to_string(), |name| name.0.clone());
+
+    let mut pairs = std::mem::take(&mut binaries.pairs);
+    pairs.retain_mut(|pair| {
+        let (Some(first), Some(second)) = (index_of(pair.bodies[0]), index_of(pair.bodies[1])) else {
+            return false;
+        };
+        if anchored[first] || anchored[second] {
+            info!("{} and {} have an anchored body, integrating them again", name(pair.bodies[0]), name(pair.bodies[1]));
+            return false;
+        }
+        if tidal_ratio(&states, (first, second)) >= BINARY_ISOLATION {
+            info!("{} and {} are no longer isolated, integrating them again", name(pair.bodies[0]), name(pair.bodies[1]));
+            return false;
+        }
+        let ((position1, velocity1, mass1), (position2, velocity2, mass2)) = (states[first], states[second]);
+        let integrated = (position2 - position1, velocity2 - velocity1);
+        // A live G edit or mass transfer changes the orbit without moving anything yet
+        let mu = config.gravitational_parameter(mass1, mass2);
+        if mu != pair.mu {
+            *pair = AnalyticPair { mu, epoch: integrated, elapsed: 0.0, ..*pair };
+            return true;
+        }
+        pair.elapsed += dt;
+        let Some((relative_position, relative_velocity)) = kepler_propagate(pair.epoch.0, pair.epoch.1, pair.mu, pair.elapsed) else {
+            return false;
+        };
+        // The integrator alone barely strays in one step, so a big gap means someone moved the pair
+        if integrated.1.distance(relative_velocity) > BINARY_EDIT_TOLERANCE * relative_velocity.length() {
+            *pair = AnalyticPair { epoch: integrated, elapsed: 0.0, ..*pair };
+            return true;
+        }
+        // The center of mass carries on where the integrator put it; only the relative motion is replaced
+        let total = mass1 + mass2;
+        let center = (position1 * mass1 + position2 * mass2) / total;
+        let center_velocity = (velocity1 * mass1 + velocity2 * mass2) / total;
+        for (entity, sign, other_mass) in [(pair.bodies[0], -1.0, mass2), (pair.bodies[1], 1.0, mass1)] {
+            if let Ok((_, mut transform, mut velocity, ..)) = bodies.get_mut(entity) {
+                transform.translation = center + relative_position * (sign * other_mass / total);
+                velocity.0 = center_velocity + relative_velocity * (sign * other_mass / total);
+            }
+        }
+        true
+    });
+
+    if states.len() <= BINARY_MAX_BODIES {
+        let radii: Vec<f32> = bodies.iter().map(|(_, _, _, _, radius, _)| radius.0).collect();
+        for (first, second) in isolated_binaries(&states, &radii, &config) {
+            let pair = [entities[first], entities[second]];
+            if anchored[first] || anchored[second] || pairs.iter().any(|known| known.bodies.iter().any(|body| pair.contains(body))) {
+                continue;
+            }
+            info!("{} and {} form an isolated binary, solving it exactly", name(pair[0]), name(pair[1]));
+            pairs.push(AnalyticPair {
+                bodies: pair,
+                mu: config.gravitational_parameter(states[first].2, states[second].2),
+                epoch: (states[second].0 - states[first].0, states[second].1 - states[first].1),
+                elapsed: 0.0,
+            });
+        }
+    }
+    binaries.pairs = pairs;
+}
+
+/// Direction a body at `relative_position` moves in on a circular orbit in the plane across the
+/// up axis, going the same way round as the default planets
+pub fn prograde_direction(relative_position: Vec3, up_axis: UpAxis) -> Option<Vec3> {
+    relative_position.cross(up_axis.up()).try_normalize()
+}
+
+/// Positions and velocities of `count` bodies evenly spaced in time along the orbit about
+/// `central` with periapsis at `periapsis` (relative to it), orbiting in the plane across the
+/// up axis the same way round as the default planets. Equal time spacing keeps them evenly
+/// spread as they go; on a circle it is equal angles.
+pub fn orbit_family(
+    central: (Vec3, Vec3),
+    periapsis: Vec3,
+    eccentricity: f32,
+    mu: f32,
+    count: usize,
+    up_axis: UpAxis,
+) -> Vec<(Vec3, Vec3)> {
+    let Some(direction) = prograde_direction(periapsis, up_axis) else {
+        return Vec::new();
+    };
+    let periapsis_velocity = direction * (mu * (1.0 + eccentricity) / periapsis.length()).sqrt();
+    let period = f64::from(orbital_period(mu, periapsis.length() / (1.0 - eccentricity)));
+    (0..count)
+        .filter_map(|index| kepler_propagate(periapsis, periapsis_velocity, mu, period * index as f64 / count as f64))
+        .map(|(position, velocity)| (central.0 + position, central.1 + velocity))
+        .collect()
+}
+
+/// Turn spinning bodies
+pub fn rotation_system(
+    mut query: Query<(&mut Transform, &Rotation)>,
+    config: Res<PhysicsConfig>,
+    reverse: Res<ReverseMode>,
+    time: Res<Time>,
+) {
+    let dt = config.step_dt(time.delta_seconds()) * reverse.direction();
+    for (mut transform, rotation) in query.iter_mut() {
+        if rotation.0 != Vec3::ZERO {
+            transform.rotate(Quat::from_scaled_axis(rotation.0 * dt));
+        }
+    }
+}
+
+/// The components the physics needs on every body
+#[derive(Bundle)]
+pub struct BodyBundle {
+    pub body: CelestialBody,
+    pub name: Name,
+    pub mass: Mass,
+    pub radius: Radius,
+    pub velocity: Velocity,
+}
+
+impl BodyBundle {
+    pub fn from_spec(spec: &BodySpec) -> Self {
+        Self {
+            body: CelestialBody,
+            name: Name(spec.name.clone()),
+            mass: Mass(spec.mass),
+            radius: Radius(spec.radius),
+            velocity: Velocity(spec.velocity),
+        }
+    }
+}
+
+/// A windowless app running only the physics on the given bodies, advancing exactly
+/// one 60 Hz step per update
+pub fn headless_app(specs: &[BodySpec]) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)))
+        .init_resource::<PhysicsConfig>()
+        .init_resource::<PlanarMode>()
+        .init_resource::<ReverseMode>()
+        .init_resource::<PhysicsProfile>()
+        .init_resource::<Paused>()
+        .init_resource::<CollisionHistory>()
+        .add_systems(Update, (gravity_system, movement_system).chain().run_if(simulation_unpaused));
+
+    for spec in specs {
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(spec.position)),
+            BodyBundle::from_spec(spec),
+        ));
+    }
+    app
+}
+
+/// The first body with this name
+pub fn body_named(app: &mut App, name: &str) -> Option<Entity> {
+    let mut query = app.world.query_filtered::<(Entity, &Name), With<CelestialBody>>();
+    query.iter(&app.world).find(|(_, body)| body.0 == name).map(|(entity, _)| entity)
+}
+
+/// (position, velocity, mass) of every body, in spawn order
+pub fn body_states(app: &mut App) -> Vec<(Vec3, Vec3, f32)> {
+    let mut query = app.world.query_filtered::<(&Transform, &Velocity, &Mass), With<CelestialBody>>();
+    query
+        .iter(&app.world)
+        .map(|(transform, velocity, mass)| (transform.translation, velocity.0, mass.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::bounding_sphere;
+    use crate::orbital::predict_flyby;
+    use crate::render::{TrailFrame, boundary_rings, grid_lines};
+    use crate::scene::{BodyConfig, DEFAULT_SEED, Scenario, orient_specs, scenario_specs, solar_system_specs};
+    use crate::SolarSystemPlugin;
+    use crate::selftest::{SELFTEST_CENTRAL_MASS, two_body_specs};
+
+    fn spec(name: &str, mass: f32, position: Vec3, velocity: Vec3) -> BodySpec {
+        BodySpec { name: name.to_string(), mass, radius: 1.0, position, velocity, color: Color::WHITE, atmosphere: None }
+    }
+
+    #[test]
+    fn interaction_cutoff_skips_only_distant_pairs() {
+        let exact = PhysicsConfig::default();
+        let cutoff = |max_interaction_distance| PhysicsConfig {
+            interaction_cutoff: true,
+            max_interaction_distance,
+            ..default()
+        };
+        let (near, far) = (Vec3::X * 10.0, Vec3::X * 200.0);
+
+        for position in [near, far] {
+            assert_eq!(
+                pairwise_force(Vec3::ZERO, 5.0, position, 3.0, &cutoff(1e9)),
+                pairwise_force(Vec3::ZERO, 5.0, position, 3.0, &exact),
+            );
+        }
+        assert!(pairwise_force(Vec3::ZERO, 5.0, near, 3.0, &cutoff(50.0)).is_some());
+        assert_eq!(pairwise_force(Vec3::ZERO, 5.0, far, 3.0, &cutoff(50.0)), None);
+    }
+
+    #[test]
+    fn planar_mode_stops_motion_out_of_the_plane() {
+        let mut app = headless_app(&[
+            spec("Star", 1000.0, Vec3::ZERO, Vec3::ZERO),
+            spec("Planet", 1.0, Vec3::X * 10.0, Vec3::new(0.0, 1.0, 3.0)),
+        ]);
+        app.insert_resource(PlanarMode(true));
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let (position, velocity, _) = body_states(&mut app)[1];
+        assert_eq!((position.y, velocity.y), (0.0, 0.0));
+        assert!(position.z > 0.0);
+    }
+
+    #[test]
+    fn restitution_zero_sticks_and_one_bounces_fully() {
+        let (velocity1, mass1, velocity2, mass2) = (Vec3::X, 2.0, Vec3::NEG_X, 3.0);
+        for restitution in [0.0, 1.0] {
+            let material = SurfaceMaterial { restitution, friction: 0.0 };
+            let (change1, change2) =
+                bounce_impulse(Vec3::X, velocity1, mass1, material, velocity2, mass2, material).expect("they are approaching");
+            let (after1, after2) = (velocity1 + change1, velocity2 + change2);
+
+            let closing_speed = (velocity2 - velocity1).dot(Vec3::X);
+            assert!(((after2 - after1).dot(Vec3::X) + restitution * closing_speed).abs() < 1e-6);
+            assert!((after1 * mass1 + after2 * mass2 - (velocity1 * mass1 + velocity2 * mass2)).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn flat_system_keeps_its_angular_momentum() {
+        let mut app = headless_app(&solar_system_specs());
+        app.init_resource::<AngularMomentumMonitor>()
+            .add_systems(Update, angular_momentum_system.after(movement_system));
+        for _ in 0..600 {
+            app.update();
+        }
+
+        let monitor = app.world.resource::<AngularMomentumMonitor>();
+        let (initial, (_, latest)) = (monitor.initial.unwrap(), monitor.latest.unwrap());
+        assert_eq!(monitor.samples, 600);
+        assert!(latest.angle_between(initial) < 1e-3);
+        assert!((latest.length() / initial.length() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn a_frame_records_time_for_each_physics_system() {
+        let mut app = headless_app(&solar_system_specs());
+        app.insert_resource(PhysicsConfig { collision_mode: CollisionMode::Bounce, ..default() })
+            .add_systems(Update, collision_system.after(movement_system));
+        app.update();
+
+        let profile = app.world.resource::<PhysicsProfile>();
+        assert!(profile.gravity_ms > 0.0);
+        assert!(profile.movement_ms > 0.0);
+        assert!(profile.collision_ms > 0.0);
+    }
+
+    #[test]
+    fn crossing_into_a_planets_soi_makes_it_the_primary() {
+        let mut app = headless_app(&[
+            spec("Star", 1000.0, Vec3::ZERO, Vec3::ZERO),
+            spec("Planet", 50.0, Vec3::X * 100.0, Vec3::ZERO),
+            spec("Probe", 0.01, Vec3::X * 60.0, Vec3::ZERO),
+        ]);
+        app.insert_resource(Paused(true)).add_systems(Update, soi_system);
+        let [star, planet, probe] = ["Star", "Planet", "Probe"].map(|name| body_named(&mut app, name).unwrap());
+        app.update();
+        assert_eq!(app.world.get::<Primary>(probe).map(|primary| primary.0), Some(star));
+
+        let soi = app.world.get::<SphereOfInfluence>(planet).unwrap().0;
+        assert!(soi < 40.0);
+        app.world.get_mut::<Transform>(probe).unwrap().translation = Vec3::X * (100.0 - soi * 0.9);
+        app.update();
+        assert_eq!(app.world.get::<Primary>(probe).map(|primary| primary.0), Some(planet));
+    }
+
+    #[test]
+    fn running_back_as_far_as_forward_returns_to_the_start() {
+        // The plugin drifts back before kicking back when reversing, undoing the forward step
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, SolarSystemPlugin::default().camera(false)))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+        // One update to spawn the bodies and start the clock
+        app.update();
+        let start = body_states(&mut app);
+        for _ in 0..600 {
+            app.update();
+        }
+        let moved = body_states(&mut app);
+        app.insert_resource(ReverseMode(true));
+        for _ in 0..600 {
+            app.update();
+        }
+
+        let end = body_states(&mut app);
+        assert_eq!(end.len(), start.len());
+        for ((position, ..), ((start_position, ..), (moved_position, ..))) in end.iter().zip(start.iter().zip(&moved)) {
+            let travelled = start_position.distance(*moved_position);
+            assert!(position.distance(*start_position) <= 1e-4 * travelled.max(1.0));
+        }
+    }
+
+    #[test]
+    fn central_only_keeps_orbits_around_the_star_closed() {
+        let mu = GRAVITATIONAL_CONSTANT * FORCE_MULTIPLIER * 1000.0;
+        let speed = (mu / 10.0).sqrt() * 1.15;
+        let mut app = headless_app(&[
+            spec("Star", 1000.0, Vec3::ZERO, Vec3::ZERO),
+            spec("Planet", 1.0, Vec3::X * 10.0, Vec3::Z * speed),
+            spec("Giant", 100.0, Vec3::NEG_X * 16.0, Vec3::ZERO),
+        ]);
+        app.insert_resource(PhysicsConfig { force_method: ForceMethod::CentralOnly, ..default() });
+        // Energy and angular momentum of the planet's orbit about the star, per unit mass
+        let orbit = |app: &mut App| {
+            let states = body_states(app);
+            let (position, velocity) = (states[1].0 - states[0].0, states[1].1 - states[0].1);
+            (0.5 * velocity.length_squared() - mu / position.length(), position.cross(velocity))
+        };
+        let (energy, momentum) = orbit(&mut app);
+        let (mut energy_error, mut momentum_error) = (0.0f32, 0.0f32);
+        for _ in 0..2000 {
+            app.update();
+            let (new_energy, new_momentum) = orbit(&mut app);
+            energy_error = energy_error.max((new_energy - energy).abs() / energy.abs());
+            momentum_error = momentum_error.max(new_momentum.distance(momentum) / momentum.length());
+        }
+
+        assert!(energy_error < 1e-2, "energy drifted by {energy_error}");
+        assert!(momentum_error < 1e-4, "angular momentum drifted by {momentum_error}");
+    }
+
+    #[test]
+    fn force_exponent_one_falls_off_as_one_over_r() {
+        let config = PhysicsConfig { force_exponent: 1.0, ..default() };
+        let force = |distance: f32| pairwise_force(Vec3::ZERO, 2.0, Vec3::X * distance, 3.0, &config).unwrap().length();
+        assert!((force(10.0) / force(20.0) - 2.0).abs() < 1e-5);
+
+        let inverse_square = PhysicsConfig::default();
+        let force = |distance: f32| pairwise_force(Vec3::ZERO, 2.0, Vec3::X * distance, 3.0, &inverse_square).unwrap().length();
+        assert!((force(10.0) / force(20.0) - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn a_body_circling_against_the_system_is_retrograde() {
+        // Two heavy planets counterclockwise seen from above, one light moon clockwise
+        let bodies = [
+            (Vec3::ZERO, Vec3::ZERO, 1000.0),
+            (Vec3::X * 10.0, Vec3::NEG_Z * 10.0, 10.0),
+            (Vec3::NEG_X * 20.0, Vec3::Z * 7.0, 10.0),
+            (Vec3::Z * 15.0, Vec3::NEG_X * 8.0, 0.1),
+        ];
+        let (com_position, com_velocity) = center_of_mass(&bodies).unwrap();
+        let system_momentum = total_angular_momentum(&bodies, com_position, com_velocity);
+        let direction = |index: usize| {
+            let (position, velocity, _) = bodies[index];
+            orbit_direction(position, velocity, com_position, com_velocity, system_momentum)
+        };
+
+        assert_eq!(direction(1), Some(OrbitDirection::Prograde));
+        assert_eq!(direction(2), Some(OrbitDirection::Prograde));
+        assert_eq!(direction(3), Some(OrbitDirection::Retrograde));
+    }
+
+    #[test]
+    fn max_dt_caps_the_step_of_a_long_frame() {
+        for max_dt in [1.0 / 60.0, 1.0 / 120.0] {
+            let mut app = headless_app(&[spec("Probe", 1.0, Vec3::ZERO, Vec3::X)]);
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 30.0)))
+                .insert_resource(PhysicsConfig {
+                    max_dt,
+                    ..default()
+                });
+            // The first update only starts the clock
+            app.update();
+            let before = body_states(&mut app)[0].0;
+            app.update();
+            let step = body_states(&mut app)[0].0.distance(before);
+            assert!((step - max_dt).abs() / max_dt < 1e-4);
+        }
+    }
+
+    #[test]
+    fn anchored_star_stays_put_and_still_pulls() {
+        let mut app = headless_app(&two_body_specs(12.0, 0.0));
+        let star = body_named(&mut app, "Star").unwrap();
+        let planet = body_named(&mut app, "Planet").unwrap();
+        app.world.entity_mut(star).insert(Anchored);
+        let position = |app: &App, entity| app.world.get::<Transform>(entity).unwrap().translation;
+        let (star_start, planet_start) = (position(&app, star), position(&app, planet));
+        for _ in 0..60 {
+            app.update();
+        }
+
+        assert_eq!(position(&app, star), star_start);
+        assert!(position(&app, planet).distance(star_start) < planet_start.distance(star_start));
+    }
+
+    fn analytic_app() -> App {
+        let mut app = headless_app(&two_body_specs(12.0, 1.0));
+        app.insert_resource(AnalyticBinaries { enabled: true, ..default() })
+            .add_systems(Update, analytic_binary_system.after(movement_system));
+        app
+    }
+
+    #[test]
+    fn analytic_binary_follows_a_live_change_of_g() {
+        let mut integrated = headless_app(&two_body_specs(12.0, 1.0));
+        let mut analytic = analytic_app();
+        for app in [&mut integrated, &mut analytic] {
+            for _ in 0..60 {
+                app.update();
+            }
+            app.world.resource_mut::<PhysicsConfig>().gravitational_constant *= 1.5;
+            for _ in 0..120 {
+                app.update();
+            }
+        }
+
+        let config = analytic.world.resource::<PhysicsConfig>();
+        let mu = config.gravitational_parameter(SELFTEST_CENTRAL_MASS, 1.0);
+        let pairs = &analytic.world.resource::<AnalyticBinaries>().pairs;
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].mu, mu);
+        // Within the integrator's own error; the old orbit would be most of a unit off by now
+        for ((position, velocity, _), (expected_position, expected_velocity, _)) in
+            body_states(&mut analytic).into_iter().zip(body_states(&mut integrated))
+        {
+            assert!(position.distance(expected_position) < 0.1, "{position} against {expected_position}");
+            assert!(velocity.distance(expected_velocity) < 0.1, "{velocity} against {expected_velocity}");
+        }
+    }
+
+    #[test]
+    fn anchoring_a_body_hands_its_pair_back_to_the_integrator() {
+        let mut app = analytic_app();
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<AnalyticBinaries>().pairs.len(), 1);
+        let star = body_named(&mut app, "Star").unwrap();
+        app.world.entity_mut(star).insert(Anchored);
+        app.update();
+        let start = app.world.get::<Transform>(star).unwrap().translation;
+        for _ in 0..60 {
+            app.update();
+        }
+
+        assert!(app.world.resource::<AnalyticBinaries>().pairs.is_empty());
+        assert_eq!(app.world.get::<Transform>(star).unwrap().translation, start);
+    }
+
+    #[test]
+    fn escaping_raises_exactly_one_ejection_warning() {
+        let mut app = headless_app(&two_body_specs(12.0, 1.0));
+        app.add_event::<EjectionWarning>()
+            .add_systems(Update, ejection_monitor_system.after(movement_system));
+        let mut reader = bevy::ecs::event::ManualEventReader::<EjectionWarning>::default();
+        let mut count_warnings = |app: &mut App, steps: usize| {
+            let mut count = 0;
+            for _ in 0..steps {
+                app.update();
+                count += reader.read(app.world.resource::<Events<EjectionWarning>>()).count();
+            }
+            count
+        };
+        assert_eq!(count_warnings(&mut app, 60), 0);
+
+        // Escape speed is sqrt(2) times circular; go comfortably past it
+        let planet = body_named(&mut app, "Planet").unwrap();
+        app.world.get_mut::<Velocity>(planet).unwrap().0 *= 1.6;
+        assert_eq!(count_warnings(&mut app, 600), 1);
+    }
+
+    #[test]
+    fn rebasing_recenters_without_moving_bodies_apart() {
+        let far = Vec3::new(5000.0, 0.0, -3000.0);
+        let mut specs = two_body_specs(12.0, 1.0);
+        for spec in &mut specs {
+            spec.position += far;
+        }
+        let mut app = headless_app(&specs);
+        app.init_resource::<OriginRebasing>()
+            .init_resource::<CameraPath>()
+            .init_resource::<SetupPreview>()
+            .init_resource::<TrailFrameHistory>()
+            .insert_resource(Paused(true))
+            .add_systems(Update, rebase_system.after(movement_system));
+        let before = body_states(&mut app);
+        app.update();
+        let after = body_states(&mut app);
+
+        assert!(after.iter().all(|(position, ..)| position.length() < 12.0));
+        let separation_change = (after[1].0 - after[0].0).distance(before[1].0 - before[0].0);
+        assert!(separation_change < 1e-4 * far.length());
+    }
+
+    #[test]
+    fn radiation_pressure_adds_the_predicted_outward_kick() {
+        let ratio = 0.5;
+        let specs = two_body_specs(12.0, 1.0);
+        let planet_velocity = |radiation: bool| {
+            let mut app = headless_app(&specs);
+            app.insert_resource(RadiationPressure(radiation))
+                .add_systems(Update, radiation_pressure_system.after(gravity_system).before(movement_system));
+            let star = body_named(&mut app, "Star").unwrap();
+            let planet = body_named(&mut app, "Planet").unwrap();
+            app.world.entity_mut(star).insert(Star);
+            app.world.entity_mut(planet).insert(RadiationSusceptible(ratio));
+            // The first update only starts the clock
+            app.update();
+            app.update();
+            app.world.get::<Velocity>(planet).unwrap().0
+        };
+        let extra = planet_velocity(true) - planet_velocity(false);
+
+        let outward = (specs[1].position - specs[0].position).normalize();
+        let expected = radiation_acceleration(
+            specs[1].position,
+            &[(specs[0].position, star_luminosity(specs[0].mass))],
+            ratio,
+            &PhysicsConfig::default(),
+        ) * PhysicsConfig::default().step_dt(1.0 / 60.0);
+        assert!(extra.dot(outward) > 0.0);
+        assert!((extra - expected).length() / expected.length() < 1e-2);
+    }
+
+    #[test]
+    fn radiation_at_a_ratio_of_one_balances_gravity_under_any_g() {
+        let (star, position) = (1000.0, Vec3::new(7.0, 0.0, -3.0));
+        for factor in [1.0, 0.2, 3.0] {
+            let config = PhysicsConfig { gravitational_constant: GRAVITATIONAL_CONSTANT * factor, ..default() };
+            let push = radiation_acceleration(position, &[(Vec3::ZERO, star_luminosity(star))], 1.0, &config);
+            let pull = config.gravitational_parameter(star, 0.0) / position.length_squared();
+            assert!((push.length() - pull).abs() / pull < 1e-4, "{} against {pull}", push.length());
+        }
+    }
+
+    #[test]
+    fn mass_transfer_moves_mass_without_changing_the_total() {
+        let mut app = headless_app(&two_body_specs(3.0, 1.0));
+        app.insert_resource(MassTransfer { enabled: true, ..default() })
+            .add_systems(Update, mass_transfer_system.after(movement_system));
+        let masses = |app: &mut App| -> HashMap<String, f32> {
+            let mut query = app.world.query::<(&Name, &Mass)>();
+            query.iter(&app.world).map(|(name, mass)| (name.0.clone(), mass.0)).collect()
+        };
+        let initial = masses(&mut app);
+        for _ in 0..30 {
+            app.update();
+        }
+        let after = masses(&mut app);
+
+        // Both bodies have unit radius, so the light planet is the less dense donor
+        let (gained, lost) = (after["Star"] - initial["Star"], initial["Planet"] - after["Planet"]);
+        assert!(lost > 0.0);
+        assert!((gained - lost).abs() / initial.values().sum::<f32>() < 1e-4);
+    }
+
+    #[test]
+    fn flyby_turns_the_probe_without_changing_its_speed() {
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 1.0);
+        let start = Vec3::new(-60.0, 0.0, 10.0);
+        let approach = Vec3::X * 1.5 * (2.0 * mu / start.length()).sqrt();
+        let mut app = headless_app(&[spec("Planet", 1000.0, Vec3::ZERO, Vec3::ZERO), spec("Probe", 1.0, start, approach)]);
+        let (planet, probe) = (body_named(&mut app, "Planet").unwrap(), body_named(&mut app, "Probe").unwrap());
+        let relative = |app: &App| {
+            let state = |entity| {
+                (app.world.get::<Transform>(entity).unwrap().translation, app.world.get::<Velocity>(entity).unwrap().0)
+            };
+            let ((probe_position, probe_velocity), (planet_position, planet_velocity)) = (state(probe), state(planet));
+            (probe_position - planet_position, probe_velocity - planet_velocity)
+        };
+        let (initial_position, initial_velocity) = relative(&app);
+        let flyby = predict_flyby(initial_position, initial_velocity, mu, 1.0).expect("the probe passes well clear");
+        assert!((flyby.outgoing.length() - flyby.incoming.length()).abs() / flyby.excess_speed < 1e-2);
+
+        // Back out at its starting distance, the probe's velocity is mirrored about the periapsis direction
+        let departure = (0..20_000)
+            .find_map(|_| {
+                app.update();
+                let (position, velocity) = relative(&app);
+                (position.dot(velocity) > 0.0 && position.length() >= initial_position.length()).then_some(velocity)
+            })
+            .expect("the probe leaves again");
+        let periapsis_direction = (flyby.incoming - flyby.outgoing).normalize();
+        let mirrored = initial_velocity - 2.0 * initial_velocity.dot(periapsis_direction) * periapsis_direction;
+        assert!((departure - mirrored).length() / initial_velocity.length() < 1e-2);
+    }
+
+    #[test]
+    fn locked_calendar_counts_a_day_per_second() {
+        let mut app = headless_app(&two_body_specs(12.0, 1.0));
+        app.insert_resource(Calendar { locked: true, days_per_second: 1.0, ..default() })
+            .add_systems(Update, calendar_system.before(gravity_system));
+        let mut real_seconds = 0.0;
+        while real_seconds < 1.0 - 1e-4 {
+            app.update();
+            real_seconds += app.world.resource::<Time<Real>>().delta_seconds();
+        }
+
+        let days = app.world.resource::<Calendar>().days().expect("the calendar has started");
+        assert!((days - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn maneuver_node_applies_its_delta_v_in_the_orbital_frame() {
+        let node = ManeuverNode { time: 0.5, delta_v: Vec3::new(0.3, -0.1, 0.2) };
+        let maneuver_app = |nodes: Vec<ManeuverNode>| {
+            let mut app = headless_app(&two_body_specs(12.0, 1.0));
+            app.init_resource::<Calendar>()
+                .add_systems(Update, (calendar_system, maneuver_system).chain().before(gravity_system));
+            let planet = body_named(&mut app, "Planet").unwrap();
+            app.world.entity_mut(planet).insert(ManeuverNodes(nodes));
+            (app, planet)
+        };
+        let (mut burning, planet) = maneuver_app(vec![node]);
+        let (mut coasting, coasting_planet) = maneuver_app(Vec::new());
+        let star = body_named(&mut coasting, "Star").unwrap();
+        let state = |app: &App, entity| {
+            (app.world.get::<Transform>(entity).unwrap().translation, app.world.get::<Velocity>(entity).unwrap().0)
+        };
+
+        for _ in 0..600 {
+            let before = (state(&coasting, coasting_planet), state(&coasting, star));
+            burning.update();
+            coasting.update();
+            if burning.world.get::<ManeuverNodes>(planet).unwrap().0.is_empty() {
+                let expected = maneuver_world_delta_v(node.delta_v, before.0, before.1);
+                let change = state(&burning, planet).1 - state(&coasting, coasting_planet).1;
+                assert!((change - expected).length() / node.delta_v.length() < 1e-4);
+                return;
+            }
+        }
+        panic!("the burn never happened");
+    }
+
+    #[test]
+    fn bound_pairs_connect_only_bound_bodies() {
+        let states = [
+            (Vec3::ZERO, Vec3::ZERO, 100.0),
+            (Vec3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0),
+            (Vec3::new(200.0, 0.0, 0.0), Vec3::new(20.0, 0.0, 0.0), 1.0),
+            (Vec3::new(-200.0, 0.0, 0.0), Vec3::new(-20.0, 0.0, 0.0), 1.0),
+        ];
+        let pairs = bound_pairs(&states, &PhysicsConfig::default());
+        let connected = |i: usize, j: usize| pairs.iter().any(|&(a, b, _)| (a, b) == (i, j));
+
+        assert!(connected(0, 1));
+        assert!(!connected(2, 3));
+
+        // Weakening gravity live lets the close pair go
+        let weak = PhysicsConfig { gravitational_constant: GRAVITATIONAL_CONSTANT * 1e-4, ..default() };
+        assert!(bound_pairs(&states, &weak).is_empty());
+    }
+
+    #[test]
+    fn restabilized_planets_move_at_circular_speed() {
+        let config = PhysicsConfig { gravitational_constant: GRAVITATIONAL_CONSTANT * 3.0, ..default() };
+        let mut states = vec![(Vec3::ZERO, Vec3::ZERO, 1000.0)];
+        for (radius, speed) in [(30.0, 2.0), (55.0, 0.5), (80.0, 4.0)] {
+            states.push((Vec3::new(radius, 0.0, 0.0), Vec3::new(0.0, 0.0, speed), 1.0));
+        }
+        let velocities = restabilized_velocities(&states, &config);
+
+        for (&(position, _, mass), velocity) in states.iter().zip(&velocities).skip(1) {
+            let expected = circular_velocity(config.gravitational_parameter(1000.0, mass), position.length(), 2.0);
+            assert!(((velocity.length() - expected) / expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn ramping_g_scales_the_force_as_the_ramp_says() {
+        let config = PhysicsConfig { gravity_schedule: GravitySchedule::Ramp { rate: 0.05 }, ..default() };
+        let force_at = |elapsed: f32| {
+            pairwise_force(Vec3::ZERO, 10.0, Vec3::new(20.0, 0.0, 0.0), 50.0, &config.at_time(elapsed)).unwrap().length()
+        };
+        let expected_ratio = (1.0 + 0.05 * 30.0) / (1.0 + 0.05 * 2.0);
+
+        assert!((force_at(30.0) / force_at(2.0) / expected_ratio - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_family_spaces_bodies_evenly_at_circular_speed() {
+        let (count, radius) = (7, 25.0);
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 0.5);
+        let center = Vec3::new(3.0, 1.0, -2.0);
+        let family = orbit_family((center, Vec3::ZERO), Vec3::new(radius, 0.0, 0.0), 0.0, mu, count, UpAxis::Y);
+        let spacing = std::f32::consts::TAU / count as f32;
+        let speed = circular_velocity(mu, radius, 2.0);
+
+        assert_eq!(family.len(), count);
+        for ((position, velocity), (next, _)) in family.iter().zip(family.iter().cycle().skip(1)) {
+            let angle = (*position - center).angle_between(*next - center);
+            assert!(((angle - spacing) / spacing).abs() < 1e-4);
+            assert!(((velocity.length() - speed) / speed).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn negative_mass_pair_runs_away_together() {
+        let mut app = headless_app(&[
+            spec("Positive", 10.0, Vec3::ZERO, Vec3::ZERO),
+            spec("Negative", -10.0, Vec3::new(10.0, 0.0, 0.0), Vec3::ZERO),
+        ]);
+        app.insert_resource(PhysicsConfig { exotic_matter: true, ..default() });
+        for _ in 0..600 {
+            app.update();
+        }
+        let states = body_states(&mut app);
+        let (positive, negative) = (states[0], states[1]);
+
+        // The same acceleration the same way, at a fixed separation, with nothing going NaN
+        assert!(states.iter().all(|(position, velocity, _)| position.is_finite() && velocity.is_finite()));
+        assert!(positive.1.length() > 0.0);
+        assert!(positive.1.distance(negative.1) / positive.1.length() < 1e-4);
+        assert!((positive.0.distance(negative.0) - 10.0).abs() / 10.0 < 1e-4);
+    }
+
+    #[test]
+    fn empty_and_one_body_systems_tick_without_trouble() {
+        let lone = BodyConfig::new("Lonely", 100.0, 2.0, [4.0, -1.0, 7.0], [0.0, 0.0, 0.3]);
+        for bodies in [vec![], vec![lone]] {
+            // The whole app as it runs, short of a window and a GPU
+            let mut app = App::new();
+            app.insert_resource(MassTransfer { enabled: true, ..default() })
+                .insert_resource(AnalyticBinaries { enabled: true, ..default() })
+                .add_plugins((
+                    DefaultPlugins
+                        .build()
+                        .disable::<bevy::winit::WinitPlugin>()
+                        .disable::<bevy::log::LogPlugin>()
+                        .set(bevy::render::RenderPlugin {
+                            render_creation: bevy::render::settings::WgpuSettings { backends: None, ..default() }.into(),
+                            ..default()
+                        }),
+                    SolarSystemPlugin::default().bodies(bodies.clone()),
+                ))
+                .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+            app.finish();
+            app.cleanup();
+            app.update();
+            if let Some(body) = body_named(&mut app, "Lonely") {
+                app.world.entity_mut(body).insert(Selected);
+                app.insert_resource(TrailFrame::Rotating(body));
+            }
+            for _ in 0..5 {
+                app.update();
+            }
+            let states = body_states(&mut app);
+            assert_eq!(states.len(), bodies.len());
+
+            // The helpers give their trivial answers
+            assert!(states.iter().all(|(position, velocity, _)| position.is_finite() && velocity.is_finite()));
+            assert_eq!(center_of_mass(&states), states.first().map(|&(position, velocity, _)| (position, velocity)));
+            assert!((0..states.len()).all(|index| dominant_attractor(&states, index, 2.0).is_none()));
+            assert_eq!(
+                restabilized_velocities(&states, &PhysicsConfig::default()),
+                states.iter().map(|state| state.1).collect::<Vec<_>>()
+            );
+            let spheres: Vec<(Vec3, f32)> = states.iter().map(|state| (state.0, 2.0)).collect();
+            assert_eq!(bounding_sphere(&spheres).map(|sphere| sphere.1), states.first().map(|_| 2.0));
+        }
+    }
+
+    #[test]
+    fn rk4_override_drifts_far_less_than_euler() {
+        // Same eccentric orbit, one in the XZ plane and one in the XY plane on opposite sides so
+        // they never meet
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 0.01);
+        let (periapsis, eccentricity) = (10.0f32, 0.6f32);
+        let periapsis_speed = (mu * (1.0 + eccentricity) / periapsis).sqrt();
+        let specs = [
+            spec("Star", 1000.0, Vec3::ZERO, Vec3::ZERO),
+            spec("Euler", 0.01, Vec3::X * periapsis, Vec3::Z * periapsis_speed),
+            spec("Probe", 0.01, Vec3::NEG_X * periapsis, Vec3::NEG_Y * periapsis_speed),
+        ];
+        let mut app = headless_app(&specs);
+        app.insert_resource(PhysicsConfig { time_scale: 4.0, ..default() });
+        let probe = body_named(&mut app, "Probe").unwrap();
+        app.world.entity_mut(probe).insert(IntegratorOverride(Integrator::Rk4));
+
+        let energy = |states: &[(Vec3, Vec3, f32)], index: usize| {
+            let (position, velocity) = (states[index].0 - states[0].0, states[index].1 - states[0].1);
+            velocity.length_squared() / 2.0 - mu / position.length()
+        };
+        let initial = body_states(&mut app);
+        let (mut euler_drift, mut probe_drift) = (0.0f32, 0.0f32);
+        let period_steps = (orbital_period(mu, periapsis / (1.0 - eccentricity)) * 15.0) as usize;
+        for _ in 0..period_steps {
+            app.update();
+            let states = body_states(&mut app);
+            euler_drift = euler_drift.max((energy(&states, 1) - energy(&initial, 1)).abs() / energy(&initial, 1).abs());
+            probe_drift = probe_drift.max((energy(&states, 2) - energy(&initial, 2)).abs() / energy(&initial, 2).abs());
+        }
+        assert!(euler_drift > 0.0);
+        assert!(probe_drift < euler_drift * 0.1, "RK4 drift {probe_drift} vs Euler {euler_drift}");
+    }
+
+    #[test]
+    fn moon_deep_in_its_planets_sphere_answers_to_the_planet() {
+        let hierarchy = [
+            (Vec3::ZERO, Vec3::ZERO, 1000.0),
+            (Vec3::new(60.0, 0.0, 0.0), Vec3::ZERO, 10.0),
+            (Vec3::new(61.5, 0.0, 0.5), Vec3::ZERO, 0.01),
+        ];
+        let soi = soi_radius(hierarchy[1].0.length(), hierarchy[1].2, hierarchy[0].2);
+        assert!(hierarchy[2].0.distance(hierarchy[1].0) < soi);
+        assert_eq!(dominant_attractor(&hierarchy, 2, 2.0), Some(1));
+        assert_eq!(dominant_attractor(&hierarchy, 1, 2.0), Some(0));
+        assert_eq!(dominant_attractor(&hierarchy, 0, 2.0), None);
+    }
+
+    #[test]
+    fn boundary_wraps_or_despawns_bodies_past_its_radius() {
+        let boundary = BoundaryConfig { radius: Some(350.0), action: BoundaryAction::Wrap };
+        let outside = Vec3::new(300.0, 200.0, 0.0);
+        let wrapped = boundary_position(&boundary, outside).unwrap();
+        assert!((wrapped.length() - 350.0).abs() < 1e-3);
+        assert!(wrapped.dot(outside) < 0.0);
+        assert_eq!(boundary_position(&BoundaryConfig { action: BoundaryAction::Despawn, ..boundary }, outside), None);
+        assert_eq!(boundary_position(&boundary, Vec3::X), Some(Vec3::X));
+
+        // Every circle of the wireframe lies on the sphere
+        let rings = boundary_rings(&boundary);
+        assert!(!rings.is_empty());
+        for (center, _, radius) in rings {
+            assert!(((center.length_squared() + radius * radius).sqrt() - 350.0).abs() / 350.0 < 1e-4);
+        }
+        assert!(boundary_rings(&BoundaryConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn velocity_scaling_keeps_the_center_of_mass_moving_as_it_was() {
+        let moving = [
+            (Vec3::ZERO, Vec3::new(0.2, 0.0, -0.1), 1000.0),
+            (Vec3::new(20.0, 0.0, 0.0), Vec3::new(0.0, 0.5, 2.0), 5.0),
+            (Vec3::new(-35.0, 1.0, 4.0), Vec3::new(1.0, 0.0, -1.5), 0.3),
+        ];
+        let (_, center) = center_of_mass(&moving).unwrap();
+        let scaled = scaled_velocities(&moving, 1.5, false);
+        for (state, scaled) in moving.iter().zip(&scaled) {
+            assert!((*scaled - center).distance((state.1 - center) * 1.5) < 1e-4);
+        }
+        let restated: Vec<(Vec3, Vec3, f32)> = moving.iter().zip(&scaled).map(|(state, velocity)| (state.0, *velocity, state.2)).collect();
+        assert!(center_of_mass(&restated).unwrap().1.distance(center) < 1e-4);
+
+        // Unless the center is asked to scale too
+        for (state, scaled) in moving.iter().zip(scaled_velocities(&moving, 1.5, true)) {
+            assert!(scaled.distance(state.1 * 1.5) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn trajectory_fan_spreads_wider_in_chaos() {
+        let config = PhysicsConfig::default();
+        let (dt, steps, samples, spread) = (1.0 / 60.0, 3000, 8, 0.01);
+        let endpoint_spread = |states: &[(Vec3, Vec3, f32)]| {
+            let ends: Vec<Vec3> = trajectory_fan(states, 0, &config, dt, steps, samples, spread)
+                .iter()
+                .filter_map(|path| path.last().copied())
+                .collect();
+            assert_eq!(ends.len(), samples);
+            let mean = ends.iter().copied().sum::<Vec3>() / ends.len() as f32;
+            ends.iter().map(|end| end.distance(mean)).fold(0.0, f32::max)
+        };
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 1.0);
+        let stable = [(Vec3::new(20.0, 0.0, 0.0), Vec3::new(0.0, 0.0, (mu / 20.0).sqrt()), 1.0), (Vec3::ZERO, Vec3::ZERO, 1000.0)];
+        let chaotic = [
+            (Vec3::new(6.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 300.0),
+            (Vec3::new(-5.0, 0.0, 3.0), Vec3::new(0.3, 0.0, -0.8), 300.0),
+            (Vec3::new(-1.0, 0.0, -7.0), Vec3::new(-0.5, 0.0, 0.2), 300.0),
+        ];
+        let (stable_spread, chaotic_spread) = (endpoint_spread(&stable), endpoint_spread(&chaotic));
+        assert!(stable_spread < chaotic_spread * 0.2, "stable {stable_spread} vs chaotic {chaotic_spread}");
+    }
+
+    #[test]
+    fn z_up_orbits_and_grid_lie_in_xy() {
+        let mut specs = scenario_specs(&Scenario::SolarSystem, DEFAULT_SEED, &PhysicsConfig::default());
+        orient_specs(&mut specs, UpAxis::Z);
+        assert!(specs.iter().any(|spec| spec.position.length() > 1.0));
+        for spec in &specs {
+            assert!(spec.position.z.abs() < 1e-4 && spec.velocity.z.abs() < 1e-4, "{} leaves XY", spec.name);
+        }
+
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 1.0);
+        let family = orbit_family((Vec3::ZERO, Vec3::ZERO), Vec3::X * 20.0, 0.0, mu, 8, UpAxis::Z);
+        assert_eq!(family.len(), 8);
+        for (position, velocity) in family {
+            assert!(position.z.abs() < 1e-4 && velocity.z.abs() < 1e-4);
+            assert!((velocity.length() - circular_velocity(mu, position.length(), 2.0)).abs() < 1e-2);
+        }
+
+        for (start, end) in grid_lines(UpAxis::Z) {
+            assert!(start.z.abs() < 1e-4 && end.z.abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn kepler_particle_returns_to_its_start_after_whole_periods() {
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 0.0);
+        let start = Vec3::new(20.0, 0.0, 0.0);
+        let start_velocity = Vec3::new(0.0, 0.3, 1.1) * circular_velocity(mu, 20.0, 2.0);
+        let period = orbital_period(mu, orbital_elements(start, start_velocity, mu).semi_major_axis);
+        // Whole steps no longer than the usual 60 Hz one, so neither max_dt nor the clock clamps them
+        let steps_per_period = (period * 60.0).ceil() as u32;
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(f64::from(period) / f64::from(steps_per_period))))
+            .init_resource::<PhysicsConfig>()
+            .init_resource::<ReverseMode>()
+            .add_systems(Update, kepler_particle_system);
+        let primary = app.world.spawn((TransformBundle::default(), Mass(1000.0))).id();
+        let particle = app
+            .world
+            .spawn((TransformBundle::default(), KeplerParticle { primary, epoch: (start, start_velocity), elapsed: 0.0 }))
+            .id();
+        // The first update only starts the clock
+        for _ in 0..=steps_per_period * 3 {
+            app.update();
+        }
+
+        let end = app.world.get::<Transform>(particle).unwrap().translation;
+        assert!(end.distance(start) / start.length() < 1e-4, "ended {end} from a start of {start}");
+    }
+
+    #[test]
+    fn reversing_through_a_bounce_restores_both_bodies() {
+        // The logged collision is undone on the way back. Bounces are the only collisions there
+        // are, so this stands in for the merge the history was asked to reverse through.
+        let body = |name: &str, x: f32, speed: f32| BodyConfig::new(name, 1.0, 1.0, [x, 0.0, 0.0], [speed, 0.0, 0.0]);
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            SolarSystemPlugin::default()
+                .camera(false)
+                .physics(PhysicsConfig { collision_mode: CollisionMode::Bounce, ..default() })
+                .bodies(vec![body("Left", -5.0, 2.0), body("Right", 5.0, -2.0)]),
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+        // One update to spawn the bodies and start the clock, then well past the collision
+        app.update();
+        let start = body_states(&mut app);
+        for _ in 0..180 {
+            app.update();
+        }
+        assert!(!app.world.resource::<CollisionHistory>().records.is_empty());
+        app.insert_resource(ReverseMode(true));
+        for _ in 0..180 {
+            app.update();
+        }
+
+        let end = body_states(&mut app);
+        assert_eq!(end.len(), 2);
+        for ((position, velocity, mass), (start_position, start_velocity, start_mass)) in end.into_iter().zip(start) {
+            assert!(position.distance(start_position) < 5e-4);
+            assert!(velocity.distance(start_velocity) < 2e-4);
+            assert_eq!(mass, start_mass);
+        }
+    }
+
+    #[test]
+    fn reversing_through_mass_transfer_restores_the_masses() {
+        // The accretor ends up heavier, larger and carrying some of the donor's momentum, and
+        // stepping back hands all of it back
+        let body = |name: &str, mass, radius, x: f32, speed: f32| BodyConfig::new(name, mass, radius, [x, 0.0, 0.0], [0.0, speed, 0.0]);
+        let mut app = App::new();
+        app.insert_resource(MassTransfer { enabled: true, rate: 0.2, ..default() }).add_plugins((
+            MinimalPlugins,
+            SolarSystemPlugin::default()
+                .camera(false)
+                // Without gravity the pair keeps its distance, so the transfer runs throughout
+                .physics(PhysicsConfig { gravitational_constant: 0.0, collision_mode: CollisionMode::None, ..default() })
+                .bodies(vec![body("Donor", 10.0, 3.0, 0.0, 0.5), body("Accretor", 100.0, 1.0, 6.0, 0.0)]),
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+        let radii = |app: &mut App| -> Vec<f32> {
+            let mut query = app.world.query_filtered::<&Radius, With<CelestialBody>>();
+            query.iter(&app.world).map(|radius| radius.0).collect()
+        };
+        app.update();
+        let (start, start_radii) = (body_states(&mut app), radii(&mut app));
+        for _ in 0..120 {
+            app.update();
+        }
+        let accretor = body_named(&mut app, "Accretor").unwrap();
+        assert!(app.world.get::<Mass>(accretor).unwrap().0 > 101.0);
+        assert!(app.world.get::<Velocity>(accretor).unwrap().0.y > 0.0);
+        app.insert_resource(ReverseMode(true));
+        for _ in 0..120 {
+            app.update();
+        }
+
+        let end = body_states(&mut app);
+        assert_eq!(end.len(), 2);
+        for ((position, velocity, mass), (start_position, start_velocity, start_mass)) in end.into_iter().zip(start) {
+            assert!(position.distance(start_position) < 1e-4);
+            assert!(velocity.distance(start_velocity) < 1e-5);
+            assert!((mass - start_mass).abs() < 1e-4, "mass {mass}, started at {start_mass}");
+        }
+        for (radius, start_radius) in radii(&mut app).into_iter().zip(start_radii) {
+            assert!((radius - start_radius).abs() < 1e-5);
+        }
+        assert!(app.world.get::<Transform>(accretor).unwrap().scale.abs_diff_eq(Vec3::ONE, 1e-5));
+    }
+
+    #[test]
+    fn switching_to_central_only_closes_a_perturbed_orbit_without_moving_anything() {
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 0.0);
+        let planet = |name: &str, mass, radius| BodySpec {
+            velocity: Vec3::Z * circular_velocity(mu, radius, 2.0),
+            radius: 0.5,
+            ..spec(name, mass, Vec3::X * radius, Vec3::ZERO)
+        };
+        let mut app = headless_app(&[spec("Star", 1000.0, Vec3::ZERO, Vec3::ZERO), planet("Inner", 1.0, 10.0), planet("Outer", 20.0, 16.0)]);
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .add_systems(Update, force_method_toggle.before(gravity_system));
+        // Relative distance from where the inner planet started after one period of its current
+        // orbit about the star (taken as fixed, as central-only treats it)
+        let orbit_closure = |app: &mut App| {
+            let states = body_states(app);
+            let (position, velocity) = (states[1].0 - states[0].0, states[1].1 - states[0].1);
+            let period = orbital_period(mu, orbital_elements(position, velocity, mu).semi_major_axis);
+            let steps = (period * 60.0).ceil() as u32;
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(f64::from(period) / f64::from(steps))));
+            for _ in 0..steps {
+                app.update();
+            }
+            let states = body_states(app);
+            (states[1].0 - states[0].0).distance(position) / position.length()
+        };
+        app.update();
+        // The outer planet's pull keeps the orbit from closing, well clear of the integrator's own error
+        assert!(orbit_closure(&mut app) > 0.05);
+
+        app.insert_resource(Paused(true));
+        let before = body_states(&mut app);
+        let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.press(KeyCode::AltLeft);
+        keys.press(KeyCode::KeyM);
+        app.update();
+        assert_eq!(app.world.resource::<PhysicsConfig>().force_method, ForceMethod::CentralOnly);
+        let after = body_states(&mut app);
+        for (before, after) in before.iter().zip(&after) {
+            assert_eq!(before.0, after.0);
+        }
+
+        let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.release_all();
+        keys.clear();
+        app.insert_resource(Paused(false)).update();
+        assert!(orbit_closure(&mut app) < 1e-2);
+    }
+}
@@ -0,0 +1,2960 @@
+//! Trails, gizmo overlays, meshes, lighting and palettes
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy::utils::{HashMap, HashSet};
+use std::collections::VecDeque;
+use bevy::pbr::{NotShadowCaster, PointLightShadowMap, ScreenSpaceAmbientOcclusionBundle, ScreenSpaceAmbientOcclusionQualityLevel, ScreenSpaceAmbientOcclusionSettings};
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Face, TextureFormat, TextureUsages};
+use bevy::render::renderer::{RenderAdapter, RenderDevice};
+use bevy::render::camera::CameraProjection;
+use bevy::core_pipeline::bloom::BloomSettings;
+use serde::{Deserialize, Serialize};
+use crate::physics::{AngularMomentumMonitor, BoundaryAction, BoundaryConfig, Calendar, CelestialBody, ManeuverNode, ManeuverNodes, Mass, MassTransfer, Name, OrbitDirection, Paused, PhysicsConfig, Radius, SphereOfInfluence, Star, Unbound, UpAxis, Velocity, bound_pairs, center_of_mass, dominant_attractor, field_acceleration, heaviest_other, orbit_direction, predict_maneuvers, predict_paths, star_luminosity, total_angular_momentum, trajectory_fan};
+use crate::orbital::{apsides, orbit_normal, orbital_elements, orbital_period};
+use crate::camera::CameraController;
+use crate::scene::BodyMesh;
+use crate::ui::Selected;
+use crate::lensing::LensingSettings;
+
+/// Recent positions of a body, oldest first, tagged with the time they were recorded
+#[derive(Component)]
+pub struct Trail {
+    pub points: VecDeque<(f32, Vec3)>,
+    pub max_len: usize,
+    pub color: Color,
+    /// Calendar time of the last point, for sampling at `trail_sample_interval`
+    pub last_sample: Option<f32>,
+}
+
+impl Trail {
+    pub fn new(color: Color) -> Self {
+        Self {
+            points: VecDeque::new(),
+            max_len: 0,
+            color,
+            last_sample: None,
+        }
+    }
+}
+
+/// Turns a body's trail off (and clears it) when false; bodies without it keep their trail
+#[derive(Component)]
+pub struct TrailEnabled(pub bool);
+
+/// Stops a body's mesh casting shadows when false, to spare the shadow pass for swarms of
+/// small bodies; bodies without it cast shadows as usual
+#[derive(Component)]
+pub struct CastsShadow(pub bool);
+
+/// Draws a body's trail in this color instead of the trail's own
+#[derive(Component)]
+pub struct TrailColor(pub Color);
+
+// How long the trail of a removed body lingers while it fades out, in seconds
+pub const TRAIL_FADE_SECONDS: f32 = 1.0;
+
+/// The trail of a body that has been removed, left behind to fade out over TRAIL_FADE_SECONDS
+/// instead of vanishing, and despawned once the timer runs out
+#[derive(Component)]
+pub struct FadingTrail(pub Timer);
+
+/// Opt-in atmospheric halo, rendered as a translucent shell slightly larger than the body
+#[derive(Component)]
+pub struct Atmosphere {
+    pub color: Color,
+    pub thickness: f32,
+}
+
+/// The shell entity spawned as a child of a body with an Atmosphere
+#[derive(Component)]
+pub struct AtmosphereShell;
+
+/// How bouncy and how grippy a body's surface is when it collides with another
+#[derive(Component, Clone, Copy)]
+pub struct SurfaceMaterial {
+    /// 0 sticks, 1 bounces with no energy loss
+    pub restitution: f32,
+    /// Fraction of the normal impulse that can be used to damp sliding at the contact
+    pub friction: f32,
+}
+
+impl Default for SurfaceMaterial {
+    fn default() -> Self {
+        Self {
+            restitution: 0.8,
+            friction: 0.1,
+        }
+    }
+}
+
+/// Marks a body whose material was cloned so its lighting can be adjusted on its own
+#[derive(Component)]
+pub struct UniqueMaterial;
+
+/// A body drawn with something other than the standard sphere, so mesh quality changes leave it alone
+#[derive(Component)]
+pub struct CustomMesh(pub BodyMesh);
+
+/// A body's place in the palette cycle, and the color it had before any palette was applied
+#[derive(Component)]
+pub struct PaletteSlot {
+    pub index: usize,
+    pub original: Color,
+}
+
+/// Translucent disk showing the selected body's orbital plane
+#[derive(Component)]
+pub struct OrbitalPlaneDisk;
+
+/// How much history trails keep
+#[derive(Resource)]
+pub struct TrailConfig {
+    /// Trails cover this much simulated time, so fast bodies get longer trails than slow ones
+    pub trail_seconds: f32,
+    /// Hard cap on points per trail to keep memory bounded
+    pub max_points: usize,
+    /// Simulated seconds between trail points, so trail density doesn't depend on how many
+    /// steps the physics takes. 0 records a point every frame.
+    pub trail_sample_interval: f32,
+    /// Draw trails as camera-facing ribbons this wide that fade with age, instead of gizmo lines
+    pub ribbon_width: Option<f32>,
+    /// Trail points closer together on screen than this many pixels are merged when drawn,
+    /// so far-off trails cost fewer segments. 0 draws every point.
+    pub decimation_pixels: f32,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        Self {
+            trail_seconds: 10.0,
+            max_points: 2000,
+            trail_sample_interval: 0.0,
+            ribbon_width: None,
+            decimation_pixels: TRAIL_DECIMATION_PIXELS,
+        }
+    }
+}
+
+// Default screen-space length below which trail segments are merged, in logical pixels
+pub const TRAIL_DECIMATION_PIXELS: f32 = 2.0;
+
+// Trails drawn before the decimation threshold starts growing with their number
+pub const DETAILED_TRAIL_COUNT: usize = 50;
+
+impl TrailConfig {
+    /// Buffer size needed to cover `trail_seconds` when a point is recorded every physics step,
+    /// with headroom for frame rate jitter
+    pub fn buffer_len(&self, steps_per_second: f32) -> usize {
+        ((self.trail_seconds * steps_per_second * 1.5).ceil() as usize).min(self.max_points)
+    }
+
+    /// Whether a trail last sampled at calendar time `last` is due another point at `now`.
+    /// Either direction counts, so reversing time keeps sampling; a little slack stops float
+    /// error in the calendar from pushing a sample one frame late.
+    pub fn sample_due(&self, last: Option<f32>, now: f32) -> bool {
+        // A clock that hasn't moved (paused) is never due, even with no interval
+        last.is_none_or(|last| now != last && (now - last).abs() >= self.trail_sample_interval * (1.0 - 1e-3))
+    }
+}
+
+/// Initial velocity arrows and predicted first orbits, shown while the simulation starts
+/// paused and faded out once it is started
+#[derive(Resource)]
+pub struct SetupPreview {
+    /// Predicted path of each body, sampled along its first orbit
+    pub paths: Vec<(Entity, Vec<Vec3>)>,
+    /// 1 while the preview is showing, falling to 0 as it fades
+    pub opacity: f32,
+    /// Set once the user starts the simulation; the preview never comes back after that
+    pub started: bool,
+}
+
+impl Default for SetupPreview {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            opacity: 1.0,
+            started: false,
+        }
+    }
+}
+
+// The setup preview's arrows are this long per unit of speed
+pub const PREVIEW_ARROW_SCALE: f32 = 5.0;
+
+// Seconds the preview takes to fade once the simulation starts
+pub const PREVIEW_FADE_SECONDS: f32 = 1.5;
+
+// Longest prediction, in 60 Hz steps, so unbound or very wide orbits stay cheap
+pub const PREVIEW_MAX_STEPS: usize = 20_000;
+
+// Predicting means integrating every pair, so larger systems only get arrows
+pub const PREVIEW_MAX_BODIES: usize = 50;
+
+// Keep one predicted point in this many steps
+pub const PREVIEW_SAMPLE_EVERY: usize = 10;
+
+/// A named list of colors for giving generated systems a cohesive look
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Palette {
+    pub name: String,
+    /// Linear RGB
+    pub colors: Vec<[f32; 3]>,
+}
+
+impl Palette {
+    pub fn new(name: &str, colors: &[[f32; 3]]) -> Self {
+        Self {
+            name: name.to_string(),
+            colors: colors.to_vec(),
+        }
+    }
+
+    /// Color for the `index`th body, cycling through the entries
+    pub fn color(&self, index: usize) -> Option<Color> {
+        let [r, g, b] = *self.colors.get(index % self.colors.len().max(1))?;
+        Some(Color::rgb_linear(r, g, b))
+    }
+}
+
+/// Available palettes and the one bodies are colored from. Cycled with T.
+#[derive(Resource)]
+pub struct Palettes {
+    pub palettes: Vec<Palette>,
+    /// `None` keeps every body's own color
+    pub active: Option<usize>,
+    /// Slot handed to the next body that shows up
+    pub next_slot: usize,
+}
+
+impl Default for Palettes {
+    fn default() -> Self {
+        Self {
+            palettes: vec![
+                Palette::new("Sunset", &[[0.9, 0.25, 0.1], [1.0, 0.5, 0.15], [0.95, 0.75, 0.3], [0.6, 0.15, 0.35], [0.3, 0.1, 0.4]]),
+                Palette::new("Ice", &[[0.7, 0.9, 1.0], [0.35, 0.6, 0.9], [0.15, 0.3, 0.7], [0.85, 0.85, 0.95], [0.4, 0.8, 0.85]]),
+                Palette::new("Earthy", &[[0.45, 0.3, 0.15], [0.3, 0.45, 0.2], [0.65, 0.55, 0.35], [0.25, 0.2, 0.15], [0.55, 0.35, 0.25]]),
+                Palette::new("Pastel", &[[1.0, 0.7, 0.75], [0.7, 0.85, 1.0], [0.75, 1.0, 0.8], [1.0, 0.95, 0.7], [0.85, 0.75, 1.0]]),
+            ],
+            active: None,
+            next_slot: 0,
+        }
+    }
+}
+
+impl Palettes {
+    pub fn active_palette(&self) -> Option<&Palette> {
+        self.active.and_then(|index| self.palettes.get(index))
+    }
+
+    /// Make the named palette active; config palettes with the same name as a built-in replace it
+    pub fn select(&mut self, name: &str) -> Result<(), String> {
+        let index = self
+            .palettes
+            .iter()
+            .rposition(|palette| palette.name == name)
+            .ok_or_else(|| format!("unknown palette `{name}`"))?;
+        self.active = Some(index);
+        Ok(())
+    }
+}
+
+/// Sphere tessellation used for body meshes
+#[derive(Resource)]
+pub struct MeshQuality {
+    /// Icosphere subdivision level; higher is smoother and costs more vertices
+    pub subdivisions: usize,
+}
+
+impl Default for MeshQuality {
+    fn default() -> Self {
+        // Matches Bevy's default sphere mesh
+        Self { subdivisions: 5 }
+    }
+}
+
+pub const MAX_SPHERE_SUBDIVISIONS: usize = 20;
+
+/// Icosphere mesh of the given radius at the configured quality
+pub fn sphere_mesh(radius: f32, quality: &MeshQuality) -> Mesh {
+    Sphere::new(radius)
+        .mesh()
+        .ico(quality.subdivisions.min(MAX_SPHERE_SUBDIVISIONS))
+        .expect("subdivisions are clamped to a supported level")
+}
+
+/// One switch for the render settings that cost the most, cycled with F1. Ordered from
+/// cheapest to nicest.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum RenderQuality {
+    Low,
+    /// Bevy's own defaults
+    #[default]
+    Medium,
+    High,
+}
+
+/// Render settings a quality preset turns into
+pub struct RenderQualitySettings {
+    pub msaa: Msaa,
+    pub shadows: bool,
+    pub shadow_map_size: usize,
+    /// Bloom needs an HDR camera, so this turns on HDR too
+    pub bloom: bool,
+    pub sphere_subdivisions: usize,
+}
+
+impl RenderQuality {
+    pub fn settings(&self) -> RenderQualitySettings {
+        match self {
+            RenderQuality::Low => RenderQualitySettings {
+                msaa: Msaa::Off,
+                shadows: false,
+                shadow_map_size: 512,
+                bloom: false,
+                sphere_subdivisions: 2,
+            },
+            RenderQuality::Medium => RenderQualitySettings {
+                msaa: Msaa::Sample4,
+                shadows: true,
+                shadow_map_size: 2048,
+                bloom: false,
+                sphere_subdivisions: MeshQuality::default().subdivisions,
+            },
+            RenderQuality::High => RenderQualitySettings {
+                msaa: Msaa::Sample4,
+                shadows: true,
+                shadow_map_size: 4096,
+                bloom: true,
+                sphere_subdivisions: 7,
+            },
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            RenderQuality::Low => RenderQuality::Medium,
+            RenderQuality::Medium => RenderQuality::High,
+            RenderQuality::High => RenderQuality::Low,
+        }
+    }
+
+    /// The next cheaper preset, if there is one
+    pub fn lower(&self) -> Option<Self> {
+        match self {
+            RenderQuality::Low => None,
+            RenderQuality::Medium => Some(RenderQuality::Low),
+            RenderQuality::High => Some(RenderQuality::Medium),
+        }
+    }
+
+    /// The next nicer preset, if there is one
+    pub fn higher(&self) -> Option<Self> {
+        match self {
+            RenderQuality::Low => Some(RenderQuality::Medium),
+            RenderQuality::Medium => Some(RenderQuality::High),
+            RenderQuality::High => None,
+        }
+    }
+}
+
+/// Steps the render quality down while the frame rate stays low and back up (never past the
+/// preset the user picked) once it has recovered for a while. Toggled with 2.
+#[derive(Resource)]
+pub struct AdaptiveQuality {
+    pub enabled: bool,
+    /// Below this many frames per second the quality is stepped down
+    pub target_fps: f32,
+    /// Above this the quality is stepped back up; the gap to `target_fps` stops it flip-flopping
+    pub recover_fps: f32,
+    /// Seconds the frame rate has to stay low before stepping down
+    pub degrade_after: f32,
+    /// Seconds it has to stay high before stepping up, longer so a brief lull isn't taken as recovery
+    pub recover_after: f32,
+    /// The preset picked with F1 or `--quality`, which recovery stops at
+    pub ceiling: RenderQuality,
+    /// Smoothed frames per second
+    pub fps: f32,
+    pub slow_for: f32,
+    pub fast_for: f32,
+    /// Set once it has said that even Low is too slow, until the frame rate recovers
+    pub suggested: bool,
+}
+
+impl Default for AdaptiveQuality {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target_fps: 45.0,
+            recover_fps: 58.0,
+            degrade_after: 2.0,
+            recover_after: 5.0,
+            ceiling: RenderQuality::default(),
+            fps: 60.0,
+            slow_for: 0.0,
+            fast_for: 0.0,
+            suggested: false,
+        }
+    }
+}
+
+// How quickly the smoothed frame rate follows the measured one, per second
+pub const ADAPTIVE_FPS_SMOOTHING: f32 = 2.0;
+
+impl AdaptiveQuality {
+    /// Take in one frame of `frame_dt` seconds at `quality` and return the preset to switch to, if any
+    pub fn observe(&mut self, frame_dt: f32, quality: RenderQuality) -> Option<RenderQuality> {
+        if frame_dt <= 0.0 {
+            return None;
+        }
+        let blend = (frame_dt * ADAPTIVE_FPS_SMOOTHING).min(1.0);
+        self.fps += (1.0 / frame_dt - self.fps) * blend;
+        self.slow_for = if self.fps < self.target_fps { self.slow_for + frame_dt } else { 0.0 };
+        self.fast_for = if self.fps > self.recover_fps { self.fast_for + frame_dt } else { 0.0 };
+
+        if self.slow_for >= self.degrade_after {
+            self.slow_for = 0.0;
+            let lower = quality.lower();
+            if lower.is_none() && !self.suggested {
+                self.suggested = true;
+                warn!(
+                    "Still under {} FPS at Low render quality, so the physics is likely the bottleneck; \
+                     fewer bodies or `interaction_cutoff` in the scene config would help",
+                    self.target_fps
+                );
+            }
+            return lower;
+        }
+        if self.fast_for >= self.recover_after {
+            self.fast_for = 0.0;
+            self.suggested = false;
+            return quality.higher().filter(|higher| *higher <= self.ceiling);
+        }
+        None
+    }
+}
+
+/// 2 turns adaptive quality on and off
+pub fn adaptive_quality_toggle(key_input: Res<ButtonInput<KeyCode>>, mut adaptive: ResMut<AdaptiveQuality>) {
+    if key_input.just_pressed(KeyCode::Digit2) {
+        adaptive.enabled = !adaptive.enabled;
+        info!("Adaptive render quality {}", if adaptive.enabled { "on" } else { "off" });
+    }
+}
+
+/// Step the render quality with the measured frame rate
+pub fn adaptive_quality_system(time: Res<Time>, mut adaptive: ResMut<AdaptiveQuality>, mut quality: ResMut<RenderQuality>) {
+    if !adaptive.enabled {
+        return;
+    }
+    if let Some(next) = adaptive.observe(time.delta_seconds(), *quality) {
+        info!("Render quality {:?} to keep up at {:.0} FPS", next, adaptive.fps);
+        *quality = next;
+    }
+}
+
+impl std::str::FromStr for RenderQuality {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "low" => Ok(RenderQuality::Low),
+            "medium" => Ok(RenderQuality::Medium),
+            "high" => Ok(RenderQuality::High),
+            _ => Err(()),
+        }
+    }
+}
+
+/// F1 cycles the render quality preset, which also becomes the most adaptive quality will go back up to
+pub fn render_quality_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut quality: ResMut<RenderQuality>,
+    mut adaptive: ResMut<AdaptiveQuality>,
+) {
+    if key_input.just_pressed(KeyCode::F1) {
+        *quality = quality.next();
+        adaptive.ceiling = *quality;
+        info!("Render quality: {:?}", *quality);
+    }
+}
+
+/// Push the preset out to the render resources, the lights and the camera whenever it changes
+pub fn apply_render_quality(
+    mut commands: Commands,
+    quality: Res<RenderQuality>,
+    mut msaa: ResMut<Msaa>,
+    mut shadow_map: ResMut<PointLightShadowMap>,
+    mut mesh_quality: ResMut<MeshQuality>,
+    mut lights: Query<&mut PointLight>,
+    mut cameras: Query<(Entity, &mut Camera), With<CameraController>>,
+) {
+    if !quality.is_changed() {
+        return;
+    }
+    let settings = quality.settings();
+    *msaa = settings.msaa;
+    shadow_map.size = settings.shadow_map_size;
+    // Only touched when it differs, since any change rebuilds every body mesh
+    if mesh_quality.subdivisions != settings.sphere_subdivisions {
+        mesh_quality.subdivisions = settings.sphere_subdivisions;
+    }
+    for mut light in lights.iter_mut() {
+        light.shadows_enabled = settings.shadows;
+    }
+    for (entity, mut camera) in cameras.iter_mut() {
+        camera.hdr = settings.bloom;
+        if settings.bloom {
+            commands.entity(entity).insert(BloomSettings::NATURAL);
+        } else {
+            commands.entity(entity).remove::<BloomSettings>();
+        }
+    }
+}
+
+/// Whether screen-space ambient occlusion darkens the creases where bodies and rings overlap,
+/// at the quality level of the render preset. Bevy's SSAO can't be combined with multisampling,
+/// so antialiasing is off while it is on. Toggled with Alt+O.
+#[derive(Resource, Default)]
+pub struct AmbientOcclusion(pub bool);
+
+impl RenderQuality {
+    pub fn ssao_quality(&self) -> ScreenSpaceAmbientOcclusionQualityLevel {
+        match self {
+            RenderQuality::Low => ScreenSpaceAmbientOcclusionQualityLevel::Low,
+            RenderQuality::Medium => ScreenSpaceAmbientOcclusionQualityLevel::Medium,
+            RenderQuality::High => ScreenSpaceAmbientOcclusionQualityLevel::High,
+        }
+    }
+}
+
+/// Whether the GPU is known to lack what Bevy's SSAO needs (the same checks its plugin makes
+/// before loading). Without a renderer to ask, nothing is ruled out.
+pub fn ssao_unsupported(device: Option<&RenderDevice>, adapter: Option<&RenderAdapter>) -> bool {
+    let storage_textures = device.is_some_and(|device| device.limits().max_storage_textures_per_shader_stage < 5);
+    let r16_storage = adapter.is_none_or(|adapter| {
+        adapter
+            .get_texture_format_features(TextureFormat::R16Float)
+            .allowed_usages
+            .contains(TextureUsages::STORAGE_BINDING)
+    });
+    storage_textures || !r16_storage
+}
+
+/// Alt+O turns ambient occlusion on or off
+pub fn ambient_occlusion_toggle(key_input: Res<ButtonInput<KeyCode>>, mut occlusion: ResMut<AmbientOcclusion>) {
+    if key_input.pressed(KeyCode::AltLeft) && key_input.just_pressed(KeyCode::KeyO) {
+        occlusion.0 = !occlusion.0;
+        info!("Ambient occlusion {}", if occlusion.0 { "on" } else { "off" });
+    }
+}
+
+/// Add or remove the camera's SSAO (and the prepasses it reads) as ambient occlusion or the
+/// render preset changes, turning multisampling off while it's on. On a GPU that can't run it
+/// the setting is turned back off with a warning, rather than costing the antialiasing for nothing.
+pub fn ambient_occlusion_system(
+    mut commands: Commands,
+    (mut occlusion, quality): (ResMut<AmbientOcclusion>, Res<RenderQuality>),
+    (device, adapter): (Option<Res<RenderDevice>>, Option<Res<RenderAdapter>>),
+    mut msaa: ResMut<Msaa>,
+    cameras: Query<Entity, With<CameraController>>,
+) {
+    if !occlusion.is_changed() && !quality.is_changed() {
+        return;
+    }
+    if occlusion.0 && ssao_unsupported(device.as_deref(), adapter.as_deref()) {
+        warn!("Ambient occlusion isn't supported by this GPU");
+        occlusion.0 = false;
+    }
+    *msaa = if occlusion.0 { Msaa::Off } else { quality.settings().msaa };
+    for entity in cameras.iter() {
+        if occlusion.0 {
+            commands.entity(entity).insert(ScreenSpaceAmbientOcclusionBundle {
+                settings: ScreenSpaceAmbientOcclusionSettings { quality_level: quality.ssao_quality() },
+                ..default()
+            });
+        } else {
+            commands.entity(entity).remove::<ScreenSpaceAmbientOcclusionBundle>();
+        }
+    }
+}
+
+/// Star lighting settings
+#[derive(Resource)]
+pub struct LightingConfig {
+    /// Point light intensity at the star
+    pub intensity: f32,
+    /// Light bodies by the inverse-square law from the star instead of the point light,
+    /// so brightness stays plausible across a wide range of orbital radii
+    pub physical_light: bool,
+    /// Converts received illumination into emissive brightness in physical mode
+    pub exposure: f32,
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        Self {
+            intensity: 10000.0,
+            physical_light: false,
+            exposure: 0.01,
+        }
+    }
+}
+
+/// Illumination received at `distance` from a source of the given intensity
+pub fn received_illumination(intensity: f32, distance: f32) -> f32 {
+    // Keep bodies right at the source from blowing up to infinity
+    intensity / distance.max(0.1).powi(2)
+}
+
+/// Stellar flux `L / (4 pi d^2)` at `distance` from a star of the given luminosity
+pub fn irradiance(luminosity: f32, distance: f32) -> f32 {
+    received_illumination(luminosity / (4.0 * std::f32::consts::PI), distance)
+}
+
+/// Distance from a star at which the irradiance drops to the given value
+pub fn irradiance_distance(luminosity: f32, irradiance: f32) -> f32 {
+    (luminosity / (4.0 * std::f32::consts::PI * irradiance)).sqrt()
+}
+
+/// Rough temperature class of a planet from the irradiance it receives
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum TemperatureBucket {
+    Frozen,
+    Cold,
+    Temperate,
+    Warm,
+    Hot,
+}
+
+impl TemperatureBucket {
+    /// Blue for cold far out through red for hot close in
+    pub fn color(&self) -> Color {
+        match self {
+            TemperatureBucket::Frozen => Color::rgb(0.2, 0.3, 1.0),
+            TemperatureBucket::Cold => Color::rgb(0.4, 0.8, 1.0),
+            TemperatureBucket::Temperate => Color::rgb(0.3, 1.0, 0.4),
+            TemperatureBucket::Warm => Color::rgb(1.0, 0.6, 0.1),
+            TemperatureBucket::Hot => Color::rgb(1.0, 0.1, 0.1),
+        }
+    }
+}
+
+/// Irradiance range counted as habitable, and whether to show it. Toggled with H.
+#[derive(Resource)]
+pub struct HabitableZone {
+    pub shown: bool,
+    pub min_irradiance: f32,
+    pub max_irradiance: f32,
+}
+
+impl Default for HabitableZone {
+    fn default() -> Self {
+        Self {
+            shown: false,
+            min_irradiance: 0.6,
+            max_irradiance: 1.5,
+        }
+    }
+}
+
+impl HabitableZone {
+    /// Temperate inside the zone, one bucket either side within a factor of two of it
+    pub fn bucket(&self, irradiance: f32) -> TemperatureBucket {
+        if irradiance < self.min_irradiance * 0.5 {
+            TemperatureBucket::Frozen
+        } else if irradiance < self.min_irradiance {
+            TemperatureBucket::Cold
+        } else if irradiance <= self.max_irradiance {
+            TemperatureBucket::Temperate
+        } else if irradiance <= self.max_irradiance * 2.0 {
+            TemperatureBucket::Warm
+        } else {
+            TemperatureBucket::Hot
+        }
+    }
+}
+
+/// Total irradiance at `position` from every (position, mass) star
+pub fn total_irradiance(position: Vec3, stars: &[(Vec3, f32)]) -> f32 {
+    stars
+        .iter()
+        .map(|(star_position, mass)| irradiance(star_luminosity(*mass), position.distance(*star_position)))
+        .sum()
+}
+
+/// Draw sphere-of-influence boundaries while patched conics is active
+pub fn soi_draw_system(mut gizmos: Gizmos, query: Query<(&Transform, &SphereOfInfluence)>) {
+    for (transform, soi) in query.iter() {
+        if soi.0.is_finite() {
+            gizmos.sphere(transform.translation, Quat::IDENTITY, soi.0, Color::rgba(0.5, 0.8, 1.0, 0.3));
+        }
+    }
+}
+
+// Blobs drawn along each mass transfer stream
+pub const STREAM_BLOBS: usize = 12;
+
+/// Draw each stream as blobs drifting from the donor, curving slightly around the accretor
+pub fn mass_transfer_stream_system(
+    mut gizmos: Gizmos,
+    transfer: Res<MassTransfer>,
+    bodies: Query<(&Transform, &Radius)>,
+    time: Res<Time>,
+) {
+    for &(donor, accretor) in &transfer.streams {
+        let (Ok((from, from_radius)), Ok((to, to_radius))) = (bodies.get(donor), bodies.get(accretor)) else {
+            continue;
+        };
+        let (start, end) = (from.translation, to.translation);
+        let across = (end - start).normalize_or_zero();
+        let side = across.any_orthonormal_vector() * to_radius.0;
+        let phase = time.elapsed_seconds().fract();
+        for i in 0..STREAM_BLOBS {
+            let t = (i as f32 + phase) / STREAM_BLOBS as f32;
+            let point = start.lerp(end, t) + side * (t * std::f32::consts::PI).sin();
+            let size = from_radius.0.min(to_radius.0) * 0.15 * (1.0 - t * 0.5);
+            gizmos.sphere(point, Quat::IDENTITY, size, Color::rgb(1.0, 0.7, 0.3));
+        }
+    }
+}
+
+// How far ahead the maneuver preview looks, at most, in simulated seconds
+pub const MANEUVER_PREVIEW_SECONDS: f32 = 60.0;
+
+/// Draw the selected bodies' planned trajectories: the current orbit up to the first burn, then
+/// each post-burn leg, with a marker at every burn
+pub fn maneuver_preview_system(
+    mut gizmos: Gizmos,
+    calendar: Res<Calendar>,
+    config: Res<PhysicsConfig>,
+    selected: Query<(Entity, &ManeuverNodes), With<Selected>>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+) {
+    let all: Vec<_> = bodies.iter().collect();
+    for (entity, nodes) in selected.iter() {
+        let Some(index) = all.iter().position(|(other, ..)| *other == entity) else {
+            continue;
+        };
+        if nodes.0.is_empty() {
+            continue;
+        }
+        // Too many bodies to predict every frame, so only the central body pulls
+        let mut states: Vec<_> = all
+            .iter()
+            .map(|(_, transform, velocity, mass)| (transform.translation, velocity.0, mass.0))
+            .collect();
+        let mut index = index;
+        if states.len() > PREVIEW_MAX_BODIES {
+            let Some(central) = heaviest_other(&states, index) else {
+                continue;
+            };
+            states = vec![states[index], states[central]];
+            index = 0;
+        }
+        let relative: Vec<ManeuverNode> = nodes
+            .0
+            .iter()
+            .map(|node| ManeuverNode { time: node.time - calendar.elapsed, ..*node })
+            .collect();
+        let last_burn = relative.last().map_or(0.0, |node| node.time.max(0.0));
+        let duration = (last_burn * 2.0).clamp(10.0, MANEUVER_PREVIEW_SECONDS);
+        let segments = predict_maneuvers(&states, index, &relative, &config, config.max_dt, duration, PREVIEW_SAMPLE_EVERY);
+        for (leg, segment) in segments.iter().enumerate() {
+            let color = if leg == 0 { Color::GRAY } else { Color::ORANGE };
+            gizmos.linestrip(segment.iter().copied(), color);
+            if leg > 0 {
+                if let Some(&burn) = segment.first() {
+                    gizmos.sphere(burn, Quat::IDENTITY, 0.4, Color::ORANGE);
+                }
+            }
+        }
+    }
+}
+
+// Most steps one fan path is predicted for, however far ahead it looks
+pub const TRAJECTORY_FAN_MAX_STEPS: usize = 4000;
+
+/// A fan of predictions for the selected body with slightly nudged velocities, showing how
+/// sensitive its path is to small errors. Toggled with F12.
+#[derive(Resource)]
+pub struct TrajectoryFan {
+    pub shown: bool,
+    pub samples: usize,
+    /// Size of each velocity nudge, in units per second
+    pub spread: f32,
+    /// How far ahead the fan looks, in simulated seconds
+    pub seconds: f32,
+    pub body: Option<Entity>,
+    pub paths: Vec<Vec<Vec3>>,
+    pub refresh: Timer,
+}
+
+impl Default for TrajectoryFan {
+    fn default() -> Self {
+        Self {
+            shown: false,
+            samples: 8,
+            spread: 0.02,
+            seconds: 30.0,
+            body: None,
+            paths: Vec::new(),
+            refresh: Timer::from_seconds(1.0, TimerMode::Repeating),
+        }
+    }
+}
+
+/// F12 shows or hides the trajectory fan
+pub fn trajectory_fan_toggle(key_input: Res<ButtonInput<KeyCode>>, mut fan: ResMut<TrajectoryFan>) {
+    if key_input.just_pressed(KeyCode::F12) {
+        fan.shown = !fan.shown;
+    }
+}
+
+/// Sample count, spread and look-ahead of the trajectory fan
+pub fn trajectory_fan_panel(mut contexts: EguiContexts, mut fan: ResMut<TrajectoryFan>) {
+    if !fan.shown {
+        return;
+    }
+    let mut changed = false;
+    egui::Window::new("Trajectory fan").resizable(false).show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Samples");
+            changed |= ui.add(egui::DragValue::new(&mut fan.samples).clamp_range(1..=32)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Spread");
+            changed |= ui.add(egui::DragValue::new(&mut fan.spread).speed(0.001).clamp_range(0.0..=1.0)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Seconds ahead");
+            changed |= ui.add(egui::DragValue::new(&mut fan.seconds).speed(0.5).clamp_range(1.0..=120.0)).changed();
+        });
+    });
+    if changed {
+        fan.paths.clear();
+    }
+}
+
+/// Recompute the fan for the selected body about once a second, or as soon as the selection or
+/// its settings change, and draw it as faint lines
+pub fn trajectory_fan_system(
+    mut gizmos: Gizmos,
+    mut fan: ResMut<TrajectoryFan>,
+    config: Res<PhysicsConfig>,
+    time: Res<Time>,
+    selected: Query<Entity, (With<Selected>, With<CelestialBody>)>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+) {
+    if !fan.shown {
+        return;
+    }
+    let body = (selected.iter().len() == 1).then(|| selected.single());
+    let ticked = fan.refresh.tick(time.delta()).just_finished();
+    if body != fan.body || ticked || fan.paths.is_empty() {
+        fan.body = body;
+        fan.paths.clear();
+        let all: Vec<_> = bodies.iter().collect();
+        let Some(mut index) = body.and_then(|body| all.iter().position(|(other, ..)| *other == body)) else {
+            return;
+        };
+        let mut states: Vec<_> = all
+            .iter()
+            .map(|(_, transform, velocity, mass)| (transform.translation, velocity.0, mass.0))
+            .collect();
+        // Same shortcut as the maneuver preview: with many bodies only the central one pulls
+        if states.len() > PREVIEW_MAX_BODIES {
+            let Some(central) = heaviest_other(&states, index) else {
+                return;
+            };
+            states = vec![states[index], states[central]];
+            index = 0;
+        }
+        let steps = ((fan.seconds / config.max_dt) as usize).min(TRAJECTORY_FAN_MAX_STEPS);
+        fan.paths = trajectory_fan(&states, index, &config, config.max_dt, steps, fan.samples, fan.spread);
+    }
+    for path in &fan.paths {
+        gizmos.linestrip(path.iter().copied(), Color::rgba(0.8, 0.8, 1.0, 0.25));
+    }
+}
+
+/// When starting paused, predict each body's first orbit (the preview is skipped otherwise) around the most massive body
+pub fn plan_setup_preview(
+    paused: Res<Paused>,
+    config: Res<PhysicsConfig>,
+    mut preview: ResMut<SetupPreview>,
+    query: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+) {
+    if !paused.0 {
+        preview.opacity = 0.0;
+        return;
+    }
+    if query.iter().len() > PREVIEW_MAX_BODIES {
+        return;
+    }
+    let bodies: Vec<_> = query.iter().collect();
+    let states: Vec<_> = bodies
+        .iter()
+        .map(|(_, transform, velocity, mass)| (transform.translation, velocity.0, mass.0))
+        .collect();
+    let Some(&(central_position, central_velocity, central_mass)) =
+        states.iter().max_by(|a, b| a.2.total_cmp(&b.2))
+    else {
+        return;
+    };
+
+    // Only bound orbits have a first orbit to show; everything else runs to the step limit
+    let dt = config.max_dt;
+    let orbit_steps: Vec<usize> = states
+        .iter()
+        .map(|&(position, velocity, mass)| {
+            let mu = config.gravitational_parameter(central_mass, mass);
+            let elements = orbital_elements(position - central_position, velocity - central_velocity, mu);
+            if elements.semi_major_axis > 0.0 && elements.eccentricity < 1.0 {
+                ((orbital_period(mu, elements.semi_major_axis) / dt).ceil() as usize).min(PREVIEW_MAX_STEPS)
+            } else {
+                PREVIEW_MAX_STEPS
+            }
+        })
+        .collect();
+    let steps = orbit_steps.iter().copied().max().unwrap_or(0);
+    let paths = predict_paths(&states, &config, dt, steps, PREVIEW_SAMPLE_EVERY);
+
+    preview.paths = bodies
+        .iter()
+        .zip(paths)
+        .zip(orbit_steps)
+        .filter(|(((_, _, _, mass), _), _)| mass.0 < central_mass)
+        .map(|(((entity, ..), mut path), steps)| {
+            path.truncate(steps / PREVIEW_SAMPLE_EVERY + 1);
+            (*entity, path)
+        })
+        .collect();
+}
+
+/// Draw the setup preview, fading it out once the simulation has been started
+#[allow(clippy::type_complexity)]
+pub fn setup_preview_system(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    mut preview: ResMut<SetupPreview>,
+    query: Query<(Entity, &Transform, &Velocity, &Radius, &Handle<StandardMaterial>), With<CelestialBody>>,
+    materials: Res<Assets<StandardMaterial>>,
+) {
+    if preview.opacity <= 0.0 {
+        return;
+    }
+    if preview.started {
+        preview.opacity -= time.delta_seconds() / PREVIEW_FADE_SECONDS;
+        if preview.opacity <= 0.0 {
+            preview.paths.clear();
+            return;
+        }
+    }
+
+    let body_color = |entity: Entity| {
+        query
+            .get(entity)
+            .ok()
+            .and_then(|(.., material)| materials.get(material))
+            .map_or(Color::WHITE, |material| material.base_color)
+            .with_a(preview.opacity)
+    };
+    for (entity, transform, velocity, radius, _) in query.iter() {
+        if velocity.0 == Vec3::ZERO {
+            continue;
+        }
+        let start = transform.translation + velocity.0.normalize() * radius.0;
+        gizmos.arrow(start, start + velocity.0 * PREVIEW_ARROW_SCALE, body_color(entity));
+    }
+    for (entity, path) in &preview.paths {
+        gizmos.linestrip(path.iter().copied(), body_color(*entity));
+    }
+}
+
+/// Record each body's position and drop points older than the trail's time window
+pub fn trail_update_system(
+    mut query: Query<(&Transform, &mut Trail, Option<&TrailEnabled>), With<CelestialBody>>,
+    config: Res<TrailConfig>,
+    time: Res<Time>,
+    calendar: Option<Res<Calendar>>,
+) {
+    // Points are stamped and aged on the simulated clock, so pausing freezes trails as they are
+    let now = calendar.map_or(time.elapsed_seconds(), |calendar| calendar.elapsed);
+    let max_len = config.buffer_len(1.0 / time.delta_seconds().max(1e-4));
+    for (transform, mut trail, enabled) in query.iter_mut() {
+        if enabled.is_some_and(|enabled| !enabled.0) {
+            if !trail.points.is_empty() {
+                trail.points.clear();
+            }
+            continue;
+        }
+        trail.max_len = max_len;
+        if config.sample_due(trail.last_sample, now) {
+            trail.last_sample = Some(now);
+            trail.points.push_back((now, transform.translation));
+        }
+        // Either way, as running time backwards makes the newest points the earliest
+        while trail.points.len() > trail.max_len
+            || trail
+                .points
+                .front()
+                .is_some_and(|(recorded, _)| (now - recorded).abs() > config.trail_seconds)
+        {
+            trail.points.pop_front();
+        }
+    }
+}
+
+/// Copy a body's trail, about to go with the body, into a FadingTrail of its own
+pub fn leave_fading_trail(commands: &mut Commands, name: &Name, trail: &Trail, trail_color: Option<&TrailColor>) {
+    if trail.points.len() < 2 {
+        return;
+    }
+    let mut fading = commands.spawn((
+        Name(name.0.clone()),
+        Trail {
+            points: trail.points.clone(),
+            ..Trail::new(trail.color)
+        },
+        FadingTrail(Timer::from_seconds(TRAIL_FADE_SECONDS, TimerMode::Once)),
+    ));
+    if let Some(color) = trail_color {
+        fading.insert(TrailColor(color.0));
+    }
+}
+
+/// Count fading trails down and remove the ones that have faded out
+pub fn fading_trail_system(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut FadingTrail)>) {
+    for (entity, mut fading) in query.iter_mut() {
+        if fading.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Frame trails are drawn in, switched with Tab
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TrailFrame {
+    #[default]
+    Inertial,
+    /// Co-rotating with this body's orbit about the body it orbits, so bodies in resonance with
+    /// it trace closed, stationary patterns
+    Rotating(Entity),
+}
+
+/// Origin (the reference body's primary) and orientation of the rotating trail frame at each
+/// time trail points were recorded, oldest first
+#[derive(Resource, Default)]
+pub struct TrailFrameHistory(pub VecDeque<(f32, Vec3, Quat)>);
+
+/// Rotating frame centered on the central body with X towards the reference body and Y along
+/// its orbit normal, or `None` if the reference isn't orbiting
+pub fn rotating_frame(reference: (Vec3, Vec3), central: (Vec3, Vec3)) -> Option<(Vec3, Quat)> {
+    let relative_position = reference.0 - central.0;
+    let normal = orbit_normal(relative_position, reference.1 - central.1)?;
+    let x = relative_position.normalize();
+    Some((central.0, Quat::from_mat3(&Mat3::from_cols(x, normal, x.cross(normal)))))
+}
+
+/// Where a point recorded in frame `then` sits when its rotating-frame coordinates are drawn
+/// in frame `now`
+pub fn rotate_into_frame(point: Vec3, then: (Vec3, Quat), now: (Vec3, Quat)) -> Vec3 {
+    now.0 + now.1 * (then.1.inverse() * (point - then.0))
+}
+
+/// Tab draws trails in the frame co-rotating with the selected body, or back in the inertial
+/// frame if it is already the reference or nothing is selected
+#[allow(clippy::type_complexity)]
+pub fn trail_frame_toggle(
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut frame: ResMut<TrailFrame>,
+    mut history: ResMut<TrailFrameHistory>,
+    selected: Query<(Entity, &Name), (With<Selected>, With<CelestialBody>)>,
+) {
+    if !key_input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    history.0.clear();
+    *frame = match selected.get_single() {
+        Ok((body, name)) if *frame != TrailFrame::Rotating(body) => {
+            info!("Trails co-rotate with {}", name.0);
+            TrailFrame::Rotating(body)
+        }
+        _ => {
+            info!("Trails in the inertial frame");
+            TrailFrame::Inertial
+        }
+    };
+}
+
+/// Record where the rotating frame is each time trail points are, for as long as trails last
+pub fn trail_frame_system(
+    mut frame: ResMut<TrailFrame>,
+    mut history: ResMut<TrailFrameHistory>,
+    config: Res<TrailConfig>,
+    time: Res<Time>,
+    calendar: Option<Res<Calendar>>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+) {
+    let TrailFrame::Rotating(reference) = *frame else {
+        return;
+    };
+    let Ok((_, transform, velocity, _)) = bodies.get(reference) else {
+        *frame = TrailFrame::Inertial;
+        history.0.clear();
+        return;
+    };
+    // The reference orbits whichever other body is most massive, as for the orbital plane
+    let Some((_, central_transform, central_velocity, _)) =
+        bodies.iter().filter(|(other, ..)| *other != reference).max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))
+    else {
+        return;
+    };
+    let Some((center, rotation)) =
+        rotating_frame((transform.translation, velocity.0), (central_transform.translation, central_velocity.0))
+    else {
+        return;
+    };
+    // On the same clock as the trail points, and likewise frozen while paused
+    let now = calendar.map_or(time.elapsed_seconds(), |calendar| calendar.elapsed);
+    if history.0.back().is_some_and(|(recorded, ..)| *recorded == now) {
+        return;
+    }
+    history.0.push_back((now, center, rotation));
+    while history.0.front().is_some_and(|(recorded, ..)| (now - recorded).abs() > config.trail_seconds) {
+        history.0.pop_front();
+    }
+}
+
+/// Draw trails as gizmo line strips, in the rotating frame if one is chosen. Points recorded
+/// before the frame was chosen are left out.
+#[allow(clippy::type_complexity)]
+pub fn trail_draw_system(
+    mut gizmos: Gizmos,
+    (config, frame, history): (Res<TrailConfig>, Res<TrailFrame>, Res<TrailFrameHistory>),
+    query: Query<(Entity, &Trail, Option<&TrailColor>, &Name, Option<&FadingTrail>)>,
+    camera: Query<(&Camera, &GlobalTransform, &Projection), With<CameraController>>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    if config.ribbon_width.is_some() {
+        return;
+    }
+    let view = camera.get_single().ok();
+    let min_pixels = decimation_threshold(config.decimation_pixels, query.iter().len());
+    let authored: Vec<(&str, Color)> =
+        query.iter().map(|(_, trail, color, name, _)| (name.0.as_str(), color.map_or(trail.color, |color| color.0))).collect();
+    for ((entity, trail, _, _, fading), color) in query.iter().zip(trail_colors(&authored)) {
+        let color = fading.map_or(color, |fading| color.with_a(color.a() * fading.0.fraction_remaining()));
+        let mut points = drawn_trail_points(trail, *frame, &history);
+        if !selected.contains(entity) {
+            points = decimated_for_view(points, view, min_pixels);
+        }
+        gizmos.linestrip(points, color);
+    }
+}
+
+/// Approximate on-screen length in pixels of the segment `from`-`to` for a camera at `camera`,
+/// with a viewport `viewport_height` pixels tall
+pub fn screen_length(from: Vec3, to: Vec3, camera: Vec3, projection: &Projection, viewport_height: f32) -> f32 {
+    match projection {
+        Projection::Perspective(perspective) => {
+            let depth = camera.distance(from.lerp(to, 0.5)).max(perspective.near);
+            from.distance(to) / depth * viewport_height / (2.0 * (perspective.fov / 2.0).tan())
+        }
+        Projection::Orthographic(orthographic) => {
+            from.distance(to) * viewport_height / orthographic.area.height().max(f32::EPSILON)
+        }
+    }
+}
+
+/// Drop trail points until each drawn segment is at least `min_pixels` long by `length`, its
+/// on-screen length. The newest point is always kept so the trail still meets its body.
+pub fn decimate_trail(points: Vec<Vec3>, min_pixels: f32, length: impl Fn(Vec3, Vec3) -> f32) -> Vec<Vec3> {
+    if min_pixels <= 0.0 || points.len() <= 2 {
+        return points;
+    }
+    let last = points.len() - 1;
+    let mut kept = Vec::with_capacity(points.len());
+    kept.push(points[0]);
+    for (index, &point) in points.iter().enumerate().skip(1) {
+        if index == last || length(kept[kept.len() - 1], point) >= min_pixels {
+            kept.push(point);
+        }
+    }
+    kept
+}
+
+/// The decimation threshold with `trails` drawn: `pixels` for up to DETAILED_TRAIL_COUNT trails,
+/// growing with the square root of their number past that so the total segment count levels off
+pub fn decimation_threshold(pixels: f32, trails: usize) -> f32 {
+    pixels * (trails as f32 / DETAILED_TRAIL_COUNT as f32).max(1.0).sqrt()
+}
+
+/// Decimate a trail's points for the camera, or leave them all if there's no viewport to measure
+fn decimated_for_view(
+    points: Vec<Vec3>,
+    view: Option<(&Camera, &GlobalTransform, &Projection)>,
+    min_pixels: f32,
+) -> Vec<Vec3> {
+    let Some((camera, transform, projection)) = view else {
+        return points;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return points;
+    };
+    let eye = transform.translation();
+    decimate_trail(points, min_pixels, |from, to| screen_length(from, to, eye, projection, viewport.y))
+}
+
+/// Where a trail's points are drawn: as recorded, or carried into the rotating frame as it is now
+pub fn drawn_trail_points(trail: &Trail, frame: TrailFrame, history: &TrailFrameHistory) -> Vec<Vec3> {
+    match (frame, history.0.back()) {
+        (TrailFrame::Rotating(_), Some(&(_, center, rotation))) => trail
+            .points
+            .iter()
+            .filter_map(|&(recorded, point)| {
+                // Frames are recorded at the same moments as trail points
+                let index = history.0.partition_point(|(time, ..)| *time < recorded);
+                let &(time, then_center, then_rotation) = history.0.get(index)?;
+                (time == recorded).then(|| rotate_into_frame(point, (then_center, then_rotation), (center, rotation)))
+            })
+            .collect(),
+        (TrailFrame::Rotating(_), None) => Vec::new(),
+        (TrailFrame::Inertial, _) => trail.points.iter().map(|(_, point)| *point).collect(),
+    }
+}
+
+/// Camera-facing strip `width` wide along `points` (oldest first), two vertices per point, fading
+/// from transparent at the oldest point to `color` at the newest
+pub fn ribbon_mesh(points: &[Vec3], camera: Vec3, width: f32, color: Color) -> Mesh {
+    let mut positions = Vec::with_capacity(points.len() * 2);
+    let mut normals = Vec::with_capacity(points.len() * 2);
+    let mut colors = Vec::with_capacity(points.len() * 2);
+    let mut indices = Vec::with_capacity(points.len().saturating_sub(1) * 6);
+    if points.len() >= 2 {
+        let [r, g, b, a] = color.as_linear_rgba_f32();
+        for (index, &point) in points.iter().enumerate() {
+            let tangent = points[(index + 1).min(points.len() - 1)] - points[index.saturating_sub(1)];
+            let facing = (camera - point).normalize_or_zero();
+            let side = tangent.cross(facing).normalize_or_zero() * width * 0.5;
+            let alpha = a * index as f32 / (points.len() - 1) as f32;
+            for offset in [side, -side] {
+                positions.push((point + offset).to_array());
+                normals.push(facing.to_array());
+                colors.push([r, g, b, alpha]);
+            }
+        }
+        for segment in 0..points.len() as u32 - 1 {
+            let [left, right, next_left, next_right] = [0, 1, 2, 3].map(|corner| segment * 2 + corner);
+            indices.extend([left, right, next_left, right, next_right, next_left]);
+        }
+    }
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+/// The ribbon mesh drawing this entity's trail
+#[derive(Component)]
+pub struct TrailRibbon(pub Entity);
+
+/// Rebuild every trail's ribbon mesh from its points each frame while `ribbon_width` is set, and
+/// remove the ribbons once it isn't or their trail is gone
+#[allow(clippy::type_complexity)]
+pub fn trail_ribbon_system(
+    mut commands: Commands,
+    (mut meshes, mut materials): (ResMut<Assets<Mesh>>, ResMut<Assets<StandardMaterial>>),
+    (config, frame, history): (Res<TrailConfig>, Res<TrailFrame>, Res<TrailFrameHistory>),
+    camera: Query<(&Camera, &GlobalTransform, &Projection), With<CameraController>>,
+    (trails, selected): (Query<(Entity, &Trail, Option<&TrailColor>, &Name, Option<&FadingTrail>)>, Query<Entity, With<Selected>>),
+    ribbons: Query<(Entity, &TrailRibbon, &Handle<Mesh>)>,
+    mut material: Local<Option<Handle<StandardMaterial>>>,
+) {
+    let mut existing: HashMap<Entity, Handle<Mesh>> = HashMap::new();
+    for (entity, ribbon, mesh) in ribbons.iter() {
+        if config.ribbon_width.is_some() && trails.contains(ribbon.0) {
+            existing.insert(ribbon.0, mesh.clone());
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    let (Some(width), Ok(view)) = (config.ribbon_width, camera.get_single()) else {
+        return;
+    };
+    let min_pixels = decimation_threshold(config.decimation_pixels, trails.iter().len());
+    // Colors come from the vertices, so every ribbon shares one material
+    let material = material
+        .get_or_insert_with(|| {
+            materials.add(StandardMaterial {
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                double_sided: true,
+                cull_mode: None,
+                ..default()
+            })
+        })
+        .clone();
+    let authored: Vec<(&str, Color)> =
+        trails.iter().map(|(_, trail, color, name, _)| (name.0.as_str(), color.map_or(trail.color, |color| color.0))).collect();
+    for ((entity, trail, _, _, fading), color) in trails.iter().zip(trail_colors(&authored)) {
+        let color = fading.map_or(color, |fading| color.with_a(color.a() * fading.0.fraction_remaining()));
+        let mut points = drawn_trail_points(trail, *frame, &history);
+        if !selected.contains(entity) {
+            points = decimated_for_view(points, Some(view), min_pixels);
+        }
+        let mesh = ribbon_mesh(&points, view.1.translation(), width, color);
+        match existing.get(&entity).and_then(|handle| meshes.get_mut(handle)) {
+            Some(ribbon) => *ribbon = mesh,
+            None => {
+                commands.spawn((
+                    PbrBundle {
+                        mesh: meshes.add(mesh),
+                        material: material.clone(),
+                        ..default()
+                    },
+                    NotShadowCaster,
+                    TrailRibbon(entity),
+                ));
+            }
+        }
+    }
+}
+
+/// Hue in degrees picked from a name, the same on every run
+pub fn name_hue(name: &str) -> f32 {
+    // FNV-1a, so the hue doesn't depend on the standard library's randomly seeded hasher
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+    (hash % 360) as f32
+}
+
+/// Color each (name, authored color) trail is drawn in: its authored color (the TrailColor, else
+/// its body's), unless another trail shares it, in which case a color picked from its name so
+/// overlapping trails can be told apart
+pub fn trail_colors(trails: &[(&str, Color)]) -> Vec<Color> {
+    let mut uses: HashMap<[u8; 4], usize> = HashMap::new();
+    for (_, color) in trails {
+        *uses.entry(color.as_rgba_u8()).or_default() += 1;
+    }
+    trails
+        .iter()
+        .map(|(name, color)| if uses[&color.as_rgba_u8()] > 1 { Color::hsl(name_hue(name), 0.75, 0.6) } else { *color })
+        .collect()
+}
+
+// Most bodies the trail legend lists
+pub const TRAIL_LEGEND_MAX: usize = 12;
+
+/// Whether the trail legend is shown, toggled with F11
+#[derive(Resource)]
+pub struct TrailLegend(pub bool);
+
+impl Default for TrailLegend {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// (name, color) entries of the trail legend, sorted by name, for trails that have points
+pub fn trail_legend(trails: &[(&str, Color, bool)]) -> Vec<(String, Color)> {
+    let authored: Vec<(&str, Color)> = trails.iter().map(|(name, color, _)| (*name, *color)).collect();
+    let mut legend: Vec<(String, Color)> = trails
+        .iter()
+        .zip(trail_colors(&authored))
+        .filter(|((.., drawn), _)| *drawn)
+        .map(|((name, ..), color)| (name.to_string(), color))
+        .collect();
+    legend.sort_by(|a, b| a.0.cmp(&b.0));
+    legend
+}
+
+/// F11 shows or hides the trail legend
+pub fn trail_legend_toggle(key_input: Res<ButtonInput<KeyCode>>, mut legend: ResMut<TrailLegend>) {
+    if key_input.just_pressed(KeyCode::F11) {
+        legend.0 = !legend.0;
+    }
+}
+
+/// Which trail belongs to which body, at the right edge
+pub fn trail_legend_overlay(
+    mut contexts: EguiContexts,
+    legend: Res<TrailLegend>,
+    query: Query<(&Trail, Option<&TrailColor>, &Name), Without<FadingTrail>>,
+) {
+    if !legend.0 {
+        return;
+    }
+    let trails: Vec<(&str, Color, bool)> = query
+        .iter()
+        .map(|(trail, color, name)| (name.0.as_str(), color.map_or(trail.color, |color| color.0), trail.points.len() > 1))
+        .collect();
+    let entries = trail_legend(&trails);
+    if entries.is_empty() {
+        return;
+    }
+    egui::Window::new("Trails")
+        .anchor(egui::Align2::RIGHT_CENTER, [-10.0, 0.0])
+        .resizable(false)
+        .title_bar(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (name, color) in entries.iter().take(TRAIL_LEGEND_MAX) {
+                let [r, g, b, _] = color.as_rgba_u8();
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::from_rgb(r, g, b), "■");
+                    ui.label(name);
+                });
+            }
+            if entries.len() > TRAIL_LEGEND_MAX {
+                ui.label(format!("and {} more", entries.len() - TRAIL_LEGEND_MAX));
+            }
+        });
+}
+
+/// Give newly added atmospheres their shell; as a child it follows the body automatically
+pub fn spawn_atmosphere_shells(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    quality: Res<MeshQuality>,
+    query: Query<(Entity, &Atmosphere, &Radius), Added<Atmosphere>>,
+) {
+    for (entity, atmosphere, radius) in query.iter() {
+        let shell = commands
+            .spawn((
+                PbrBundle {
+                    mesh: meshes.add(sphere_mesh(radius.0 + atmosphere.thickness, &quality)),
+                    material: materials.add(StandardMaterial {
+                        base_color: atmosphere.color,
+                        alpha_mode: AlphaMode::Add,
+                        unlit: true,
+                        // Only the far side of the shell is drawn, so it glows around the rim
+                        cull_mode: Some(Face::Front),
+                        ..default()
+                    }),
+                    ..default()
+                },
+                AtmosphereShell,
+                NotShadowCaster,
+            ))
+            .id();
+        commands.entity(entity).add_child(shell);
+    }
+}
+
+/// Translucent sphere a softening length across, spawned as a child of each body while the
+/// softening cores are shown
+#[derive(Component)]
+pub struct SofteningCore;
+
+/// Whether softening cores are drawn, toggled with ;
+#[derive(Resource, Default)]
+pub struct SofteningCores {
+    pub shown: bool,
+    pub mesh: Option<Handle<Mesh>>,
+    pub material: Option<Handle<StandardMaterial>>,
+}
+
+/// Scale of a unit-sphere core so it is `softening` across in the world, under a body of the given scale
+pub fn softening_core_scale(softening: f32, body_scale: f32) -> f32 {
+    softening / body_scale
+}
+
+/// ; shows and hides the softening cores
+pub fn softening_core_toggle(key_input: Res<ButtonInput<KeyCode>>, mut cores: ResMut<SofteningCores>) {
+    if key_input.just_pressed(KeyCode::Semicolon) {
+        cores.shown = !cores.shown;
+    }
+}
+
+/// Keep one core per body sized to the softening length, or none while they are hidden or
+/// softening is off
+#[allow(clippy::type_complexity)]
+pub fn softening_core_system(
+    mut commands: Commands,
+    mut cores: ResMut<SofteningCores>,
+    config: Res<PhysicsConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    bodies: Query<(Entity, &Transform), With<CelestialBody>>,
+    mut existing: Query<(Entity, &mut Transform, &Parent), (With<SofteningCore>, Without<CelestialBody>)>,
+) {
+    if !cores.shown || config.softening <= 0.0 {
+        for (core, ..) in existing.iter() {
+            commands.entity(core).despawn();
+        }
+        return;
+    }
+
+    let mut covered = HashSet::new();
+    for (core, mut transform, parent) in existing.iter_mut() {
+        let Ok((_, body_transform)) = bodies.get(parent.get()) else {
+            commands.entity(core).despawn();
+            continue;
+        };
+        transform.scale = Vec3::splat(softening_core_scale(config.softening, body_transform.scale.x));
+        covered.insert(parent.get());
+    }
+
+    // Unit sphere, scaled per body
+    let mesh = cores.mesh.get_or_insert_with(|| meshes.add(Sphere::new(1.0).mesh().ico(3).expect("level 3 is supported"))).clone();
+    let material = cores
+        .material
+        .get_or_insert_with(|| {
+            materials.add(StandardMaterial {
+                base_color: Color::rgba(1.0, 0.4, 0.8, 0.25),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            })
+        })
+        .clone();
+    for (body, body_transform) in bodies.iter().filter(|(body, _)| !covered.contains(body)) {
+        let core = commands
+            .spawn((
+                PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_scale(Vec3::splat(softening_core_scale(config.softening, body_transform.scale.x))),
+                    ..default()
+                },
+                SofteningCore,
+                NotShadowCaster,
+            ))
+            .id();
+        commands.entity(body).add_child(core);
+    }
+}
+
+// Length of the angular momentum arrow when it matches its starting magnitude
+pub const ANGULAR_MOMENTUM_ARROW_LENGTH: f32 = 15.0;
+
+/// Draw the last measured angular momentum as an arrow from the center of mass, scaled to its
+/// starting magnitude
+pub fn angular_momentum_arrow_system(mut gizmos: Gizmos, monitor: Res<AngularMomentumMonitor>) {
+    let (Some(initial), Some((com_position, momentum))) = (monitor.initial, monitor.latest) else {
+        return;
+    };
+    let Some(initial_direction) = initial.try_normalize() else {
+        return;
+    };
+    let direction = momentum.try_normalize().unwrap_or(initial_direction);
+    gizmos.arrow(
+        com_position,
+        com_position + direction * ANGULAR_MOMENTUM_ARROW_LENGTH * momentum.length() / initial.length(),
+        Color::FUCHSIA,
+    );
+}
+
+/// Whether bodies show arrows for their orbital direction. Toggled with O.
+#[derive(Resource)]
+pub struct OrbitDirectionMarkers(pub bool);
+
+impl Default for OrbitDirectionMarkers {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// O toggles the orbital direction arrows (Alt+O is ambient occlusion)
+pub fn orbit_direction_toggle(key_input: Res<ButtonInput<KeyCode>>, mut markers: ResMut<OrbitDirectionMarkers>) {
+    if key_input.just_pressed(KeyCode::KeyO) && !key_input.pressed(KeyCode::AltLeft) {
+        markers.0 = !markers.0;
+    }
+}
+
+/// Draw a short arrow ahead of each body along its motion, green if it orbits with the
+/// system's net rotation and red if against it
+pub fn orbit_direction_system(
+    mut gizmos: Gizmos,
+    markers: Res<OrbitDirectionMarkers>,
+    query: Query<(&Transform, &Velocity, &Mass, &Radius), With<CelestialBody>>,
+) {
+    if !markers.0 {
+        return;
+    }
+    let bodies: Vec<(Vec3, Vec3, f32)> = query
+        .iter()
+        .map(|(transform, velocity, mass, _)| (transform.translation, velocity.0, mass.0))
+        .collect();
+    let Some((com_position, com_velocity)) = center_of_mass(&bodies) else {
+        return;
+    };
+    let system_momentum = total_angular_momentum(&bodies, com_position, com_velocity);
+
+    for (transform, velocity, _, radius) in query.iter() {
+        let Some(direction) =
+            orbit_direction(transform.translation, velocity.0, com_position, com_velocity, system_momentum)
+        else {
+            continue;
+        };
+        let Some(heading) = (velocity.0 - com_velocity).try_normalize() else {
+            continue;
+        };
+        let color = match direction {
+            OrbitDirection::Prograde => Color::GREEN,
+            OrbitDirection::Retrograde => Color::RED,
+        };
+        let start = transform.translation + heading * radius.0 * 1.2;
+        gizmos.arrow(start, start + heading * (radius.0 + 1.0), color);
+    }
+}
+
+/// Bound pairs drawn as connecting lines, toggled with E
+#[derive(Resource)]
+pub struct BoundPairLines {
+    pub shown: bool,
+    /// Membership is recomputed on this timer rather than every frame
+    pub refresh: Timer,
+    pub pairs: Vec<(Entity, Entity, f32)>,
+}
+
+impl Default for BoundPairLines {
+    fn default() -> Self {
+        Self {
+            shown: false,
+            refresh: Timer::from_seconds(0.5, TimerMode::Repeating),
+            pairs: Vec::new(),
+        }
+    }
+}
+
+// Above this many bodies the pairwise check is too slow to keep repeating
+pub const BOUND_PAIR_MAX_BODIES: usize = 2000;
+
+/// E shows and hides the bound pair lines
+pub fn bound_pair_toggle(key_input: Res<ButtonInput<KeyCode>>, mut lines: ResMut<BoundPairLines>) {
+    if key_input.just_pressed(KeyCode::KeyE) {
+        lines.shown = !lines.shown;
+        // Draw straight away instead of waiting out the timer
+        let duration = lines.refresh.duration();
+        lines.refresh.set_elapsed(duration);
+    }
+}
+
+/// Refresh which pairs are bound now and then, and draw a line between each, brighter the more
+/// tightly bound
+pub fn bound_pair_system(
+    mut gizmos: Gizmos,
+    mut lines: ResMut<BoundPairLines>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+    (config, calendar): (Res<PhysicsConfig>, Res<Calendar>),
+    time: Res<Time>,
+) {
+    if !lines.shown {
+        return;
+    }
+    lines.refresh.tick(time.delta());
+    if lines.refresh.just_finished() {
+        let all: Vec<_> = bodies.iter().collect();
+        lines.pairs = if all.len() > BOUND_PAIR_MAX_BODIES {
+            Vec::new()
+        } else {
+            let states: Vec<_> = all
+                .iter()
+                .map(|(_, transform, velocity, mass)| (transform.translation, velocity.0, mass.0))
+                .collect();
+            bound_pairs(&states, &config.at_time(calendar.elapsed))
+                .into_iter()
+                .map(|(i, j, strength)| (all[i].0, all[j].0, strength))
+                .collect()
+        };
+    }
+
+    for &(first, second, strength) in &lines.pairs {
+        let (Ok((_, from, ..)), Ok((_, to, ..))) = (bodies.get(first), bodies.get(second)) else {
+            continue;
+        };
+        let brightness = strength.min(1.0);
+        gizmos.line(from.translation, to.translation, Color::rgba(0.3, 0.8, 1.0, 0.2 + 0.8 * brightness));
+    }
+}
+
+/// Ring unbound bodies in red
+pub fn unbound_highlight_system(mut gizmos: Gizmos, query: Query<(&Transform, &Radius), With<Unbound>>) {
+    for (transform, radius) in query.iter() {
+        gizmos.sphere(transform.translation, Quat::IDENTITY, radius.0 * 1.6, Color::RED);
+    }
+}
+
+/// Whether the simulation boundary is drawn, toggled with 7
+#[derive(Resource)]
+pub struct BoundaryShown(pub bool);
+
+impl Default for BoundaryShown {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+// Latitude rings (and as many meridians) the boundary wireframe is drawn with
+pub const BOUNDARY_RINGS: usize = 6;
+
+/// Circles (center, normal, radius) that draw the boundary as a wireframe sphere: latitude
+/// rings and meridians, all lying on the sphere
+pub fn boundary_rings(boundary: &BoundaryConfig) -> Vec<(Vec3, Direction3d, f32)> {
+    let Some(radius) = boundary.radius else {
+        return Vec::new();
+    };
+    let latitudes = (1..BOUNDARY_RINGS).map(|ring| {
+        let angle = std::f32::consts::PI * ring as f32 / BOUNDARY_RINGS as f32;
+        (Vec3::Y * radius * angle.cos(), Direction3d::Y, radius * angle.sin())
+    });
+    let meridians = (0..BOUNDARY_RINGS).filter_map(|ring| {
+        let angle = std::f32::consts::PI * ring as f32 / BOUNDARY_RINGS as f32;
+        let normal = Direction3d::new(Vec3::new(angle.cos(), 0.0, angle.sin())).ok()?;
+        Some((Vec3::ZERO, normal, radius))
+    });
+    latitudes.chain(meridians).collect()
+}
+
+/// 7 shows or hides the boundary
+pub fn boundary_toggle(key_input: Res<ButtonInput<KeyCode>>, mut shown: ResMut<BoundaryShown>) {
+    if key_input.just_pressed(KeyCode::Digit7) {
+        shown.0 = !shown.0;
+    }
+}
+
+/// Draw the boundary as a translucent wireframe sphere, red where bodies are despawned and blue
+/// where they wrap
+pub fn boundary_draw_system(mut gizmos: Gizmos, shown: Res<BoundaryShown>, config: Res<PhysicsConfig>) {
+    if !shown.0 {
+        return;
+    }
+    let color = match config.boundary.action {
+        BoundaryAction::Despawn => Color::rgba(1.0, 0.25, 0.2, 0.25),
+        BoundaryAction::Wrap => Color::rgba(0.3, 0.5, 1.0, 0.25),
+    };
+    for (center, normal, radius) in boundary_rings(&config.boundary) {
+        gizmos.circle(center, normal, radius, color).segments(64);
+    }
+}
+
+/// Keep the orbital plane disk aligned with the selected body's current orbit
+#[allow(clippy::type_complexity)]
+pub fn orbital_plane_system(
+    config: Res<PhysicsConfig>,
+    selected: Query<(Entity, &Transform, &Velocity, &Mass), (With<Selected>, With<CelestialBody>)>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+    mut disks: Query<
+        (&mut Transform, &mut Visibility),
+        (With<OrbitalPlaneDisk>, Without<CelestialBody>),
+    >,
+) {
+    let Ok((mut disk_transform, mut disk_visibility)) = disks.get_single_mut() else {
+        return;
+    };
+    *disk_visibility = Visibility::Hidden;
+
+    let Ok((entity, transform, velocity, mass)) = selected.get_single() else {
+        return;
+    };
+
+    // The body orbits whichever other body is most massive
+    let Some((_, central_transform, central_velocity, central_mass)) = bodies
+        .iter()
+        .filter(|(other, ..)| *other != entity)
+        .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))
+    else {
+        return;
+    };
+
+    let relative_position = transform.translation - central_transform.translation;
+    let relative_velocity = velocity.0 - central_velocity.0;
+    let Some(normal) = orbit_normal(relative_position, relative_velocity) else {
+        return;
+    };
+
+    let elements = orbital_elements(
+        relative_position,
+        relative_velocity,
+        config.gravitational_parameter(central_mass.0, mass.0),
+    );
+    // Bound orbits are covered out to apoapsis, anything else just past the body
+    let extent = if elements.eccentricity < 1.0 {
+        elements.semi_major_axis * (1.0 + elements.eccentricity)
+    } else {
+        relative_position.length() * 1.5
+    };
+
+    disk_transform.translation = central_transform.translation;
+    disk_transform.rotation = Quat::from_rotation_arc(Vec3::Z, normal);
+    disk_transform.scale = Vec3::splat(extent);
+    *disk_visibility = Visibility::Visible;
+}
+
+/// Line from each selected body to the body pulling on it hardest, labelled with its name, so
+/// which body dominates (its planet or the star) shows at a glance. Logs when it changes.
+pub fn dominant_attractor_system(
+    mut gizmos: Gizmos,
+    mut contexts: EguiContexts,
+    config: Res<PhysicsConfig>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraController>>,
+    selected: Query<Entity, (With<Selected>, With<CelestialBody>)>,
+    bodies: Query<(Entity, &Transform, &Mass, &Name), With<CelestialBody>>,
+    mut last: Local<HashMap<Entity, Entity>>,
+) {
+    last.retain(|body, _| selected.contains(*body));
+    if selected.is_empty() {
+        return;
+    }
+    let entities: Vec<Entity> = bodies.iter().map(|(entity, ..)| entity).collect();
+    let states: Vec<(Vec3, Vec3, f32)> =
+        bodies.iter().map(|(_, transform, mass, _)| (transform.translation, Vec3::ZERO, mass.0)).collect();
+    let camera = cameras.get_single().ok();
+    for body in selected.iter() {
+        let Some(index) = entities.iter().position(|&entity| entity == body) else {
+            continue;
+        };
+        let Some(attractor) = dominant_attractor(&states, index, config.force_exponent) else {
+            last.remove(&body);
+            continue;
+        };
+        let (Ok((_, _, _, name)), Ok((_, _, _, attractor_name))) = (bodies.get(body), bodies.get(entities[attractor])) else {
+            continue;
+        };
+        if last.insert(body, entities[attractor]).is_some_and(|previous| previous != entities[attractor]) {
+            info!("{} is now pulled hardest by {}", name.0, attractor_name.0);
+        }
+
+        let (from, to) = (states[index].0, states[attractor].0);
+        gizmos.line(from, to, Color::rgba(1.0, 0.85, 0.3, 0.5));
+        let Some(position) = camera.and_then(|(camera, transform)| camera.world_to_viewport(transform, from.lerp(to, 0.5))) else {
+            continue;
+        };
+        egui::Area::new(egui::Id::new(("dominant_attractor", body)))
+            .fixed_pos(egui::pos2(position.x, position.y))
+            .interactable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.colored_label(egui::Color32::from_rgb(255, 216, 77), format!("pulled by {}", attractor_name.0));
+            });
+    }
+}
+
+// Above this many bodies the apsidal markers aren't drawn
+pub const APSIDES_MAX_BODIES: usize = 200;
+
+/// Whether each body's periapsis and apoapsis are marked, toggled with 6
+#[derive(Resource, Default)]
+pub struct ApsidalMarkers(pub bool);
+
+/// 6 shows or hides the apsidal markers (Alt+6 is the orbit strobe)
+pub fn apsidal_markers_toggle(key_input: Res<ButtonInput<KeyCode>>, mut shown: ResMut<ApsidalMarkers>) {
+    if key_input.just_pressed(KeyCode::Digit6) && !key_input.pressed(KeyCode::AltLeft) {
+        shown.0 = !shown.0;
+    }
+}
+
+/// Mark the periapsis (orange) and apoapsis (blue) of every body's current orbit about whatever
+/// pulls on it hardest, recomputed each frame so the markers turn as the orbit precesses
+pub fn apsidal_markers_system(
+    mut gizmos: Gizmos,
+    shown: Res<ApsidalMarkers>,
+    config: Res<PhysicsConfig>,
+    bodies: Query<(&Transform, &Velocity, &Mass, &Radius), With<CelestialBody>>,
+) {
+    if !shown.0 || bodies.iter().len() > APSIDES_MAX_BODIES {
+        return;
+    }
+    let states: Vec<(Vec3, Vec3, f32)> =
+        bodies.iter().map(|(transform, velocity, mass, _)| (transform.translation, velocity.0, mass.0)).collect();
+    for (index, (.., radius)) in bodies.iter().enumerate() {
+        let Some(central) = dominant_attractor(&states, index, config.force_exponent) else {
+            continue;
+        };
+        let (position, velocity, mass) = states[index];
+        let (focus, central_velocity, central_mass) = states[central];
+        let mu = config.gravitational_parameter(central_mass, mass);
+        if mu.is_nan() || mu <= 0.0 {
+            continue;
+        }
+        let Some((periapsis, apoapsis)) = apsides(position - focus, velocity - central_velocity, mu) else {
+            continue;
+        };
+        let size = radius.0 * 0.4;
+        gizmos.sphere(focus + periapsis, Quat::IDENTITY, size, Color::ORANGE);
+        if let Some(apoapsis) = apoapsis {
+            gizmos.sphere(focus + apoapsis, Quat::IDENTITY, size, Color::rgb(0.4, 0.7, 1.0));
+        }
+    }
+}
+
+// Default seeds along each side of the field line grid
+pub const FIELD_LINE_SEEDS: usize = 12;
+// Default longest field line, in seed spacings
+pub const FIELD_LINE_LENGTH: f32 = 3.0;
+// Steps each field line is traced in per seed spacing
+pub const FIELD_LINE_STEPS_PER_SPACING: f32 = 4.0;
+// Above this many bodies the field lines aren't drawn
+pub const FIELD_LINES_MAX_BODIES: usize = 50;
+
+/// Gravitational field lines traced across the orbital plane from a square grid of seeds covering
+/// the system, toggled with Alt+G
+#[derive(Resource)]
+pub struct FieldLines {
+    pub shown: bool,
+    /// Seeds along each side of the grid
+    pub seeds: usize,
+    /// Longest a line is traced, in seed spacings
+    pub length: f32,
+}
+
+impl Default for FieldLines {
+    fn default() -> Self {
+        Self {
+            shown: false,
+            seeds: FIELD_LINE_SEEDS,
+            length: FIELD_LINE_LENGTH,
+        }
+    }
+}
+
+/// Follow the field from `start` in steps of `step` for up to `length`, kept in whatever plane
+/// `flatten` projects onto. `bodies` are (position, mass, radius); the line stops on reaching one,
+/// or where the field vanishes.
+pub fn field_line(
+    start: Vec3,
+    bodies: &[(Vec3, f32, f32)],
+    config: &PhysicsConfig,
+    step: f32,
+    length: f32,
+    flatten: impl Fn(Vec3) -> Vec3,
+) -> Vec<Vec3> {
+    let masses: Vec<(Vec3, f32)> = bodies.iter().map(|&(position, mass, _)| (position, mass)).collect();
+    let mut points = vec![start];
+    let mut point = start;
+    for _ in 0..(length / step) as usize {
+        if bodies.iter().any(|&(position, _, radius)| point.distance(position) <= radius + step) {
+            break;
+        }
+        let Some(direction) = flatten(field_acceleration(point, &masses, config)).try_normalize() else {
+            break;
+        };
+        point += direction * step;
+        points.push(point);
+    }
+    points
+}
+
+/// Alt+G shows or hides the field lines
+pub fn field_lines_toggle(key_input: Res<ButtonInput<KeyCode>>, mut lines: ResMut<FieldLines>) {
+    if key_input.pressed(KeyCode::AltLeft) && key_input.just_pressed(KeyCode::KeyG) {
+        lines.shown = !lines.shown;
+        info!("Field lines {}", if lines.shown { "on" } else { "off" });
+    }
+}
+
+/// Trace and draw the field lines, retraced every frame as the bodies move
+pub fn field_lines_system(
+    mut gizmos: Gizmos,
+    lines: Res<FieldLines>,
+    config: Res<PhysicsConfig>,
+    bodies: Query<(&Transform, &Mass, &Radius), With<CelestialBody>>,
+) {
+    if !lines.shown || lines.seeds == 0 || bodies.iter().len() > FIELD_LINES_MAX_BODIES {
+        return;
+    }
+    let states: Vec<(Vec3, f32, f32)> =
+        bodies.iter().map(|(transform, mass, radius)| (transform.translation, mass.0, radius.0)).collect();
+    let masses: Vec<(Vec3, Vec3, f32)> = states.iter().map(|&(position, mass, _)| (position, Vec3::ZERO, mass)).collect();
+    let Some((center, _)) = center_of_mass(&masses) else {
+        return;
+    };
+    let flatten = |vector: Vec3| config.up_axis.flatten(vector);
+    let center = flatten(center);
+    // A little past the outermost body, so lines come in from outside the system too
+    let half_extent = states.iter().fold(1.0f32, |extent, (position, ..)| extent.max(flatten(*position - center).length())) * 1.2;
+    let spacing = 2.0 * half_extent / lines.seeds as f32;
+    let rotation = config.up_axis.rotation_from_y_up();
+    for i in 0..lines.seeds {
+        for j in 0..lines.seeds {
+            let offset = Vec3::new(spacing * (i as f32 + 0.5) - half_extent, 0.0, spacing * (j as f32 + 0.5) - half_extent);
+            let line = field_line(
+                center + rotation * offset,
+                &states,
+                &config,
+                spacing / FIELD_LINE_STEPS_PER_SPACING,
+                spacing * lines.length,
+                flatten,
+            );
+            gizmos.linestrip(line, Color::rgba(0.5, 0.7, 1.0, 0.4));
+        }
+    }
+}
+
+// Seconds a body stays highlighted after completing an orbit
+pub const STROBE_FLASH_SECONDS: f32 = 0.4;
+
+/// Whether bodies flash and count their orbits each time they complete one, toggled with Alt+6
+#[derive(Resource, Default)]
+pub struct OrbitStrobe(pub bool);
+
+/// Orbits a body has completed around whatever pulls on it hardest since the strobe was turned on.
+/// Counted by the angle swept around it rather than periapsis passages, so near-circular orbits
+/// (whose periapsis is ill-defined) count too.
+#[derive(Component, Default)]
+pub struct OrbitCounter {
+    pub primary: Option<Entity>,
+    /// Position relative to the primary at the previous observation
+    pub previous: Option<Vec3>,
+    /// Radians swept since the last completed orbit
+    pub swept: f32,
+    pub orbits: u32,
+    /// Seconds left of the flash from the last completed orbit
+    pub flash: f32,
+}
+
+impl OrbitCounter {
+    /// Feed in the current state relative to `primary`, returning whether that completed an orbit.
+    /// A new primary starts the count over.
+    pub fn observe(&mut self, primary: Entity, relative_position: Vec3, relative_velocity: Vec3) -> bool {
+        if self.primary != Some(primary) {
+            *self = Self { primary: Some(primary), ..default() };
+        }
+        let previous = self.previous.replace(relative_position);
+        // Signed around the current orbit normal, so forward motion always sweeps a positive angle
+        if let (Some(previous), Some(normal)) = (previous, relative_position.cross(relative_velocity).try_normalize()) {
+            self.swept += previous.cross(relative_position).dot(normal).atan2(previous.dot(relative_position));
+        }
+        if self.swept < std::f32::consts::TAU {
+            return false;
+        }
+        self.swept -= std::f32::consts::TAU;
+        self.orbits += 1;
+        self.flash = STROBE_FLASH_SECONDS;
+        true
+    }
+}
+
+/// Sent each time a body completes an orbit while the strobe is on
+#[derive(Event)]
+pub struct OrbitCompleted {
+    pub body: Entity,
+    pub orbits: u32,
+}
+
+/// Alt+6 turns the orbit strobe on and off; either way the counts start over
+pub fn orbit_strobe_toggle(
+    mut commands: Commands,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut strobe: ResMut<OrbitStrobe>,
+    counters: Query<Entity, With<OrbitCounter>>,
+) {
+    if !(key_input.pressed(KeyCode::AltLeft) && key_input.just_pressed(KeyCode::Digit6)) {
+        return;
+    }
+    strobe.0 = !strobe.0;
+    info!("Orbit strobe {}", if strobe.0 { "on" } else { "off" });
+    for entity in counters.iter() {
+        commands.entity(entity).remove::<OrbitCounter>();
+    }
+}
+
+/// Count every body's completed orbits about its dominant attractor, starting a flash and sending
+/// an OrbitCompleted each time one comes round
+#[allow(clippy::type_complexity)]
+pub fn orbit_counter_system(
+    mut commands: Commands,
+    strobe: Res<OrbitStrobe>,
+    config: Res<PhysicsConfig>,
+    time: Res<Time>,
+    mut bodies: Query<(Entity, &Transform, &Velocity, &Mass, Option<&mut OrbitCounter>), With<CelestialBody>>,
+    mut completed: EventWriter<OrbitCompleted>,
+) {
+    if !strobe.0 || bodies.iter().len() > APSIDES_MAX_BODIES {
+        return;
+    }
+    let entities: Vec<Entity> = bodies.iter().map(|(entity, ..)| entity).collect();
+    let states: Vec<(Vec3, Vec3, f32)> =
+        bodies.iter().map(|(_, transform, velocity, mass, _)| (transform.translation, velocity.0, mass.0)).collect();
+    for (index, (entity, .., counter)) in bodies.iter_mut().enumerate() {
+        let Some(central) = dominant_attractor(&states, index, config.force_exponent) else {
+            continue;
+        };
+        let Some(mut counter) = counter else {
+            commands.entity(entity).insert(OrbitCounter::default());
+            continue;
+        };
+        counter.flash = (counter.flash - time.delta_seconds()).max(0.0);
+        let ((position, velocity, _), (central_position, central_velocity, _)) = (states[index], states[central]);
+        if counter.observe(entities[central], position - central_position, velocity - central_velocity) {
+            completed.send(OrbitCompleted { body: entity, orbits: counter.orbits });
+        }
+    }
+}
+
+/// Ring each body that just completed an orbit with a shrinking, fading flash
+pub fn orbit_strobe_system(mut gizmos: Gizmos, strobe: Res<OrbitStrobe>, counters: Query<(&Transform, &Radius, &OrbitCounter)>) {
+    if !strobe.0 {
+        return;
+    }
+    for (transform, radius, counter) in counters.iter() {
+        let fade = counter.flash / STROBE_FLASH_SECONDS;
+        if fade > 0.0 {
+            gizmos.sphere(transform.translation, Quat::IDENTITY, radius.0 * (1.5 + fade), Color::rgba(1.0, 1.0, 1.0, fade));
+        }
+    }
+}
+
+/// Completed orbit count next to every counted body
+pub fn orbit_counter_labels(
+    mut contexts: EguiContexts,
+    strobe: Res<OrbitStrobe>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraController>>,
+    counters: Query<(Entity, &Transform, &Radius, &OrbitCounter)>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    if !strobe.0 {
+        return;
+    }
+    for (entity, transform, radius, counter) in counters.iter() {
+        let Some(position) = camera.world_to_viewport(camera_transform, transform.translation + Vec3::Y * radius.0) else {
+            continue;
+        };
+        let color = if counter.flash > 0.0 { egui::Color32::WHITE } else { egui::Color32::GRAY };
+        egui::Area::new(egui::Id::new(("orbit_counter", entity)))
+            .fixed_pos(egui::pos2(position.x, position.y))
+            .interactable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.colored_label(color, counter.orbits.to_string());
+            });
+    }
+}
+
+/// H toggles the habitable zone overlay (Alt+H is the mass histogram)
+pub fn habitable_zone_toggle(key_input: Res<ButtonInput<KeyCode>>, mut zone: ResMut<HabitableZone>) {
+    if key_input.just_pressed(KeyCode::KeyH) && !key_input.pressed(KeyCode::AltLeft) {
+        zone.shown = !zone.shown;
+    }
+}
+
+// Concentric rings used to shade the habitable zone band
+pub const HABITABLE_ZONE_RINGS: usize = 24;
+
+/// Shade each star's habitable zone in the XZ plane and ring every planet in its temperature color
+#[allow(clippy::type_complexity)]
+pub fn habitable_zone_system(
+    mut gizmos: Gizmos,
+    zone: Res<HabitableZone>,
+    config: Res<PhysicsConfig>,
+    stars: Query<(&Transform, &Mass), With<Star>>,
+    planets: Query<(&Transform, &Radius), (With<CelestialBody>, Without<Star>)>,
+) {
+    if !zone.shown {
+        return;
+    }
+    let stars: Vec<(Vec3, f32)> = stars.iter().map(|(transform, mass)| (transform.translation, mass.0)).collect();
+    for (position, mass) in &stars {
+        let luminosity = star_luminosity(*mass);
+        // Higher irradiance is closer in
+        let inner = irradiance_distance(luminosity, zone.max_irradiance);
+        let outer = irradiance_distance(luminosity, zone.min_irradiance);
+        for ring in 0..=HABITABLE_ZONE_RINGS {
+            let radius = inner + (outer - inner) * ring as f32 / HABITABLE_ZONE_RINGS as f32;
+            gizmos.circle(*position, config.up_axis.direction(), radius, Color::rgba(0.3, 1.0, 0.4, 0.12)).segments(96);
+        }
+    }
+    if stars.is_empty() {
+        return;
+    }
+    for (transform, radius) in planets.iter() {
+        let bucket = zone.bucket(total_irradiance(transform.translation, &stars));
+        gizmos.sphere(transform.translation, Quat::IDENTITY, radius.0 * 1.3, bucket.color());
+    }
+}
+
+/// [ and ] lower and raise sphere tessellation
+pub fn mesh_quality_input(key_input: Res<ButtonInput<KeyCode>>, mut quality: ResMut<MeshQuality>) {
+    let subdivisions = if key_input.just_pressed(KeyCode::BracketLeft) {
+        quality.subdivisions.saturating_sub(1)
+    } else if key_input.just_pressed(KeyCode::BracketRight) {
+        (quality.subdivisions + 1).min(MAX_SPHERE_SUBDIVISIONS)
+    } else {
+        return;
+    };
+    if subdivisions != quality.subdivisions {
+        quality.subdivisions = subdivisions;
+        info!("Sphere subdivisions: {subdivisions}");
+    }
+}
+
+/// Rebuild body and atmosphere meshes in place when the quality changes
+#[allow(clippy::type_complexity)]
+pub fn regenerate_body_meshes(
+    quality: Res<MeshQuality>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    bodies: Query<(&Handle<Mesh>, &Radius, &Transform), (With<CelestialBody>, Without<CustomMesh>)>,
+    shells: Query<(&Handle<Mesh>, &Parent), With<AtmosphereShell>>,
+    atmospheres: Query<(&Radius, &Atmosphere)>,
+) {
+    if !quality.is_changed() || quality.is_added() {
+        return;
+    }
+
+    // Stress bodies share one unit sphere scaled per body, so rebuild each mesh only once
+    let mut rebuilt = HashSet::new();
+    for (handle, radius, transform) in bodies.iter() {
+        if rebuilt.insert(handle.id()) {
+            meshes.insert(handle.id(), sphere_mesh(radius.0 / transform.scale.x, &quality));
+        }
+    }
+    for (handle, parent) in shells.iter() {
+        if let Ok((radius, atmosphere)) = atmospheres.get(parent.get()) {
+            meshes.insert(handle.id(), sphere_mesh(radius.0 + atmosphere.thickness, &quality));
+        }
+    }
+}
+
+/// I toggles inverse-square physical lighting
+pub fn physical_light_toggle(key_input: Res<ButtonInput<KeyCode>>, mut lighting: ResMut<LightingConfig>) {
+    if key_input.just_pressed(KeyCode::KeyI) {
+        lighting.physical_light = !lighting.physical_light;
+        info!("Physical lighting {}", if lighting.physical_light { "on" } else { "off" });
+    }
+}
+
+/// In physical mode, light each body through its emissive by `intensity / distance²` from the
+/// point light (which is switched off so it doesn't light things twice)
+#[allow(clippy::type_complexity)]
+pub fn physical_light_system(
+    mut commands: Commands,
+    lighting: Res<LightingConfig>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut lights: Query<(&mut PointLight, &GlobalTransform)>,
+    mut bodies: Query<
+        (Entity, &Transform, &mut Handle<StandardMaterial>, Has<UniqueMaterial>),
+        (With<CelestialBody>, Without<Star>),
+    >,
+) {
+    let Ok((mut light, light_transform)) = lights.get_single_mut() else {
+        return;
+    };
+    let intensity = if lighting.physical_light { 0.0 } else { lighting.intensity };
+    if light.intensity != intensity {
+        light.intensity = intensity;
+    } else if !lighting.physical_light && !lighting.is_changed() {
+        // Nothing to undo
+        return;
+    }
+
+    let source = light_transform.translation();
+    for (entity, transform, mut handle, unique) in bodies.iter_mut() {
+        // Stress bodies share materials, so give each its own before changing it
+        if !unique {
+            if !lighting.physical_light {
+                // Never adjusted, so there is nothing to reset
+                continue;
+            }
+            let Some(material) = materials.get(handle.id()).cloned() else {
+                continue;
+            };
+            *handle = materials.add(material);
+            commands.entity(entity).insert(UniqueMaterial);
+        }
+        let Some(material) = materials.get_mut(handle.id()) else {
+            continue;
+        };
+        material.emissive = if lighting.physical_light {
+            let illumination = received_illumination(lighting.intensity, transform.translation.distance(source));
+            material.base_color * illumination * lighting.exposure
+        } else {
+            Color::BLACK
+        };
+    }
+}
+
+/// T cycles through the palettes, and back to the bodies' own colors
+pub fn palette_toggle(key_input: Res<ButtonInput<KeyCode>>, mut palettes: ResMut<Palettes>) {
+    if !key_input.just_pressed(KeyCode::KeyT) {
+        return;
+    }
+    palettes.active = match palettes.active {
+        None if !palettes.palettes.is_empty() => Some(0),
+        Some(index) if index + 1 < palettes.palettes.len() => Some(index + 1),
+        _ => None,
+    };
+    match palettes.active_palette() {
+        Some(palette) => info!("Palette: {}", palette.name),
+        None => info!("Palette: off"),
+    }
+}
+
+/// Give newly spawned bodies (other than stars) the next slots in the palette cycle, in spawn order
+#[allow(clippy::type_complexity)]
+pub fn assign_palette_slots(
+    mut commands: Commands,
+    mut palettes: ResMut<Palettes>,
+    materials: Res<Assets<StandardMaterial>>,
+    bodies: Query<(Entity, &Handle<StandardMaterial>), (With<CelestialBody>, Without<PaletteSlot>, Without<Star>)>,
+) {
+    let mut new_bodies: Vec<_> = bodies.iter().collect();
+    if new_bodies.is_empty() {
+        return;
+    }
+    new_bodies.sort_by_key(|(entity, _)| *entity);
+    for (entity, material) in new_bodies {
+        let original = materials.get(material).map_or(Color::WHITE, |material| material.base_color);
+        commands.entity(entity).insert(PaletteSlot {
+            index: palettes.next_slot,
+            original,
+        });
+        palettes.next_slot += 1;
+    }
+}
+
+/// Recolor bodies and their trails from the active palette when it changes or new bodies arrive
+#[allow(clippy::type_complexity)]
+pub fn apply_palette(
+    mut commands: Commands,
+    palettes: Res<Palettes>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut bodies: Query<(Entity, Ref<PaletteSlot>, &mut Handle<StandardMaterial>, Option<&mut Trail>)>,
+) {
+    if !palettes.is_changed() && !bodies.iter().any(|(_, slot, ..)| slot.is_added()) {
+        return;
+    }
+    // One material per color, so recoloring thousands of stress bodies stays cheap
+    let mut shared: Vec<(Color, Handle<StandardMaterial>)> = Vec::new();
+    for (entity, slot, mut handle, trail) in bodies.iter_mut() {
+        let color = palettes
+            .active_palette()
+            .and_then(|palette| palette.color(slot.index))
+            .unwrap_or(slot.original);
+        if materials.get(handle.id()).is_some_and(|material| material.base_color == color) {
+            continue;
+        }
+        *handle = match shared.iter().find(|(shared_color, _)| *shared_color == color) {
+            Some((_, shared_handle)) => shared_handle.clone(),
+            None => {
+                let mut material = materials.get(handle.id()).cloned().unwrap_or_default();
+                material.base_color = color;
+                let shared_handle = materials.add(material);
+                shared.push((color, shared_handle.clone()));
+                shared_handle
+            }
+        };
+        // The new material is shared again, so physical lighting has to split it off first
+        commands.entity(entity).remove::<UniqueMaterial>();
+        if let Some(mut trail) = trail {
+            trail.color = color;
+        }
+    }
+}
+
+/// Whether the lensing distortion is drawn. Off by default since it costs a fullscreen pass
+/// and is only loosely inspired by real lensing. Toggled with G.
+#[derive(Resource, Default)]
+pub struct GravitationalLensing(pub bool);
+
+// Einstein radius of a body, in world units, per square root of its mass
+pub const LENSING_RADIUS_PER_ROOT_MASS: f32 = 0.15;
+
+/// Lensing uniforms for the most massive of the (position, mass) bodies as seen by a
+/// perspective camera, or `None` if it is behind the camera
+pub fn lensing_settings(
+    camera_transform: &GlobalTransform,
+    projection: &PerspectiveProjection,
+    bodies: &[(Vec3, f32)],
+) -> Option<LensingSettings> {
+    let &(position, mass) = bodies.iter().max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let depth = (position - camera_transform.translation()).dot(camera_transform.forward());
+    if depth <= projection.near {
+        return None;
+    }
+    let view = camera_transform.compute_matrix().inverse();
+    let ndc = projection.get_projection_matrix().project_point3(view.transform_point3(position));
+
+    // Half the screen height spans tan(fov / 2) * depth in world units
+    let half_height = (projection.fov / 2.0).tan() * depth;
+    let einstein_radius = LENSING_RADIUS_PER_ROOT_MASS * mass.sqrt() / half_height * 0.5;
+    Some(LensingSettings {
+        center: Vec2::new(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5),
+        strength: einstein_radius * einstein_radius,
+        aspect: projection.aspect_ratio,
+    })
+}
+
+/// G toggles the lensing distortion (Alt+G is the field lines)
+pub fn lensing_toggle(key_input: Res<ButtonInput<KeyCode>>, mut lensing: ResMut<GravitationalLensing>) {
+    if key_input.just_pressed(KeyCode::KeyG) && !key_input.pressed(KeyCode::AltLeft) {
+        lensing.0 = !lensing.0;
+        info!("Gravitational lensing {}", if lensing.0 { "on" } else { "off" });
+    }
+}
+
+/// Keep the camera's lensing uniforms following the most massive body, removing them
+/// (which skips the pass) while lensing is off or the body is out of view
+pub fn lensing_system(
+    mut commands: Commands,
+    lensing: Res<GravitationalLensing>,
+    cameras: Query<(Entity, &GlobalTransform, &Projection), With<CameraController>>,
+    bodies: Query<(&Transform, &Mass), With<CelestialBody>>,
+) {
+    let bodies: Vec<(Vec3, f32)> = bodies.iter().map(|(transform, mass)| (transform.translation, mass.0)).collect();
+    for (entity, camera_transform, projection) in cameras.iter() {
+        let settings = match projection {
+            Projection::Perspective(projection) if lensing.0 => {
+                lensing_settings(camera_transform, projection, &bodies)
+            }
+            _ => None,
+        };
+        match settings {
+            Some(settings) => commands.entity(entity).insert(settings),
+            None => commands.entity(entity).remove::<LensingSettings>(),
+        };
+    }
+}
+
+// Spacing of the reference grid's lines, and how many there are either side of the origin
+pub const GRID_SPACING: f32 = 10.0;
+
+pub const GRID_HALF_LINES: i32 = 10;
+
+/// Whether the reference grid is drawn, from `--grid`
+#[derive(Resource, Default)]
+pub struct ReferenceGrid(pub bool);
+
+/// (start, end) of every line of the reference grid, a square around the origin lying in the
+/// plane across the up axis
+pub fn grid_lines(up_axis: UpAxis) -> Vec<(Vec3, Vec3)> {
+    let rotation = up_axis.rotation_from_y_up();
+    let extent = GRID_SPACING * GRID_HALF_LINES as f32;
+    (-GRID_HALF_LINES..=GRID_HALF_LINES)
+        .flat_map(|line| {
+            let offset = GRID_SPACING * line as f32;
+            [
+                (Vec3::new(offset, 0.0, -extent), Vec3::new(offset, 0.0, extent)),
+                (Vec3::new(-extent, 0.0, offset), Vec3::new(extent, 0.0, offset)),
+            ]
+        })
+        .map(|(start, end)| (rotation * start, rotation * end))
+        .collect()
+}
+
+/// Draw the reference grid in the orbital plane
+pub fn reference_grid_system(mut gizmos: Gizmos, grid: Res<ReferenceGrid>, config: Res<PhysicsConfig>) {
+    if !grid.0 {
+        return;
+    }
+    for (start, end) in grid_lines(config.up_axis) {
+        gizmos.line(start, end, Color::rgba(0.5, 0.5, 0.6, 0.2));
+    }
+}
+
+/// Keep NotShadowCaster in step with each body's CastsShadow
+pub fn shadow_casting_system(mut commands: Commands, query: Query<(Entity, &CastsShadow), Changed<CastsShadow>>) {
+    for (entity, shadow) in query.iter() {
+        if shadow.0 {
+            commands.entity(entity).remove::<NotShadowCaster>();
+        } else {
+            commands.entity(entity).insert(NotShadowCaster);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{AngularMomentumMonitor, BodyBundle, Calendar, angular_momentum_system, body_states, boundary_system, calendar_system, headless_app, movement_system};
+    use crate::scene::{BodySpec, LoadedScene, SceneConfig, solar_system_specs, spawn_loaded_scene};
+    use crate::selftest::two_body_specs;
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::render::mesh::VertexAttributeValues;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    fn spec(name: &str, mass: f32, position: Vec3, velocity: Vec3) -> BodySpec {
+        BodySpec { name: name.to_string(), mass, radius: 1.0, position, velocity, color: Color::WHITE, atmosphere: None }
+    }
+
+    #[test]
+    fn orbital_plane_normal_is_perpendicular_to_the_orbit() {
+        let (position, velocity) = (Vec3::new(10.0, 0.0, 2.0), Vec3::new(0.5, 3.0, 4.0));
+        let mut app = headless_app(&[
+            spec("Star", 1000.0, Vec3::ZERO, Vec3::ZERO),
+            spec("Planet", 1.0, position, velocity),
+        ]);
+        app.insert_resource(Paused(true)).add_systems(Update, orbital_plane_system);
+        let disk = app.world.spawn((Transform::default(), Visibility::Hidden, OrbitalPlaneDisk)).id();
+        let mut names = app.world.query::<(Entity, &Name)>();
+        let planet = names.iter(&app.world).find(|(_, name)| name.0 == "Planet").map(|(entity, _)| entity).unwrap();
+        app.world.entity_mut(planet).insert(Selected);
+        app.update();
+
+        assert_eq!(app.world.get::<Visibility>(disk), Some(&Visibility::Visible));
+        let normal = app.world.get::<Transform>(disk).unwrap().rotation * Vec3::Z;
+        assert!(normal.dot(position.normalize()).abs() < 1e-5);
+        assert!(normal.dot(velocity.normalize()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn trails_span_the_same_time_at_any_speed() {
+        let mut app = headless_app(&[
+            spec("Slow", 1e-6, Vec3::ZERO, Vec3::X),
+            spec("Fast", 1e-6, Vec3::Z * 1000.0, Vec3::X * 2.0),
+        ]);
+        app.insert_resource(TrailConfig { trail_seconds: 1.0, ..default() })
+            .add_systems(Update, trail_update_system.after(movement_system));
+        let mut bodies = app.world.query_filtered::<Entity, With<CelestialBody>>();
+        for body in bodies.iter(&app.world).collect::<Vec<_>>() {
+            app.world.entity_mut(body).insert(Trail::new(Color::WHITE));
+        }
+        for _ in 0..180 {
+            app.update();
+        }
+
+        let mut trails = app.world.query::<&Trail>();
+        let spans: Vec<(f32, f32)> = trails
+            .iter(&app.world)
+            .map(|trail| {
+                let (first, last) = (trail.points.front().unwrap(), trail.points.back().unwrap());
+                (last.0 - first.0, first.1.distance(last.1))
+            })
+            .collect();
+        assert!((spans[0].0 - 1.0).abs() < 0.05);
+        assert!((spans[0].0 - spans[1].0).abs() < 1e-6);
+        assert!((spans[1].1 / spans[0].1 - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn atmosphere_spawns_one_larger_shell() {
+        let mut app = headless_app(&[spec("Planet", 1.0, Vec3::ZERO, Vec3::ZERO)]);
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<MeshQuality>()
+            .add_systems(Update, spawn_atmosphere_shells);
+        let mut bodies = app.world.query_filtered::<Entity, With<CelestialBody>>();
+        let planet = bodies.single(&app.world);
+        app.world.entity_mut(planet).insert(Atmosphere { color: Color::BLUE, thickness: 0.25 });
+        app.update();
+
+        let children = app.world.get::<Children>(planet).expect("the shell is a child of the body");
+        assert_eq!(children.len(), 1);
+        let shell = app.world.entity(children[0]);
+        assert!(shell.contains::<AtmosphereShell>());
+        let mesh = app.world.resource::<Assets<Mesh>>().get(shell.get::<Handle<Mesh>>().unwrap()).unwrap();
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            panic!("sphere meshes have float positions");
+        };
+        let shell_radius = positions.iter().map(|position| Vec3::from_array(*position).length()).fold(0.0, f32::max);
+        assert!(shell_radius > 1.0);
+    }
+
+    #[test]
+    fn higher_mesh_quality_has_more_vertices() {
+        let vertices = |subdivisions| sphere_mesh(1.0, &MeshQuality { subdivisions }).count_vertices();
+        assert!(vertices(4) > vertices(2));
+        assert!(vertices(2) > vertices(0));
+    }
+
+    #[test]
+    fn illumination_falls_off_with_the_square_of_distance() {
+        for (intensity, distance) in [(1.0, 1.0), (2500.0, 20.0), (1e6, 350.0)] {
+            let ratio = received_illumination(intensity, 2.0 * distance) / received_illumination(intensity, distance);
+            assert!((ratio - 0.25).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn disabled_trail_stops_growing() {
+        let mut app = headless_app(&[spec("Planet", 1.0, Vec3::ZERO, Vec3::X)]);
+        app.init_resource::<TrailConfig>().add_systems(Update, trail_update_system.after(movement_system));
+        let mut bodies = app.world.query_filtered::<Entity, With<CelestialBody>>();
+        let planet = bodies.single(&app.world);
+        app.world.entity_mut(planet).insert(Trail::new(Color::WHITE));
+        for _ in 0..30 {
+            app.update();
+        }
+        assert!(app.world.get::<Trail>(planet).unwrap().points.len() > 10);
+
+        app.world.entity_mut(planet).insert(TrailEnabled(false));
+        for _ in 0..30 {
+            app.update();
+        }
+        assert!(app.world.get::<Trail>(planet).unwrap().points.is_empty());
+    }
+
+    #[test]
+    fn closer_planet_gets_more_irradiance_and_a_hotter_bucket() {
+        let zone = HabitableZone::default();
+        let stars = [(Vec3::ZERO, 1000.0)];
+        let inner_edge = irradiance_distance(star_luminosity(1000.0), zone.max_irradiance);
+        let (near, far) = (Vec3::X * inner_edge * 0.8, Vec3::Z * inner_edge * 1.5);
+        let (near_irradiance, far_irradiance) = (total_irradiance(near, &stars), total_irradiance(far, &stars));
+
+        assert!(near_irradiance > far_irradiance);
+        assert!(zone.bucket(near_irradiance) > zone.bucket(far_irradiance));
+        assert_eq!(zone.bucket(near_irradiance), TemperatureBucket::Warm);
+    }
+
+    #[test]
+    fn palette_colors_cycle_in_spawn_order() {
+        let specs: Vec<BodySpec> = (0..7).map(|i| spec(&format!("Body {i}"), 1.0, Vec3::X * (10.0 + i as f32), Vec3::ZERO)).collect();
+        let mut app = headless_app(&specs);
+        app.init_resource::<Assets<StandardMaterial>>()
+            .insert_resource(Paused(true))
+            .insert_resource(Palettes {
+                active: Some(0),
+                ..default()
+            })
+            .add_systems(Update, (assign_palette_slots, apply_palette).chain());
+        let mut bodies = app.world.query_filtered::<Entity, With<CelestialBody>>();
+        let mut entities: Vec<Entity> = bodies.iter(&app.world).collect();
+        entities.sort();
+        for &entity in &entities {
+            let material = app.world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial::default());
+            app.world.entity_mut(entity).insert(material);
+        }
+        app.update();
+
+        let palette = app.world.resource::<Palettes>().active_palette().unwrap().clone();
+        assert!(palette.colors.len() < entities.len());
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        for (index, &entity) in entities.iter().enumerate() {
+            let material = materials.get(app.world.get::<Handle<StandardMaterial>>(entity).unwrap()).unwrap();
+            assert_eq!(Some(material.base_color), palette.color(index));
+        }
+    }
+
+    #[test]
+    fn low_quality_turns_msaa_shadows_and_bloom_off_and_high_back_on() {
+        let mut app = App::new();
+        app.init_resource::<Msaa>()
+            .init_resource::<PointLightShadowMap>()
+            .init_resource::<MeshQuality>()
+            .insert_resource(RenderQuality::Low)
+            .add_systems(Update, apply_render_quality);
+        let light = app.world.spawn(PointLight::default()).id();
+        let camera = app
+            .world
+            .spawn((Camera::default(), CameraController {
+                sensitivity: 0.0,
+                speed: 0.0,
+                adaptive_speed: false,
+                min_speed: 0.0,
+                max_speed: 0.0,
+                follow_stiffness: 0.0,
+                follow_damping: 0.0,
+                follow_lead: 0.0,
+            }))
+            .id();
+        let render_state = |app: &mut App| {
+            app.update();
+            let world = &app.world;
+            let shadows = world.get::<PointLight>(light).unwrap().shadows_enabled;
+            let bloom = world.get::<BloomSettings>(camera).is_some() && world.get::<Camera>(camera).unwrap().hdr;
+            (*world.resource::<Msaa>() != Msaa::Off, shadows, bloom)
+        };
+
+        assert_eq!(render_state(&mut app), (false, false, false));
+        *app.world.resource_mut::<RenderQuality>() = RenderQuality::High;
+        assert_eq!(render_state(&mut app), (true, true, true));
+    }
+
+    #[test]
+    fn softening_cores_have_the_softening_radius_at_any_scale() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .insert_resource(PhysicsConfig { softening: 1.5, ..default() })
+            .insert_resource(SofteningCores { shown: true, ..default() })
+            .add_systems(Update, softening_core_system);
+        for scale in [1.0, 0.4, 3.0] {
+            app.world.spawn((Transform::from_scale(Vec3::splat(scale)), CelestialBody));
+        }
+        app.update();
+        app.update();
+
+        let mut cores = app.world.query_filtered::<(&Transform, &Parent), With<SofteningCore>>();
+        let radii: Vec<f32> = cores
+            .iter(&app.world)
+            .map(|(transform, parent)| transform.scale.x * app.world.get::<Transform>(parent.get()).unwrap().scale.x)
+            .collect();
+        assert_eq!(radii.len(), 3);
+        for radius in radii {
+            assert!((radius - 1.5).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn body_keeping_pace_with_the_frame_stays_put() {
+        let omega = 0.2;
+        let circle = |radius: f32, angle: f32| Vec3::new(radius * angle.cos(), 0.0, -radius * angle.sin());
+        let frame_at = |t: f32| {
+            let velocity = Vec3::new(-20.0 * omega * (omega * t).sin(), 0.0, -20.0 * omega * (omega * t).cos());
+            rotating_frame((circle(20.0, omega * t), velocity), (Vec3::ZERO, Vec3::ZERO)).expect("the orbit turns")
+        };
+        let now = frame_at(30.0);
+        let end = circle(32.0, omega * 30.0 + 0.7);
+
+        for step in 0..30 {
+            let t = step as f32;
+            let seen = rotate_into_frame(circle(32.0, omega * t + 0.7), frame_at(t), now);
+            assert!(seen.distance(end) / 32.0 < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sustained_low_fps_steps_quality_down_to_low() {
+        let mut adaptive = AdaptiveQuality::default();
+        let mut quality = RenderQuality::High;
+        let mut steps = Vec::new();
+        // Ten seconds at 20 FPS
+        for _ in 0..20 * 10 {
+            if let Some(next) = adaptive.observe(1.0 / 20.0, quality) {
+                steps.push(next);
+                quality = next;
+            }
+        }
+
+        assert_eq!(steps, [RenderQuality::Medium, RenderQuality::Low]);
+    }
+
+    #[test]
+    fn trail_points_follow_the_sample_interval() {
+        let (interval, duration) = (0.5, 10.0);
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)))
+            .insert_resource(TrailConfig {
+                trail_seconds: 1000.0,
+                max_points: 100_000,
+                trail_sample_interval: interval,
+                ribbon_width: None,
+                decimation_pixels: 0.0,
+            })
+            .init_resource::<Calendar>()
+            .add_systems(
+                Update,
+                (|mut calendar: ResMut<Calendar>, time: Res<Time>| calendar.elapsed += time.delta_seconds(), trail_update_system).chain(),
+            );
+        let body = app.world.spawn((Transform::default(), Trail::new(Color::WHITE), CelestialBody)).id();
+        while app.world.resource::<Calendar>().elapsed < duration - 1e-3 {
+            app.update();
+        }
+
+        // The first point comes straight away, before any interval has passed
+        let points = app.world.get::<Trail>(body).unwrap().points.len();
+        assert_eq!(points, 1 + (duration / interval) as usize);
+    }
+
+    #[test]
+    fn trail_legend_keeps_distinct_colors_and_separates_shared_ones() {
+        let trails = [
+            ("Ceres", Color::rgb(0.8, 0.3, 0.2), true),
+            ("Vesta", Color::rgb(0.2, 0.5, 0.9), true),
+            ("Pallas", Color::GRAY, true),
+            ("Hygiea", Color::GRAY, true),
+            ("Juno", Color::GRAY, false),
+        ];
+        let legend = trail_legend(&trails);
+        let names: Vec<&str> = legend.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Ceres", "Hygiea", "Pallas", "Vesta"]);
+        let color_of = |name: &str| legend.iter().find(|entry| entry.0 == name).map(|entry| entry.1.as_rgba_u8());
+        assert_eq!(color_of("Ceres"), Some(trails[0].1.as_rgba_u8()));
+        assert_eq!(color_of("Vesta"), Some(trails[1].1.as_rgba_u8()));
+        assert_ne!(color_of("Pallas"), color_of("Hygiea"));
+
+        // The same bodies get the same colors every time
+        let shared = [("Pallas", Color::GRAY), ("Hygiea", Color::GRAY)];
+        assert_eq!(trail_colors(&shared), trail_colors(&shared));
+    }
+
+    #[test]
+    fn nothing_is_recorded_while_paused() {
+        let mut app = headless_app(&solar_system_specs());
+        app.init_resource::<Calendar>()
+            .init_resource::<TrailConfig>()
+            .init_resource::<TrailFrameHistory>()
+            .init_resource::<AngularMomentumMonitor>()
+            .add_systems(
+                Update,
+                (calendar_system, trail_update_system, trail_frame_system, angular_momentum_system).chain().after(movement_system),
+            );
+        let bodies: Vec<Entity> = app.world.query_filtered::<Entity, With<CelestialBody>>().iter(&app.world).collect();
+        for &body in &bodies {
+            app.world.entity_mut(body).insert(Trail::new(Color::WHITE));
+        }
+        app.insert_resource(TrailFrame::Rotating(bodies[1]));
+        let recorded = |app: &mut App| {
+            let points: usize = app.world.query::<&Trail>().iter(&app.world).map(|trail| trail.points.len()).sum();
+            (
+                points,
+                app.world.resource::<TrailFrameHistory>().0.len(),
+                app.world.resource::<AngularMomentumMonitor>().samples,
+                app.world.resource::<Calendar>().elapsed,
+            )
+        };
+
+        for _ in 0..10 {
+            app.update();
+        }
+        let running = recorded(&mut app);
+        assert!(running.0 > 0 && running.1 > 0 && running.2 > 0);
+        app.insert_resource(Paused(true));
+        for _ in 0..30 {
+            app.update();
+        }
+        assert_eq!(recorded(&mut app), running);
+
+        // And picks up again once resumed
+        app.insert_resource(Paused(false));
+        for _ in 0..5 {
+            app.update();
+        }
+        let resumed = recorded(&mut app);
+        assert!(resumed.0 > running.0 && resumed.2 > running.2);
+    }
+
+    #[test]
+    fn shadows_are_off_only_where_configured() {
+        let scene = ron::from_str::<SceneConfig>(
+            "(bodies: [
+                (name: \"Planet\", mass: 10.0, radius: 1.0, position: (0.0, 0.0, 0.0), velocity: (0.0, 0.0, 0.0), color: (1.0, 1.0, 1.0)),
+                (name: \"Pebble\", mass: 0.1, radius: 0.1, position: (5.0, 0.0, 0.0), velocity: (0.0, 0.0, 1.0), color: (1.0, 1.0, 1.0), casts_shadow: false),
+            ])",
+        )
+        .unwrap();
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_resource::<MeshQuality>()
+            .insert_resource(LoadedScene(scene.bodies))
+            .add_systems(Update, (spawn_loaded_scene.run_if(resource_exists::<LoadedScene>), shadow_casting_system).chain());
+        app.update();
+        app.update();
+
+        let mut shadows = app.world.query::<(&Name, Has<NotShadowCaster>)>();
+        assert_eq!(shadows.iter(&app.world).count(), 2);
+        let shadowless: Vec<String> =
+            shadows.iter(&app.world).filter(|(_, shadowless)| *shadowless).map(|(name, _)| name.0.clone()).collect();
+        assert_eq!(shadowless, ["Pebble"]);
+    }
+
+    #[test]
+    fn removed_bodys_trail_fades_out_on_its_own() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)))
+            .insert_resource(PhysicsConfig {
+                boundary: BoundaryConfig { radius: Some(10.0), action: BoundaryAction::Despawn },
+                ..default()
+            })
+            .add_systems(Update, (boundary_system, fading_trail_system).chain());
+        let mut trail = Trail::new(Color::WHITE);
+        trail.points.extend([(0.0, Vec3::X * 8.0), (0.5, Vec3::X * 9.0), (1.0, Vec3::X * 11.0)]);
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(Vec3::X * 11.0)),
+            BodyBundle::from_spec(&spec("Escapee", 1.0, Vec3::X * 11.0, Vec3::X)),
+            trail,
+        ));
+        let fading = |app: &mut App| {
+            app.world.query_filtered::<&Trail, With<FadingTrail>>().iter(&app.world).map(|trail| trail.points.len()).collect::<Vec<_>>()
+        };
+
+        // The body is gone at the boundary but its trail is left behind
+        app.update();
+        assert_eq!(app.world.query::<&CelestialBody>().iter(&app.world).count(), 0);
+        assert_eq!(fading(&mut app), [3]);
+
+        for _ in 0..(TRAIL_FADE_SECONDS * 60.0) as usize + 2 {
+            app.update();
+        }
+        assert!(fading(&mut app).is_empty());
+    }
+
+    #[test]
+    fn ribbon_has_two_vertices_per_point_and_two_triangles_per_segment() {
+        let points: Vec<Vec3> = (0..10).map(|i| Vec3::new(i as f32, 0.0, (i as f32 * 0.5).sin())).collect();
+        let ribbon = ribbon_mesh(&points, Vec3::new(5.0, 20.0, 0.0), 0.5, Color::WHITE);
+
+        assert_eq!(ribbon.count_vertices(), 20);
+        assert_eq!(ribbon.indices().unwrap().len(), 9 * 2 * 3);
+    }
+
+    #[test]
+    fn strobe_counts_and_flashes_each_completed_orbit() {
+        let mut app = headless_app(&two_body_specs(12.0, 1.2));
+        app.insert_resource(OrbitStrobe(true))
+            .add_event::<OrbitCompleted>()
+            .add_systems(Update, orbit_counter_system.after(movement_system));
+        let states = body_states(&mut app);
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 1.0);
+        let period = orbital_period(mu, orbital_elements(states[1].0 - states[0].0, states[1].1 - states[0].1, mu).semi_major_axis);
+        let mut reader = ManualEventReader::<OrbitCompleted>::default();
+        let mut flashes = 0;
+        for _ in 0..(period * 3.5 * 60.0) as usize {
+            app.update();
+            flashes += reader.read(app.world.resource::<Events<OrbitCompleted>>()).count();
+        }
+
+        let orbits: u32 = app.world.query::<&OrbitCounter>().iter(&app.world).map(|counter| counter.orbits).sum();
+        assert_eq!(orbits, 3);
+        assert_eq!(flashes, 3);
+    }
+
+    #[test]
+    fn field_line_runs_steadily_into_a_lone_mass() {
+        let (center, radius) = (Vec3::new(3.0, 0.0, -2.0), 2.0);
+        let (start, step) = (Vec3::new(18.0, 0.0, 9.0), 0.5);
+        let line = field_line(start, &[(center, 1000.0, radius)], &PhysicsConfig::default(), step, 100.0, |vector| vector);
+
+        let distances: Vec<f32> = line.iter().map(|point| point.distance(center)).collect();
+        assert!(distances.windows(2).all(|pair| pair[1] < pair[0]), "the line turns away from the mass");
+        // It stops within a step of the surface
+        assert!(*distances.last().unwrap() <= radius + step + 1e-4 * start.distance(center));
+    }
+
+    #[test]
+    fn decimation_thins_out_distant_trails_only() {
+        let points: Vec<Vec3> = (0..200).map(|i| Vec3::new((i as f32 * 0.05).cos(), 0.0, (i as f32 * 0.05).sin()) * 10.0).collect();
+        let projection = Projection::Perspective(PerspectiveProjection::default());
+        let drawn = |camera: Vec3| {
+            decimate_trail(points.clone(), TRAIL_DECIMATION_PIXELS, |from, to| screen_length(from, to, camera, &projection, 720.0)).len()
+        };
+
+        // Far off the segments are under the threshold on screen; up close every point is kept
+        assert!(drawn(Vec3::new(0.0, 2000.0, 0.0)) < points.len() / 2);
+        assert_eq!(drawn(Vec3::new(0.0, 20.0, 0.0)), points.len());
+        assert_eq!(decimate_trail(points.clone(), 0.0, |_, _| 0.0).len(), points.len());
+    }
+
+    #[test]
+    fn ambient_occlusion_adds_ssao_at_the_preset_level_and_turns_msaa_off() {
+        let mut app = App::new();
+        app.init_resource::<Msaa>()
+            .insert_resource(RenderQuality::High)
+            .insert_resource(AmbientOcclusion(true))
+            .add_systems(Update, ambient_occlusion_system);
+        let camera = app
+            .world
+            .spawn((Camera::default(), CameraController {
+                sensitivity: 0.0,
+                speed: 0.0,
+                adaptive_speed: false,
+                min_speed: 0.0,
+                max_speed: 0.0,
+                follow_stiffness: 0.0,
+                follow_damping: 0.0,
+                follow_lead: 0.0,
+            }))
+            .id();
+        let occlusion_state = |app: &mut App| {
+            app.update();
+            let quality = app.world.get::<ScreenSpaceAmbientOcclusionSettings>(camera).map(|settings| settings.quality_level);
+            (quality == Some(ScreenSpaceAmbientOcclusionQualityLevel::High), *app.world.resource::<Msaa>() == Msaa::Off)
+        };
+
+        assert_eq!(occlusion_state(&mut app), (true, true));
+        app.world.resource_mut::<AmbientOcclusion>().0 = false;
+        assert_eq!(occlusion_state(&mut app), (false, false));
+        assert!(app.world.get::<ScreenSpaceAmbientOcclusionSettings>(camera).is_none());
+    }
+}
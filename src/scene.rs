@@ -0,0 +1,2223 @@
+//! Launch options, scenarios, scene configs, save slots and exports
+
+use bevy::prelude::*;
+use bevy::ecs::system::EntityCommands;
+use bevy::pbr::NotShadowCaster;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::physics::{AnalyticBinaries, AngularMomentumMonitor, BodyBundle, Calendar, CelestialBody, CollisionHistory, CollisionMode, Integrator, IntegratorOverride, KeplerParticle, Mass, Name, Paused, PhysicsConfig, RadiationSusceptible, Radius, Rotation, Star, UpAxis, Velocity, collision_system, headless_app, movement_system, simulation_unpaused};
+use crate::orbital::circular_velocity;
+use crate::camera::{CameraClip, CameraController, FollowTarget, default_camera_transform, framing_transform};
+use crate::render::{Atmosphere, BoundPairLines, CastsShadow, CustomMesh, FadingTrail, LightingConfig, MeshQuality, OrbitalPlaneDisk, Palette, RenderQuality, SetupPreview, SurfaceMaterial, Trail, TrailColor, TrailEnabled, TrailFrame, TrailFrameHistory, TrajectoryFan, name_hue, sphere_mesh};
+use crate::ui::ApsidalDrift;
+
+/// Options parsed from the command line
+#[derive(Resource, Default)]
+pub struct LaunchOptions {
+    /// Replace the default system with this many random bodies
+    pub stress: Option<usize>,
+    pub seed: Option<u64>,
+    /// Run the headless determinism check for this many steps and exit
+    pub check_determinism: Option<usize>,
+    /// Run the integration accuracy self-test and exit
+    pub selftest: bool,
+    /// Fixed timestep and no wall-clock-dependent behavior, so runs repeat exactly
+    pub deterministic: bool,
+    /// Open paused with the setup preview showing
+    pub start_paused: bool,
+    /// Load the system from this RON scene config instead of a built-in scenario
+    pub config: Option<String>,
+    /// Color bodies from this palette
+    pub palette: Option<String>,
+    /// Lock the time scale so this many calendar days pass per real second
+    pub days_per_second: Option<f32>,
+    pub quality: RenderQuality,
+    /// Start with screen-space ambient occlusion on
+    pub ssao: bool,
+    /// Simulated seconds between trail points
+    pub trail_interval: Option<f32>,
+    /// Draw trails as ribbons this wide instead of lines
+    pub trail_ribbons: Option<f32>,
+    /// Merge trail segments shorter than this many pixels on screen
+    pub trail_decimation: Option<f32>,
+    /// Camera clipping plane distances
+    pub near_clip: Option<f32>,
+    pub far_clip: Option<f32>,
+    /// Field line seeds along each side of the grid
+    pub field_seeds: Option<usize>,
+    /// Longest field line, in seed spacings
+    pub field_length: Option<f32>,
+    /// Despawn bodies further than this from the origin
+    pub boundary: Option<f32>,
+    pub up_axis: Option<UpAxis>,
+    /// Draw the reference grid in the orbital plane
+    pub grid: bool,
+    /// Spawn an accretion disk of this many particles
+    pub disk: Option<usize>,
+    /// Spawn a Newton's cradle of this many resting balls
+    pub cradle: Option<usize>,
+    pub disk_shape: DiskConfig,
+    /// Add this many massless Kepler particles orbiting the heaviest body
+    pub kepler_particles: Option<usize>,
+    /// Run the chosen system headlessly and write every body's trajectory to this CSV file
+    pub export_trajectory: Option<String>,
+    /// Steps to run for `export_trajectory`
+    pub steps: Option<usize>,
+}
+
+impl LaunchOptions {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--stress" => options.stress = Some(parse_value(&arg, args.next())?),
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+                "--selftest" => options.selftest = true,
+                "--deterministic" => options.deterministic = true,
+                "--paused" => options.start_paused = true,
+                "--up-axis" => options.up_axis = Some(parse_value(&arg, args.next())?),
+                "--grid" => options.grid = true,
+                "--config" => options.config = Some(parse_value(&arg, args.next())?),
+                "--palette" => options.palette = Some(parse_value(&arg, args.next())?),
+                "--days-per-second" => options.days_per_second = Some(parse_value(&arg, args.next())?),
+                "--quality" => options.quality = parse_value(&arg, args.next())?,
+                "--ssao" => options.ssao = true,
+                "--trail-interval" => options.trail_interval = Some(parse_value(&arg, args.next())?),
+                "--trail-ribbons" => {
+                    let width: f32 = parse_value(&arg, args.next())?;
+                    if width.is_nan() || width <= 0.0 {
+                        return Err(format!("`{arg}` needs a positive width, not {width}"));
+                    }
+                    options.trail_ribbons = Some(width);
+                }
+                "--trail-decimation" => {
+                    let pixels: f32 = parse_value(&arg, args.next())?;
+                    if pixels.is_nan() || pixels < 0.0 {
+                        return Err(format!("`{arg}` needs a length in pixels of 0 or more, not {pixels}"));
+                    }
+                    options.trail_decimation = Some(pixels);
+                }
+                "--near-clip" | "--far-clip" => {
+                    let distance: f32 = parse_value(&arg, args.next())?;
+                    if distance.is_nan() || distance <= 0.0 {
+                        return Err(format!("`{arg}` needs a positive distance, not {distance}"));
+                    }
+                    if arg == "--near-clip" {
+                        options.near_clip = Some(distance);
+                    } else {
+                        options.far_clip = Some(distance);
+                    }
+                }
+                "--field-seeds" => options.field_seeds = Some(parse_value(&arg, args.next())?),
+                "--field-length" => {
+                    let length: f32 = parse_value(&arg, args.next())?;
+                    if length.is_nan() || length <= 0.0 {
+                        return Err(format!("`{arg}` needs a positive length, not {length}"));
+                    }
+                    options.field_length = Some(length);
+                }
+                "--boundary" => {
+                    let radius: f32 = parse_value(&arg, args.next())?;
+                    if radius.is_nan() || radius <= 0.0 {
+                        return Err(format!("`{arg}` needs a positive radius, not {radius}"));
+                    }
+                    options.boundary = Some(radius);
+                }
+                "--disk" => options.disk = Some(parse_value(&arg, args.next())?),
+                "--cradle" => options.cradle = Some(parse_value(&arg, args.next())?),
+                "--disk-inner" => options.disk_shape.inner_radius = parse_value(&arg, args.next())?,
+                "--disk-outer" => options.disk_shape.outer_radius = parse_value(&arg, args.next())?,
+                "--disk-thickness" => options.disk_shape.thickness = parse_value(&arg, args.next())?,
+                "--disk-profile" => options.disk_shape.profile = parse_value(&arg, args.next())?,
+                "--kepler-particles" => options.kepler_particles = Some(parse_value(&arg, args.next())?),
+                "--export-trajectory" => options.export_trajectory = Some(parse_value(&arg, args.next())?),
+                "--steps" => options.steps = Some(parse_value(&arg, args.next())?),
+                "--check-determinism" => {
+                    options.check_determinism = Some(parse_value(&arg, args.next())?)
+                }
+                _ => return Err(format!("unknown option `{arg}`")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+pub fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("`{flag}` needs a value"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{value}` for `{flag}`"))
+}
+
+/// The system that was loaded at startup
+#[derive(Resource, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Scenario {
+    /// The Sun and its three planets
+    #[default]
+    SolarSystem,
+    /// Random bodies collapsing under gravity
+    Stress { count: usize },
+    /// Bodies read from a scene config file
+    Config,
+    /// A star ringed by a disk of small particles
+    AccretionDisk { count: usize, shape: DiskConfig },
+    /// A row of equal, perfectly elastic balls struck end-on by one more, like Newton's cradle
+    Cradle { count: usize },
+}
+
+impl Scenario {
+    pub fn from_options(options: &LaunchOptions) -> Self {
+        if options.config.is_some() {
+            return Scenario::Config;
+        }
+        match (options.stress, options.disk, options.cradle) {
+            (Some(count), ..) => Scenario::Stress { count },
+            (None, Some(count), _) => Scenario::AccretionDisk { count, shape: options.disk_shape },
+            (None, None, Some(count)) => Scenario::Cradle { count },
+            (None, None, None) => Scenario::SolarSystem,
+        }
+    }
+
+    /// Whether its layout comes from the seeded generator, so a reroll gives a different one
+    pub fn is_random(&self) -> bool {
+        matches!(self, Scenario::Stress { .. } | Scenario::AccretionDisk { .. })
+    }
+
+    /// Physics settings the scenario needs to do what it's for, applied over the launch's own
+    pub fn adjust_physics(&self, physics: &mut PhysicsConfig) {
+        if let Scenario::Cradle { .. } = self {
+            physics.collision_mode = CollisionMode::Bounce;
+        }
+    }
+
+    /// Surface every body of the scenario bounces with, where it isn't the default
+    pub fn surface_material(&self) -> Option<SurfaceMaterial> {
+        match self {
+            Scenario::Cradle { .. } => Some(CRADLE_MATERIAL),
+            _ => None,
+        }
+    }
+
+    /// Where the camera starts and how fast time runs to see the scenario at its best, for
+    /// bodies laid out with `up_axis` up
+    pub fn recommended_view(&self, up_axis: UpAxis) -> ScenarioView {
+        let fov = PerspectiveProjection::default().fov;
+        // Worked out Y-up, as the scenarios are laid out, then turned like orient_specs turns the bodies
+        let rotation = up_axis.rotation_from_y_up();
+        let frame = |center: Vec3, radius: f32, view_direction: Vec3| {
+            framing_transform(rotation * center, radius, fov, rotation * view_direction, up_axis)
+        };
+        match self {
+            Scenario::SolarSystem => ScenarioView { camera: default_camera_transform(up_axis), time_scale: Some(1.0) },
+            // Far enough back to take in the whole sphere they start in as it collapses
+            Scenario::Stress { .. } => ScenarioView {
+                camera: frame(Vec3::ZERO, STRESS_RADIUS, Vec3::new(1.0, -0.6, -1.0)),
+                time_scale: Some(1.0),
+            },
+            // Nearly face-on so the spiral structure and gaps read
+            Scenario::AccretionDisk { shape, .. } => ScenarioView {
+                camera: frame(Vec3::ZERO, shape.outer_radius, Vec3::new(0.0, -1.0, -0.3)),
+                time_scale: Some(1.0),
+            },
+            // Side-on and slowed down so each strike can be followed along the row
+            Scenario::Cradle { count } => {
+                let pitch = 2.0 * CRADLE_BALL_RADIUS + CRADLE_GAP;
+                let last = pitch * count.saturating_sub(1) as f32 / 2.0 + CRADLE_BALL_RADIUS;
+                let first = -last - pitch - CRADLE_RUN_UP;
+                ScenarioView {
+                    camera: frame(Vec3::X * (first + last) / 2.0, (last - first) / 2.0, Vec3::new(0.0, -0.2, -1.0)),
+                    time_scale: Some(0.5),
+                }
+            }
+            // A config brings its own time scale, and only the bodies know how big it is
+            Scenario::Config => ScenarioView { camera: default_camera_transform(up_axis), time_scale: None },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scenario::SolarSystem => "SolarSystem",
+            Scenario::Stress { .. } => "Stress",
+            Scenario::Config => "Config",
+            Scenario::AccretionDisk { .. } => "AccretionDisk",
+            Scenario::Cradle { .. } => "Cradle",
+        }
+    }
+}
+
+/// A scenario's recommended starting view, see Scenario::recommended_view
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScenarioView {
+    pub camera: Transform,
+    /// `None` keeps the configured time scale
+    pub time_scale: Option<f32>,
+}
+
+/// Move the camera and set the time scale to the scenario's recommended view whenever a
+/// scenario is chosen, including the one the app starts with
+pub fn apply_scenario_view(
+    scenario: Res<Scenario>,
+    mut config: ResMut<PhysicsConfig>,
+    mut cameras: Query<&mut Transform, With<CameraController>>,
+) {
+    let view = scenario.recommended_view(config.up_axis);
+    if let Some(time_scale) = view.time_scale {
+        config.time_scale = time_scale;
+    }
+    for mut transform in cameras.iter_mut() {
+        *transform = view.camera;
+    }
+}
+
+/// Authoring data for one body in a scene config
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct BodyConfig {
+    pub name: String,
+    pub mass: f32,
+    pub radius: f32,
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+    /// Linear RGB
+    pub color: [f32; 3],
+    /// Glows and is left alone by the lighting corrections
+    #[serde(default)]
+    pub star: bool,
+    #[serde(default = "default_trail")]
+    pub trail: bool,
+    /// Overrides `color` for the trail
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trail_color: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "BodyMesh::is_sphere")]
+    pub mesh: BodyMesh,
+    /// Spin in radians per second about this axis
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub angular_velocity: [f32; 3],
+    /// Cross-section-to-mass ratio for radiation pressure, see RadiationSusceptible
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radiation: Option<f32>,
+    /// Integrator for this body instead of the physics config's, see IntegratorOverride
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrator: Option<Integrator>,
+    /// Whether the body's mesh casts shadows, see CastsShadow
+    #[serde(default = "default_casts_shadow", skip_serializing_if = "is_true")]
+    pub casts_shadow: bool,
+    /// Linear RGBA halo color and how far it reaches past the surface, see Atmosphere
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atmosphere: Option<([f32; 4], f32)>,
+}
+
+pub fn is_zero(vector: &[f32; 3]) -> bool {
+    *vector == [0.0; 3]
+}
+
+pub fn default_casts_shadow() -> bool {
+    true
+}
+
+pub fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// What a body looks like
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub enum BodyMesh {
+    /// Icosphere at the configured mesh quality
+    #[default]
+    Sphere,
+    /// A plain 20-faced icosahedron, for rough low-poly bodies
+    Icosahedron,
+    /// A cube with its corners on the body's radius
+    Cube,
+    /// A mesh loaded through the AssetServer, such as `"rock.glb#Mesh0/Primitive0"`,
+    /// scaled by the body's radius
+    Asset(String),
+}
+
+impl BodyMesh {
+    pub fn is_sphere(&self) -> bool {
+        *self == BodyMesh::Sphere
+    }
+}
+
+/// Mesh for a body of the given radius, along with the scale its transform needs
+pub fn body_mesh(
+    source: &BodyMesh,
+    radius: f32,
+    quality: &MeshQuality,
+    meshes: &mut Assets<Mesh>,
+    asset_server: &AssetServer,
+) -> (Handle<Mesh>, f32) {
+    match source {
+        BodyMesh::Sphere => (meshes.add(sphere_mesh(radius, quality)), 1.0),
+        BodyMesh::Icosahedron => {
+            let mesh = Sphere::new(radius)
+                .mesh()
+                .ico(0)
+                .expect("an unsubdivided icosphere is always valid");
+            (meshes.add(mesh), 1.0)
+        }
+        BodyMesh::Cube => (meshes.add(Cuboid::from_size(Vec3::splat(2.0 * radius / 3f32.sqrt()))), 1.0),
+        BodyMesh::Asset(path) => (asset_server.load(path.clone()), radius),
+    }
+}
+
+pub fn default_trail() -> bool {
+    true
+}
+
+impl BodyConfig {
+    /// A white sphere with a trail, and the config defaults for everything else
+    pub fn new(name: &str, mass: f32, radius: f32, position: [f32; 3], velocity: [f32; 3]) -> Self {
+        Self {
+            name: name.to_string(),
+            mass,
+            radius,
+            position,
+            velocity,
+            color: [1.0; 3],
+            star: false,
+            trail: default_trail(),
+            trail_color: None,
+            mesh: BodyMesh::Sphere,
+            angular_velocity: [0.0; 3],
+            radiation: None,
+            integrator: None,
+            casts_shadow: default_casts_shadow(),
+            atmosphere: None,
+        }
+    }
+
+    pub fn spec(&self) -> BodySpec {
+        let [r, g, b] = self.color;
+        BodySpec {
+            name: self.name.clone(),
+            mass: self.mass,
+            radius: self.radius,
+            position: Vec3::from_array(self.position),
+            velocity: Vec3::from_array(self.velocity),
+            color: Color::rgb_linear(r, g, b),
+            atmosphere: self.atmosphere.map(|([r, g, b, a], thickness)| (Color::rgba_linear(r, g, b, a), thickness)),
+        }
+    }
+}
+
+/// A whole system as stored in a RON scene config: the physics settings and every body
+#[derive(Serialize, Deserialize)]
+pub struct SceneConfig {
+    #[serde(default)]
+    pub physics: PhysicsConfig,
+    pub bodies: Vec<BodyConfig>,
+    /// Extra palettes on top of the built-in ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub palettes: Vec<Palette>,
+    /// Name of the palette to color bodies from, instead of their own colors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+    /// Multiplies every body's position when the scene is loaded, see scale_distances
+    #[serde(default = "default_distance_scale", skip_serializing_if = "is_one")]
+    pub distance_scale: f32,
+    /// NASA Horizons state vectors to add bodies from, relative to the config, see parse_horizons
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ephemeris: Option<String>,
+    /// Open paused with the setup preview showing, as `--paused` does
+    #[serde(default)]
+    pub start_paused: bool,
+}
+
+pub fn default_distance_scale() -> f32 {
+    1.0
+}
+
+pub fn is_one(value: &f32) -> bool {
+    *value == 1.0
+}
+
+/// Spread the bodies `scale` times further apart, with velocities divided by the square root of
+/// `scale` so every orbit keeps its shape. Gravity looks the same under that change apart from
+/// time running `scale`^1.5 times slower, so each period stretches by that much, as Kepler's
+/// third law says it should for the wider orbit.
+pub fn scale_distances(bodies: &mut [BodyConfig], scale: f32) {
+    for body in bodies {
+        body.position = (Vec3::from_array(body.position) * scale).to_array();
+        body.velocity = (Vec3::from_array(body.velocity) / scale.sqrt()).to_array();
+    }
+}
+
+impl SceneConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+        let mut scene: Self = ron::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))?;
+        if let Some(file) = scene.ephemeris.take() {
+            let ephemeris = path.parent().unwrap_or(Path::new("")).join(file);
+            let text = std::fs::read_to_string(&ephemeris)
+                .map_err(|err| format!("could not read {}: {err}", ephemeris.display()))?;
+            let bodies = parse_horizons(&text, &scene.physics).map_err(|err| format!("{}: {err}", ephemeris.display()))?;
+            scene.bodies.extend(bodies);
+        }
+        scene.validate().map_err(|err| format!("{}: {err}", path.display()))?;
+        // Applied once here, so a scene exported afterwards already holds the scaled distances
+        scale_distances(&mut scene.bodies, scene.distance_scale);
+        scene.distance_scale = 1.0;
+        Ok(scene)
+    }
+
+    /// Every body needs a mass the physics can divide by, and a positive one unless exotic matter is on
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(radius) = self.physics.boundary.radius.filter(|radius| radius.is_nan() || *radius <= 0.0) {
+            return Err(format!("the boundary radius has to be positive, not {radius}"));
+        }
+        if self.distance_scale.is_nan() || self.distance_scale <= 0.0 || self.distance_scale.is_infinite() {
+            return Err(format!("the distance scale has to be positive, not {}", self.distance_scale));
+        }
+        for body in &self.bodies {
+            if body.mass == 0.0 || !body.mass.is_finite() {
+                return Err(format!("{} has mass {}, which can't be simulated", body.name, body.mass));
+            }
+            if body.mass < 0.0 && !self.physics.exotic_matter {
+                return Err(format!(
+                    "{} has negative mass; set `exotic_matter: true` in the physics settings to allow it",
+                    body.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|err| err.to_string())
+    }
+}
+
+// Kilometres in an astronomical unit, the simulation's unit of length for Horizons data
+pub const AU_KM: f64 = 149_597_870.7;
+
+// The Sun's GM in km^3/s^2, which Horizons masses are taken relative to
+pub const SUN_GM_KM3_S2: f64 = 1.327_124_400_41e11;
+
+// Simulated mass the Sun gets, the same as the built-in system's star
+pub const HORIZONS_SUN_MASS: f32 = 1000.0;
+
+// The Sun's radius in km, and the radius it is drawn with; other radii scale by the cube root
+pub const SUN_RADIUS_KM: f64 = 695_700.0;
+pub const HORIZONS_SUN_RADIUS: f32 = 3.0;
+
+// Mass and drawn radius for bodies (such as spacecraft) whose output has no GM or radius
+pub const HORIZONS_MIN_MASS: f32 = 1e-6;
+pub const HORIZONS_MIN_RADIUS: f32 = 0.2;
+
+/// Bodies from NASA JPL Horizons state vector output: one or more vector tables (EPHEM_TYPE
+/// VECTORS, in the default text layout rather than CSV, with output units KM-S), simply
+/// pasted one after another. Each body is named from its `Target body name:` line and placed
+/// at the first state between its `$$SOE` and `$$EOE` markers, from the `X = Y = Z =` and
+/// `VX= VY= VZ=` fields. All of them should share one center, ideally the solar system
+/// barycenter (`@0`) so the Sun moves too.
+///
+/// Positions are converted from km to AU and velocities from km/s to AU per simulated second,
+/// with the simulated second chosen so that the Sun, given HORIZONS_SUN_MASS, pulls as hard
+/// as it really does under `physics`' gravitational constant. Masses come from the `GM` in
+/// each body's physical data header, relative to the Sun's, and drawn radii from its mean
+/// radius compressed by the cube root so planets stay visible.
+pub fn parse_horizons(text: &str, physics: &PhysicsConfig) -> Result<Vec<BodyConfig>, String> {
+    // Real seconds per simulated second
+    let seconds = (f64::from(physics.gravitational_parameter(HORIZONS_SUN_MASS, 0.0)) * AU_KM.powi(3) / SUN_GM_KM3_S2).sqrt();
+    let mut bodies = Vec::new();
+    let mut rest = text;
+    while let Some(end) = rest.find("$$EOE") {
+        let (section, after) = rest.split_at(end);
+        rest = &after["$$EOE".len()..];
+        let name = section
+            .rsplit_once("Target body name:")
+            .map(|(_, line)| line.lines().next().unwrap_or_default())
+            .map(|line| line.split_once(" (").map_or(line, |(name, _)| name).split('{').next().unwrap_or_default().trim().to_string())
+            .filter(|name| !name.is_empty())
+            .ok_or("a vector table has no `Target body name:` line")?;
+        if let Some((_, units)) = section.split_once("Output units") {
+            let units = units.lines().next().unwrap_or_default();
+            if !units.contains("KM-S") {
+                return Err(format!("{name} is in units `{}`, not KM-S", units.trim_start_matches([' ', ':']).trim()));
+            }
+        }
+        let (_, states) = section.split_once("$$SOE").ok_or_else(|| format!("{name} has `$$EOE` but no `$$SOE`"))?;
+        let spaced = states.replace('=', " = ");
+        let tokens: Vec<&str> = spaced.split_whitespace().collect();
+        let field = |key: &str| {
+            tokens
+                .windows(3)
+                .find(|window| window[0] == key && window[1] == "=")
+                .and_then(|window| window[2].parse::<f64>().ok())
+                .ok_or_else(|| format!("{name} has no `{key}` in its first state"))
+        };
+        let [x, y, z, vx, vy, vz] = [field("X")?, field("Y")?, field("Z")?, field("VX")?, field("VY")?, field("VZ")?];
+        let gm = header_value(section, "gm", "km^3/s^2");
+        let radius_km = header_value(section, "radius", "km");
+        let mass = gm.map_or(HORIZONS_MIN_MASS, |gm| (gm / SUN_GM_KM3_S2) as f32 * HORIZONS_SUN_MASS);
+        let radius = radius_km.map_or(HORIZONS_MIN_RADIUS, |radius| {
+            (HORIZONS_SUN_RADIUS * ((radius / SUN_RADIUS_KM) as f32).cbrt()).max(HORIZONS_MIN_RADIUS)
+        });
+        let star = name == "Sun";
+        let color = if star { Color::YELLOW } else { Color::hsl(name_hue(&name), 0.6, 0.6) };
+        let [r, g, b, _] = color.as_linear_rgba_f32();
+        bodies.push(BodyConfig {
+            color: [r, g, b],
+            star,
+            ..BodyConfig::new(
+                &name,
+                mass.max(HORIZONS_MIN_MASS),
+                radius,
+                [x, y, z].map(|km| (km / AU_KM) as f32),
+                [vx, vy, vz].map(|speed| (speed * seconds / AU_KM) as f32),
+            )
+        });
+    }
+    if bodies.is_empty() {
+        return Err("no Horizons vector tables (`$$SOE` ... `$$EOE`) found".to_string());
+    }
+    Ok(bodies)
+}
+
+/// A value from a Horizons physical data header such as `Vol. Mean Radius (km) = 6371.01+-0.02`:
+/// the number after the first `=` following `label` (matched in any case) with `unit` in between.
+/// Uncertainties (`1-sigma`) are skipped.
+fn header_value(section: &str, label: &str, unit: &str) -> Option<f64> {
+    section.lines().find_map(|line| {
+        let lower = line.to_lowercase();
+        lower.match_indices(label).find_map(|(start, _)| {
+            let equals = start + lower[start..].find('=')?;
+            let between = &lower[start..equals];
+            if !between.contains(unit) || between.contains("sigma") {
+                return None;
+            }
+            leading_number(&line[equals + 1..])
+        })
+    })
+}
+
+/// The longest number `text` starts with, ignoring leading spaces, so `6371.01+-0.02` is 6371.01
+fn leading_number(text: &str) -> Option<f64> {
+    let text = text.trim_start();
+    text.char_indices().map(|(index, character)| index + character.len_utf8()).rev().find_map(|end| text[..end].parse().ok())
+}
+
+/// Bodies waiting to be spawned, from the scene config given with `--config` or a loaded save slot
+#[derive(Resource)]
+pub struct LoadedScene(pub Vec<BodyConfig>);
+
+pub const SCENE_EXPORT_FILE: &str = "scene.ron";
+
+// Where F7 writes the top-down orbit diagram
+pub const ORBIT_DIAGRAM_FILE: &str = "orbits.svg";
+
+pub const SAVE_DIR: &str = "saves";
+
+pub const SAVE_SLOTS: usize = 5;
+
+pub fn slot_path(dir: &Path, slot: usize) -> PathBuf {
+    dir.join(format!("slot{slot}.ron"))
+}
+
+pub fn save_slot(dir: &Path, slot: usize, scene: &SceneConfig) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|err| format!("could not create {}: {err}", dir.display()))?;
+    let path = slot_path(dir, slot);
+    std::fs::write(&path, scene.to_ron()?).map_err(|err| format!("could not write {}: {err}", path.display()))
+}
+
+pub fn load_slot(dir: &Path, slot: usize) -> Result<SceneConfig, String> {
+    SceneConfig::load(&slot_path(dir, slot))
+}
+
+/// What the save menu shows for a filled slot
+pub struct SlotSummary {
+    pub saved: std::time::SystemTime,
+    pub body_count: usize,
+}
+
+/// Summary of each slot, `None` where it is empty or unreadable
+pub fn slot_summaries(dir: &Path) -> Vec<Option<SlotSummary>> {
+    (1..=SAVE_SLOTS)
+        .map(|slot| {
+            let saved = std::fs::metadata(slot_path(dir, slot)).and_then(|meta| meta.modified()).ok()?;
+            let scene = load_slot(dir, slot).ok()?;
+            Some(SlotSummary {
+                saved,
+                body_count: scene.bodies.len(),
+            })
+        })
+        .collect()
+}
+
+/// Something the user picked in the save menu
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlotAction {
+    Save(usize),
+    Load(usize),
+}
+
+/// Numbered save slots, opened with F5. The simulation is paused while it is open.
+#[derive(Resource)]
+pub struct SaveMenu {
+    /// Folder the slots are kept in
+    pub dir: PathBuf,
+    pub open: bool,
+    /// Whether the simulation was already paused when the menu opened, so closing leaves it that way
+    pub was_paused: bool,
+    pub slots: Vec<Option<SlotSummary>>,
+    /// Waiting to be carried out by save_slot_system or load_slot_system
+    pub pending: Option<SlotAction>,
+}
+
+impl Default for SaveMenu {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(SAVE_DIR),
+            open: false,
+            was_paused: false,
+            slots: Vec::new(),
+            pending: None,
+        }
+    }
+}
+
+impl SaveMenu {
+    pub fn set_open(&mut self, open: bool, paused: &mut Paused) {
+        if open == self.open {
+            return;
+        }
+        self.open = open;
+        if open {
+            self.was_paused = paused.0;
+            paused.0 = true;
+            self.slots = slot_summaries(&self.dir);
+        } else {
+            paused.0 = self.was_paused;
+        }
+    }
+}
+
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Small seeded random number generator (SplitMix64) so random scenes are reproducible
+#[derive(Resource)]
+pub struct SimRng {
+    pub state: u64,
+    /// What it was seeded with, shown so a good random layout can be reproduced with `--seed`
+    pub seed: u64,
+}
+
+impl Default for SimRng {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed, seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Uniformly distributed point inside a sphere
+    pub fn in_sphere(&mut self, radius: f32) -> Vec3 {
+        loop {
+            let point = Vec3::new(
+                self.range(-1.0, 1.0),
+                self.range(-1.0, 1.0),
+                self.range(-1.0, 1.0),
+            );
+            if point.length_squared() <= 1.0 {
+                return point * radius;
+            }
+        }
+    }
+}
+
+/// Setup scene with very conservative orbital velocities
+pub fn setup_scene(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    (config, clip): (Res<PhysicsConfig>, Res<CameraClip>),
+    lighting: Res<LightingConfig>,
+) {
+    // Camera positioned to see the whole system
+    commands.spawn((
+        Camera3dBundle {
+            transform: default_camera_transform(config.up_axis),
+            projection: clip.perspective().into(),
+            ..default()
+        },
+        CameraController {
+            sensitivity: 2.0,
+            speed: 25.0,
+            adaptive_speed: true,
+            min_speed: 2.0,
+            max_speed: 500.0,
+            follow_stiffness: 20.0,
+            follow_damping: 9.0,
+            follow_lead: 1.0,
+        },
+    ));
+
+    // Orbital plane of the selected body, hidden until something is selected
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Circle::new(1.0)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(0.4, 0.7, 1.0, 0.15),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                double_sided: true,
+                cull_mode: None,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        OrbitalPlaneDisk,
+    ));
+
+    // Strong point light at the sun
+    commands.spawn(PointLightBundle {
+        point_light: PointLight {
+            intensity: lighting.intensity,
+            shadows_enabled: true,
+            range: 200.0,
+            ..default()
+        },
+        transform: Transform::from_xyz(0.0, 5.0, 0.0),
+        ..default()
+    });
+
+    // Ambient light for visibility
+    commands.insert_resource(AmbientLight {
+        color: Color::WHITE,
+        brightness: 0.4,
+    });
+}
+
+/// Spawn the bodies of the built-in scenario, drawing any randomness from SimRng
+pub fn spawn_scenario(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut rng: ResMut<SimRng>,
+    scenario: Res<Scenario>,
+    quality: Res<MeshQuality>,
+    config: Res<PhysicsConfig>,
+) {
+    match *scenario {
+        Scenario::SolarSystem => {
+            spawn_default_system(&mut commands, &mut meshes, &mut materials, &quality)
+        }
+        Scenario::Stress { count } => spawn_stress_system(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &quality,
+            &mut rng,
+            count,
+        ),
+        Scenario::AccretionDisk { count, shape } => spawn_disk_system(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &quality,
+            disk_bodies(&mut rng, &shape, count, &config),
+        ),
+        Scenario::Cradle { count } => spawn_cradle_system(&mut commands, &mut meshes, &mut materials, &quality, count),
+        // Spawned by spawn_loaded_scene
+        Scenario::Config => {}
+    }
+}
+
+/// Asks for the built-in scenario to be respawned from this seed, or a fresh random one
+#[derive(Event)]
+pub struct RerollScenario {
+    pub seed: Option<u64>,
+}
+
+/// Alt+R rerolls a random scenario with a new seed
+pub fn reroll_input(key_input: Res<ButtonInput<KeyCode>>, scenario: Res<Scenario>, mut reroll: EventWriter<RerollScenario>) {
+    if key_input.pressed(KeyCode::AltLeft) && key_input.just_pressed(KeyCode::KeyR) && scenario.is_random() {
+        reroll.send(RerollScenario { seed: None });
+    }
+}
+
+/// Clear out every body and reseed SimRng for the scenario spawner that runs next. A fresh seed
+/// is drawn from the generator itself, so a run of rerolls is reproducible too.
+pub fn reroll_scenario(
+    mut commands: Commands,
+    mut reroll: EventReader<RerollScenario>,
+    mut rng: ResMut<SimRng>,
+    bodies: Query<Entity, With<CelestialBody>>,
+) {
+    let Some(seed) = reroll.read().last().map(|reroll| reroll.seed) else {
+        return;
+    };
+    let seed = seed.unwrap_or_else(|| rng.next_u64());
+    for entity in bodies.iter() {
+        // Recursive so atmosphere shells go too
+        commands.entity(entity).despawn_recursive();
+    }
+    *rng = SimRng::new(seed);
+    info!("Respawning with seed {seed} (launch with `--seed {seed}` to get this layout again)");
+}
+
+/// Sent to empty the scene, see clear_scene_system
+#[derive(Event)]
+pub struct ClearScene;
+
+/// Ctrl+Delete clears the scene. Delete alone does nothing, there is no undo
+pub fn clear_scene_input(key_input: Res<ButtonInput<KeyCode>>, mut clear: EventWriter<ClearScene>) {
+    if key_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) && key_input.just_pressed(KeyCode::Delete) {
+        clear.send(ClearScene);
+    }
+}
+
+/// Despawn every body and everything that goes with one (atmosphere shells and softening cores
+/// as children, fading trails, Kepler particles) and forget what was measured on them: the
+/// calendar restarts at zero and the angular momentum, collision log, trail frame and analysis
+/// panels start over. Unlike a reroll nothing is spawned again, leaving an empty system to
+/// build a new one in with the spawn panels; Alt+R still brings back a random scenario.
+#[allow(clippy::type_complexity)]
+pub fn clear_scene_system(
+    mut commands: Commands,
+    mut clear: EventReader<ClearScene>,
+    entities: Query<Entity, Or<(With<CelestialBody>, With<FadingTrail>, With<KeplerParticle>)>>,
+    cameras: Query<Entity, With<FollowTarget>>,
+    (mut calendar, mut monitor, mut history): (ResMut<Calendar>, ResMut<AngularMomentumMonitor>, ResMut<CollisionHistory>),
+    (mut frame, mut frame_history, mut binaries): (ResMut<TrailFrame>, ResMut<TrailFrameHistory>, ResMut<AnalyticBinaries>),
+    (mut drift, mut pairs, mut fan, mut preview): (ResMut<ApsidalDrift>, ResMut<BoundPairLines>, ResMut<TrajectoryFan>, ResMut<SetupPreview>),
+) {
+    if clear.read().count() == 0 {
+        return;
+    }
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for camera in cameras.iter() {
+        commands.entity(camera).remove::<FollowTarget>();
+    }
+    commands.remove_resource::<LoadedScene>();
+    *calendar = Calendar { locked: calendar.locked, days_per_second: calendar.days_per_second, ..default() };
+    *monitor = AngularMomentumMonitor::default();
+    *history = CollisionHistory::default();
+    *frame = TrailFrame::Inertial;
+    frame_history.0.clear();
+    binaries.pairs.clear();
+    drift.body = None;
+    drift.tracker = default();
+    pairs.pairs.clear();
+    fan.body = None;
+    fan.paths.clear();
+    preview.paths.clear();
+    info!("Cleared the scene");
+}
+
+/// The Sun and its three planets
+pub fn spawn_default_system(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    quality: &MeshQuality,
+) {
+    for (index, spec) in solar_system_specs().iter().enumerate() {
+        // The Sun comes first and glows
+        let star = index == 0;
+        let mut body = commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(sphere_mesh(spec.radius, quality)),
+                material: materials.add(StandardMaterial {
+                    base_color: spec.color,
+                    emissive: if star { spec.color * 2.0 } else { Color::BLACK },
+                    ..default()
+                }),
+                transform: Transform::from_translation(spec.position),
+                ..default()
+            },
+            BodyBundle::from_spec(spec),
+            Trail::new(spec.color),
+        ));
+        if star {
+            body.insert(Star);
+        }
+        if let Some((color, thickness)) = spec.atmosphere {
+            body.insert(Atmosphere { color, thickness });
+        }
+    }
+}
+
+/// The Sun and its three planets, Sun first
+pub fn solar_system_specs() -> Vec<BodySpec> {
+    let body = |name: &str, mass, radius, distance, speed, color| BodySpec {
+        name: name.to_string(),
+        mass,
+        radius,
+        position: Vec3::new(distance, 0.0, 0.0),
+        velocity: Vec3::new(0.0, 0.0, speed),
+        color,
+        atmosphere: None,
+    };
+    vec![
+        // Sun - stationary and massive
+        body("Sun", 1000.0, 3.0, 0.0, 0.0, Color::YELLOW),
+        // Inner planet - very slow orbit
+        body("Inner Planet", 5.0, 0.8, 12.0, 0.8, Color::rgb(0.8, 0.4, 0.2)),
+        // Middle planet - even slower, with a thin blue atmosphere
+        BodySpec {
+            atmosphere: Some((Color::rgba(0.3, 0.6, 1.0, 0.35), 0.25)),
+            ..body("Middle Planet", 8.0, 1.0, 20.0, 0.6, Color::rgb(0.2, 0.4, 0.8))
+        },
+        // Outer planet - slowest
+        body("Outer Planet", 6.0, 0.9, 30.0, 0.4, Color::rgb(0.8, 0.3, 0.1)),
+    ]
+}
+
+// Random bodies for stress tests are spawned inside a sphere of this radius
+pub const STRESS_RADIUS: f32 = 40.0;
+
+/// Authoring data for a body, independent of how (or whether) it is rendered
+pub struct BodySpec {
+    pub name: String,
+    pub mass: f32,
+    pub radius: f32,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub color: Color,
+    /// Halo color and how far it reaches past the surface, see Atmosphere
+    pub atmosphere: Option<(Color, f32)>,
+}
+
+/// Turn specs laid out Y-up, as the built-in scenarios are, to the configured up axis
+pub fn orient_specs(specs: &mut [BodySpec], up_axis: UpAxis) {
+    let rotation = up_axis.rotation_from_y_up();
+    for spec in specs {
+        spec.position = rotation * spec.position;
+        spec.velocity = rotation * spec.velocity;
+    }
+}
+
+/// orient_specs for the bodies spawn_scenario spawned. Scene configs are left as they were authored,
+/// so Z-up data loads as it is.
+pub fn orient_scenario(
+    scenario: Res<Scenario>,
+    config: Res<PhysicsConfig>,
+    mut bodies: Query<(&mut Transform, &mut Velocity), With<CelestialBody>>,
+) {
+    if *scenario == Scenario::Config || config.up_axis == UpAxis::Y {
+        return;
+    }
+    let rotation = config.up_axis.rotation_from_y_up();
+    for (mut transform, mut velocity) in bodies.iter_mut() {
+        transform.translation = rotation * transform.translation;
+        velocity.0 = rotation * velocity.0;
+    }
+}
+
+/// Spawn the bodies waiting in LoadedScene
+pub fn spawn_loaded_scene(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    quality: Res<MeshQuality>,
+    asset_server: Res<AssetServer>,
+    scene: Res<LoadedScene>,
+) {
+    for body in &scene.0 {
+        let spec = body.spec();
+        let material = if body.star {
+            StandardMaterial {
+                base_color: spec.color,
+                emissive: spec.color * 2.0,
+                ..default()
+            }
+        } else {
+            StandardMaterial {
+                base_color: spec.color,
+                ..default()
+            }
+        };
+        let (mesh, scale) = body_mesh(&body.mesh, spec.radius, &quality, &mut meshes, &asset_server);
+        let mut entity = commands.spawn((
+            PbrBundle {
+                mesh,
+                material: materials.add(material),
+                transform: Transform::from_translation(spec.position).with_scale(Vec3::splat(scale)),
+                ..default()
+            },
+            BodyBundle::from_spec(&spec),
+            Trail::new(spec.color),
+        ));
+        insert_physics_components(&mut entity, body);
+        if !body.trail {
+            entity.insert(TrailEnabled(false));
+        }
+        if let Some([r, g, b]) = body.trail_color {
+            entity.insert(TrailColor(Color::rgb_linear(r, g, b)));
+        }
+        if !body.mesh.is_sphere() {
+            entity.insert(CustomMesh(body.mesh.clone()));
+        }
+        if !body.casts_shadow {
+            entity.insert(CastsShadow(false));
+        }
+        if let Some((color, thickness)) = spec.atmosphere {
+            entity.insert(Atmosphere { color, thickness });
+        }
+    }
+    commands.remove_resource::<LoadedScene>();
+}
+
+/// The optional components of a config body that the physics reads
+pub fn insert_physics_components(entity: &mut EntityCommands, body: &BodyConfig) {
+    if body.star {
+        entity.insert(Star);
+    }
+    if !is_zero(&body.angular_velocity) {
+        entity.insert(Rotation(Vec3::from_array(body.angular_velocity)));
+    }
+    if let Some(ratio) = body.radiation {
+        entity.insert(RadiationSusceptible(ratio));
+    }
+    if let Some(integrator) = body.integrator {
+        entity.insert(IntegratorOverride(integrator));
+    }
+}
+
+/// Spawn the built-in scenario's bodies without meshes or trails, for apps that run the plugin
+/// without its camera
+pub fn spawn_headless_scenario(
+    mut commands: Commands,
+    mut rng: ResMut<SimRng>,
+    scenario: Res<Scenario>,
+    config: Res<PhysicsConfig>,
+) {
+    let (mut specs, star) = match *scenario {
+        Scenario::SolarSystem => (solar_system_specs(), Some(0)),
+        Scenario::Stress { count } => (stress_bodies(&mut rng, count), None),
+        Scenario::AccretionDisk { count, shape } => {
+            // The star comes last
+            let specs = disk_bodies(&mut rng, &shape, count, &config);
+            let star = specs.len().checked_sub(1);
+            (specs, star)
+        }
+        Scenario::Cradle { count } => (cradle_specs(count), None),
+        // Spawned by spawn_headless_scene
+        Scenario::Config => return,
+    };
+    orient_specs(&mut specs, config.up_axis);
+    for (index, spec) in specs.iter().enumerate() {
+        let mut body = commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(spec.position)),
+            BodyBundle::from_spec(spec),
+        ));
+        if star == Some(index) {
+            body.insert(Star);
+        }
+        if let Some(material) = scenario.surface_material() {
+            body.insert(material);
+        }
+    }
+}
+
+/// Spawn the bodies waiting in LoadedScene without meshes or trails
+pub fn spawn_headless_scene(mut commands: Commands, scene: Res<LoadedScene>) {
+    for body in &scene.0 {
+        let spec = body.spec();
+        let mut entity = commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(spec.position)),
+            BodyBundle::from_spec(&spec),
+        ));
+        insert_physics_components(&mut entity, body);
+    }
+    commands.remove_resource::<LoadedScene>();
+}
+
+/// Everything a scene config records about a live body
+pub type SceneBodyData = (
+    &'static Name,
+    &'static Mass,
+    &'static Radius,
+    &'static Transform,
+    &'static Velocity,
+    &'static Handle<StandardMaterial>,
+    Has<Star>,
+    Option<&'static TrailEnabled>,
+    Option<&'static TrailColor>,
+    Option<&'static CustomMesh>,
+    Option<&'static Rotation>,
+    Option<&'static RadiationSusceptible>,
+    Option<&'static IntegratorOverride>,
+    Option<&'static CastsShadow>,
+    Option<&'static Atmosphere>,
+);
+
+/// The live system, including any interactive edits, as a scene config
+pub fn scene_snapshot(
+    config: &PhysicsConfig,
+    materials: &Assets<StandardMaterial>,
+    bodies: &Query<SceneBodyData, With<CelestialBody>>,
+) -> SceneConfig {
+    let linear = |color: Color| {
+        let [r, g, b, _] = color.as_linear_rgba_f32();
+        [r, g, b]
+    };
+    SceneConfig {
+        physics: config.clone(),
+        bodies: bodies
+            .iter()
+            .map(|(name, mass, radius, transform, velocity, material, star, trail, trail_color, mesh, rotation, radiation, integrator, shadow, atmosphere)| BodyConfig {
+                name: name.0.clone(),
+                mass: mass.0,
+                radius: radius.0,
+                position: transform.translation.to_array(),
+                velocity: velocity.0.to_array(),
+                color: linear(materials.get(material).map_or(Color::WHITE, |material| material.base_color)),
+                star,
+                trail: trail.is_none_or(|trail| trail.0),
+                trail_color: trail_color.map(|color| linear(color.0)),
+                mesh: mesh.map_or(BodyMesh::Sphere, |mesh| mesh.0.clone()),
+                angular_velocity: rotation.map_or([0.0; 3], |rotation| rotation.0.to_array()),
+                radiation: radiation.map(|radiation| radiation.0),
+                integrator: integrator.map(|integrator| integrator.0),
+                casts_shadow: shadow.is_none_or(|shadow| shadow.0),
+                atmosphere: atmosphere.map(|atmosphere| (atmosphere.color.as_linear_rgba_f32(), atmosphere.thickness)),
+            })
+            .collect(),
+        // Bodies are saved with whatever palette colors they are showing
+        palettes: Vec::new(),
+        palette: None,
+        distance_scale: 1.0,
+        ephemeris: None,
+        start_paused: false,
+    }
+}
+
+/// F6 writes the live system, including any interactive edits, to a scene config
+pub fn export_scene_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    config: Res<PhysicsConfig>,
+    materials: Res<Assets<StandardMaterial>>,
+    bodies: Query<SceneBodyData, With<CelestialBody>>,
+) {
+    if !key_input.just_pressed(KeyCode::F6) {
+        return;
+    }
+    let scene = scene_snapshot(&config, &materials, &bodies);
+    match scene.to_ron().and_then(|text| std::fs::write(SCENE_EXPORT_FILE, text).map_err(|err| err.to_string())) {
+        Ok(()) => info!("Exported {} bodies to {SCENE_EXPORT_FILE}", scene.bodies.len()),
+        Err(err) => warn!("Could not export {SCENE_EXPORT_FILE}: {err}"),
+    }
+}
+
+/// One body in the orbit diagram
+pub struct DiagramBody {
+    pub name: String,
+    pub position: Vec3,
+    pub radius: f32,
+    pub color: Color,
+    pub trail: Vec<Vec3>,
+}
+
+// Pixel size of the diagram, and the share of it left as a border
+pub const DIAGRAM_SIZE: f32 = 800.0;
+
+pub const DIAGRAM_MARGIN: f32 = 0.05;
+
+/// A top-down SVG of the bodies and their trails. The viewBox is in world units (x across,
+/// z down), fitted around everything drawn, so coordinates in the file are world XZ coordinates.
+pub fn orbit_svg(bodies: &[DiagramBody]) -> String {
+    let hex = |color: Color| {
+        let [r, g, b, _] = color.as_rgba_u8();
+        format!("#{r:02x}{g:02x}{b:02x}")
+    };
+    let (mut min, mut max) = (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY));
+    for body in bodies {
+        let position = body.position.xz();
+        min = min.min(position - body.radius);
+        max = max.max(position + body.radius);
+        for point in &body.trail {
+            min = min.min(point.xz());
+            max = max.max(point.xz());
+        }
+    }
+    if bodies.is_empty() {
+        (min, max) = (Vec2::splat(-1.0), Vec2::splat(1.0));
+    }
+    // Square, so the picture isn't stretched
+    let extent = (max - min).max_element().max(1.0) * (1.0 + 2.0 * DIAGRAM_MARGIN);
+    let corner = (min + max) / 2.0 - extent / 2.0;
+    let font_size = extent / 50.0;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{DIAGRAM_SIZE}\" height=\"{DIAGRAM_SIZE}\" viewBox=\"{} {} {extent} {extent}\">\n",
+        corner.x, corner.y
+    );
+    svg += &format!("  <rect x=\"{}\" y=\"{}\" width=\"{extent}\" height=\"{extent}\" fill=\"black\"/>\n", corner.x, corner.y);
+    for body in bodies.iter().filter(|body| body.trail.len() > 1) {
+        let points: Vec<String> = body.trail.iter().map(|point| format!("{},{}", point.x, point.z)).collect();
+        svg += &format!(
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"/>\n",
+            points.join(" "),
+            hex(body.color)
+        );
+    }
+    for body in bodies {
+        let name = body.name.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        svg += &format!(
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n",
+            body.position.x,
+            body.position.z,
+            body.radius,
+            hex(body.color)
+        );
+        svg += &format!(
+            "  <text x=\"{}\" y=\"{}\" font-size=\"{font_size}\" fill=\"white\">{name}</text>\n",
+            body.position.x + body.radius,
+            body.position.z - body.radius
+        );
+    }
+    svg + "</svg>\n"
+}
+
+/// F7 writes a top-down SVG diagram of the current orbits
+#[allow(clippy::type_complexity)]
+pub fn export_diagram_input(
+    key_input: Res<ButtonInput<KeyCode>>,
+    materials: Res<Assets<StandardMaterial>>,
+    bodies: Query<(&Name, &Transform, &Radius, &Handle<StandardMaterial>, Option<&Trail>), With<CelestialBody>>,
+) {
+    if !key_input.just_pressed(KeyCode::F7) {
+        return;
+    }
+    let diagram: Vec<DiagramBody> = bodies
+        .iter()
+        .map(|(name, transform, radius, material, trail)| DiagramBody {
+            name: name.0.clone(),
+            position: transform.translation,
+            radius: radius.0,
+            color: materials.get(material).map_or(Color::WHITE, |material| material.base_color),
+            trail: trail.map_or(Vec::new(), |trail| trail.points.iter().map(|(_, point)| *point).collect()),
+        })
+        .collect();
+    match std::fs::write(ORBIT_DIAGRAM_FILE, orbit_svg(&diagram)) {
+        Ok(()) => info!("Wrote the orbit diagram of {} bodies to {ORBIT_DIAGRAM_FILE}", diagram.len()),
+        Err(err) => warn!("Could not write {ORBIT_DIAGRAM_FILE}: {err}"),
+    }
+}
+
+/// Write the live system to the slot picked in the save menu
+pub fn save_slot_system(
+    mut menu: ResMut<SaveMenu>,
+    config: Res<PhysicsConfig>,
+    materials: Res<Assets<StandardMaterial>>,
+    bodies: Query<SceneBodyData, With<CelestialBody>>,
+) {
+    let Some(SlotAction::Save(slot)) = menu.pending else {
+        return;
+    };
+    menu.pending = None;
+    match save_slot(&menu.dir, slot, &scene_snapshot(&config, &materials, &bodies)) {
+        Ok(()) => info!("Saved {} bodies to slot {slot}", bodies.iter().len()),
+        Err(err) => warn!("Could not save slot {slot}: {err}"),
+    }
+    menu.slots = slot_summaries(&menu.dir);
+}
+
+/// Replace the live system with the slot picked in the save menu
+pub fn load_slot_system(
+    mut commands: Commands,
+    mut menu: ResMut<SaveMenu>,
+    mut config: ResMut<PhysicsConfig>,
+    bodies: Query<Entity, With<CelestialBody>>,
+) {
+    let Some(SlotAction::Load(slot)) = menu.pending else {
+        return;
+    };
+    menu.pending = None;
+    let scene = match load_slot(&menu.dir, slot) {
+        Ok(scene) => scene,
+        Err(err) => {
+            warn!("Could not load slot {slot}: {err}");
+            return;
+        }
+    };
+    for entity in bodies.iter() {
+        // Recursive so atmosphere shells go too
+        commands.entity(entity).despawn_recursive();
+    }
+    info!("Loaded {} bodies from slot {slot}", scene.bodies.len());
+    commands.insert_resource(LoadedScene(scene.bodies));
+    *config = scene.physics;
+}
+
+/// `count` randomly placed and massed bodies at rest, colored from a small random palette
+pub fn stress_bodies(rng: &mut SimRng, count: usize) -> Vec<BodySpec> {
+    let palette: Vec<Color> = (0..8)
+        .map(|_| Color::rgb(rng.range(0.3, 1.0), rng.range(0.3, 1.0), rng.range(0.3, 1.0)))
+        .collect();
+
+    (0..count)
+        .map(|i| {
+            let mass = rng.range(1.0, 20.0);
+            BodySpec {
+                name: format!("Body {i}"),
+                mass,
+                radius: 0.3 * mass.cbrt(),
+                position: rng.in_sphere(STRESS_RADIUS),
+                velocity: Vec3::ZERO,
+                color: palette[(rng.next_u64() % palette.len() as u64) as usize],
+                atmosphere: None,
+            }
+        })
+        .collect()
+}
+
+/// Random bodies left to collapse under gravity
+pub fn spawn_stress_system(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    quality: &MeshQuality,
+    rng: &mut SimRng,
+    count: usize,
+) {
+    spawn_many_bodies(commands, meshes, materials, quality, stress_bodies(rng, count));
+}
+
+/// Shape of the accretion disk scenario
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DiskConfig {
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    /// Full height of the disk; particles sit uniformly within half of it above and below
+    pub thickness: f32,
+    /// Surface density falls off as `1 / r^profile`, so 0 is an even spread and larger values
+    /// crowd the particles towards the inner edge
+    pub profile: f32,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            inner_radius: 8.0,
+            outer_radius: 40.0,
+            thickness: 1.0,
+            profile: 1.0,
+        }
+    }
+}
+
+// Central star of the accretion disk, matching the default Sun
+pub const DISK_STAR_MASS: f32 = 1000.0;
+
+pub const DISK_STAR_RADIUS: f32 = 3.0;
+
+/// Random spread of the particles' orbital speeds around circular, as a fraction
+pub const DISK_SPEED_SPREAD: f32 = 0.02;
+
+/// `count` light particles in a flat disk around a star at the origin, on near-circular orbits
+/// (the disk's own gravity is left out of the orbital speeds), followed by the star itself
+pub fn disk_bodies(rng: &mut SimRng, shape: &DiskConfig, count: usize, config: &PhysicsConfig) -> Vec<BodySpec> {
+    let mu = config.gravitational_parameter(DISK_STAR_MASS, 0.0);
+    let (inner, outer) = (shape.inner_radius.min(shape.outer_radius), shape.inner_radius.max(shape.outer_radius));
+    // Invert the cumulative particle count, which grows as r^(2 - profile) for surface density r^-profile
+    let exponent = 2.0 - shape.profile;
+    let sample_radius = |u: f32| {
+        if exponent.abs() < 1e-3 {
+            inner * (outer / inner).powf(u)
+        } else {
+            (inner.powf(exponent) + u * (outer.powf(exponent) - inner.powf(exponent))).powf(1.0 / exponent)
+        }
+    };
+
+    let mut specs: Vec<BodySpec> = (0..count)
+        .map(|i| {
+            let radius = sample_radius(rng.next_f32()).clamp(inner, outer);
+            let angle = rng.range(0.0, std::f32::consts::TAU);
+            let (sin, cos) = angle.sin_cos();
+            let height = rng.range(-0.5, 0.5) * shape.thickness;
+            let speed = (mu / radius).sqrt() * (1.0 + rng.range(-DISK_SPEED_SPREAD, DISK_SPEED_SPREAD));
+            // Warmer near the star
+            let heat = (outer - radius) / (outer - inner).max(1e-3);
+            BodySpec {
+                name: format!("Particle {i}"),
+                mass: 0.01,
+                radius: 0.1,
+                position: Vec3::new(radius * cos, height, radius * sin),
+                velocity: Vec3::new(-sin, 0.0, cos) * speed,
+                color: Color::rgb(0.6 + 0.4 * heat, 0.4 + 0.3 * heat, 0.8 - 0.5 * heat),
+                atmosphere: None,
+            }
+        })
+        .collect();
+    specs.push(BodySpec {
+        name: "Star".to_string(),
+        mass: DISK_STAR_MASS,
+        radius: DISK_STAR_RADIUS,
+        position: Vec3::ZERO,
+        velocity: Vec3::ZERO,
+        color: Color::YELLOW,
+        atmosphere: None,
+    });
+    specs
+}
+
+/// A star at the origin ringed by a disk of particles, from disk_bodies (star last)
+pub fn spawn_disk_system(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    quality: &MeshQuality,
+    mut particles: Vec<BodySpec>,
+) {
+    let Some(star) = particles.pop() else {
+        return;
+    };
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(sphere_mesh(star.radius, quality)),
+            material: materials.add(StandardMaterial {
+                base_color: star.color,
+                emissive: Color::rgb(1.0, 1.0, 0.0) * 2.0,
+                ..default()
+            }),
+            transform: Transform::from_translation(star.position),
+            ..default()
+        },
+        BodyBundle::from_spec(&star),
+        Star,
+    ));
+    // Quantized so the particles share a handful of materials
+    for particle in &mut particles {
+        let [r, g, b, _] = particle.color.as_rgba_f32();
+        let quantize = |channel: f32| (channel * 8.0).round() / 8.0;
+        particle.color = Color::rgb(quantize(r), quantize(g), quantize(b));
+    }
+    spawn_many_bodies(commands, meshes, materials, quality, particles);
+}
+
+// Radius and mass of each cradle ball
+pub const CRADLE_BALL_RADIUS: f32 = 1.0;
+pub const CRADLE_BALL_MASS: f32 = 1.0;
+
+// Gap left between neighbouring resting balls, so each collision is resolved on its own
+pub const CRADLE_GAP: f32 = 0.2;
+
+// The striker starts this far from the end of the row, moving at this speed. Fast enough that
+// the balls' pull on each other is a small nudge over the few seconds the demo takes.
+pub const CRADLE_RUN_UP: f32 = 6.0;
+pub const CRADLE_STRIKE_SPEED: f32 = 4.0;
+
+// Perfectly elastic and frictionless, so each collision hands the whole momentum on
+pub const CRADLE_MATERIAL: SurfaceMaterial = SurfaceMaterial { restitution: 1.0, friction: 0.0 };
+
+/// The striker first, heading along +X, then the resting balls of the row centered on the origin
+pub fn cradle_specs(count: usize) -> Vec<BodySpec> {
+    let pitch = 2.0 * CRADLE_BALL_RADIUS + CRADLE_GAP;
+    let first = -pitch * count.saturating_sub(1) as f32 / 2.0;
+    let ball = |name: String, x: f32, speed: f32, color: Color| BodySpec {
+        name,
+        mass: CRADLE_BALL_MASS,
+        radius: CRADLE_BALL_RADIUS,
+        position: Vec3::new(x, 0.0, 0.0),
+        velocity: Vec3::X * speed,
+        color,
+        atmosphere: None,
+    };
+    let striker = ball("Striker".to_string(), first - pitch - CRADLE_RUN_UP, CRADLE_STRIKE_SPEED, Color::rgb(1.0, 0.5, 0.2));
+    std::iter::once(striker)
+        .chain((0..count).map(|i| ball(format!("Ball {}", i + 1), first + pitch * i as f32, 0.0, Color::rgb(0.7, 0.75, 0.8))))
+        .collect()
+}
+
+/// Newton's cradle: a row of elastic balls struck end-on
+pub fn spawn_cradle_system(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    quality: &MeshQuality,
+    count: usize,
+) {
+    let mesh = meshes.add(sphere_mesh(CRADLE_BALL_RADIUS, quality));
+    for spec in cradle_specs(count) {
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: materials.add(StandardMaterial {
+                    base_color: spec.color,
+                    metallic: 0.8,
+                    perceptual_roughness: 0.3,
+                    ..default()
+                }),
+                transform: Transform::from_translation(spec.position),
+                ..default()
+            },
+            BodyBundle::from_spec(&spec),
+            Trail::new(spec.color),
+            CRADLE_MATERIAL,
+        ));
+    }
+}
+
+/// Spawn lots of trail-less bodies sharing one unit sphere and one material per color, so
+/// thousands of them stay cheap to spawn
+pub fn spawn_many_bodies(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    quality: &MeshQuality,
+    specs: Vec<BodySpec>,
+) {
+    let mesh = meshes.add(sphere_mesh(1.0, quality));
+    let mut palette: Vec<(Color, Handle<StandardMaterial>)> = Vec::new();
+
+    for spec in specs {
+        let material = match palette.iter().find(|(color, _)| *color == spec.color) {
+            Some((_, handle)) => handle.clone(),
+            None => {
+                let handle = materials.add(StandardMaterial {
+                    base_color: spec.color,
+                    ..default()
+                });
+                palette.push((spec.color, handle.clone()));
+                handle
+            }
+        };
+
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material,
+                transform: Transform::from_translation(spec.position)
+                    .with_scale(Vec3::splat(spec.radius)),
+                ..default()
+            },
+            BodyBundle::from_spec(&spec),
+            // No Trail: thousands of trails would swamp the gizmo renderer
+        ));
+    }
+}
+
+// Kepler particles start this far from their primary
+pub const KEPLER_PARTICLE_RADII: (f32, f32) = (8.0, 45.0);
+
+// Largest tilt of a Kepler particle's orbit out of the plane, in radians
+pub const KEPLER_PARTICLE_TILT: f32 = 0.05;
+
+/// Starting states relative to a primary of gravitational parameter `mu`, Y-up: a thin disk of
+/// nearly circular orbits, all bound
+pub fn kepler_particle_states(rng: &mut SimRng, count: usize, mu: f32) -> Vec<(Vec3, Vec3)> {
+    let (inner, outer) = KEPLER_PARTICLE_RADII;
+    (0..count)
+        .map(|_| {
+            let radius = rng.range(inner, outer);
+            let angle = rng.range(0.0, std::f32::consts::TAU);
+            let outward = Vec3::new(angle.cos(), 0.0, angle.sin());
+            // Tipping the velocity about the radial direction tilts the orbit's plane
+            let tilt = Quat::from_axis_angle(outward, rng.range(-KEPLER_PARTICLE_TILT, KEPLER_PARTICLE_TILT));
+            let speed = circular_velocity(mu, radius, 2.0) * rng.range(0.9, 1.05);
+            (tilt * (outward * radius), tilt * (Vec3::Y.cross(outward) * -speed))
+        })
+        .collect()
+}
+
+/// Spawn the `--kepler-particles` swarm about the heaviest body, as small unlit dots when there
+/// is anything to draw them with
+#[allow(clippy::type_complexity)]
+pub fn spawn_kepler_particles(
+    mut commands: Commands,
+    mut rng: ResMut<SimRng>,
+    (options, config): (Res<LaunchOptions>, Res<PhysicsConfig>),
+    (meshes, materials): (Option<ResMut<Assets<Mesh>>>, Option<ResMut<Assets<StandardMaterial>>>),
+    bodies: Query<(Entity, &Mass), With<CelestialBody>>,
+) {
+    let Some(count) = options.kepler_particles else {
+        return;
+    };
+    let Some((primary, mass)) = bodies.iter().max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0)) else {
+        return;
+    };
+    let rotation = config.up_axis.rotation_from_y_up();
+    let states = kepler_particle_states(&mut rng, count, config.gravitational_parameter(mass.0, 0.0));
+    let looks = meshes.zip(materials).map(|(mut meshes, mut materials)| {
+        let mesh = meshes.add(Sphere::new(0.08));
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgb(0.7, 0.8, 1.0),
+            unlit: true,
+            ..default()
+        });
+        (mesh, material)
+    });
+    for (position, velocity) in states {
+        let particle = KeplerParticle {
+            primary,
+            epoch: (rotation * position, rotation * velocity),
+            elapsed: 0.0,
+        };
+        match &looks {
+            Some((mesh, material)) => commands.spawn((
+                PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    ..default()
+                },
+                NotShadowCaster,
+                particle,
+            )),
+            None => commands.spawn((TransformBundle::default(), particle)),
+        };
+    }
+    info!("Added {count} Kepler particles");
+}
+
+/// Bodies of a built-in scenario, placed with a generator seeded by `seed`. Scene configs
+/// bring their own bodies, so there is nothing to generate for them.
+pub fn scenario_specs(scenario: &Scenario, seed: u64, config: &PhysicsConfig) -> Vec<BodySpec> {
+    let mut rng = SimRng::new(seed);
+    match scenario {
+        Scenario::SolarSystem => solar_system_specs(),
+        Scenario::Stress { count } => stress_bodies(&mut rng, *count),
+        Scenario::AccretionDisk { count, shape } => disk_bodies(&mut rng, shape, *count, config),
+        Scenario::Cradle { count } => cradle_specs(*count),
+        Scenario::Config => Vec::new(),
+    }
+}
+
+// Columns of the trajectory CSV
+pub const TRAJECTORY_HEADER: &str = "step,time,body,x,y,z,vx,vy,vz";
+
+/// Run the bodies headlessly for `steps` steps and return every body's position and velocity
+/// after each step as CSV, one row per body per step. Collisions are run as the app runs them,
+/// with every body given `material` if there is one.
+pub fn trajectory_csv(specs: &[BodySpec], config: PhysicsConfig, material: Option<SurfaceMaterial>, steps: usize) -> String {
+    let dt = config.step_dt(1.0 / 60.0);
+    let mut app = headless_app(specs);
+    app.insert_resource(config)
+        .add_systems(Update, collision_system.after(movement_system).run_if(simulation_unpaused));
+    if let Some(material) = material {
+        let mut query = app.world.query_filtered::<Entity, With<CelestialBody>>();
+        let bodies: Vec<Entity> = query.iter(&app.world).collect();
+        for body in bodies {
+            app.world.entity_mut(body).insert(material);
+        }
+    }
+    // The first update only starts the clock and moves nothing
+    app.update();
+    let mut csv = format!("{TRAJECTORY_HEADER}\n");
+    let mut query = app.world.query_filtered::<(&Name, &Transform, &Velocity), With<CelestialBody>>();
+    for step in 1..=steps {
+        app.update();
+        for (name, transform, velocity) in query.iter(&app.world) {
+            let [x, y, z] = transform.translation.to_array();
+            let [vx, vy, vz] = velocity.0.to_array();
+            // Names are quoted, with embedded quotes doubled, in case they hold commas
+            let name = name.0.replace('"', "\"\"");
+            csv.push_str(&format!("{step},{},\"{name}\",{x},{y},{z},{vx},{vy},{vz}\n", step as f32 * dt));
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashMap;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+    use crate::orbital::{orbital_elements, orbital_period};
+    use crate::physics::{body_states, headless_app};
+    use crate::selftest::two_body_specs;
+    use crate::SolarSystemPlugin;
+
+    fn args(line: &str) -> LaunchOptions {
+        LaunchOptions::from_args(line.split_whitespace().map(str::to_string)).unwrap()
+    }
+
+    #[test]
+    fn stress_option_spawns_that_many_bodies() {
+        let options = args("--stress 50");
+        let mut app = headless_app(&[]);
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<MeshQuality>()
+            .init_resource::<SimRng>()
+            .insert_resource(Scenario::from_options(&options))
+            .add_systems(Startup, spawn_scenario);
+        app.update();
+
+        let mut bodies = app.world.query_filtered::<(), With<CelestialBody>>();
+        assert_eq!(bodies.iter(&app.world).count(), 50);
+    }
+
+    #[test]
+    fn start_paused_config_holds_the_physics_until_unpaused() {
+        let path = std::env::temp_dir().join(format!("solar_system_simulator_paused_{}.ron", std::process::id()));
+        let body = r#"(name: "Planet", mass: 1.0, radius: 1.0, position: (10.0, 0.0, 0.0), velocity: (0.0, 0.0, 1.0), color: (1.0, 1.0, 1.0))"#;
+        std::fs::write(&path, format!("(bodies: [{body}], start_paused: true)")).unwrap();
+        let scene = SceneConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(scene.start_paused);
+
+        let specs: Vec<BodySpec> = scene.bodies.iter().map(BodyConfig::spec).collect();
+        let mut app = headless_app(&specs);
+        app.insert_resource(Paused(scene.start_paused));
+        let start = specs[0].position;
+        let position = |app: &mut App| {
+            let mut bodies = app.world.query_filtered::<&Transform, With<CelestialBody>>();
+            bodies.single(&app.world).translation
+        };
+        for _ in 0..60 {
+            app.update();
+        }
+        assert_eq!(position(&mut app), start);
+
+        app.insert_resource(Paused(false));
+        for _ in 0..60 {
+            app.update();
+        }
+        assert_ne!(position(&mut app), start);
+    }
+
+    #[test]
+    fn exported_scene_loads_the_same_bodies() {
+        let planet = BodyConfig {
+            name: "Planet".to_string(),
+            mass: 1.5,
+            radius: 0.75,
+            position: [12.0, 0.5, -3.25],
+            velocity: [0.0, 0.125, 8.5],
+            color: [0.2, 0.4, 0.9],
+            star: false,
+            trail: false,
+            trail_color: Some([1.0, 0.5, 0.0]),
+            mesh: BodyMesh::Cube,
+            angular_velocity: [0.0, 0.3, 0.0],
+            radiation: Some(0.02),
+            integrator: Some(Integrator::Rk4),
+            casts_shadow: false,
+            atmosphere: Some(([0.1, 0.3, 1.0, 0.35], 0.25)),
+        };
+        let sun = BodyConfig { color: [1.0, 0.9, 0.3], star: true, ..BodyConfig::new("Sun", 1000.0, 5.0, [0.0; 3], [0.0; 3]) };
+        let mut app = headless_app(&[]);
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_resource::<MeshQuality>()
+            .insert_resource(Paused(true))
+            .insert_resource(LoadedScene(vec![sun.clone(), planet.clone()]))
+            .add_systems(Startup, spawn_loaded_scene);
+        app.update();
+
+        let mut state = bevy::ecs::system::SystemState::<(
+            Res<PhysicsConfig>,
+            Res<Assets<StandardMaterial>>,
+            Query<SceneBodyData, With<CelestialBody>>,
+        )>::new(&mut app.world);
+        let (config, materials, bodies) = state.get(&app.world);
+        let exported = scene_snapshot(&config, &materials, &bodies);
+        let path = std::env::temp_dir().join(format!("solar_system_simulator_export_{}.ron", std::process::id()));
+        std::fs::write(&path, exported.to_ron().unwrap()).unwrap();
+        let loaded = SceneConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut bodies = loaded.bodies;
+        bodies.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(bodies, vec![planet, sun]);
+        assert_eq!(ron::to_string(&loaded.physics).unwrap(), ron::to_string(&*config).unwrap());
+    }
+
+    #[test]
+    fn saving_and_loading_slot_two_leaves_slot_one_alone() {
+        let body = |name: &str, x: f32| BodyConfig::new(name, 1.0, 1.0, [x, 0.0, 0.0], [0.0, 0.0, 1.0]);
+        let dir = std::env::temp_dir().join(format!("solar_system_simulator_saves_{}", std::process::id()));
+        let first = SceneConfig {
+            physics: PhysicsConfig::default(),
+            bodies: vec![body("First", 10.0)],
+            palettes: Vec::new(),
+            palette: None,
+            distance_scale: 1.0,
+            ephemeris: None,
+            start_paused: false,
+        };
+        save_slot(&dir, 1, &first).unwrap();
+        let slot_one = std::fs::read_to_string(slot_path(&dir, 1)).unwrap();
+
+        let live = vec![body("Second", 20.0), body("Third", 30.0)];
+        let mut app = headless_app(&[]);
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_resource::<MeshQuality>()
+            .insert_resource(Paused(true))
+            .insert_resource(SaveMenu {
+                dir: dir.clone(),
+                ..default()
+            })
+            .insert_resource(LoadedScene(live.clone()))
+            .add_systems(Startup, spawn_loaded_scene)
+            .add_systems(Update, (save_slot_system, load_slot_system).chain());
+        app.update();
+        app.world.resource_mut::<SaveMenu>().pending = Some(SlotAction::Save(2));
+        app.update();
+        app.world.resource_mut::<SaveMenu>().pending = Some(SlotAction::Load(2));
+        app.update();
+
+        let mut loaded = app.world.resource::<LoadedScene>().0.clone();
+        loaded.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(loaded, live);
+        assert_eq!(std::fs::read_to_string(slot_path(&dir, 1)).unwrap(), slot_one);
+        assert_eq!(load_slot(&dir, 1).unwrap().bodies, first.bodies);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_primitive_mesh_spawns_that_mesh() {
+        let body = |name: &str, mesh: BodyMesh| BodyConfig { mesh, ..BodyConfig::new(name, 1.0, 2.0, [0.0; 3], [0.0; 3]) };
+        let mut app = headless_app(&[]);
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_resource::<MeshQuality>()
+            .insert_resource(LoadedScene(vec![
+                body("Rock", BodyMesh::Cube),
+                body("Ball", BodyMesh::Sphere),
+                body("Asteroid", BodyMesh::Asset("rock.glb#Mesh0/Primitive0".to_string())),
+            ]))
+            .add_systems(Startup, spawn_loaded_scene);
+        app.update();
+
+        let mut bodies = app.world.query::<(&Name, &Handle<Mesh>, &Transform, Option<&CustomMesh>)>();
+        let bodies: HashMap<String, (Handle<Mesh>, f32, Option<BodyMesh>)> = bodies
+            .iter(&app.world)
+            .map(|(name, mesh, transform, custom)| {
+                (name.0.clone(), (mesh.clone(), transform.scale.x, custom.map(|custom| custom.0.clone())))
+            })
+            .collect();
+        let meshes = app.world.resource::<Assets<Mesh>>();
+        let cube = Mesh::from(Cuboid::from_size(Vec3::splat(4.0 / 3f32.sqrt())));
+
+        let (rock, _, custom) = &bodies["Rock"];
+        assert_eq!(*custom, Some(BodyMesh::Cube));
+        assert_eq!(meshes.get(rock).unwrap().count_vertices(), cube.count_vertices());
+        let (ball, _, custom) = &bodies["Ball"];
+        assert_eq!(*custom, None);
+        assert!(meshes.get(ball).unwrap().count_vertices() > cube.count_vertices());
+        // Asset meshes are loaded in the background and scaled up to the radius
+        let (asteroid, scale, _) = &bodies["Asteroid"];
+        assert_eq!(asteroid.path().map(ToString::to_string).as_deref(), Some("rock.glb#Mesh0/Primitive0"));
+        assert_eq!(*scale, 2.0);
+    }
+
+    #[test]
+    fn orbit_diagram_draws_a_circle_at_each_body() {
+        let diagram: Vec<DiagramBody> = two_body_specs(12.0, 0.8)
+            .iter()
+            .map(|spec| DiagramBody {
+                name: spec.name.clone(),
+                position: spec.position + Vec3::new(0.0, 2.0, 5.0),
+                radius: spec.radius,
+                color: spec.color,
+                trail: vec![spec.position, spec.position + spec.velocity],
+            })
+            .collect();
+        let svg = orbit_svg(&diagram);
+        let attribute = |element: &str, name: &str| -> f32 {
+            let start = element.find(&format!(" {name}=\"")).unwrap() + name.len() + 3;
+            element[start..].split('"').next().unwrap().parse().unwrap()
+        };
+        let circles: Vec<Vec2> = svg
+            .lines()
+            .filter(|line| line.trim_start().starts_with("<circle"))
+            .map(|line| Vec2::new(attribute(line, "cx"), attribute(line, "cy")))
+            .collect();
+
+        assert_eq!(circles.len(), diagram.len());
+        for (body, circle) in diagram.iter().zip(&circles) {
+            assert!(body.position.xz().distance(*circle) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn disk_particles_land_inside_the_slab_and_annulus() {
+        let shape = DiskConfig { inner_radius: 5.0, outer_radius: 25.0, thickness: 0.5, profile: 1.5 };
+        let particles = disk_bodies(&mut SimRng::new(DEFAULT_SEED), &shape, 2000, &PhysicsConfig::default());
+
+        // Plus the star
+        assert_eq!(particles.len(), 2001);
+        for spec in particles.iter().filter(|spec| spec.mass < DISK_STAR_MASS) {
+            let radial = spec.position.xz().length();
+            assert!(spec.position.y.abs() <= shape.thickness / 2.0);
+            assert!((shape.inner_radius..=shape.outer_radius).contains(&radial));
+        }
+    }
+
+    #[test]
+    fn trajectory_csv_has_a_header_and_a_row_per_body_per_step() {
+        let specs = two_body_specs(20.0, 1.0);
+        let steps = 25;
+        let csv = trajectory_csv(&specs, PhysicsConfig::default(), None, steps);
+        let mut lines = csv.lines();
+
+        assert_eq!(lines.next(), Some(TRAJECTORY_HEADER));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), steps * specs.len());
+        for row in rows {
+            let fields: Vec<&str> = row.split(',').collect();
+            assert_eq!(fields.len(), 9);
+            assert!(fields[0].parse::<usize>().is_ok());
+            // Everything but the step and the quoted name is a finite number
+            for (_, field) in fields.iter().enumerate().filter(|(index, _)| *index != 0 && *index != 2) {
+                assert!(field.parse::<f32>().is_ok_and(f32::is_finite));
+            }
+        }
+    }
+
+    #[test]
+    fn cradle_trajectory_export_runs_the_collisions() {
+        let scenario = Scenario::Cradle { count: 4 };
+        let mut physics = PhysicsConfig::default();
+        scenario.adjust_physics(&mut physics);
+        let specs = scenario_specs(&scenario, DEFAULT_SEED, &physics);
+        let steps = 240;
+        let csv = trajectory_csv(&specs, physics, scenario.surface_material(), steps);
+
+        // The striker's momentum has passed down the row to the far ball
+        let last_step: Vec<(String, f32)> = csv
+            .lines()
+            .skip(1)
+            .filter(|row| row.starts_with(&format!("{steps},")))
+            .map(|row| {
+                let fields: Vec<&str> = row.split(',').collect();
+                (fields[2].trim_matches('"').to_string(), fields[6].parse::<f32>().unwrap() / CRADLE_STRIKE_SPEED)
+            })
+            .collect();
+        assert_eq!(last_step.len(), specs.len());
+        for (name, speed) in last_step {
+            let expected = if name == "Ball 4" { 1.0 } else { 0.0 };
+            assert!((speed - expected).abs() < 0.05, "{name} ends at {speed} of the strike speed");
+        }
+    }
+
+    #[test]
+    fn distance_scale_keeps_each_orbit_and_stretches_its_period_by_k_to_the_1_5() {
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 1.0);
+        let body = |name: &str, mass, position: Vec3, velocity: Vec3| {
+            BodyConfig::new(name, mass, 1.0, position.to_array(), velocity.to_array())
+        };
+        let original = vec![
+            body("Star", 1000.0, Vec3::ZERO, Vec3::ZERO),
+            body("Planet", 1.0, Vec3::X * 20.0, Vec3::Z * circular_velocity(mu, 20.0, 2.0) * 1.15),
+        ];
+        let orbit = |bodies: &[BodyConfig]| {
+            let relative = |field: fn(&BodyConfig) -> [f32; 3]| Vec3::from_array(field(&bodies[1])) - Vec3::from_array(field(&bodies[0]));
+            let elements = orbital_elements(relative(|body| body.position), relative(|body| body.velocity), mu);
+            (elements.eccentricity, orbital_period(mu, elements.semi_major_axis))
+        };
+        let (eccentricity, period) = orbit(&original);
+        for scale in [0.01_f32, 0.3, 7.0] {
+            let mut scaled = original.clone();
+            scale_distances(&mut scaled, scale);
+            let (scaled_eccentricity, scaled_period) = orbit(&scaled);
+            assert!((scaled_eccentricity - eccentricity).abs() < 1e-4, "eccentricity at scale {scale}");
+            assert!((scaled_period / (period * scale.powf(1.5)) - 1.0).abs() < 1e-4, "period at scale {scale}");
+        }
+    }
+
+    #[test]
+    fn cradle_passes_the_strikers_momentum_to_the_far_ball() {
+        let mut app = headless_app(&cradle_specs(4));
+        app.insert_resource(PhysicsConfig { collision_mode: CollisionMode::Bounce, ..default() })
+            .add_systems(Update, collision_system.after(movement_system));
+        let balls: Vec<Entity> = app.world.query_filtered::<Entity, With<CelestialBody>>().iter(&app.world).collect();
+        for ball in balls {
+            app.world.entity_mut(ball).insert(CRADLE_MATERIAL);
+        }
+        for _ in 0..240 {
+            app.update();
+        }
+
+        // The balls' pull on each other nudges them a little while they roll
+        let speeds: Vec<f32> = body_states(&mut app).iter().map(|(_, velocity, _)| velocity.x / CRADLE_STRIKE_SPEED).collect();
+        let (far, struck) = speeds.split_last().unwrap();
+        assert!((far - 1.0).abs() < 0.05, "the far ball ends at {far} of the strike speed");
+        for speed in struck {
+            assert!(speed.abs() < 0.05, "a struck ball is left at {speed} of the strike speed");
+        }
+    }
+
+    #[test]
+    fn reroll_changes_the_layout_and_its_seed_brings_it_back() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, SolarSystemPlugin::default().camera(false).scenario(Scenario::Stress { count: 8 })))
+            .insert_resource(Paused(true));
+        app.update();
+        let layout = |app: &mut App| {
+            let mut positions: Vec<[u32; 3]> = body_states(app).iter().map(|(position, ..)| position.to_array().map(f32::to_bits)).collect();
+            positions.sort_unstable();
+            (app.world.resource::<SimRng>().seed, positions)
+        };
+        let first = layout(&mut app);
+        assert_eq!(first.1.len(), 8);
+
+        app.world.send_event(RerollScenario { seed: None });
+        app.update();
+        let rerolled = layout(&mut app);
+        assert_eq!(rerolled.1.len(), 8);
+        assert_ne!(rerolled.0, first.0);
+        assert_ne!(rerolled.1, first.1);
+
+        app.world.send_event(RerollScenario { seed: Some(first.0) });
+        app.update();
+        assert_eq!(layout(&mut app), first);
+    }
+
+    #[test]
+    fn horizons_vectors_load_at_their_scaled_positions_and_speeds() {
+        // A Sun at the origin and an Earth 1 AU out at the real circular speed
+        let sample = r#"
+*******************************************************************************
+ Revised: July 31, 2013                  Sun                                 10
+  Vol. mean radius, km  = 695700        Volume, 10^12 km^3   = 1412000
+  GM, km^3/s^2          = 132712440041.279419
+*******************************************************************************
+Target body name: Sun (10)                        {source: DE441}
+Center body name: Solar System Barycenter (0)     {source: DE441}
+Output units    : KM-S
+$$SOE
+2460000.500000000 = A.D. 2023-Feb-25 00:00:00.0000 TDB
+ X = 0.000000000000000E+00 Y = 0.000000000000000E+00 Z = 0.000000000000000E+00
+ VX= 0.000000000000000E+00 VY= 0.000000000000000E+00 VZ= 0.000000000000000E+00
+$$EOE
+*******************************************************************************
+ GEOPHYSICAL PROPERTIES (revised May 9, 2022):
+  Vol. Mean Radius (km)    = 6371.01+-0.02   Mass x10^24 (kg)= 5.97219+-0.0006
+  GM, km^3/s^2             = 398600.435436   Mass ratio (Sun/Earth) = 332946.0487
+  GM 1-sigma, km^3/s^2     =  0.0014
+*******************************************************************************
+Target body name: Earth (399)                     {source: DE441}
+Center body name: Solar System Barycenter (0)     {source: DE441}
+Output units    : KM-S
+$$SOE
+2460000.500000000 = A.D. 2023-Feb-25 00:00:00.0000 TDB
+ X = 1.495978707000000E+08 Y = 0.000000000000000E+00 Z = 0.000000000000000E+00
+ VX= 0.000000000000000E+00 VY= 2.978469183000000E+01 VZ=-1.000000000000000E-06
+ LT= 4.990047838000000E+02 RG= 1.495978707000000E+08 RR= 0.000000000000000E+00
+2460001.500000000 = A.D. 2023-Feb-26 00:00:00.0000 TDB
+ X = 1.000000000000000E+00 Y = 2.000000000000000E+00 Z = 3.000000000000000E+00
+ VX= 4.000000000000000E+00 VY= 5.000000000000000E+00 VZ= 6.000000000000000E+00
+$$EOE
+"#;
+        let physics = PhysicsConfig::default();
+        let mut bodies = parse_horizons(sample, &physics).unwrap();
+        let scale = 9.0;
+        scale_distances(&mut bodies, scale);
+
+        let [sun, earth] = bodies.as_slice() else {
+            panic!("expected the Sun and Earth, got {} bodies", bodies.len());
+        };
+        assert!(sun.name == "Sun" && sun.star);
+        assert!(earth.name == "Earth" && !earth.star);
+        assert!((sun.mass - HORIZONS_SUN_MASS).abs() / HORIZONS_SUN_MASS < 1e-3);
+        assert!((earth.mass / HORIZONS_SUN_MASS - 3.003_489e-6).abs() / 3e-6 < 1e-3);
+        assert!((sun.radius - HORIZONS_SUN_RADIUS).abs() < 1e-3);
+        // 1 unit from the Sun at the simulated circular speed, spread out by the distance scale
+        let speed = circular_velocity(physics.gravitational_parameter(HORIZONS_SUN_MASS, 0.0), 1.0, 2.0) / scale.sqrt();
+        assert!(Vec3::from_array(earth.position).distance(Vec3::X * scale) / scale < 1e-3);
+        assert!(Vec3::from_array(earth.velocity).distance(Vec3::Y * speed) / speed < 1e-3);
+    }
+
+    #[test]
+    fn choosing_a_scenario_applies_its_camera_and_time_scale() {
+        let mut app = App::new();
+        app.insert_resource(Scenario::Cradle { count: 5 })
+            .insert_resource(PhysicsConfig { time_scale: 3.0, up_axis: UpAxis::Z, ..default() })
+            .add_systems(Update, apply_scenario_view.run_if(resource_changed::<Scenario>));
+        let camera = app
+            .world
+            .spawn((Transform::default(), CameraController {
+                sensitivity: 0.0,
+                speed: 0.0,
+                adaptive_speed: false,
+                min_speed: 0.0,
+                max_speed: 0.0,
+                follow_stiffness: 0.0,
+                follow_damping: 0.0,
+                follow_lead: 0.0,
+            }))
+            .id();
+        let check_view = |app: &mut App| {
+            app.update();
+            let view = app.world.resource::<Scenario>().recommended_view(UpAxis::Z);
+            let transform = app.world.get::<Transform>(camera).unwrap();
+            assert!(transform.translation.distance(view.camera.translation) < 1e-4);
+            assert!(transform.rotation.dot(view.camera.rotation).abs() > 1.0 - 1e-4);
+            assert_eq!(Some(app.world.resource::<PhysicsConfig>().time_scale), view.time_scale);
+        };
+
+        check_view(&mut app);
+        assert_eq!(app.world.resource::<PhysicsConfig>().time_scale, 0.5);
+        *app.world.resource_mut::<Scenario>() = Scenario::Stress { count: 10 };
+        check_view(&mut app);
+    }
+
+    #[test]
+    fn clearing_the_scene_leaves_an_empty_system_that_keeps_running() {
+        let mut app = App::new();
+        app.insert_resource(LaunchOptions { kepler_particles: Some(20), ..default() })
+            .add_plugins((MinimalPlugins, SolarSystemPlugin::default().camera(false)))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)));
+        for _ in 0..60 {
+            app.update();
+        }
+        let count = |app: &mut App| {
+            let bodies = app.world.query_filtered::<(), With<CelestialBody>>().iter(&app.world).count();
+            bodies + app.world.query_filtered::<(), With<KeplerParticle>>().iter(&app.world).count()
+        };
+        assert_eq!(count(&mut app), 4 + 20);
+
+        app.world.send_event(ClearScene);
+        app.update();
+        // The calendar starts over
+        assert!(app.world.resource::<Calendar>().elapsed < 0.05);
+        for _ in 0..60 {
+            app.update();
+        }
+        assert_eq!(count(&mut app), 0);
+    }
+
+    #[test]
+    fn clearing_the_scene_takes_ctrl_with_delete() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>().add_event::<ClearScene>().add_systems(Update, clear_scene_input);
+        let sent = |app: &mut App| app.world.resource_mut::<Events<ClearScene>>().drain().count();
+
+        app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::Delete);
+        app.update();
+        assert_eq!(sent(&mut app), 0);
+
+        let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.release_all();
+        keys.clear();
+        keys.press(KeyCode::ControlLeft);
+        keys.press(KeyCode::Delete);
+        app.update();
+        assert_eq!(sent(&mut app), 1);
+    }
+}
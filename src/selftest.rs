@@ -0,0 +1,240 @@
+//! Headless runs, the determinism check and the `--selftest` suite
+
+use bevy::prelude::*;
+use crate::physics::{FORCE_MULTIPLIER, GRAVITATIONAL_CONSTANT, Name, PhysicsConfig, Velocity, body_states, center_of_mass, headless_app, total_angular_momentum};
+use crate::orbital::{circular_velocity, orbital_period};
+use crate::scene::{BodySpec, SimRng, stress_bodies};
+
+// Number of random bodies simulated by the headless determinism check
+pub const HEADLESS_BODY_COUNT: usize = 20;
+
+/// Run the physics without a window for `steps` fixed steps and return each body's
+/// final (name, position, velocity), in spawn order.
+///
+/// Two calls with the same inputs must return bit-identical results. The only source of
+/// nondeterminism found in the physics was its use of wall-clock frame time as `dt`; here
+/// time is advanced by exactly one 60 Hz step per update instead. Bodies are iterated in
+/// spawn order, the pairwise loop is serial, and randomness comes only from the seeded SimRng.
+pub fn run_headless(seed: u64, steps: usize) -> Vec<(String, Vec3, Vec3)> {
+    let mut rng = SimRng::new(seed);
+    let mut app = headless_app(&stress_bodies(&mut rng, HEADLESS_BODY_COUNT));
+
+    for _ in 0..steps {
+        app.update();
+    }
+
+    let mut query = app.world.query::<(&Name, &Transform, &Velocity)>();
+    query
+        .iter(&app.world)
+        .map(|(name, transform, velocity)| (name.0.clone(), transform.translation, velocity.0))
+        .collect()
+}
+
+/// Run the headless physics twice and report whether the results match bit for bit
+pub fn check_determinism(seed: u64, steps: usize) -> bool {
+    state_bits(run_headless(seed, steps)) == state_bits(run_headless(seed, steps))
+}
+
+/// The raw bits of each body's state, so comparing them tells apart values that `==` wouldn't
+fn state_bits(states: Vec<(String, Vec3, Vec3)>) -> Vec<(String, [u32; 6])> {
+    states
+        .into_iter()
+        .map(|(name, position, velocity)| {
+            let [px, py, pz] = position.to_array();
+            let [vx, vy, vz] = velocity.to_array();
+            (name, [px, py, pz, vx, vy, vz].map(f32::to_bits))
+        })
+        .collect()
+}
+
+/// Outcome of one analytic accuracy check
+pub struct SelfTestResult {
+    pub name: String,
+    /// Measured relative error
+    pub error: f32,
+    pub tolerance: f32,
+}
+
+impl SelfTestResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_finite() && self.error <= self.tolerance
+    }
+}
+
+impl std::fmt::Display for SelfTestResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:<40} error {:.3e} (tolerance {:.0e})",
+            if self.passed() { "PASS" } else { "FAIL" },
+            self.name,
+            self.error,
+            self.tolerance
+        )
+    }
+}
+
+// Mass of the central body in the self-test two-body systems
+pub const SELFTEST_CENTRAL_MASS: f32 = 1000.0;
+
+/// A star and a light planet at `radius` in the center-of-mass frame, moving at
+/// `speed_factor` times the circular velocity
+pub fn two_body_specs(radius: f32, speed_factor: f32) -> Vec<BodySpec> {
+    let (central_mass, body_mass) = (SELFTEST_CENTRAL_MASS, 1.0);
+    let config = PhysicsConfig::default();
+    let mu = config.gravitational_parameter(central_mass, body_mass);
+    let exponent = config.force_exponent;
+    let relative_velocity = Vec3::Z * circular_velocity(mu, radius, exponent) * speed_factor;
+    let body_share = body_mass / (central_mass + body_mass);
+
+    let spec = |name: &str, mass, position, velocity| BodySpec {
+        name: name.to_string(),
+        mass,
+        radius: 1.0,
+        position,
+        velocity,
+        color: Color::WHITE,
+        atmosphere: None,
+    };
+    vec![
+        spec("Star", central_mass, Vec3::X * -radius * body_share, -relative_velocity * body_share),
+        spec("Planet", body_mass, Vec3::X * radius * (1.0 - body_share), relative_velocity * (1.0 - body_share)),
+    ]
+}
+
+/// Total kinetic plus potential energy, using the same scaled gravity as the physics
+pub fn total_energy(bodies: &[(Vec3, Vec3, f32)]) -> f32 {
+    let kinetic: f32 = bodies.iter().map(|(_, velocity, mass)| 0.5 * mass * velocity.length_squared()).sum();
+    let mut potential = 0.0;
+    for (i, (position1, _, mass1)) in bodies.iter().enumerate() {
+        for (position2, _, mass2) in &bodies[i + 1..] {
+            potential -= GRAVITATIONAL_CONSTANT * FORCE_MULTIPLIER * mass1 * mass2 / position1.distance(*position2);
+        }
+    }
+    kinetic + potential
+}
+
+/// Step a two-body app until the planet has swept `orbits` full turns (or `max_steps` pass),
+/// calling `observe` with the body states after every step. Returns the simulated time taken.
+pub fn run_orbits(
+    app: &mut App,
+    orbits: f32,
+    max_steps: usize,
+    mut observe: impl FnMut(&[(Vec3, Vec3, f32)]),
+) -> f32 {
+    let target = orbits * std::f32::consts::TAU;
+    let mut swept = 0.0;
+    let mut elapsed = 0.0;
+    let mut previous = body_states(app);
+
+    for _ in 0..max_steps {
+        app.update();
+        elapsed += app.world.resource::<Time>().delta_seconds();
+        let states = body_states(app);
+        observe(&states);
+
+        let before = previous[1].0 - previous[0].0;
+        let after = states[1].0 - states[0].0;
+        // atan2 rather than acos, which is too imprecise for the tiny per-step angles of wide orbits
+        let step_angle = before.cross(after).length().atan2(before.dot(after));
+        if swept + step_angle >= target {
+            // Interpolate within the final step for a sharper period estimate
+            let fraction = (target - swept) / step_angle;
+            return elapsed - app.world.resource::<Time>().delta_seconds() * (1.0 - fraction);
+        }
+        swept += step_angle;
+        previous = states;
+    }
+    elapsed
+}
+
+// Tolerances for the semi-implicit Euler step at 60 Hz: it is symplectic, so energy and orbit
+// radius oscillate with an amplitude of order `omega * dt` instead of drifting away, and it
+// conserves angular momentum for central forces up to rounding
+pub const SELFTEST_RADIUS_TOLERANCE: f32 = 1e-2;
+
+pub const SELFTEST_ENERGY_TOLERANCE: f32 = 1e-2;
+
+pub const SELFTEST_PERIOD_TOLERANCE: f32 = 1e-3;
+
+pub const SELFTEST_ANGULAR_MOMENTUM_TOLERANCE: f32 = 1e-3;
+
+/// Run every analytic accuracy check against the physics
+pub fn run_selftest() -> Vec<SelfTestResult> {
+    let mut results = Vec::new();
+    let max_steps = 200_000;
+
+    // A circular orbit should keep its radius
+    let radius = 12.0;
+    let mut app = headless_app(&two_body_specs(radius, 1.0));
+    let mut worst = 0.0f32;
+    run_orbits(&mut app, 1.0, max_steps, |states| {
+        let separation = states[1].0.distance(states[0].0);
+        worst = worst.max((separation - radius).abs() / radius);
+    });
+    results.push(SelfTestResult {
+        name: "circular orbit radius stability".to_string(),
+        error: worst,
+        tolerance: SELFTEST_RADIUS_TOLERANCE,
+    });
+
+    // An eccentric two-body orbit should conserve energy and angular momentum
+    let mut app = headless_app(&two_body_specs(radius, 0.8));
+    let initial = body_states(&mut app);
+    let initial_energy = total_energy(&initial);
+    let (com_position, com_velocity) = center_of_mass(&initial).unwrap_or_default();
+    let initial_momentum = total_angular_momentum(&initial, com_position, com_velocity);
+    let (mut energy_error, mut momentum_error) = (0.0f32, 0.0f32);
+    run_orbits(&mut app, 2.0, max_steps, |states| {
+        energy_error = energy_error.max((total_energy(states) - initial_energy).abs() / initial_energy.abs());
+        let (com_position, com_velocity) = center_of_mass(states).unwrap_or_default();
+        let momentum = total_angular_momentum(states, com_position, com_velocity);
+        momentum_error = momentum_error.max((momentum - initial_momentum).length() / initial_momentum.length());
+    });
+    results.push(SelfTestResult {
+        name: "two-body energy conservation".to_string(),
+        error: energy_error,
+        tolerance: SELFTEST_ENERGY_TOLERANCE,
+    });
+    results.push(SelfTestResult {
+        name: "angular momentum conservation".to_string(),
+        error: momentum_error,
+        tolerance: SELFTEST_ANGULAR_MOMENTUM_TOLERANCE,
+    });
+
+    // Measured periods should follow Kepler's third law at every radius
+    for radius in [8.0, 15.0, 25.0, 40.0] {
+        let mut app = headless_app(&two_body_specs(radius, 1.0));
+        let period = run_orbits(&mut app, 1.0, max_steps, |_| {});
+        let expected = orbital_period(PhysicsConfig::default().gravitational_parameter(SELFTEST_CENTRAL_MASS, 1.0), radius);
+        results.push(SelfTestResult {
+            name: format!("Kepler's third law at r = {radius}"),
+            error: (period - expected).abs() / expected,
+            tolerance: SELFTEST_PERIOD_TOLERANCE,
+        });
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selftest_passes() {
+        let failed: Vec<String> = run_selftest()
+            .iter()
+            .filter(|result| !result.passed())
+            .map(ToString::to_string)
+            .collect();
+        assert!(failed.is_empty(), "failed checks:\n{}", failed.join("\n"));
+    }
+
+    #[test]
+    fn headless_runs_with_the_same_seed_match_bit_for_bit() {
+        let first = run_headless(7, 500);
+        assert_eq!(first.len(), HEADLESS_BODY_COUNT);
+        assert_eq!(state_bits(first), state_bits(run_headless(7, 500)));
+    }
+}
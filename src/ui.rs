@@ -0,0 +1,1862 @@
+//! Egui panels, overlays and mouse interaction with bodies
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::time::Duration;
+use bevy::window::PrimaryWindow;
+use crate::physics::{Anchored, CALENDAR_YEAR_DAYS, Calendar, CelestialBody, ManeuverNode, ManeuverNodes, Mass, Name, Paused, PhysicsConfig, PhysicsProfile, Radius, Star, Velocity, orbit_family, prograde_direction, scaled_velocities};
+use crate::orbital::{KeplerElements, MIN_PROGRESS_ECCENTRICITY, NoFlyby, orbit_progress, predict_flyby, state_from_elements};
+use crate::camera::{CameraController, CameraFlight, framing_transform, ray_plane_intersection, ray_sphere_hit};
+use crate::render::{Atmosphere, CastsShadow, HabitableZone, MeshQuality, Trail, TrailColor, TrailEnabled, total_irradiance};
+use crate::scene::{BodyConfig, BodySpec, LoadedScene, RerollScenario, SaveMenu, Scenario, SimRng, SlotAction, spawn_many_bodies};
+
+/// Marks the body the user clicked on
+#[derive(Component)]
+pub struct Selected;
+
+pub const WINDOW_TITLE: &str = "Solar System Simulator";
+
+/// Whether the mouse is over an egui panel, so scene clicks should be ignored
+#[derive(Resource, Default)]
+pub struct PointerOverUi(pub bool);
+
+/// Whether an egui text field has keyboard focus, so key presses should be ignored
+#[derive(Resource, Default)]
+pub struct KeyboardOverUi(pub bool);
+
+/// Whether the developer inspector panel is shown
+#[derive(Resource, Default)]
+pub struct InspectorOpen(pub bool);
+
+/// A body being dragged to fling it; the drag is measured on the camera-facing plane through it
+pub struct Grab {
+    pub entity: Entity,
+    pub plane_normal: Vec3,
+    /// Where the cursor first hit the drag plane
+    pub start: Vec3,
+    /// Where the cursor currently is on the drag plane
+    pub current: Vec3,
+    /// Let go this frame, so the fling is applied
+    pub released: bool,
+}
+
+/// The in-progress fling, if any
+#[derive(Resource, Default)]
+pub struct FlingDrag(pub Option<Grab>);
+
+/// Grab the body centered at `center` with the cursor ray, to drag across the camera-facing plane
+/// through it
+pub fn start_fling(entity: Entity, center: Vec3, ray_origin: Vec3, ray_direction: Vec3, camera_forward: Vec3) -> Option<Grab> {
+    let start = ray_plane_intersection(ray_origin, ray_direction, center, camera_forward)?;
+    Some(Grab {
+        entity,
+        plane_normal: camera_forward,
+        start,
+        current: start,
+        released: false,
+    })
+}
+
+/// Where the cursor ray meets the fling's drag plane, `None` if it misses it
+pub fn fling_target(grab: &Grab, ray_origin: Vec3, ray_direction: Vec3) -> Option<Vec3> {
+    ray_plane_intersection(ray_origin, ray_direction, grab.start, grab.plane_normal)
+}
+
+/// A body being slid to a new place by hand while paused. It slides in the camera-facing plane
+/// through it, or in the orbital plane through it while Alt is held.
+pub struct Placement {
+    pub entity: Entity,
+    pub plane_point: Vec3,
+    pub plane_normal: Vec3,
+    /// From the grabbed point on the plane to the body's center, so the body doesn't jump to
+    /// the cursor when picked up
+    pub offset: Vec3,
+    /// Where the body's center goes
+    pub target: Vec3,
+    /// Let go this frame; the placement ends once the body is at `target`
+    pub released: bool,
+}
+
+/// The in-progress placement, if any
+#[derive(Resource, Default)]
+pub struct PlacementDrag(pub Option<Placement>);
+
+/// Pick up the body centered at `center` with the cursor ray, to slide it across the plane
+/// through it with this normal
+pub fn start_placement(entity: Entity, center: Vec3, ray_origin: Vec3, ray_direction: Vec3, plane_normal: Vec3) -> Option<Placement> {
+    let grabbed = ray_plane_intersection(ray_origin, ray_direction, center, plane_normal)?;
+    Some(Placement {
+        entity,
+        plane_point: center,
+        plane_normal,
+        offset: center - grabbed,
+        target: center,
+        released: false,
+    })
+}
+
+/// Where the cursor ray puts the placed body's center, `None` if the ray misses its plane
+pub fn placement_target(placement: &Placement, ray_origin: Vec3, ray_direction: Vec3) -> Option<Vec3> {
+    ray_plane_intersection(ray_origin, ray_direction, placement.plane_point, placement.plane_normal)
+        .map(|point| point + placement.offset)
+}
+
+// Velocity change per world unit of drag when flinging a body
+pub const FLING_IMPULSE_PER_UNIT: f32 = 0.05;
+
+/// State of the velocity scaling panel, opened with 8
+#[derive(Resource)]
+pub struct VelocityScaling {
+    pub open: bool,
+    pub factor: f32,
+    /// Scale the motion of the center of mass too, rather than only velocities relative to it
+    pub include_center: bool,
+}
+
+impl Default for VelocityScaling {
+    fn default() -> Self {
+        Self {
+            open: false,
+            factor: 1.1,
+            include_center: false,
+        }
+    }
+}
+
+/// 8 opens and closes the velocity scaling panel
+pub fn velocity_scaling_toggle(key_input: Res<ButtonInput<KeyCode>>, mut scaling: ResMut<VelocityScaling>) {
+    if key_input.just_pressed(KeyCode::Digit8) {
+        scaling.open = !scaling.open;
+    }
+}
+
+/// Multiply every body's velocity by a factor in one go, to watch the orbits expand (above 1)
+/// or collapse (below 1). Anchored bodies stay put.
+pub fn velocity_scaling_panel(
+    mut contexts: EguiContexts,
+    mut scaling: ResMut<VelocityScaling>,
+    mut bodies: Query<(&Transform, &mut Velocity, &Mass, Has<Anchored>), With<CelestialBody>>,
+) {
+    if !scaling.open {
+        return;
+    }
+    let mut apply = false;
+    egui::Window::new("Scale velocities").resizable(false).show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Factor");
+            ui.add(egui::DragValue::new(&mut scaling.factor).speed(0.01).clamp_range(0.0..=10.0));
+        });
+        ui.checkbox(&mut scaling.include_center, "Scale the center of mass motion too");
+        apply = ui.button("Apply").clicked();
+    });
+    if !apply {
+        return;
+    }
+    let states: Vec<(Vec3, Vec3, f32)> =
+        bodies.iter().map(|(transform, velocity, mass, _)| (transform.translation, velocity.0, mass.0)).collect();
+    let velocities = scaled_velocities(&states, scaling.factor, scaling.include_center);
+    for ((_, mut velocity, _, anchored), scaled) in bodies.iter_mut().zip(velocities) {
+        if !anchored {
+            velocity.0 = scaled;
+        }
+    }
+    info!("Scaled every velocity by {}", scaling.factor);
+}
+
+/// Current calendar date and time scale
+pub fn calendar_overlay(
+    mut contexts: EguiContexts,
+    calendar: Res<Calendar>,
+    config: Res<PhysicsConfig>,
+    (scenario, rng): (Res<Scenario>, Res<SimRng>),
+    mut reroll: EventWriter<RerollScenario>,
+    mut entered_seed: Local<Option<u64>>,
+) {
+    egui::Window::new("Calendar")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .resizable(false)
+        .title_bar(false)
+        .show(contexts.ctx_mut(), |ui| {
+            match calendar.days() {
+                Some(days) => {
+                    let year = (days / CALENDAR_YEAR_DAYS).floor();
+                    ui.label(format!("Year {}, day {:.0}", year + 1.0, days - year * CALENDAR_YEAR_DAYS + 1.0));
+                }
+                None => {
+                    ui.label("No calendar: the reference body isn't orbiting");
+                }
+            }
+            if calendar.locked {
+                ui.label(format!("{} days per second ({:.3}x)", calendar.days_per_second, config.time_scale));
+            } else {
+                ui.label(format!("Time scale {:.3}x", config.time_scale));
+            }
+            if !scenario.is_random() {
+                return;
+            }
+            ui.horizontal(|ui| {
+                let seed = entered_seed.get_or_insert(rng.seed);
+                ui.label("Seed");
+                ui.add(egui::DragValue::new(seed));
+                if ui.button("Respawn").clicked() {
+                    reroll.send(RerollScenario { seed: Some(*seed) });
+                }
+                if ui.button("Reroll").clicked() {
+                    reroll.send(RerollScenario { seed: None });
+                    *entered_seed = None;
+                }
+            });
+            // Follow rerolls made with the key too
+            if rng.is_changed() && entered_seed.is_some_and(|seed| seed != rng.seed) {
+                *entered_seed = None;
+            }
+        });
+}
+
+/// Lists the selected body's burns for editing, with (prograde, radial, normal) delta-v. New
+/// burns go at the next apoapsis of a bound orbit, or a few seconds out otherwise.
+#[allow(clippy::type_complexity)]
+pub fn maneuver_panel(
+    mut contexts: EguiContexts,
+    config: Res<PhysicsConfig>,
+    mut commands: Commands,
+    calendar: Res<Calendar>,
+    mut selected: Query<(Entity, &Name, Option<&mut ManeuverNodes>), (With<Selected>, With<CelestialBody>)>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+) {
+    if selected.iter().len() != 1 {
+        return;
+    }
+    let Ok((entity, name, nodes)) = selected.get_single_mut() else {
+        return;
+    };
+    let next_apoapsis = bodies.get(entity).ok().and_then(|(_, transform, velocity, mass)| {
+        let (_, central_transform, central_velocity, central_mass) = bodies
+            .iter()
+            .filter(|(other, ..)| *other != entity)
+            .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))?;
+        orbit_progress(
+            transform.translation - central_transform.translation,
+            velocity.0 - central_velocity.0,
+            config.gravitational_parameter(central_mass.0, mass.0),
+        )
+        .map(|progress| progress.time_to_apoapsis)
+    });
+
+    egui::Window::new("Maneuvers")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -80.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.strong(&name.0);
+            let Some(mut nodes) = nodes else {
+                if ui.button("Add burn").clicked() {
+                    let time = calendar.elapsed + next_apoapsis.unwrap_or(5.0);
+                    commands.entity(entity).insert(ManeuverNodes(vec![ManeuverNode { time, delta_v: Vec3::ZERO }]));
+                }
+                return;
+            };
+            let mut removed = None;
+            for (i, node) in nodes.0.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    let mut wait = node.time - calendar.elapsed;
+                    ui.label("in");
+                    if ui.add(egui::DragValue::new(&mut wait).speed(0.1).suffix(" s")).changed() {
+                        node.time = calendar.elapsed + wait.max(0.0);
+                    }
+                    for (label, value) in [("pro", &mut node.delta_v.x), ("rad", &mut node.delta_v.y), ("nrm", &mut node.delta_v.z)] {
+                        ui.label(label);
+                        ui.add(egui::DragValue::new(value).speed(0.01));
+                    }
+                    if ui.small_button("x").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = removed {
+                nodes.0.remove(i);
+            }
+            if ui.button("Add burn").clicked() {
+                let after = nodes.0.last().map_or(calendar.elapsed, |node| node.time);
+                nodes.0.push(ManeuverNode { time: after + next_apoapsis.unwrap_or(5.0), delta_v: Vec3::ZERO });
+            }
+            nodes.0.sort_by(|a, b| a.time.total_cmp(&b.time));
+        });
+}
+
+/// Velocity change for a fling dragged from `start` to `end`
+pub fn fling_impulse(start: Vec3, end: Vec3) -> Vec3 {
+    (end - start) * FLING_IMPULSE_PER_UNIT
+}
+
+/// Tool for populating an orbit: press on a central body, drag out to where periapsis should be
+/// and release to spawn `count` equal bodies along the orbit. Toggled with 1; flinging is off
+/// while it is active.
+#[derive(Resource)]
+pub struct OrbitSpawner {
+    pub active: bool,
+    pub count: usize,
+    pub eccentricity: f32,
+    pub mass: f32,
+    pub radius: f32,
+    /// Central body being dragged from and the periapsis point under the cursor
+    pub drag: Option<(Entity, Vec3)>,
+    /// Released drag waiting to be spawned
+    pub pending: Option<(Entity, Vec3)>,
+}
+
+impl Default for OrbitSpawner {
+    fn default() -> Self {
+        Self {
+            active: false,
+            count: 12,
+            eccentricity: 0.0,
+            mass: 0.5,
+            radius: 0.4,
+            drag: None,
+            pending: None,
+        }
+    }
+}
+
+// Points in the orbit spawner's preview ellipse
+pub const ORBIT_SPAWNER_SEGMENTS: usize = 96;
+
+/// 1 turns the orbit spawner on and off
+pub fn orbit_spawner_toggle(key_input: Res<ButtonInput<KeyCode>>, mut spawner: ResMut<OrbitSpawner>) {
+    if key_input.just_pressed(KeyCode::Digit1) {
+        spawner.active = !spawner.active;
+        spawner.drag = None;
+    }
+}
+
+pub fn orbit_spawner_inactive(spawner: Res<OrbitSpawner>) -> bool {
+    !spawner.active
+}
+
+/// Press on a body and drag across its horizontal plane to lay out the orbit, previewed as an
+/// ellipse, then release to queue it for spawning
+pub fn orbit_spawner_drag(
+    mut gizmos: Gizmos,
+    (mut spawner, config): (ResMut<OrbitSpawner>, Res<PhysicsConfig>),
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    over_ui: Res<PointerOverUi>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraController>>,
+    bodies: Query<(Entity, &Transform, &Radius), With<CelestialBody>>,
+) {
+    if !spawner.active {
+        return;
+    }
+    let ray = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(cameras.get_single().ok())
+        .and_then(|(cursor, (camera, camera_transform))| camera.viewport_to_world(camera_transform, cursor));
+
+    if mouse_button.just_pressed(MouseButton::Left) && !over_ui.0 {
+        spawner.drag = ray.and_then(|ray| {
+            bodies
+                .iter()
+                .filter_map(|(entity, transform, radius)| {
+                    ray_sphere_hit(ray.origin, *ray.direction, transform.translation, radius.0)
+                        .map(|distance| ((entity, transform.translation), distance))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|((entity, center), _)| (entity, center))
+        });
+    }
+
+    let Some((central, point)) = spawner.drag else {
+        return;
+    };
+    let Ok((_, central_transform, central_radius)) = bodies.get(central) else {
+        spawner.drag = None;
+        return;
+    };
+    let center = central_transform.translation;
+    let point = ray
+        .and_then(|ray| ray_plane_intersection(ray.origin, *ray.direction, center, config.up_axis.up()))
+        .unwrap_or(point);
+    spawner.drag = Some((central, point));
+    let periapsis = point - center;
+    let long_enough = periapsis.length() > central_radius.0;
+
+    if mouse_button.just_released(MouseButton::Left) {
+        spawner.drag = None;
+        if long_enough {
+            spawner.pending = Some((central, periapsis));
+        }
+    } else if long_enough {
+        // r = p / (1 + e cos(theta)), measured from the periapsis direction
+        let eccentricity = spawner.eccentricity;
+        let semi_latus_rectum = periapsis.length() * (1.0 + eccentricity);
+        let Some(z) = prograde_direction(periapsis, config.up_axis) else {
+            return;
+        };
+        let x = periapsis.normalize();
+        gizmos.linestrip(
+            (0..=ORBIT_SPAWNER_SEGMENTS).map(|segment| {
+                let theta = std::f32::consts::TAU * segment as f32 / ORBIT_SPAWNER_SEGMENTS as f32;
+                let r = semi_latus_rectum / (1.0 + eccentricity * theta.cos());
+                center + (x * theta.cos() + z * theta.sin()) * r
+            }),
+            Color::rgb(0.6, 0.9, 1.0),
+        );
+    }
+}
+
+/// Spawn the queued orbit's bodies with the velocities its elements give them
+pub fn orbit_spawner_spawn(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    quality: Res<MeshQuality>,
+    mut spawner: ResMut<OrbitSpawner>,
+    config: Res<PhysicsConfig>,
+    bodies: Query<(&Transform, &Velocity, &Mass, &Name), With<CelestialBody>>,
+) {
+    let Some((central, periapsis)) = spawner.pending.take() else {
+        return;
+    };
+    let Ok((transform, velocity, mass, name)) = bodies.get(central) else {
+        return;
+    };
+    let mu = config.gravitational_parameter(mass.0, spawner.mass);
+    let family = orbit_family((transform.translation, velocity.0), periapsis, spawner.eccentricity, mu, spawner.count, config.up_axis);
+    info!("Spawned {} bodies orbiting {}", family.len(), name.0);
+    let specs = family
+        .into_iter()
+        .enumerate()
+        .map(|(index, (position, velocity))| BodySpec {
+            name: format!("{} orbiter {}", name.0, index + 1),
+            mass: spawner.mass,
+            radius: spawner.radius,
+            position,
+            velocity,
+            color: Color::rgb(0.75, 0.8, 0.85),
+            atmosphere: None,
+        })
+        .collect();
+    spawn_many_bodies(&mut commands, &mut meshes, &mut materials, &quality, specs);
+}
+
+/// Settings for the orbit spawner, shown while it is active
+pub fn orbit_spawner_panel(mut contexts: EguiContexts, mut spawner: ResMut<OrbitSpawner>) {
+    if !spawner.active {
+        return;
+    }
+    egui::Window::new("Orbit spawner")
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Drag from a body out to periapsis");
+            ui.add(egui::Slider::new(&mut spawner.count, 1..=500).text("bodies"));
+            ui.add(egui::Slider::new(&mut spawner.eccentricity, 0.0..=0.95).text("eccentricity"));
+            ui.add(egui::Slider::new(&mut spawner.mass, 0.01..=20.0).logarithmic(true).text("mass"));
+            ui.add(egui::Slider::new(&mut spawner.radius, 0.05..=3.0).text("radius"));
+        });
+}
+
+/// Which way the add planet panel takes the new body's motion
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SpawnTab {
+    /// Raw position and velocity
+    #[default]
+    State,
+    /// An orbit about a chosen central body
+    Elements,
+}
+
+/// The add planet panel, opened with 3
+#[derive(Resource)]
+pub struct SpawnPanel {
+    pub open: bool,
+    pub tab: SpawnTab,
+    pub name: String,
+    pub mass: f32,
+    pub radius: f32,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub central: Option<Entity>,
+    pub elements: KeplerElements,
+    /// Why the last spawn was refused
+    pub error: Option<String>,
+    /// Numbers the default names
+    pub spawned: usize,
+}
+
+impl Default for SpawnPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            tab: SpawnTab::default(),
+            name: String::new(),
+            mass: 3.0,
+            radius: 0.6,
+            position: Vec3::new(25.0, 0.0, 0.0),
+            velocity: Vec3::new(0.0, 0.0, 0.6),
+            central: None,
+            elements: KeplerElements::default(),
+            error: None,
+            spawned: 0,
+        }
+    }
+}
+
+/// Masses the panels accept: positive, or anything but zero with exotic matter on
+pub fn mass_range(config: &PhysicsConfig) -> std::ops::RangeInclusive<f32> {
+    if config.exotic_matter {
+        -f32::MAX..=f32::MAX
+    } else {
+        0.001..=f32::MAX
+    }
+}
+
+/// 3 opens and closes the add planet panel
+pub fn spawn_panel_toggle(key_input: Res<ButtonInput<KeyCode>>, mut panel: ResMut<SpawnPanel>) {
+    if key_input.just_pressed(KeyCode::Digit3) {
+        panel.open = !panel.open;
+    }
+}
+
+/// Add a planet from a position and velocity, or on an orbit given by its elements
+pub fn spawn_panel(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut panel: ResMut<SpawnPanel>,
+    config: Res<PhysicsConfig>,
+    bodies: Query<(Entity, &Name, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+    pending: Option<ResMut<LoadedScene>>,
+) {
+    if !panel.open {
+        return;
+    }
+    let panel = &mut *panel;
+    // Orbit the heaviest body unless told otherwise
+    if panel.central.is_none_or(|central| bodies.get(central).is_err()) {
+        panel.central = bodies.iter().max_by(|a, b| a.4 .0.total_cmp(&b.4 .0)).map(|(entity, ..)| entity);
+    }
+    let mut spawn = false;
+    egui::Window::new("Add planet").resizable(false).show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut panel.tab, SpawnTab::State, "Position and velocity");
+            ui.selectable_value(&mut panel.tab, SpawnTab::Elements, "By orbital elements");
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut panel.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Mass");
+            ui.add(egui::DragValue::new(&mut panel.mass).speed(0.1).clamp_range(mass_range(&config)));
+            ui.label("Radius");
+            ui.add(egui::DragValue::new(&mut panel.radius).speed(0.01).clamp_range(0.01..=f32::MAX));
+        });
+        match panel.tab {
+            SpawnTab::State => {
+                for (label, vector) in [("Position", &mut panel.position), ("Velocity", &mut panel.velocity)] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        for axis in [&mut vector.x, &mut vector.y, &mut vector.z] {
+                            ui.add(egui::DragValue::new(axis).speed(0.05));
+                        }
+                    });
+                }
+            }
+            SpawnTab::Elements => {
+                let central_name = |entity: Option<Entity>| {
+                    entity.and_then(|entity| bodies.get(entity).ok()).map_or("none".to_string(), |body| body.1 .0.clone())
+                };
+                egui::ComboBox::from_label("Orbiting")
+                    .selected_text(central_name(panel.central))
+                    .show_ui(ui, |ui| {
+                        for (entity, name, ..) in bodies.iter() {
+                            ui.selectable_value(&mut panel.central, Some(entity), &name.0);
+                        }
+                    });
+                let elements = &mut panel.elements;
+                egui::Grid::new("spawn_elements").show(ui, |ui| {
+                    let row = |ui: &mut egui::Ui, label: &str, value: &mut f32, speed: f64, suffix: &str| {
+                        ui.label(label);
+                        ui.add(egui::DragValue::new(value).speed(speed).suffix(suffix));
+                        ui.end_row();
+                    };
+                    row(ui, "Semi-major axis", &mut elements.semi_major_axis, 0.1, "");
+                    row(ui, "Eccentricity", &mut elements.eccentricity, 0.005, "");
+                    row(ui, "Inclination", &mut elements.inclination, 0.5, "°");
+                    row(ui, "Ascending node", &mut elements.ascending_node, 0.5, "°");
+                    row(ui, "Argument of periapsis", &mut elements.argument_of_periapsis, 0.5, "°");
+                    row(ui, "True anomaly", &mut elements.true_anomaly, 0.5, "°");
+                });
+            }
+        }
+        spawn = ui.button("Spawn").clicked();
+        if let Some(error) = &panel.error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+    });
+    if !spawn {
+        return;
+    }
+
+    let state = match panel.tab {
+        SpawnTab::State => Ok((panel.position, panel.velocity)),
+        SpawnTab::Elements => panel
+            .central
+            .and_then(|central| bodies.get(central).ok())
+            .ok_or_else(|| "pick a body to orbit".to_string())
+            .and_then(|(_, _, transform, velocity, mass)| {
+                let mu = config.gravitational_parameter(mass.0, panel.mass);
+                // Elements are measured from the plane across the up axis
+                let rotation = config.up_axis.rotation_from_y_up();
+                state_from_elements(&panel.elements, mu).map(|(position, relative_velocity)| {
+                    (transform.translation + rotation * position, velocity.0 + rotation * relative_velocity)
+                })
+            }),
+    };
+    let (position, velocity) = match state {
+        Ok(state) => state,
+        Err(error) => {
+            panel.error = Some(error);
+            return;
+        }
+    };
+    if panel.mass == 0.0 {
+        panel.error = Some("mass can't be zero".to_string());
+        return;
+    }
+    panel.error = None;
+    panel.spawned += 1;
+    let name = if panel.name.trim().is_empty() { format!("New Planet {}", panel.spawned) } else { panel.name.trim().to_string() };
+    info!("Added {name}");
+    queue_body(&mut commands, pending, BodyConfig {
+        color: [0.6, 0.7, 0.5],
+        ..BodyConfig::new(&name, panel.mass, panel.radius, position.to_array(), velocity.to_array())
+    });
+}
+
+/// Queue a body to spawn behind any bodies still waiting in LoadedScene
+pub fn queue_body(commands: &mut Commands, pending: Option<ResMut<LoadedScene>>, body: BodyConfig) {
+    match pending {
+        Some(mut pending) => pending.0.push(body),
+        None => commands.insert_resource(LoadedScene(vec![body])),
+    }
+}
+
+/// Most matches the find body panel lists at once
+pub const BODY_SEARCH_LIMIT: usize = 50;
+
+/// State of the find body panel, opened with 5
+#[derive(Resource, Default)]
+pub struct BodySearch {
+    pub open: bool,
+    pub query: String,
+}
+
+/// Bodies whose name contains the query, ignoring case, in the order given. An empty query
+/// matches everything
+pub fn search_bodies<T: Copy>(bodies: &[(T, &str)], query: &str) -> Vec<T> {
+    let query = query.trim().to_lowercase();
+    bodies
+        .iter()
+        .filter(|(_, name)| name.to_lowercase().contains(&query))
+        .map(|(body, _)| *body)
+        .collect()
+}
+
+/// 5 opens and closes the find body panel
+pub fn body_search_toggle(key_input: Res<ButtonInput<KeyCode>>, mut search: ResMut<BodySearch>) {
+    if key_input.just_pressed(KeyCode::Digit5) {
+        search.open = !search.open;
+    }
+}
+
+/// Type part of a name to narrow the body list; clicking a match selects it alone and flies
+/// the camera to it
+pub fn body_search_panel(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    (mut search, config): (ResMut<BodySearch>, Res<PhysicsConfig>),
+    cameras: Query<(Entity, &Transform, &Projection), With<CameraController>>,
+    bodies: Query<(Entity, &Name, &Transform, &Radius), With<CelestialBody>>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    if !search.open {
+        return;
+    }
+    let mut names: Vec<(Entity, &str)> = bodies.iter().map(|(entity, name, ..)| (entity, name.0.as_str())).collect();
+    names.sort_by(|a, b| a.1.cmp(b.1));
+    let mut picked = None;
+    egui::Window::new("Find body").resizable(false).show(contexts.ctx_mut(), |ui| {
+        ui.text_edit_singleline(&mut search.query);
+        let matches = search_bodies(&names, &search.query);
+        if matches.is_empty() {
+            ui.label(format!("No bodies match \"{}\"", search.query.trim()));
+            return;
+        }
+        egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+            for &entity in matches.iter().take(BODY_SEARCH_LIMIT) {
+                let Ok((_, name, ..)) = bodies.get(entity) else {
+                    continue;
+                };
+                if ui.selectable_label(selected.contains(entity), &name.0).clicked() {
+                    picked = Some(entity);
+                }
+            }
+        });
+        if matches.len() > BODY_SEARCH_LIMIT {
+            ui.label(format!("and {} more", matches.len() - BODY_SEARCH_LIMIT));
+        }
+    });
+    let Some((entity, name, transform, radius)) = picked.and_then(|entity| bodies.get(entity).ok()) else {
+        return;
+    };
+
+    for other in selected.iter() {
+        commands.entity(other).remove::<Selected>();
+    }
+    info!("Selected {}", name.0);
+    commands.entity(entity).insert(Selected);
+    let Ok((camera, camera_transform, projection)) = cameras.get_single() else {
+        return;
+    };
+    let fov = match projection {
+        Projection::Perspective(perspective) => perspective.fov,
+        Projection::Orthographic(_) => std::f32::consts::FRAC_PI_4,
+    };
+    commands.entity(camera).insert(CameraFlight {
+        from: *camera_transform,
+        to: framing_transform(transform.translation, radius.0, fov, *camera_transform.forward(), config.up_axis),
+        elapsed: 0.0,
+    });
+}
+
+/// Press on a body, drag and release to fling it. The arrow previews the drag; on release
+/// fling_apply_system changes the velocity in proportion to it. Selection still happens on the
+/// press as usual.
+pub fn fling_system(
+    mut gizmos: Gizmos,
+    mut drag: ResMut<FlingDrag>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    over_ui: Res<PointerOverUi>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraController>>,
+    bodies: Query<(Entity, &Transform, &Radius), With<CelestialBody>>,
+) {
+    let cursor_ray = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(cameras.get_single().ok())
+        .and_then(|(cursor, (camera, camera_transform))| {
+            camera
+                .viewport_to_world(camera_transform, cursor)
+                .map(|ray| (ray, camera_transform.forward()))
+        });
+
+    if mouse_button.just_pressed(MouseButton::Left) && !over_ui.0 {
+        drag.0 = cursor_ray.and_then(|(ray, camera_forward)| {
+            let (entity, transform, _) = bodies
+                .iter()
+                .filter_map(|body| {
+                    ray_sphere_hit(ray.origin, *ray.direction, body.1.translation, body.2 .0)
+                        .map(|distance| (body, distance))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))?
+                .0;
+            start_fling(entity, transform.translation, ray.origin, *ray.direction, camera_forward)
+        });
+    }
+
+    let Some(grab) = drag.0.as_mut() else {
+        return;
+    };
+    let Ok((_, transform, _)) = bodies.get(grab.entity) else {
+        drag.0 = None;
+        return;
+    };
+    if let Some(point) = cursor_ray.and_then(|(ray, _)| fling_target(grab, ray.origin, *ray.direction)) {
+        grab.current = point;
+    }
+
+    if mouse_button.just_released(MouseButton::Left) {
+        grab.released = true;
+    } else if grab.start.distance(grab.current) > 0.01 {
+        let offset = grab.current - grab.start;
+        gizmos.arrow(transform.translation, transform.translation + offset, Color::ORANGE_RED);
+    }
+}
+
+/// Give a released fling's body its velocity change
+pub fn fling_apply_system(mut drag: ResMut<FlingDrag>, mut bodies: Query<&mut Velocity, With<CelestialBody>>) {
+    let Some(grab) = drag.0.as_ref().filter(|grab| grab.released) else {
+        return;
+    };
+    if let Ok(mut velocity) = bodies.get_mut(grab.entity) {
+        velocity.0 += fling_impulse(grab.start, grab.current);
+    }
+    drag.0 = None;
+}
+
+/// While paused, press on a body and drag to move it, without touching its velocity
+pub fn placement_drag_system(
+    mut drag: ResMut<PlacementDrag>,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    (key_input, config): (Res<ButtonInput<KeyCode>>, Res<PhysicsConfig>),
+    over_ui: Res<PointerOverUi>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraController>>,
+    bodies: Query<(Entity, &Transform, &Radius), With<CelestialBody>>,
+) {
+    let cursor_ray = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(cameras.get_single().ok())
+        .and_then(|(cursor, (camera, camera_transform))| {
+            camera
+                .viewport_to_world(camera_transform, cursor)
+                .map(|ray| (ray, camera_transform.forward()))
+        });
+
+    if mouse_button.just_pressed(MouseButton::Left) && !over_ui.0 {
+        drag.0 = cursor_ray.and_then(|(ray, camera_forward)| {
+            let (entity, transform, _) = bodies
+                .iter()
+                .filter_map(|body| {
+                    ray_sphere_hit(ray.origin, *ray.direction, body.1.translation, body.2 .0)
+                        .map(|distance| (body, distance))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))?
+                .0;
+            let plane_normal = if key_input.pressed(KeyCode::AltLeft) { config.up_axis.up() } else { camera_forward };
+            start_placement(entity, transform.translation, ray.origin, *ray.direction, plane_normal)
+        });
+    }
+
+    let Some(placement) = drag.0.as_mut() else {
+        return;
+    };
+    if let Some(target) = cursor_ray.and_then(|(ray, _)| placement_target(placement, ray.origin, *ray.direction)) {
+        placement.target = target;
+    }
+    // Also catches a button let go of while the drag was interrupted by unpausing
+    if !mouse_button.pressed(MouseButton::Left) {
+        placement.released = true;
+    }
+}
+
+/// Move the placed body to where the cursor puts it. Its old trail would draw a line through
+/// the jump, so it starts over.
+pub fn placement_apply_system(
+    mut drag: ResMut<PlacementDrag>,
+    mut bodies: Query<(&mut Transform, &Name, Option<&mut Trail>), With<CelestialBody>>,
+) {
+    let Some(placement) = drag.0.as_ref() else {
+        return;
+    };
+    let Ok((mut transform, name, trail)) = bodies.get_mut(placement.entity) else {
+        drag.0 = None;
+        return;
+    };
+    if transform.translation != placement.target {
+        transform.translation = placement.target;
+        if let Some(mut trail) = trail {
+            trail.points.clear();
+        }
+    }
+    if placement.released {
+        info!("Moved {} to {:.2}", name.0, placement.target);
+        drag.0 = None;
+    }
+}
+
+/// Left click selects the body under the cursor, clicking empty space clears the selection.
+/// Holding Shift adds or removes the body from the current selection instead.
+pub fn selection_system(
+    mut commands: Commands,
+    mouse_button: Res<ButtonInput<MouseButton>>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraController>>,
+    bodies: Query<(Entity, &Transform, &Radius, &Name), With<CelestialBody>>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    if !mouse_button.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    // Only CelestialBody entities are candidates, so atmosphere shells never block a click
+    let hit = bodies
+        .iter()
+        .filter_map(|(entity, transform, radius, name)| {
+            ray_sphere_hit(ray.origin, *ray.direction, transform.translation, radius.0)
+                .map(|distance| (entity, name, distance))
+        })
+        .min_by(|a, b| a.2.total_cmp(&b.2));
+
+    if key_input.pressed(KeyCode::ShiftLeft) {
+        if let Some((entity, name, _)) = hit {
+            if selected.contains(entity) {
+                commands.entity(entity).remove::<Selected>();
+            } else {
+                info!("Added {} to the selection", name.0);
+                commands.entity(entity).insert(Selected);
+            }
+        }
+        return;
+    }
+
+    for entity in selected.iter() {
+        commands.entity(entity).remove::<Selected>();
+    }
+    if let Some((entity, name, _)) = hit {
+        info!("Selected {}", name.0);
+        commands.entity(entity).insert(Selected);
+    }
+}
+
+/// F8 anchors or releases the selected bodies
+#[allow(clippy::type_complexity)]
+pub fn anchor_toggle(
+    mut commands: Commands,
+    key_input: Res<ButtonInput<KeyCode>>,
+    selected: Query<(Entity, &Name, Has<Anchored>), (With<Selected>, With<CelestialBody>)>,
+) {
+    if !key_input.just_pressed(KeyCode::F8) {
+        return;
+    }
+    for (entity, name, anchored) in selected.iter() {
+        if anchored {
+            commands.entity(entity).remove::<Anchored>();
+            info!("Released {}", name.0);
+        } else {
+            commands.entity(entity).insert(Anchored);
+            info!("Anchored {}", name.0);
+        }
+    }
+}
+
+/// Small overlay with how far each selected body is through its orbit
+#[allow(clippy::type_complexity)]
+pub fn orbit_progress_overlay(
+    mut contexts: EguiContexts,
+    config: Res<PhysicsConfig>,
+    zone: Res<HabitableZone>,
+    selected: Query<(Entity, &Name, &Transform, &Velocity, &Mass), (With<Selected>, With<CelestialBody>)>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+    stars: Query<(&Transform, &Mass), With<Star>>,
+) {
+    if selected.is_empty() {
+        return;
+    }
+    let stars: Vec<(Vec3, f32)> = stars.iter().map(|(transform, mass)| (transform.translation, mass.0)).collect();
+    egui::Window::new("Orbit")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (entity, name, transform, velocity, mass) in selected.iter() {
+                // Same choice of central body as the orbital plane disk
+                let progress = bodies
+                    .iter()
+                    .filter(|(other, ..)| *other != entity)
+                    .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))
+                    .and_then(|(_, central_transform, central_velocity, central_mass)| {
+                        orbit_progress(
+                            transform.translation - central_transform.translation,
+                            velocity.0 - central_velocity.0,
+                            config.gravitational_parameter(central_mass.0, mass.0),
+                        )
+                    });
+                ui.strong(&name.0);
+                match progress {
+                    Some(progress) => {
+                        ui.label(format!("Orbit: {:.1}% of {:.1} s", progress.completion * 100.0, progress.period));
+                        ui.label(format!("Periapsis in {:.1} s", progress.time_to_periapsis));
+                        ui.label(format!("Apoapsis in {:.1} s", progress.time_to_apoapsis));
+                    }
+                    None => {
+                        ui.label("Orbit: n/a");
+                    }
+                }
+                if !stars.is_empty() {
+                    let received = total_irradiance(transform.translation, &stars);
+                    let bucket = zone.bucket(received);
+                    let [r, g, b, _] = bucket.color().as_rgba_u8();
+                    ui.colored_label(
+                        egui::Color32::from_rgb(r, g, b),
+                        format!("Irradiance: {received:.2} ({bucket:?})"),
+                    );
+                }
+            }
+        });
+}
+
+/// With a probe and a planet selected, predict what swinging past the planet will do to the
+/// probe. The lighter of the two is taken as the probe.
+#[allow(clippy::type_complexity)]
+pub fn flyby_panel(
+    mut contexts: EguiContexts,
+    config: Res<PhysicsConfig>,
+    selected: Query<(&Name, &Transform, &Velocity, &Mass, &Radius), (With<Selected>, With<CelestialBody>)>,
+) {
+    let mut pair: Vec<_> = selected.iter().collect();
+    if pair.len() != 2 {
+        return;
+    }
+    pair.sort_by(|a, b| a.3 .0.total_cmp(&b.3 .0));
+    let [(probe_name, probe_transform, probe_velocity, probe_mass, _), (planet_name, planet_transform, planet_velocity, planet_mass, planet_radius)] =
+        [pair[0], pair[1]];
+    let prediction = predict_flyby(
+        probe_transform.translation - planet_transform.translation,
+        probe_velocity.0 - planet_velocity.0,
+        config.gravitational_parameter(planet_mass.0, probe_mass.0),
+        planet_radius.0,
+    );
+
+    egui::Window::new("Flyby")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.strong(format!("{} past {}", probe_name.0, planet_name.0));
+            match prediction {
+                Ok(flyby) => {
+                    let after = planet_velocity.0 + flyby.outgoing;
+                    ui.label(format!("Excess speed: {:.2}", flyby.excess_speed));
+                    ui.label(format!("Turned by {:.1} deg", flyby.turning_angle.to_degrees()));
+                    ui.label(format!("Closest approach: {:.2}", flyby.periapsis));
+                    ui.label(format!(
+                        "Delta-v: {:.2} ({:+.2} speed)",
+                        flyby.delta_v().length(),
+                        after.length() - probe_velocity.0.length()
+                    ));
+                    ui.label(format!("Velocity after: ({:.2}, {:.2}, {:.2})", after.x, after.y, after.z));
+                }
+                Err(reason) => {
+                    ui.label(match reason {
+                        NoFlyby::Captured => "No flyby: captured by the planet",
+                        NoFlyby::Receding => "No flyby: already moving away",
+                        NoFlyby::Radial => "No flyby: heading straight in",
+                        NoFlyby::Impact => "No flyby: it will hit the planet",
+                    });
+                }
+            }
+        });
+}
+
+/// Periapsis direction of every completed orbit, for watching the apsides precess
+#[derive(Default)]
+pub struct ApsidalTracker {
+    /// Radial speed at the previous observation; periapsis is where it turns from negative to positive
+    pub previous_radial_speed: Option<f32>,
+    /// Unit periapsis direction at each passage
+    pub directions: Vec<Vec3>,
+    /// Orbit normal at the first passage, which the drift angles are signed around
+    pub normal: Option<Vec3>,
+}
+
+impl ApsidalTracker {
+    /// Feed in the current relative state, recording the periapsis direction if the body just
+    /// went through periapsis. Near-circular orbits are skipped since their periapsis is ill-defined.
+    pub fn observe(&mut self, relative_position: Vec3, relative_velocity: Vec3, mu: f32) -> bool {
+        let radial_speed = relative_position.dot(relative_velocity);
+        let passed = self.previous_radial_speed.is_some_and(|previous| previous < 0.0 && radial_speed >= 0.0);
+        self.previous_radial_speed = Some(radial_speed);
+        if !passed {
+            return false;
+        }
+        let angular_momentum = relative_position.cross(relative_velocity);
+        let eccentricity_vector =
+            relative_velocity.cross(angular_momentum) / mu - relative_position.normalize();
+        if eccentricity_vector.length() < MIN_PROGRESS_ECCENTRICITY {
+            return false;
+        }
+        self.normal.get_or_insert(angular_momentum.normalize());
+        self.directions.push(eccentricity_vector.normalize());
+        true
+    }
+
+    /// Angle in radians each periapsis has turned from the first, positive in the direction of motion
+    pub fn drift_angles(&self) -> Vec<f32> {
+        let (Some(first), Some(normal)) = (self.directions.first(), self.normal) else {
+            return Vec::new();
+        };
+        self.directions
+            .iter()
+            .map(|direction| first.cross(*direction).dot(normal).atan2(first.dot(*direction)))
+            .collect()
+    }
+}
+
+/// Which body the apsidal drift plot follows, and what it has recorded. Toggled with Z.
+#[derive(Resource, Default)]
+pub struct ApsidalDrift {
+    pub shown: bool,
+    pub body: Option<Entity>,
+    pub tracker: ApsidalTracker,
+}
+
+/// Z shows and hides the apsidal drift plot
+pub fn apsidal_drift_toggle(key_input: Res<ButtonInput<KeyCode>>, mut drift: ResMut<ApsidalDrift>) {
+    if key_input.just_pressed(KeyCode::KeyZ) {
+        drift.shown = !drift.shown;
+    }
+}
+
+/// Record periapsis passages of the selected body around the heaviest other body, starting
+/// over whenever the selection changes
+pub fn apsidal_drift_system(
+    config: Res<PhysicsConfig>,
+    mut drift: ResMut<ApsidalDrift>,
+    selected: Query<Entity, (With<Selected>, With<CelestialBody>)>,
+    bodies: Query<(Entity, &Transform, &Velocity, &Mass), With<CelestialBody>>,
+) {
+    let body = selected.iter().next();
+    if body != drift.body {
+        drift.body = body;
+        drift.tracker = ApsidalTracker::default();
+    }
+    let Some((body, transform, velocity, mass)) = body.and_then(|body| bodies.get(body).ok()) else {
+        return;
+    };
+    let Some((_, central_transform, central_velocity, central_mass)) = bodies
+        .iter()
+        .filter(|(other, ..)| *other != body)
+        .max_by(|a, b| a.3 .0.total_cmp(&b.3 .0))
+    else {
+        return;
+    };
+    drift.tracker.observe(
+        transform.translation - central_transform.translation,
+        velocity.0 - central_velocity.0,
+        config.gravitational_parameter(central_mass.0, mass.0),
+    );
+}
+
+// Size of the apsidal drift plot, in points
+pub const DRIFT_PLOT_SIZE: [f32; 2] = [240.0, 120.0];
+
+/// Apsidal angle against orbit number for the selected body
+pub fn apsidal_drift_plot(mut contexts: EguiContexts, drift: Res<ApsidalDrift>, names: Query<&Name>) {
+    if !drift.shown {
+        return;
+    }
+    let Some(name) = drift.body.and_then(|body| names.get(body).ok()) else {
+        return;
+    };
+    let angles: Vec<f32> = drift.tracker.drift_angles().iter().map(|angle| angle.to_degrees()).collect();
+    egui::Window::new("Apsidal drift")
+        .anchor(egui::Align2::LEFT_TOP, [10.0, 10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.strong(&name.0);
+            if angles.len() < 2 {
+                ui.label(format!("Waiting for periapsis ({} recorded)", angles.len()));
+                return;
+            }
+            let latest = angles[angles.len() - 1];
+            ui.label(format!(
+                "{:+.3} deg after {} orbits ({:+.4} deg per orbit)",
+                latest,
+                angles.len() - 1,
+                latest / (angles.len() - 1) as f32
+            ));
+
+            let (response, painter) = ui.allocate_painter(egui::Vec2::from(DRIFT_PLOT_SIZE), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::GRAY));
+            // Symmetric around zero so the sign of the drift reads at a glance
+            let range = angles.iter().fold(1e-3f32, |range, angle| range.max(angle.abs()));
+            let point = |orbit: usize, angle: f32| {
+                egui::pos2(
+                    rect.left() + rect.width() * orbit as f32 / (angles.len() - 1) as f32,
+                    rect.center().y - rect.height() / 2.0 * angle / range,
+                )
+            };
+            painter.line_segment(
+                [egui::pos2(rect.left(), rect.center().y), egui::pos2(rect.right(), rect.center().y)],
+                egui::Stroke::new(1.0, egui::Color32::DARK_GRAY),
+            );
+            let line: Vec<egui::Pos2> = angles.iter().enumerate().map(|(orbit, angle)| point(orbit, *angle)).collect();
+            painter.add(egui::Shape::line(line, egui::Stroke::new(1.5, egui::Color32::LIGHT_BLUE)));
+            ui.label(format!("+-{range:.3} deg"));
+        });
+}
+
+/// Whether the mass histogram panel is shown. Toggled with Alt+H.
+#[derive(Resource, Default)]
+pub struct MassHistogram(pub bool);
+
+// Histogram bins per factor of ten in mass
+pub const MASS_BINS_PER_DECADE: f32 = 2.0;
+
+// Size of the mass histogram, in points
+pub const MASS_HISTOGRAM_SIZE: [f32; 2] = [240.0, 100.0];
+
+/// Summary of the masses in the system: how many bodies, their total mass, the heaviest, and
+/// a histogram with MASS_BINS_PER_DECADE logarithmic bins from the lightest body's to the heaviest's
+#[derive(Debug, PartialEq)]
+pub struct MassDistribution {
+    pub count: usize,
+    pub total: f32,
+    pub largest: Option<(String, f32)>,
+    /// Bodies in each bin, lightest first
+    pub bins: Vec<usize>,
+    /// Index of the first bin, which starts at 10^(first_bin / MASS_BINS_PER_DECADE)
+    pub first_bin: i32,
+}
+
+impl MassDistribution {
+    /// Lower and upper mass of a bin
+    pub fn bin_range(&self, bin: usize) -> (f32, f32) {
+        let edge = |index: i32| 10f32.powf(index as f32 / MASS_BINS_PER_DECADE);
+        (edge(self.first_bin + bin as i32), edge(self.first_bin + bin as i32 + 1))
+    }
+}
+
+/// Bin each (name, mass). Negative (exotic) masses count towards the total but can't go on a log axis.
+pub fn mass_distribution(bodies: &[(&str, f32)]) -> MassDistribution {
+    let bin = |mass: f32| (mass.log10() * MASS_BINS_PER_DECADE).floor() as i32;
+    let binned: Vec<i32> = bodies.iter().filter(|(_, mass)| *mass > 0.0).map(|(_, mass)| bin(*mass)).collect();
+    let first_bin = binned.iter().copied().min().unwrap_or(0);
+    let mut bins = vec![0; binned.iter().copied().max().map_or(0, |last| (last - first_bin + 1) as usize)];
+    for index in binned {
+        bins[(index - first_bin) as usize] += 1;
+    }
+    MassDistribution {
+        count: bodies.len(),
+        total: bodies.iter().map(|(_, mass)| mass).sum(),
+        largest: bodies.iter().max_by(|a, b| a.1.total_cmp(&b.1)).map(|(name, mass)| (name.to_string(), *mass)),
+        bins,
+        first_bin,
+    }
+}
+
+/// Alt+H shows and hides the mass histogram
+pub fn mass_histogram_toggle(key_input: Res<ButtonInput<KeyCode>>, mut histogram: ResMut<MassHistogram>) {
+    if key_input.pressed(KeyCode::AltLeft) && key_input.just_pressed(KeyCode::KeyH) {
+        histogram.0 = !histogram.0;
+    }
+}
+
+/// Histogram of body masses with the count, total and heaviest body, rebuilt every frame so it
+/// follows mergers and escapes
+pub fn mass_histogram_panel(
+    mut contexts: EguiContexts,
+    histogram: Res<MassHistogram>,
+    bodies: Query<(&Name, &Mass), With<CelestialBody>>,
+) {
+    if !histogram.0 {
+        return;
+    }
+    let masses: Vec<(&str, f32)> = bodies.iter().map(|(name, mass)| (name.0.as_str(), mass.0)).collect();
+    let distribution = mass_distribution(&masses);
+    egui::Window::new("Masses")
+        .anchor(egui::Align2::LEFT_CENTER, [10.0, 0.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("{} bodies, total mass {:.4}", distribution.count, distribution.total));
+            if let Some((name, mass)) = &distribution.largest {
+                ui.label(format!("Largest: {name} ({mass:.4}, {:.1}%)", 100.0 * mass / distribution.total));
+            }
+            if distribution.bins.is_empty() {
+                return;
+            }
+            let (response, painter) = ui.allocate_painter(egui::Vec2::from(MASS_HISTOGRAM_SIZE), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::GRAY));
+            let tallest = distribution.bins.iter().copied().max().unwrap_or(1).max(1);
+            let width = rect.width() / distribution.bins.len() as f32;
+            for (bin, &count) in distribution.bins.iter().enumerate() {
+                let left = rect.left() + width * bin as f32;
+                let top = rect.bottom() - rect.height() * count as f32 / tallest as f32;
+                let bar = egui::Rect::from_min_max(egui::pos2(left + 1.0, top), egui::pos2(left + width - 1.0, rect.bottom()));
+                painter.rect_filled(bar, 0.0, egui::Color32::LIGHT_BLUE);
+            }
+            let (lightest, _) = distribution.bin_range(0);
+            let (_, heaviest) = distribution.bin_range(distribution.bins.len() - 1);
+            ui.label(format!("{lightest:.2e} to {heaviest:.2e} (log scale), tallest bar {tallest}"));
+        });
+}
+
+/// Record whether the pointer is over an egui panel this frame
+pub fn track_pointer_over_ui(mut contexts: EguiContexts, mut over_ui: ResMut<PointerOverUi>) {
+    over_ui.0 = contexts.ctx_mut().is_pointer_over_area();
+}
+
+/// Run condition that keeps clicks on egui panels from reaching the 3D scene
+pub fn pointer_not_over_ui(over_ui: Res<PointerOverUi>) -> bool {
+    !over_ui.0
+}
+
+/// Record whether egui wants the keyboard this frame, as it does while a text field is focused
+pub fn track_keyboard_over_ui(mut contexts: EguiContexts, mut over_ui: ResMut<KeyboardOverUi>) {
+    over_ui.0 = contexts.ctx_mut().wants_keyboard_input();
+}
+
+/// Run condition that keeps typing into egui text fields from triggering the hotkeys
+pub fn keyboard_not_over_ui(over_ui: Res<KeyboardOverUi>) -> bool {
+    !over_ui.0
+}
+
+/// What the cursor is pointing at in the 3D view
+#[derive(Clone, PartialEq, Debug)]
+pub enum HoverPoint {
+    /// The nearest point on this body's surface
+    Body { name: String, point: Vec3 },
+    /// Where the cursor ray crosses the orbital plane
+    Plane(Vec3),
+}
+
+/// The point under a cursor ray: the first body surface it hits, else where it crosses the
+/// orbital plane (through the origin, across the up axis). Bodies are (name, center, radius).
+pub fn hover_point(origin: Vec3, direction: Vec3, bodies: &[(&str, Vec3, f32)], up: Vec3) -> Option<HoverPoint> {
+    let hit = bodies
+        .iter()
+        .filter_map(|&(name, center, radius)| ray_sphere_hit(origin, direction, center, radius).map(|distance| (name, distance)))
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    match hit {
+        Some((name, distance)) => Some(HoverPoint::Body { name: name.to_string(), point: origin + direction * distance }),
+        None => ray_plane_intersection(origin, direction, Vec3::ZERO, up).map(HoverPoint::Plane),
+    }
+}
+
+/// Label the cursor with the world coordinates under it, for placing and measuring by eye
+pub fn hover_readout_overlay(
+    mut contexts: EguiContexts,
+    (config, over_ui): (Res<PhysicsConfig>, Res<PointerOverUi>),
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraController>>,
+    bodies: Query<(&Name, &Transform, &Radius), With<CelestialBody>>,
+) {
+    if over_ui.0 {
+        return;
+    }
+    let Some((cursor, ray)) = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(cameras.get_single().ok())
+        .and_then(|(cursor, (camera, camera_transform))| Some((cursor, camera.viewport_to_world(camera_transform, cursor)?)))
+    else {
+        return;
+    };
+    let spheres: Vec<(&str, Vec3, f32)> =
+        bodies.iter().map(|(name, transform, radius)| (name.0.as_str(), transform.translation, radius.0)).collect();
+    let text = match hover_point(ray.origin, *ray.direction, &spheres, config.up_axis.up()) {
+        Some(HoverPoint::Body { name, point }) => format!("{name} ({:.2}, {:.2}, {:.2})", point.x, point.y, point.z),
+        Some(HoverPoint::Plane(point)) => format!("({:.2}, {:.2}, {:.2})", point.x, point.y, point.z),
+        None => return,
+    };
+    egui::Area::new(egui::Id::new("hover_readout"))
+        .fixed_pos(egui::pos2(cursor.x + 16.0, cursor.y + 16.0))
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.colored_label(egui::Color32::from_gray(220), text);
+        });
+}
+
+/// F3 toggles the inspector
+pub fn inspector_toggle(key_input: Res<ButtonInput<KeyCode>>, mut open: ResMut<InspectorOpen>) {
+    if key_input.just_pressed(KeyCode::F3) {
+        open.0 = !open.0;
+    }
+}
+
+/// Drag fields for each axis of a vector; returns true if any of them changed
+pub fn vec3_editor(ui: &mut egui::Ui, label: &str, value: &mut Vec3, speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut changed = false;
+        for axis in [&mut value.x, &mut value.y, &mut value.z] {
+            changed |= ui.add(egui::DragValue::new(axis).speed(speed)).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+/// The inspector's Transform fields that were edited this frame; rotation is in degrees, as shown
+#[derive(Default)]
+pub struct TransformEdit {
+    pub translation: Option<Vec3>,
+    pub rotation: Option<Vec3>,
+    pub scale: Option<Vec3>,
+}
+
+impl TransformEdit {
+    pub fn is_empty(&self) -> bool {
+        self.translation.is_none() && self.rotation.is_none() && self.scale.is_none()
+    }
+}
+
+/// Write the inspector's Transform edits back into the body
+pub fn apply_transform_edit(transform: &mut Transform, edit: &TransformEdit) {
+    if let Some(translation) = edit.translation {
+        transform.translation = translation;
+    }
+    if let Some(angles) = edit.rotation {
+        let angles = angles * (std::f32::consts::PI / 180.0);
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, angles.x, angles.y, angles.z);
+    }
+    if let Some(scale) = edit.scale {
+        transform.scale = scale;
+    }
+}
+
+/// Raw component values of the selected body, edited in place so changes feed straight into the physics
+#[allow(clippy::type_complexity)]
+pub fn inspector_panel(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    open: Res<InspectorOpen>,
+    config: Res<PhysicsConfig>,
+    mut selected: Query<
+        (
+            Entity,
+            &Name,
+            &mut Transform,
+            &mut Velocity,
+            &mut Mass,
+            &mut Radius,
+            Option<&Trail>,
+            Option<&TrailEnabled>,
+            Option<&TrailColor>,
+            Option<&Atmosphere>,
+            Option<&CastsShadow>,
+        ),
+        (With<CelestialBody>, With<Selected>),
+    >,
+) {
+    if !open.0 {
+        return;
+    }
+
+    egui::Window::new("Inspector").show(contexts.ctx_mut(), |ui| {
+        let Ok((
+            entity,
+            name,
+            mut transform,
+            mut velocity,
+            mut mass,
+            mut radius,
+            trail,
+            trail_enabled,
+            trail_color,
+            atmosphere,
+            casts_shadow,
+        )) = selected.get_single_mut()
+        else {
+            ui.label("Select a single body to inspect it");
+            return;
+        };
+
+        ui.heading(&name.0);
+        ui.label(format!("Entity {entity:?}"));
+
+        // Edit copies and only write back on change, so untouched components don't look modified
+        ui.separator();
+        ui.label("Transform");
+        let mut edit = TransformEdit::default();
+        let mut translation = transform.translation;
+        if vec3_editor(ui, "translation", &mut translation, 0.1) {
+            edit.translation = Some(translation);
+        }
+        let (yaw, pitch, roll) = transform.rotation.to_euler(EulerRot::YXZ);
+        let mut angles = Vec3::new(yaw, pitch, roll) * (180.0 / std::f32::consts::PI);
+        if vec3_editor(ui, "rotation (deg)", &mut angles, 1.0) {
+            edit.rotation = Some(angles);
+        }
+        let mut scale = transform.scale;
+        if vec3_editor(ui, "scale", &mut scale, 0.01) {
+            edit.scale = Some(scale);
+        }
+        if !edit.is_empty() {
+            apply_transform_edit(&mut transform, &edit);
+        }
+
+        ui.separator();
+        let mut body_velocity = velocity.0;
+        if vec3_editor(ui, "Velocity", &mut body_velocity, 0.01) {
+            velocity.0 = body_velocity;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Mass");
+            let mut value = mass.0;
+            if ui
+                .add(egui::DragValue::new(&mut value).speed(0.1).clamp_range(mass_range(&config)))
+                .changed()
+                && value != 0.0
+            {
+                mass.0 = value;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Radius");
+            let mut value = radius.0;
+            if ui
+                .add(egui::DragValue::new(&mut value).speed(0.01).clamp_range(0.01..=f32::MAX))
+                .changed()
+            {
+                radius.0 = value;
+            }
+        });
+
+        ui.separator();
+        ui.label("CelestialBody");
+        ui.label("Selected");
+        if let Some(trail) = trail {
+            ui.label(format!("Trail: {} / {} points", trail.points.len(), trail.max_len));
+            let mut enabled = trail_enabled.is_none_or(|enabled| enabled.0);
+            if ui.checkbox(&mut enabled, "trail enabled").changed() {
+                commands.entity(entity).insert(TrailEnabled(enabled));
+            }
+            let [r, g, b, _] = trail_color.map_or(trail.color, |color| color.0).as_rgba_f32();
+            let mut rgb = [r, g, b];
+            ui.horizontal(|ui| {
+                ui.label("trail color");
+                if ui.color_edit_button_rgb(&mut rgb).changed() {
+                    commands.entity(entity).insert(TrailColor(Color::rgb(rgb[0], rgb[1], rgb[2])));
+                }
+            });
+        }
+        if let Some(atmosphere) = atmosphere {
+            ui.label(format!("Atmosphere: thickness {}", atmosphere.thickness));
+        }
+        let mut shadow = casts_shadow.is_none_or(|shadow| shadow.0);
+        if ui.checkbox(&mut shadow, "casts shadow").changed() {
+            commands.entity(entity).insert(CastsShadow(shadow));
+        }
+    });
+}
+
+/// F4 toggles the profiler overlay
+pub fn profiler_toggle(key_input: Res<ButtonInput<KeyCode>>, mut profile: ResMut<PhysicsProfile>) {
+    if key_input.just_pressed(KeyCode::F4) {
+        profile.overlay_open = !profile.overlay_open;
+    }
+}
+
+/// Small overlay with the average time spent per physics system
+pub fn profiler_overlay(mut contexts: EguiContexts, mut profile: ResMut<PhysicsProfile>, time: Res<Time>) {
+    PhysicsProfile::smooth(&mut profile.frame_ms, time.delta());
+    if !profile.overlay_open {
+        return;
+    }
+
+    let physics_ms = profile.gravity_ms + profile.movement_ms + profile.collision_ms;
+    egui::Window::new("Profiler")
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("gravity    {:7.3} ms", profile.gravity_ms));
+            ui.label(format!("movement   {:7.3} ms", profile.movement_ms));
+            ui.label(format!("collision  {:7.3} ms", profile.collision_ms));
+            ui.label(format!("rendering and other {:7.3} ms", (profile.frame_ms - physics_ms).max(0.0)));
+            ui.label(format!("frame      {:7.3} ms", profile.frame_ms));
+        });
+}
+
+/// Window title naming the loaded scenario and how many bodies it has
+pub fn window_title(scenario: Scenario, body_count: usize) -> String {
+    let bodies = if body_count == 1 { "body" } else { "bodies" };
+    format!("{WINDOW_TITLE} — {} ({body_count} {bodies})", scenario.name())
+}
+
+/// Keep the window title in sync as bodies are added or removed
+pub fn window_title_system(
+    scenario: Res<Scenario>,
+    bodies: Query<(), With<CelestialBody>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    let title = window_title(*scenario, bodies.iter().count());
+    // Only assign on change so the window isn't marked as modified every frame
+    if window.title != title {
+        window.title = title;
+    }
+}
+
+/// F5 opens and closes the save menu
+pub fn save_menu_toggle(
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut menu: ResMut<SaveMenu>,
+    mut paused: ResMut<Paused>,
+) {
+    if key_input.just_pressed(KeyCode::F5) {
+        let open = !menu.open;
+        menu.set_open(open, &mut paused);
+    }
+}
+
+/// "3 min ago" style age of a save
+pub fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => format!("{seconds} s ago"),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        3600..=86399 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+pub fn save_menu_panel(mut contexts: EguiContexts, mut menu: ResMut<SaveMenu>, mut paused: ResMut<Paused>) {
+    if !menu.open {
+        return;
+    }
+    let mut close = false;
+    let mut action = None;
+    egui::Window::new("Save slots")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (index, summary) in menu.slots.iter().enumerate() {
+                let slot = index + 1;
+                ui.horizontal(|ui| {
+                    ui.label(format!("Slot {slot}"));
+                    match summary {
+                        Some(summary) => {
+                            let age = summary.saved.elapsed().unwrap_or_default();
+                            ui.label(format!("{} bodies, saved {}", summary.body_count, format_age(age)));
+                            if ui.button("Load").clicked() {
+                                action = Some(SlotAction::Load(slot));
+                            }
+                            if ui.button("Overwrite").clicked() {
+                                action = Some(SlotAction::Save(slot));
+                            }
+                        }
+                        None => {
+                            ui.label("empty");
+                            if ui.button("Save").clicked() {
+                                action = Some(SlotAction::Save(slot));
+                            }
+                        }
+                    }
+                });
+            }
+            close = ui.button("Close").clicked();
+        });
+    if action.is_some() {
+        menu.pending = action;
+    }
+    if close {
+        menu.set_open(false, &mut paused);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{ReverseMode, body_named, body_states, headless_app, reverse_mode_input, simulation_unpaused};
+    use crate::scene::spawn_headless_scenario;
+    use crate::selftest::{run_orbits, two_body_specs};
+
+    #[test]
+    fn inspector_translation_edit_moves_the_body() {
+        let planet = BodySpec {
+            name: "Planet".to_string(),
+            mass: 1.0,
+            radius: 1.0,
+            position: Vec3::ZERO,
+            velocity: Vec3::X,
+            color: Color::WHITE,
+            atmosphere: None,
+        };
+        let mut app = headless_app(&[planet]);
+        app.update();
+        let moved_to = Vec3::new(5.0, -2.0, 8.0);
+        let mut bodies = app.world.query_filtered::<&mut Transform, With<CelestialBody>>();
+        apply_transform_edit(
+            &mut bodies.single_mut(&mut app.world),
+            &TransformEdit { translation: Some(moved_to), ..default() },
+        );
+
+        // The physics carries on from the edited position
+        app.update();
+        let (position, velocity, _) = body_states(&mut app)[0];
+        assert!(position.distance(moved_to + velocity / 60.0) < 1e-5);
+    }
+
+    #[test]
+    fn window_title_names_the_scenario_and_its_bodies() {
+        let scenario = Scenario::Stress { count: 7 };
+        let mut app = headless_app(&[]);
+        app.init_resource::<SimRng>()
+            .insert_resource(scenario)
+            .add_systems(Startup, spawn_headless_scenario)
+            .add_systems(Update, window_title_system);
+        app.world.spawn((Window::default(), PrimaryWindow));
+        app.update();
+
+        let mut windows = app.world.query::<&Window>();
+        let title = &windows.single(&app.world).title;
+        assert!(title.contains(scenario.name()), "{title}");
+        assert!(title.contains("(7 bodies)"), "{title}");
+    }
+
+    #[test]
+    fn fling_changes_velocity_by_the_drag_impulse() {
+        let velocity = Vec3::new(0.0, 0.0, 0.4);
+        let planet = BodySpec {
+            name: "Planet".to_string(),
+            mass: 1.0,
+            radius: 1.0,
+            position: Vec3::ZERO,
+            velocity,
+            color: Color::WHITE,
+            atmosphere: None,
+        };
+        let mut app = headless_app(&[planet]);
+        app.insert_resource(Paused(true))
+            .init_resource::<FlingDrag>()
+            .add_systems(Update, fling_apply_system);
+        let mut bodies = app.world.query_filtered::<Entity, With<CelestialBody>>();
+        let entity = bodies.single(&app.world);
+
+        // Press on the body from a camera looking down -Z, drag, then let go
+        let camera = Vec3::new(0.3, -0.2, 30.0);
+        let mut grab = start_fling(entity, Vec3::ZERO, camera, (Vec3::ZERO - camera).normalize(), Vec3::NEG_Z).unwrap();
+        let drag = Vec3::new(6.0, -4.0, 0.0);
+        grab.current = fling_target(&grab, camera, (drag - camera).normalize()).unwrap();
+        app.world.resource_mut::<FlingDrag>().0 = Some(grab);
+        app.update();
+        assert_eq!(app.world.get::<Velocity>(entity).unwrap().0, velocity, "still dragging");
+        app.world.resource_mut::<FlingDrag>().0.as_mut().unwrap().released = true;
+        app.update();
+
+        let expected = velocity + drag * FLING_IMPULSE_PER_UNIT;
+        assert!(app.world.get::<Velocity>(entity).unwrap().0.distance(expected) < 1e-5);
+        assert!(app.world.resource::<FlingDrag>().0.is_none());
+    }
+
+    #[test]
+    fn lone_keplerian_orbit_keeps_its_periapsis() {
+        let mut app = headless_app(&two_body_specs(12.0, 0.8));
+        let mu = PhysicsConfig::default().gravitational_parameter(1000.0, 1.0);
+        let mut tracker = ApsidalTracker::default();
+        run_orbits(&mut app, 5.5, 200_000, |states| {
+            tracker.observe(states[1].0 - states[0].0, states[1].1 - states[0].1, mu);
+        });
+
+        assert!(tracker.directions.len() >= 5);
+        for angle in tracker.drift_angles() {
+            assert!(angle.abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn body_search_matches_substrings_in_any_case() {
+        let catalog = [(0, "Earth"), (1, "Mars"), (2, "Marseille Comet"), (3, "Jupiter"), (4, "SMARTS-1")];
+
+        assert_eq!(search_bodies(&catalog, "MaR"), [1, 2, 4]);
+        assert!(search_bodies(&catalog, "Pluto").is_empty());
+        assert_eq!(search_bodies(&catalog, "").len(), catalog.len());
+    }
+
+    #[test]
+    fn paused_drag_moves_a_body_but_not_its_velocity() {
+        let mut app = headless_app(&two_body_specs(20.0, 1.0));
+        app.insert_resource(Paused(true))
+            .init_resource::<PlacementDrag>()
+            .add_systems(Update, placement_apply_system.run_if(not(simulation_unpaused)));
+        let planet = body_named(&mut app, "Planet").unwrap();
+        let center = app.world.get::<Transform>(planet).unwrap().translation;
+        let velocity = app.world.get::<Velocity>(planet).unwrap().0;
+
+        // Looking down -Z and grabbing the body a little off its center
+        let camera = center + Vec3::new(0.3, -0.2, 30.0);
+        let mut placement = start_placement(planet, center, camera, Vec3::NEG_Z, Vec3::NEG_Z).unwrap();
+        let drag = Vec3::new(5.0, 3.0, 0.0);
+        placement.target = placement_target(&placement, camera + drag, Vec3::NEG_Z).unwrap();
+        placement.released = true;
+        app.world.resource_mut::<PlacementDrag>().0 = Some(placement);
+        app.update();
+
+        assert!(app.world.get::<Transform>(planet).unwrap().translation.distance(center + drag) < 1e-4);
+        assert!(app.world.get::<Velocity>(planet).unwrap().0.distance(velocity) < 1e-4);
+        assert!(app.world.resource::<PlacementDrag>().0.is_none());
+    }
+
+    #[test]
+    fn hover_reads_out_the_plane_point_or_the_body_surface() {
+        let camera = Vec3::new(0.0, 30.0, 30.0);
+        let target = Vec3::new(5.0, 0.0, -3.0);
+        let bodies = [("Moon", Vec3::new(-12.0, 0.0, 4.0), 1.0)];
+        // f32 rays lose precision with length, so errors are relative to the camera's distance
+        let tolerance = 1e-4 * camera.length();
+
+        let Some(HoverPoint::Plane(point)) = hover_point(camera, (target - camera).normalize(), &bodies, Vec3::Y) else {
+            panic!("the ray should cross the plane");
+        };
+        assert!(point.distance(target) < tolerance);
+
+        let surface = bodies[0].1 + (camera - bodies[0].1).normalize() * bodies[0].2;
+        let Some(HoverPoint::Body { name, point }) = hover_point(camera, (bodies[0].1 - camera).normalize(), &bodies, Vec3::Y) else {
+            panic!("the ray should hit the moon");
+        };
+        assert_eq!(name, "Moon");
+        assert!(point.distance(surface) < tolerance);
+    }
+
+    #[test]
+    fn mass_histogram_bins_two_to_a_decade_with_the_total_and_largest() {
+        let distribution = mass_distribution(&[("a", 1.0), ("b", 2.0), ("c", 5.0), ("d", 10.0), ("e", 50.0), ("f", 100.0), ("g", 0.5)]);
+
+        let expected = MassDistribution {
+            count: 7,
+            total: 168.5,
+            largest: Some(("f".to_string(), 100.0)),
+            bins: vec![1, 2, 1, 1, 1, 1],
+            first_bin: -1,
+        };
+        assert_eq!(distribution, expected);
+        // Lightest first, from half a decade below 1 to half a decade above 100
+        assert!((distribution.bin_range(0).0 - 10f32.powf(-0.5)).abs() < 1e-4);
+        assert!((distribution.bin_range(5).1 - 10f32.powf(2.5)).abs() / 100.0 < 1e-4);
+    }
+
+    #[test]
+    fn hotkeys_wait_while_a_text_field_has_the_keyboard() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ReverseMode>()
+            .insert_resource(KeyboardOverUi(true))
+            .add_systems(Update, reverse_mode_input.run_if(keyboard_not_over_ui));
+        // Backspace deletes a character in the field rather than running time backwards
+        app.world.resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::Backspace);
+        app.update();
+        assert!(!app.world.resource::<ReverseMode>().0);
+
+        app.insert_resource(KeyboardOverUi(false)).update();
+        assert!(app.world.resource::<ReverseMode>().0);
+    }
+
+    #[test]
+    fn added_body_queues_behind_a_scene_still_waiting_to_spawn() {
+        let body = |name: &str| BodyConfig::new(name, 1.0, 1.0, [0.0; 3], [0.0; 3]);
+        let mut app = App::new();
+        app.add_systems(Update, move |mut commands: Commands, pending: Option<ResMut<LoadedScene>>| {
+            queue_body(&mut commands, pending, body("Added"))
+        });
+        app.update();
+        assert_eq!(app.world.resource::<LoadedScene>().0.len(), 1);
+
+        app.insert_resource(LoadedScene(vec![body("Loaded")])).update();
+        let names: Vec<_> = app.world.resource::<LoadedScene>().0.iter().map(|body| body.name.clone()).collect();
+        assert_eq!(names, ["Loaded", "Added"]);
+    }
+}